@@ -1,10 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::fs::OpenOptions;
-use std::io::{BufWriter, Write};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::PathBuf;
-use anyhow::{Result, Context};
-use chrono::{DateTime, Utc};
-use std::sync::Mutex;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+/// While batching, unflushed entries are written out at least this often
+const AUTO_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEntry {
@@ -42,22 +48,81 @@ pub struct AuditLog {
     pub total_operations: usize,
     pub successful_operations: usize,
     pub failed_operations: usize,
+    /// Lines that couldn't be parsed and were skipped
+    pub malformed_lines: usize,
     pub last_updated: DateTime<Utc>,
 }
 
+/// How `get_audit_statistics_grouped` groups entries
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AuditBucket {
+    Daily,
+    /// ISO weeks, starting on Monday
+    Weekly,
+    ByOperation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditGroupStats {
+    /// `2024-01-31`, `2024-W05` or the operation name
+    pub key: String,
+    /// First day of a daily or weekly bucket
+    pub start: Option<NaiveDate>,
+    pub total_operations: usize,
+    pub successful_operations: usize,
+    pub failed_operations: usize,
+    /// Successful over total, 0 to 1
+    pub success_ratio: f64,
+    /// Sum of the entries' file sizes
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupedAuditStatistics {
+    pub bucket: AuditBucket,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// In key order, so time buckets run oldest first
+    pub groups: Vec<AuditGroupStats>,
+    pub malformed_lines: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentTransfer {
+    pub operation: AuditOperation,
+    pub source_path: Option<String>,
+    pub dest_path: Option<String>,
+    pub file_size: Option<u64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRepairResult {
+    pub kept_lines: usize,
+    pub dropped_lines: usize,
+    pub backup_path: Option<String>,
+}
+
 pub struct AuditLogger {
     log_file: PathBuf,
     session_id: String,
     current_user: String,
     writer: Mutex<BufWriter<std::fs::File>>,
+    /// Open `begin_batch` calls; entries aren't flushed one by one while this is above zero
+    batch_depth: AtomicUsize,
+    /// Entries written since the last flush
+    dirty: AtomicBool,
 }
 
 impl AuditLogger {
     pub fn new() -> Result<Self> {
-        let app_data_dir = Self::get_app_data_dir()?;
+        let app_data_dir = crate::utils::app_data_dir()?;
         std::fs::create_dir_all(&app_data_dir)?;
-        
-        let log_file = app_data_dir.join("audit.log");
+        Self::open(app_data_dir.join("audit.log"))
+    }
+
+    /// A logger appending to `log_file`
+    fn open(log_file: PathBuf) -> Result<Self> {
         let file = OpenOptions::new()
             .create(true)
             .append(true)
@@ -70,26 +135,11 @@ impl AuditLogger {
             session_id: uuid::Uuid::new_v4().to_string(),
             current_user: whoami::username(),
             writer: Mutex::new(writer),
+            batch_depth: AtomicUsize::new(0),
+            dirty: AtomicBool::new(false),
         })
     }
 
-    /// Get the application data directory
-    fn get_app_data_dir() -> Result<PathBuf> {
-        #[cfg(target_os = "windows")]
-        {
-            let app_data = std::env::var("APPDATA")
-                .context("APPDATA environment variable not found")?;
-            Ok(PathBuf::from(app_data).join("Circle9"))
-        }
-        
-        #[cfg(not(target_os = "windows"))]
-        {
-            let home = std::env::var("HOME")
-                .context("HOME environment variable not found")?;
-            Ok(PathBuf::from(home).join(".circle9"))
-        }
-    }
-
     /// Log an audit entry
     pub fn log_operation(
         &self,
@@ -100,7 +150,35 @@ impl AuditLogger {
         success: bool,
         error_message: Option<String>,
     ) -> Result<()> {
-        let entry = AuditEntry {
+        let entry = self.new_entry(operation, source_path, dest_path, file_size, success, error_message);
+        self.write_entry(&entry, self.batch_depth.load(Ordering::SeqCst) > 0)
+    }
+
+    /// Log an entry without flushing it, e.g. one per file of a recursive transfer. It reaches
+    /// disk with the next flush, at most `AUTO_FLUSH_INTERVAL` later.
+    pub fn log_operation_batched(
+        &self,
+        operation: AuditOperation,
+        source_path: Option<String>,
+        dest_path: Option<String>,
+        file_size: Option<u64>,
+        success: bool,
+        error_message: Option<String>,
+    ) -> Result<()> {
+        let entry = self.new_entry(operation, source_path, dest_path, file_size, success, error_message);
+        self.write_entry(&entry, true)
+    }
+
+    fn new_entry(
+        &self,
+        operation: AuditOperation,
+        source_path: Option<String>,
+        dest_path: Option<String>,
+        file_size: Option<u64>,
+        success: bool,
+        error_message: Option<String>,
+    ) -> AuditEntry {
+        AuditEntry {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             operation,
@@ -111,33 +189,75 @@ impl AuditLogger {
             success,
             error_message,
             session_id: self.session_id.clone(),
-        };
-
-        self.write_entry(&entry)?;
-        Ok(())
+        }
     }
 
-    /// Write an audit entry to the log file
-    fn write_entry(&self, entry: &AuditEntry) -> Result<()> {
+    /// Write an audit entry to the log file, leaving it buffered when `defer_flush` is set
+    fn write_entry(&self, entry: &AuditEntry, defer_flush: bool) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
         let json_line = serde_json::to_string(entry)?;
         writeln!(writer, "{}", json_line)?;
-        writer.flush()?;
+        if defer_flush {
+            self.dirty.store(true, Ordering::SeqCst);
+            start_auto_flush();
+        } else {
+            writer.flush()?;
+            self.dirty.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Stop flushing after every entry until the matching `end_batch`. Batches nest.
+    pub fn begin_batch(&self) {
+        self.batch_depth.fetch_add(1, Ordering::SeqCst);
+        start_auto_flush();
+    }
+
+    /// Close a batch; the last one open flushes everything written during it
+    pub fn end_batch(&self) -> Result<()> {
+        let previous = self.batch_depth
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |depth| Some(depth.saturating_sub(1)))
+            .unwrap_or(0);
+        if previous <= 1 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush_if_dirty(&self) -> Result<()> {
+        if self.dirty.load(Ordering::SeqCst) {
+            self.flush()?;
+        }
         Ok(())
     }
 
     /// Read audit entries from the log file
     pub fn read_entries(&self, limit: Option<usize>) -> Result<Vec<AuditEntry>> {
+        let (entries, malformed) = self.read_entries_lenient(limit)?;
+        if malformed > 0 {
+            tracing::warn!("Skipped {} malformed audit log lines; run repair_audit_log to drop them", malformed);
+        }
+        Ok(entries)
+    }
+
+    /// Read entries, skipping lines that don't parse (e.g. a write cut short by a crash).
+    /// Returns the entries and the number of lines skipped.
+    fn read_entries_lenient(&self, limit: Option<usize>) -> Result<(Vec<AuditEntry>, usize)> {
+        // Batched entries still in the buffer would otherwise be missing
+        self.flush_if_dirty()?;
         let content = std::fs::read_to_string(&self.log_file)?;
         let mut entries = Vec::new();
+        let mut malformed = 0;
         
         for line in content.lines() {
             if line.trim().is_empty() {
                 continue;
             }
             
-            let entry: AuditEntry = serde_json::from_str(line)?;
-            entries.push(entry);
+            match serde_json::from_str::<AuditEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => malformed += 1,
+            }
             
             if let Some(limit) = limit {
                 if entries.len() >= limit {
@@ -146,25 +266,210 @@ impl AuditLogger {
             }
         }
         
-        Ok(entries)
+        Ok((entries, malformed))
+    }
+
+    /// Call `visit` with each entry in file order without loading the whole log.
+    /// Returns the number of malformed lines skipped; a log not written yet has none.
+    fn for_each_entry<F: FnMut(AuditEntry)>(&self, mut visit: F) -> Result<usize> {
+        self.flush_if_dirty()?;
+        let file = match std::fs::File::open(&self.log_file) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let reader = BufReader::new(file);
+        let mut malformed = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) => visit(entry),
+                Err(_) => malformed += 1,
+            }
+        }
+        Ok(malformed)
+    }
+
+    /// Counts, success ratio and bytes per day, week or operation, optionally limited to
+    /// entries between `from` and `to` (inclusive)
+    pub fn grouped_statistics(
+        &self,
+        bucket: AuditBucket,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<GroupedAuditStatistics> {
+        let mut groups: BTreeMap<String, AuditGroupStats> = BTreeMap::new();
+        let malformed_lines = self.for_each_entry(|entry| {
+            if from.map_or(false, |from| entry.timestamp < from) || to.map_or(false, |to| entry.timestamp > to) {
+                return;
+            }
+            let day = entry.timestamp.date_naive();
+            let (key, start) = match bucket {
+                AuditBucket::Daily => (day.format("%Y-%m-%d").to_string(), Some(day)),
+                AuditBucket::Weekly => {
+                    let week = day.iso_week();
+                    let monday = day - ChronoDuration::days(i64::from(day.weekday().num_days_from_monday()));
+                    (format!("{}-W{:02}", week.year(), week.week()), Some(monday))
+                }
+                AuditBucket::ByOperation => (format!("{:?}", entry.operation), None),
+            };
+            let group = groups.entry(key.clone()).or_insert_with(|| AuditGroupStats {
+                key,
+                start,
+                total_operations: 0,
+                successful_operations: 0,
+                failed_operations: 0,
+                success_ratio: 0.0,
+                total_bytes: 0,
+            });
+            group.total_operations += 1;
+            if entry.success {
+                group.successful_operations += 1;
+            } else {
+                group.failed_operations += 1;
+            }
+            group.total_bytes += entry.file_size.unwrap_or(0);
+        })?;
+
+        let groups = groups.into_values()
+            .map(|mut group| {
+                group.success_ratio = group.successful_operations as f64 / group.total_operations as f64;
+                group
+            })
+            .collect();
+        Ok(GroupedAuditStatistics { bucket, from, to, groups, malformed_lines })
+    }
+
+    /// The latest successful copies, moves and completed transfers, one per path, newest first
+    pub fn recent_transfers(&self, limit: usize) -> Result<Vec<RecentTransfer>> {
+        // Latest entry per path, with its position in the file; only one per path is held
+        let mut latest: std::collections::HashMap<String, (usize, AuditEntry)> = std::collections::HashMap::new();
+        let mut position = 0;
+        self.for_each_entry(|entry| {
+            position += 1;
+            let counts = entry.success && matches!(
+                entry.operation,
+                AuditOperation::FileCopy | AuditOperation::FileMove | AuditOperation::TransferCompleted
+            );
+            if let (true, Some(path)) = (counts, entry.source_path.clone().or_else(|| entry.dest_path.clone())) {
+                latest.insert(path, (position, entry));
+            }
+        })?;
+
+        let mut latest: Vec<(usize, AuditEntry)> = latest.into_values().collect();
+        latest.sort_by(|a, b| b.0.cmp(&a.0));
+        Ok(latest.into_iter()
+            .take(limit)
+            .map(|(_, entry)| RecentTransfer {
+                operation: entry.operation,
+                source_path: entry.source_path,
+                dest_path: entry.dest_path,
+                file_size: entry.file_size,
+                timestamp: entry.timestamp,
+            })
+            .collect())
+    }
+
+    /// Rewrite the log keeping only valid lines, after copying the original to `audit.log.bak`
+    pub fn repair(&self) -> Result<AuditRepairResult> {
+        // Hold the writer so nothing is appended while the file is rewritten
+        let mut writer = self.writer.lock().unwrap();
+        writer.flush()?;
+
+        let content = std::fs::read_to_string(&self.log_file)?;
+        let mut kept = Vec::new();
+        let mut dropped_lines = 0;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            if serde_json::from_str::<AuditEntry>(line).is_ok() {
+                kept.push(line);
+            } else {
+                dropped_lines += 1;
+            }
+        }
+
+        if dropped_lines == 0 {
+            return Ok(AuditRepairResult { kept_lines: kept.len(), dropped_lines, backup_path: None });
+        }
+
+        let backup_path = self.log_file.with_extension("log.bak");
+        std::fs::copy(&self.log_file, &backup_path)?;
+
+        // The file is opened in append mode, so truncating and writing rebuilds it in place
+        writer.get_mut().set_len(0)?;
+        for line in &kept {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+
+        tracing::info!("Repaired audit log: dropped {} malformed lines", dropped_lines);
+        Ok(AuditRepairResult {
+            kept_lines: kept.len(),
+            dropped_lines,
+            backup_path: Some(backup_path.to_string_lossy().to_string()),
+        })
+    }
+
+    /// Drop entries older than `days`, returning how many were removed
+    pub fn prune_older_than(&self, days: u32) -> Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.flush()?;
+
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(days));
+        let content = std::fs::read_to_string(&self.log_file)?;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+        // Malformed lines are left for repair() to deal with
+        let kept: Vec<&str> = lines.iter()
+            .copied()
+            .filter(|line| serde_json::from_str::<AuditEntry>(line).map_or(true, |e| e.timestamp >= cutoff))
+            .collect();
+
+        let dropped = lines.len() - kept.len();
+        if dropped == 0 {
+            return Ok(0);
+        }
+
+        writer.get_mut().set_len(0)?;
+        for line in &kept {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        Ok(dropped)
     }
 
     /// Get audit statistics
     pub fn get_statistics(&self) -> Result<AuditLog> {
-        let entries = self.read_entries(None)?;
+        let mut entries = Vec::new();
+        let mut successful_operations = 0;
+        let malformed_lines = self.for_each_entry(|entry| {
+            if entry.success {
+                successful_operations += 1;
+            }
+            entries.push(entry);
+        })?;
         let total_operations = entries.len();
-        let successful_operations = entries.iter().filter(|e| e.success).count();
         let failed_operations = total_operations - successful_operations;
-        
+
         Ok(AuditLog {
             entries,
             total_operations,
             successful_operations,
             failed_operations,
+            malformed_lines,
             last_updated: Utc::now(),
         })
     }
 
+    /// Flush any buffered entries to disk
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.flush()?;
+        self.dirty.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
     /// Clear the audit log
     pub fn clear_log(&self) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
@@ -175,12 +480,13 @@ impl AuditLogger {
 
     /// Export audit log to a file
     pub fn export_log(&self, export_path: &str) -> Result<()> {
-        let entries = self.read_entries(None)?;
+        let (entries, malformed_lines) = self.read_entries_lenient(None)?;
         let audit_log = AuditLog {
             entries,
             total_operations: 0, // Will be calculated
             successful_operations: 0, // Will be calculated
             failed_operations: 0, // Will be calculated
+            malformed_lines,
             last_updated: Utc::now(),
         };
         
@@ -205,6 +511,21 @@ lazy_static::lazy_static! {
     pub static ref AUDIT_LOGGER: AuditLogger = AuditLogger::new().unwrap();
 }
 
+static AUTO_FLUSH: Once = Once::new();
+
+/// Start the thread that writes out batched entries, so a crash loses at most
+/// `AUTO_FLUSH_INTERVAL` of them
+fn start_auto_flush() {
+    AUTO_FLUSH.call_once(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(AUTO_FLUSH_INTERVAL);
+            if let Err(e) = AUDIT_LOGGER.flush_if_dirty() {
+                tracing::warn!("Failed to flush audit log: {}", e);
+            }
+        });
+    });
+}
+
 // Tauri commands for audit logging
 
 #[tauri::command]
@@ -242,24 +563,64 @@ pub async fn log_file_operation(
     ).map_err(|e| e.to_string())
 }
 
+/// Stop flushing the audit log after every entry until `end_audit_batch`
+#[tauri::command]
+pub async fn begin_audit_batch() -> Result<(), String> {
+    AUDIT_LOGGER.begin_batch();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn end_audit_batch() -> Result<(), String> {
+    AUDIT_LOGGER.end_batch().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_audit_entries(limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
     AUDIT_LOGGER.read_entries(limit)
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_recent_transfers(limit: Option<usize>) -> Result<Vec<RecentTransfer>, String> {
+    AUDIT_LOGGER.recent_transfers(limit.unwrap_or(20))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_audit_statistics() -> Result<AuditLog, String> {
     AUDIT_LOGGER.get_statistics()
         .map_err(|e| e.to_string())
 }
 
+/// Audit totals per day, week or operation type for dashboard charts
+#[tauri::command]
+pub async fn get_audit_statistics_grouped(
+    bucket: AuditBucket,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<GroupedAuditStatistics, String> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err("The start of the range is after its end".to_string());
+        }
+    }
+    AUDIT_LOGGER.grouped_statistics(bucket, from, to)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn clear_audit_log() -> Result<(), String> {
     AUDIT_LOGGER.clear_log()
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn repair_audit_log() -> Result<AuditRepairResult, String> {
+    AUDIT_LOGGER.repair()
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn export_audit_log(export_path: String) -> Result<(), String> {
     AUDIT_LOGGER.export_log(&export_path)
@@ -275,3 +636,98 @@ pub async fn get_session_id() -> Result<String, String> {
 pub async fn get_current_user() -> Result<String, String> {
     Ok(AUDIT_LOGGER.get_current_user().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A logger on its own file under the temp dir, removed when dropped
+    struct TempLogger(AuditLogger);
+
+    impl TempLogger {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("circle9-audit-{}.log", uuid::Uuid::new_v4()));
+            Self(AuditLogger::open(path).unwrap())
+        }
+    }
+
+    impl Drop for TempLogger {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0.log_file);
+        }
+    }
+
+    fn log_transfer(logger: &AuditLogger, i: usize) {
+        logger.log_operation(
+            AuditOperation::TransferCompleted,
+            Some(format!("/src/file-{}", i)),
+            Some(format!("/dest/file-{}", i)),
+            Some(4096),
+            true,
+            None,
+        ).unwrap();
+    }
+
+    #[test]
+    fn readers_see_entries_still_in_the_batch() {
+        let logger = TempLogger::new();
+        logger.0.begin_batch();
+        for i in 0..10 {
+            log_transfer(&logger.0, i);
+        }
+        assert_eq!(logger.0.read_entries(None).unwrap().len(), 10);
+        assert_eq!(logger.0.get_statistics().unwrap().total_operations, 10);
+        logger.0.end_batch().unwrap();
+    }
+
+    #[test]
+    fn missing_log_gives_empty_statistics() {
+        let logger = TempLogger::new();
+        std::fs::remove_file(&logger.0.log_file).unwrap();
+        assert_eq!(logger.0.get_statistics().unwrap().total_operations, 0);
+        assert!(logger.0.recent_transfers(10).unwrap().is_empty());
+        assert!(logger.0.grouped_statistics(AuditBucket::Daily, None, None).unwrap().groups.is_empty());
+    }
+
+    #[test]
+    fn recent_transfers_keeps_the_latest_per_path() {
+        let logger = TempLogger::new();
+        for i in [1, 2, 1, 3] {
+            log_transfer(&logger.0, i);
+        }
+        let recent: Vec<String> = logger.0.recent_transfers(10).unwrap()
+            .into_iter()
+            .filter_map(|t| t.source_path)
+            .collect();
+        assert_eq!(recent, ["/src/file-3", "/src/file-1", "/src/file-2"]);
+        assert_eq!(logger.0.recent_transfers(1).unwrap().len(), 1);
+    }
+
+    /// Per-entry flushing against one batch for a 1000-file transfer. Timing depends on the
+    /// disk, so run it by hand: `cargo test audit_batching_1000_files -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn audit_batching_1000_files() {
+        const FILES: usize = 1000;
+
+        let unbatched = TempLogger::new();
+        let start = std::time::Instant::now();
+        for i in 0..FILES {
+            log_transfer(&unbatched.0, i);
+        }
+        let unbatched_time = start.elapsed();
+
+        let batched = TempLogger::new();
+        let start = std::time::Instant::now();
+        batched.0.begin_batch();
+        for i in 0..FILES {
+            log_transfer(&batched.0, i);
+        }
+        batched.0.end_batch().unwrap();
+        let batched_time = start.elapsed();
+
+        assert_eq!(unbatched.0.read_entries(None).unwrap().len(), FILES);
+        assert_eq!(batched.0.read_entries(None).unwrap().len(), FILES);
+        println!("{} entries: flushed each {:?}, batched {:?}", FILES, unbatched_time, batched_time);
+    }
+}
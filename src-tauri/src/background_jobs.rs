@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Manager, State};
+use crate::copy_agent::CopyAgent;
+use crate::scheduler::TransferScheduler;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundJobKind {
+    Schedule,
+    ExecStream,
+    DirSizeScan,
+    Watch,
+    Tail,
+    StreamDownload,
+    Checksum,
+    /// A copy agent task; these aren't in the registry but are reported when terminated
+    Transfer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundJob {
+    pub id: String,
+    pub kind: BackgroundJobKind,
+    /// What the job operates on: a path, a command line, a transfer source, ...
+    pub target: String,
+    pub connection_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Payload of `background_job_terminated`, emitted for each job stopped because its connection closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobTerminated {
+    pub job: BackgroundJob,
+    pub reason: String,
+}
+
+struct RegisteredJob {
+    job: BackgroundJob,
+    cancel: Box<dyn Fn() + Send>,
+}
+
+lazy_static::lazy_static! {
+    static ref JOBS: Mutex<HashMap<String, RegisteredJob>> = Mutex::new(HashMap::new());
+}
+
+/// Track a running job; `cancel` is called by `cancel_background_job` and should only
+/// signal the job to stop, since it runs with the registry locked
+pub fn register<F>(id: &str, kind: BackgroundJobKind, target: &str, connection_id: Option<&str>, cancel: F)
+where
+    F: Fn() + Send + 'static,
+{
+    let job = BackgroundJob {
+        id: id.to_string(),
+        kind,
+        target: target.to_string(),
+        connection_id: connection_id.map(str::to_string),
+        started_at: Utc::now(),
+    };
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.insert(id.to_string(), RegisteredJob { job, cancel: Box::new(cancel) });
+    }
+}
+
+/// Forget a job once it has finished
+pub fn unregister(id: &str) {
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.remove(id);
+    }
+}
+
+fn registered_jobs() -> Vec<BackgroundJob> {
+    JOBS.lock()
+        .map(|jobs| jobs.values().map(|j| j.job.clone()).collect())
+        .unwrap_or_default()
+}
+
+/// Signal a registered job to stop, returning false if no such job is running
+fn cancel_registered(id: &str) -> bool {
+    match JOBS.lock() {
+        Ok(jobs) => match jobs.get(id) {
+            Some(registered) => {
+                (registered.cancel)();
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Stop every job and transfer bound to a connection that has gone away, emitting
+/// `background_job_terminated` for each. Returns how many were stopped.
+pub fn terminate_connection_jobs(app_handle: &AppHandle, connection_id: &str, reason: &str) -> usize {
+    let mut terminated = Vec::new();
+    if let Ok(mut jobs) = JOBS.lock() {
+        let ids: Vec<String> = jobs.values()
+            .filter(|registered| registered.job.connection_id.as_deref() == Some(connection_id))
+            .map(|registered| registered.job.id.clone())
+            .collect();
+        for id in ids {
+            if let Some(registered) = jobs.remove(&id) {
+                (registered.cancel)();
+                terminated.push(registered.job);
+            }
+        }
+    }
+
+    match app_handle.state::<CopyAgent>().cancel_connection_transfers(connection_id, reason) {
+        Ok(tasks) => terminated.extend(tasks.into_iter().map(|task| BackgroundJob {
+            id: task.id,
+            kind: BackgroundJobKind::Transfer,
+            target: task.source_path,
+            connection_id: task.connection_id,
+            started_at: task.started_at.unwrap_or(task.created_at),
+        })),
+        Err(e) => tracing::error!("Failed to stop transfers for {}: {}", connection_id, e),
+    }
+
+    for job in &terminated {
+        let event = JobTerminated { job: job.clone(), reason: reason.to_string() };
+        if let Err(e) = app_handle.emit_all("background_job_terminated", &event) {
+            tracing::error!("Failed to emit background_job_terminated: {}", e);
+        }
+    }
+    if !terminated.is_empty() {
+        tracing::info!("Stopped {} jobs for {}: {}", terminated.len(), connection_id, reason);
+    }
+    terminated.len()
+}
+
+// Tauri commands for background jobs
+
+#[tauri::command]
+pub async fn list_background_jobs(
+    scheduler: State<'_, TransferScheduler>
+) -> Result<Vec<BackgroundJob>, String> {
+    let mut jobs: Vec<BackgroundJob> = scheduler.list().into_iter()
+        .map(|schedule| BackgroundJob {
+            id: schedule.id,
+            kind: BackgroundJobKind::Schedule,
+            target: schedule.task.source_path,
+            connection_id: None,
+            started_at: schedule.created_at,
+        })
+        .collect();
+    jobs.extend(registered_jobs());
+    jobs.sort_by_key(|job| job.started_at);
+    Ok(jobs)
+}
+
+#[tauri::command]
+pub async fn cancel_background_job(
+    scheduler: State<'_, TransferScheduler>,
+    job_id: String
+) -> Result<bool, String> {
+    if scheduler.list().iter().any(|schedule| schedule.id == job_id) {
+        scheduler.cancel(&job_id).map_err(|e| e.to_string())?;
+        return Ok(true);
+    }
+    Ok(cancel_registered(&job_id))
+}
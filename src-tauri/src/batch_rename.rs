@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use regex::Regex;
+use ssh2::{FileType, RenameFlags};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::State;
+use crate::audit_log::{AuditOperation, AUDIT_LOGGER};
+use crate::case_agent::CaseAgent;
+use crate::error::{Circle9Error, Result};
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchRenameOptions {
+    /// Treat the replacement as a template with `{name}`, `{ext}` and `{n}` / `{n:3}`
+    /// instead of a regex replacement with `$1`-style captures
+    pub template: bool,
+    /// Rename everything else when some targets collide, rather than renaming nothing
+    pub skip_conflicts: bool,
+    /// First value of `{n}`; defaults to 1
+    pub sequence_start: Option<u64>,
+    pub include_dirs: bool,
+    /// Work out the renames without applying them
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedRename {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedRename {
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchRenameResult {
+    /// With `dry_run`, the renames that would be applied
+    pub applied: Vec<PlannedRename>,
+    pub skipped: Vec<SkippedRename>,
+    /// Nothing was renamed because of conflicts and `skip_conflicts` wasn't set
+    pub aborted: bool,
+}
+
+/// Expand a rename template for `name`, with `sequence` as its `{n}`
+fn expand_template(template: &str, name: &str, sequence: u64) -> Result<String> {
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], &name[dot..]),
+        _ => (name, ""),
+    };
+
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        let close = rest[open..].find('}')
+            .map(|i| open + i)
+            .ok_or_else(|| Circle9Error::InvalidValue(format!("Unclosed placeholder in {}", template)))?;
+        match &rest[open + 1..close] {
+            "name" => result.push_str(stem),
+            "ext" => result.push_str(ext),
+            "n" => result.push_str(&sequence.to_string()),
+            placeholder => {
+                let width = placeholder.strip_prefix("n:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .ok_or_else(|| Circle9Error::InvalidValue(format!("Unknown placeholder {{{}}}", placeholder)))?;
+                result.push_str(&format!("{:0width$}", sequence, width = width));
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Why `name` can't be used as a file name, if it can't
+fn invalid_name(name: &str) -> Option<&'static str> {
+    if name.is_empty() || name == "." || name == ".." {
+        Some("Target name is empty or reserved")
+    } else if name.contains('/') || name.contains('\0') {
+        Some("Target name contains '/' or a NUL byte")
+    } else {
+        None
+    }
+}
+
+/// Work out every rename in `dir` before touching anything. A target that matches another
+/// entry's current name, or another target, ignoring case, is a conflict even if that entry
+/// is being renamed too, so the order renames run in never matters.
+pub fn plan_renames(
+    connection: &SSHConnection,
+    dir: &str,
+    pattern: &str,
+    replacement: &str,
+    options: &BatchRenameOptions,
+) -> Result<(Vec<PlannedRename>, Vec<SkippedRename>)> {
+    let regex = Regex::new(pattern)
+        .map_err(|e| Circle9Error::InvalidValue(format!("Invalid pattern: {}", e)))?;
+
+    let entries = connection.sftp()?.readdir(Path::new(dir))?;
+    let mut names: Vec<(String, bool)> = entries.iter()
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_str()?.to_string();
+            Some((name, stat.file_type() == FileType::Directory))
+        })
+        .collect();
+    names.sort();
+
+    let mut existing: HashMap<String, String> = HashMap::new();
+    for (name, _) in &names {
+        existing.insert(CaseAgent::normalize_filename(name), name.clone());
+    }
+
+    let mut candidates = Vec::new();
+    let mut sequence = options.sequence_start.unwrap_or(1);
+    for (name, is_dir) in &names {
+        if (*is_dir && !options.include_dirs) || !regex.is_match(name) {
+            continue;
+        }
+        let target = if options.template {
+            let target = expand_template(replacement, name, sequence)?;
+            sequence += 1;
+            target
+        } else {
+            regex.replace_all(name, replacement).into_owned()
+        };
+        if &target != name {
+            candidates.push(PlannedRename { from: name.clone(), to: target });
+        }
+    }
+
+    let mut target_counts: HashMap<String, usize> = HashMap::new();
+    for rename in &candidates {
+        *target_counts.entry(CaseAgent::normalize_filename(&rename.to)).or_insert(0) += 1;
+    }
+
+    let mut planned = Vec::new();
+    let mut skipped = Vec::new();
+    for rename in candidates {
+        let key = CaseAgent::normalize_filename(&rename.to);
+        let reason = if let Some(reason) = invalid_name(&rename.to) {
+            Some(reason.to_string())
+        } else if target_counts[&key] > 1 {
+            Some("Another file would get the same name".to_string())
+        } else {
+            match existing.get(&key) {
+                // Changing only the case of its own name is fine
+                Some(other) if CaseAgent::normalize_filename(other) == CaseAgent::normalize_filename(&rename.from) => None,
+                Some(other) if other == &rename.to => Some(format!("{} already exists", other)),
+                Some(other) => Some(format!("{} already exists with different case", other)),
+                None => None,
+            }
+        };
+        match reason {
+            Some(reason) => skipped.push(SkippedRename { from: rename.from, to: rename.to, reason }),
+            None => planned.push(rename),
+        }
+    }
+    Ok((planned, skipped))
+}
+
+/// Apply the renames in order, logging each to the audit log
+pub fn apply_renames(connection: &SSHConnection, dir: &str, renames: Vec<PlannedRename>) -> Result<BatchRenameResult> {
+    let mut result = BatchRenameResult::default();
+    let dir = Path::new(dir);
+    let sftp = connection.sftp()?;
+
+    for rename in renames {
+        let from = dir.join(&rename.from);
+        let to = dir.join(&rename.to);
+        // No OVERWRITE flag, so a file that appeared since planning isn't clobbered
+        let outcome = sftp.rename(&from, &to, Some(RenameFlags::ATOMIC | RenameFlags::NATIVE));
+
+        let error = outcome.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = AUDIT_LOGGER.log_operation(
+            AuditOperation::FileMove,
+            Some(from.to_string_lossy().to_string()),
+            Some(to.to_string_lossy().to_string()),
+            None,
+            error.is_none(),
+            error.clone(),
+        ) {
+            tracing::warn!("Failed to audit rename of {}: {}", from.display(), e);
+        }
+
+        match error {
+            None => result.applied.push(rename),
+            Some(reason) => result.skipped.push(SkippedRename { from: rename.from, to: rename.to, reason }),
+        }
+    }
+    Ok(result)
+}
+
+// Tauri commands for batch renaming
+
+/// Rename the entries of `dir` whose names match the regex `pattern`
+#[tauri::command]
+pub async fn batch_rename(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+    pattern: String,
+    replacement: String,
+    options: Option<BatchRenameOptions>,
+) -> Result<BatchRenameResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let dir = expand_tilde(&connection, &dir).map_err(|e| e.to_string())?;
+    let options = options.unwrap_or_default();
+
+    let (planned, conflicts) = plan_renames(&connection, &dir, &pattern, &replacement, &options)
+        .map_err(|e| e.to_string())?;
+    if !conflicts.is_empty() && !options.skip_conflicts {
+        return Ok(BatchRenameResult {
+            applied: Vec::new(),
+            skipped: conflicts,
+            aborted: true,
+        });
+    }
+    if options.dry_run {
+        return Ok(BatchRenameResult {
+            applied: planned,
+            skipped: conflicts,
+            aborted: false,
+        });
+    }
+
+    let mut result = apply_renames(&connection, &dir, planned).map_err(|e| e.to_string())?;
+    result.skipped.extend(conflicts);
+    ssh_client.listing_cache.invalidate(&connection_id, &dir);
+    tracing::info!("Renamed {} entries in {}", result.applied.len(), dir);
+    Ok(result)
+}
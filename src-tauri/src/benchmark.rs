@@ -0,0 +1,332 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{System, SystemExt};
+use tauri::{AppHandle, State};
+use crate::error::{Circle9Error, Result};
+use crate::settings::{self, SettingsPatch};
+use crate::ssh_client::{ConnectionTuning, SSHClient, SSHConnection, MAX_SFTP_PIPELINE_DEPTH, SFTP_REQUEST_SIZE};
+
+/// Small round trips averaged for the latency figure
+const PING_ROUNDS: usize = 5;
+
+const DEFAULT_TEST_SIZE: u64 = 8 * 1024 * 1024;
+const MAX_TEST_SIZE: u64 = 256 * 1024 * 1024;
+
+const MIN_CHUNK_SIZE: usize = 32 * 1024;
+const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const MAX_RECOMMENDED_CONCURRENCY: usize = 8;
+
+/// Written to the local temp directory to gauge how fast downloads can land on disk
+const DISK_TEST_SIZE: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub connection_id: String,
+    pub latency: LatencyStats,
+    pub test_size: u64,
+    pub upload_mb_per_sec: f64,
+    pub download_mb_per_sec: f64,
+    /// The connection's current tuning with chunk size, concurrency and pipeline depth
+    /// sized for the measured link
+    pub recommended_tuning: ConnectionTuning,
+    /// Whether `recommended_tuning` was applied to the connection
+    pub applied: bool,
+}
+
+/// Time `PING_ROUNDS` realpath round trips
+fn measure_latency(connection: &SSHConnection) -> Result<LatencyStats> {
+    let mut samples = Vec::with_capacity(PING_ROUNDS);
+    for _ in 0..PING_ROUNDS {
+        let started = Instant::now();
+        connection.sftp()?.realpath(Path::new("."))?;
+        samples.push(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    Ok(LatencyStats {
+        min_ms: samples.iter().cloned().fold(f64::INFINITY, f64::min),
+        avg_ms: samples.iter().sum::<f64>() / samples.len() as f64,
+        max_ms: samples.iter().cloned().fold(0.0, f64::max),
+    })
+}
+
+/// Bytes that won't shrink if the session compresses
+fn fill_incompressible(buffer: &mut [u8], seed: &mut u64) {
+    for byte in buffer.iter_mut() {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *byte = *seed as u8;
+    }
+}
+
+fn mb_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+/// Create the scratch file in /tmp, or the login directory when /tmp isn't writable
+fn create_scratch(connection: &SSHConnection) -> Result<(PathBuf, ssh2::File)> {
+    let name = format!(".circle9-benchmark-{}", uuid::Uuid::new_v4());
+    let mut candidates = vec![PathBuf::from("/tmp").join(&name)];
+    if let Some(home) = &connection.home_dir {
+        candidates.push(Path::new(home).join(&name));
+    }
+
+    let sftp = connection.sftp()?;
+    let mut last_error = None;
+    for path in candidates {
+        match sftp.create(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .map(Circle9Error::from)
+        .unwrap_or_else(|| Circle9Error::SSHError("No writable directory for the benchmark file".to_string())))
+}
+
+/// Upload then download `size` bytes, returning (upload, download) MB/s
+fn measure_throughput(connection: &SSHConnection, size: u64, buffer_size: usize) -> Result<(f64, f64)> {
+    let (path, mut file) = create_scratch(connection)?;
+    let result = (|| -> Result<(f64, f64)> {
+        let mut buffer = vec![0u8; buffer_size];
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+
+        let started = Instant::now();
+        let mut written = 0u64;
+        while written < size {
+            let n = buffer.len().min((size - written) as usize);
+            fill_incompressible(&mut buffer[..n], &mut seed);
+            file.write_all(&buffer[..n])?;
+            written += n as u64;
+        }
+        file.fsync().ok();
+        drop(file);
+        let upload = mb_per_sec(written, started.elapsed());
+
+        let started = Instant::now();
+        let mut remote = connection.sftp()?.open(&path)?;
+        let mut read = 0u64;
+        loop {
+            let n = remote.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            read += n as u64;
+        }
+        if read != written {
+            return Err(Circle9Error::TransferError(format!(
+                "Read back {} of {} benchmark bytes", read, written
+            )));
+        }
+        Ok((upload, mb_per_sec(read, started.elapsed())))
+    })();
+
+    // Always remove the scratch file, even when a measurement failed
+    if let Err(e) = connection.sftp().and_then(|sftp| Ok(sftp.unlink(&path)?)) {
+        tracing::warn!("Failed to remove benchmark file {}: {}", path.display(), e);
+    }
+    result
+}
+
+/// Size chunks and the request pipeline to the bandwidth-delay product, and run more
+/// transfers side by side the further away the server is
+fn recommend(current: &ConnectionTuning, latency: &LatencyStats, download_mb_per_sec: f64) -> ConnectionTuning {
+    let bytes_per_sec = download_mb_per_sec * 1024.0 * 1024.0;
+    let bdp = (bytes_per_sec * latency.avg_ms / 1000.0) as usize;
+
+    let chunk_size = bdp.next_power_of_two().clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+    let pipeline_depth = ((bdp + SFTP_REQUEST_SIZE - 1) / SFTP_REQUEST_SIZE).clamp(1, MAX_SFTP_PIPELINE_DEPTH);
+    let concurrency = (1 + (latency.avg_ms / 25.0) as usize).clamp(2, MAX_RECOMMENDED_CONCURRENCY);
+
+    ConnectionTuning {
+        chunk_size,
+        max_concurrent_transfers: concurrency,
+        sftp_pipeline_depth: pipeline_depth,
+        ..current.clone()
+    }
+}
+
+pub fn benchmark(connection: &SSHConnection, connection_id: &str, test_size: u64) -> Result<BenchmarkResult> {
+    let tuning = connection.tuning();
+    let latency = measure_latency(connection)?;
+    let (upload_mb_per_sec, download_mb_per_sec) = measure_throughput(connection, test_size, tuning.sftp_buffer_size())?;
+
+    Ok(BenchmarkResult {
+        connection_id: connection_id.to_string(),
+        recommended_tuning: recommend(&tuning, &latency, download_mb_per_sec),
+        latency,
+        test_size,
+        upload_mb_per_sec,
+        download_mb_per_sec,
+        applied: false,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyRecommendation {
+    pub benchmark: BenchmarkResult,
+    pub cpu_count: usize,
+    /// One-minute load average; 0 where the platform doesn't report one
+    pub load_average: f64,
+    pub disk_write_mb_per_sec: f64,
+    pub max_concurrent_transfers: usize,
+    pub chunk_size: usize,
+    /// Why each limit was chosen, in the order they were applied
+    pub reasoning: Vec<String>,
+    pub applied: bool,
+}
+
+/// Write and sync `DISK_TEST_SIZE` bytes in the temp directory, returning MB/s
+fn measure_disk_write(buffer_size: usize) -> Result<f64> {
+    let path = std::env::temp_dir().join(format!(".circle9-disk-{}", uuid::Uuid::new_v4()));
+    let result = (|| -> Result<f64> {
+        let mut file = std::fs::File::create(&path)?;
+        let mut buffer = vec![0u8; buffer_size];
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let started = Instant::now();
+        let mut written = 0u64;
+        while written < DISK_TEST_SIZE {
+            let n = buffer.len().min((DISK_TEST_SIZE - written) as usize);
+            fill_incompressible(&mut buffer[..n], &mut seed);
+            file.write_all(&buffer[..n])?;
+            written += n as u64;
+        }
+        file.sync_all()?;
+        Ok(mb_per_sec(written, started.elapsed()))
+    })();
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::warn!("Failed to remove disk test file {}: {}", path.display(), e);
+    }
+    result
+}
+
+/// Start from what the link wants, then cut back for a busy CPU and a disk slower than the link
+pub fn recommend_concurrency_for(connection: &SSHConnection, connection_id: &str) -> Result<ConcurrencyRecommendation> {
+    let benchmark = benchmark(connection, connection_id, DEFAULT_TEST_SIZE)?;
+    let disk_write_mb_per_sec = measure_disk_write(benchmark.recommended_tuning.sftp_buffer_size())?;
+
+    let cpu_count = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let load_average = System::new().load_average().one;
+
+    let mut reasoning = Vec::new();
+    let mut concurrency = benchmark.recommended_tuning.max_concurrent_transfers;
+    reasoning.push(format!(
+        "{:.0} ms round trips suggest {} transfers at once to keep the link busy",
+        benchmark.latency.avg_ms, concurrency
+    ));
+
+    // Each transfer spends a core on encryption; leave one for the app itself
+    let free_cpus = ((cpu_count as f64 - load_average).floor() as usize).saturating_sub(1).max(1);
+    if free_cpus < concurrency {
+        concurrency = free_cpus;
+        reasoning.push(format!(
+            "Limited to {} because only about {} of {} CPUs are free (load {:.2})",
+            concurrency, free_cpus, cpu_count, load_average
+        ));
+    }
+
+    // Parallel writers on a disk that can't keep up with one download only add seeking
+    if disk_write_mb_per_sec < benchmark.download_mb_per_sec * 1.5 {
+        let limit = if disk_write_mb_per_sec < benchmark.download_mb_per_sec { 1 } else { 2 };
+        if limit < concurrency {
+            concurrency = limit;
+            reasoning.push(format!(
+                "Limited to {} because the local disk writes {:.1} MB/s against {:.1} MB/s from the server",
+                concurrency, disk_write_mb_per_sec, benchmark.download_mb_per_sec
+            ));
+        }
+    }
+
+    let chunk_size = benchmark.recommended_tuning.chunk_size;
+    reasoning.push(format!(
+        "{} KiB chunks cover the {:.1} MB/s link's bandwidth-delay product",
+        chunk_size / 1024, benchmark.download_mb_per_sec
+    ));
+
+    Ok(ConcurrencyRecommendation {
+        benchmark,
+        cpu_count,
+        load_average,
+        disk_write_mb_per_sec,
+        max_concurrent_transfers: concurrency,
+        chunk_size,
+        reasoning,
+        applied: false,
+    })
+}
+
+// Tauri commands for connection benchmarks
+
+/// Measure latency and throughput to the server with a temporary file of `test_size`
+/// bytes (8 MiB by default), which is removed afterwards. With `apply`, the recommended
+/// tuning replaces the connection's.
+#[tauri::command]
+pub async fn benchmark_connection(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    test_size: Option<u64>,
+    apply: Option<bool>,
+) -> Result<BenchmarkResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let test_size = test_size.unwrap_or(DEFAULT_TEST_SIZE);
+    if test_size == 0 || test_size > MAX_TEST_SIZE {
+        return Err(format!("Test size must be between 1 and {} bytes", MAX_TEST_SIZE));
+    }
+
+    let mut result = benchmark(&connection, &connection_id, test_size).map_err(|e| e.to_string())?;
+    tracing::info!(
+        "Benchmarked {}: {:.1} ms, up {:.1} MB/s, down {:.1} MB/s",
+        connection_id, result.latency.avg_ms, result.upload_mb_per_sec, result.download_mb_per_sec
+    );
+
+    if apply.unwrap_or(false) {
+        ssh_client.set_tuning(&connection_id, result.recommended_tuning.clone())
+            .map_err(|e| e.to_string())?;
+        result.applied = true;
+    }
+    Ok(result)
+}
+
+/// Benchmark the link and the local CPU and disk, and recommend a concurrency and chunk
+/// size with the reasoning behind them. With `apply`, they become the connection's tuning
+/// and the global concurrency limit.
+#[tauri::command]
+pub async fn recommend_concurrency(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    apply: Option<bool>,
+) -> Result<ConcurrencyRecommendation, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    let mut recommendation = recommend_concurrency_for(&connection, &connection_id)
+        .map_err(|e| e.to_string())?;
+
+    if apply.unwrap_or(false) {
+        let tuning = ConnectionTuning {
+            chunk_size: recommendation.chunk_size,
+            max_concurrent_transfers: recommendation.max_concurrent_transfers,
+            ..recommendation.benchmark.recommended_tuning.clone()
+        };
+        ssh_client.set_tuning(&connection_id, tuning)
+            .map_err(|e| e.to_string())?;
+        let patch = SettingsPatch {
+            max_concurrent_transfers: Some(recommendation.max_concurrent_transfers),
+            ..SettingsPatch::default()
+        };
+        settings::update(&app_handle, patch)
+            .map_err(|e| e.to_string())?;
+        recommendation.applied = true;
+    }
+    Ok(recommendation)
+}
@@ -3,15 +3,37 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use anyhow::{Result, Context};
 use chrono::{DateTime, Utc};
+use std::io::{Read, Write};
+use tauri::State;
+use crate::remote_exec::{exec_command, shell_quote};
+use crate::settings::{self, CaseStrategy};
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+/// Stand-ins for a trailing `.` and ` `, which Windows would otherwise strip
+const TRAILING_DOT: char = '\u{F02E}';
+const TRAILING_SPACE: char = '\u{F020}';
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaseConflict {
     pub original_name: String,
     pub conflict_name: String,
     pub resolution: CaseResolution,
+    #[serde(default)]
+    pub reason: ConflictReason,
     pub timestamp: DateTime<Utc>,
 }
 
+/// What makes two names clash on the destination
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ConflictReason {
+    /// The names differ only in letter case
+    #[default]
+    CaseOnly,
+    /// Windows strips trailing dots and spaces, so e.g. `data.` and `data` become one file
+    TrailingDotOrSpace,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CaseResolution {
     AutoRename(String),
@@ -28,9 +50,19 @@ pub struct CaseConflictLog {
     pub user_prompts: usize,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteCaseProbe {
+    pub dir: String,
+    pub mount_point: String,
+    pub case_sensitive: bool,
+    pub cached: bool,
+}
+
 pub struct CaseAgent {
     conflict_log: CaseConflictLog,
     case_mapping: HashMap<String, String>, // Maps original names to resolved names
+    remote_case_sensitivity: HashMap<(String, String), bool>, // (connection, mount point) -> sensitive
+    remote_mount_points: HashMap<(String, String), String>, // (connection, dir) -> mount point
 }
 
 impl CaseAgent {
@@ -43,14 +75,19 @@ impl CaseAgent {
                 user_prompts: 0,
             },
             case_mapping: HashMap::new(),
+            remote_case_sensitivity: HashMap::new(),
+            remote_mount_points: HashMap::new(),
         }
     }
 
-    /// Check for case conflicts when copying from Windows to Linux
+    /// Check for case conflicts when copying from Windows to Linux.
+    /// On a case-insensitive remote filesystem a case-only difference would overwrite
+    /// the existing file, so it is escalated to a prompt instead of an auto-rename.
     pub fn check_windows_to_linux_conflict(
         &mut self,
         windows_path: &Path,
         linux_path: &Path,
+        remote_case_sensitive: bool,
     ) -> Result<Option<CaseConflict>> {
         let windows_name = windows_path.file_name()
             .and_then(|n| n.to_str())
@@ -65,7 +102,12 @@ impl CaseAgent {
             let conflict = CaseConflict {
                 original_name: windows_name.to_string(),
                 conflict_name: linux_name.to_string(),
-                resolution: CaseResolution::AutoRename(self.generate_unique_name(linux_path)?),
+                resolution: if remote_case_sensitive && settings::current().case_strategy == CaseStrategy::AutoRename {
+                    CaseResolution::AutoRename(self.generate_unique_name(linux_path)?)
+                } else {
+                    CaseResolution::UserPrompt
+                },
+                reason: ConflictReason::CaseOnly,
                 timestamp: Utc::now(),
             };
 
@@ -90,12 +132,30 @@ impl CaseAgent {
             .and_then(|n| n.to_str())
             .unwrap_or("");
 
+        // Windows would store `data.` as `data`, clobbering any file already called that
+        if Self::has_trailing_dot_or_space(linux_name) {
+            let stripped_path = windows_path.with_file_name(Self::windows_stored_name(windows_name));
+            if stripped_path.exists() {
+                let conflict = CaseConflict {
+                    original_name: linux_name.to_string(),
+                    conflict_name: Self::windows_stored_name(windows_name),
+                    resolution: CaseResolution::AutoRename(Self::encode_trailing(linux_name)),
+                    reason: ConflictReason::TrailingDotOrSpace,
+                    timestamp: Utc::now(),
+                };
+
+                self.log_conflict(&conflict);
+                return Ok(Some(conflict));
+            }
+        }
+
         // Windows is case-insensitive, so we need to check if a file with the same name exists
         if std::path::Path::new(windows_path).exists() {
             let conflict = CaseConflict {
                 original_name: linux_name.to_string(),
                 conflict_name: windows_name.to_string(),
                 resolution: CaseResolution::UserPrompt,
+                reason: ConflictReason::CaseOnly,
                 timestamp: Utc::now(),
             };
 
@@ -106,8 +166,91 @@ impl CaseAgent {
         Ok(None)
     }
 
+    /// Find Linux names in one directory that Windows would store under the same name
+    /// once trailing dots and spaces are stripped, e.g. `file`, `file.` and `file...`.
+    /// Every name with a trailing dot or space in a colliding group gets a round-trip rename.
+    pub fn find_trailing_collisions(&mut self, names: &[String]) -> Vec<CaseConflict> {
+        let mut groups: HashMap<String, Vec<&String>> = HashMap::new();
+        for name in names {
+            groups.entry(Self::windows_stored_name(name).to_lowercase()).or_default().push(name);
+        }
+
+        let mut conflicts = Vec::new();
+        for group in groups.values().filter(|group| group.len() > 1) {
+            for name in group.iter().filter(|name| Self::has_trailing_dot_or_space(name)) {
+                let conflict = CaseConflict {
+                    original_name: name.to_string(),
+                    conflict_name: Self::windows_stored_name(name),
+                    resolution: CaseResolution::AutoRename(Self::encode_trailing(name)),
+                    reason: ConflictReason::TrailingDotOrSpace,
+                    timestamp: Utc::now(),
+                };
+                self.log_conflict(&conflict);
+                conflicts.push(conflict);
+            }
+        }
+        conflicts.sort_by(|a, b| a.original_name.cmp(&b.original_name));
+        conflicts
+    }
+
+    pub fn has_trailing_dot_or_space(name: &str) -> bool {
+        name.ends_with('.') || name.ends_with(' ')
+    }
+
+    /// The name Windows actually stores: trailing dots and spaces removed
+    pub fn windows_stored_name(name: &str) -> String {
+        name.trim_end_matches(|c| c == '.' || c == ' ').to_string()
+    }
+
+    /// Swap the trailing dots and spaces for their private-use stand-ins, U+F02E and U+F020,
+    /// so the name survives Windows and `decode_trailing` can restore it. This is the mapping
+    /// Cygwin and WSL use; unlike an escape such as `%2E` it can't collide with a real name.
+    pub fn encode_trailing(name: &str) -> String {
+        let stem = Self::windows_stored_name(name);
+        let tail: String = name[stem.len()..].chars()
+            .map(|c| if c == '.' { TRAILING_DOT } else { TRAILING_SPACE })
+            .collect();
+        format!("{}{}", stem, tail)
+    }
+
+    /// Reverse `encode_trailing` when copying back to Linux
+    pub fn decode_trailing(name: &str) -> String {
+        let stem = name.trim_end_matches([TRAILING_DOT, TRAILING_SPACE]);
+        let tail: String = name[stem.len()..].chars()
+            .map(|c| if c == TRAILING_DOT { '.' } else { ' ' })
+            .collect();
+        format!("{}{}", stem, tail)
+    }
+
+    /// `decode_trailing` applied to every component of an upload's destination, so files and
+    /// directories auto-renamed on the way to Windows get their Linux names back.
+    /// Names are left alone when the user resolves conflicts by prompt instead.
+    pub fn restore_trailing_names(path: &Path) -> PathBuf {
+        if !matches!(settings::current().case_strategy, CaseStrategy::AutoRename) {
+            return path.to_path_buf();
+        }
+        path.components()
+            .map(|component| match component {
+                std::path::Component::Normal(name) => match name.to_str() {
+                    Some(name) => PathBuf::from(Self::decode_trailing(name)),
+                    None => PathBuf::from(name),
+                },
+                other => PathBuf::from(other.as_os_str()),
+            })
+            .collect()
+    }
+
     /// Generate a unique name to avoid conflicts
     fn generate_unique_name(&self, path: &Path) -> Result<String> {
+        Self::generate_unique_name_with(path, |p| p.exists())
+    }
+
+    /// Generate a unique name next to `path`, using `exists` to test candidates
+    /// (e.g. an SFTP stat for remote destinations)
+    pub fn generate_unique_name_with<F>(path: &Path, exists: F) -> Result<String>
+    where
+        F: Fn(&Path) -> bool,
+    {
         let parent = path.parent().unwrap_or(Path::new("."));
         let stem = path.file_stem()
             .and_then(|s| s.to_str())
@@ -122,7 +265,7 @@ impl CaseAgent {
             let new_name = format!("{}_{}{}", stem, counter, extension);
             let new_path = parent.join(&new_name);
             
-            if !new_path.exists() {
+            if !exists(&new_path) {
                 return Ok(new_name);
             }
             
@@ -182,6 +325,52 @@ impl CaseAgent {
         { true }
     }
 
+    /// Cached case sensitivity of the filesystem holding a remote directory, if probed
+    pub fn remote_case_sensitivity(&self, connection_id: &str, dir: &str) -> Option<bool> {
+        let mount = self.remote_mount_points.get(&(connection_id.to_string(), dir.to_string()))?;
+        self.remote_case_sensitivity.get(&(connection_id.to_string(), mount.clone())).copied()
+    }
+
+    /// Cached probe result for a mount point
+    pub fn mount_case_sensitivity(&self, connection_id: &str, mount_point: &str) -> Option<bool> {
+        self.remote_case_sensitivity.get(&(connection_id.to_string(), mount_point.to_string())).copied()
+    }
+
+    /// Remember the probe result for a remote mount point
+    pub fn record_remote_case_sensitivity(&mut self, connection_id: &str, dir: &str, mount_point: &str, case_sensitive: bool) {
+        self.remote_mount_points.insert((connection_id.to_string(), dir.to_string()), mount_point.to_string());
+        self.remote_case_sensitivity.insert((connection_id.to_string(), mount_point.to_string()), case_sensitive);
+    }
+
+    /// Probe whether a remote directory lives on a case-insensitive filesystem by writing
+    /// two files whose names differ only in case and checking whether they are the same file
+    pub fn probe_remote_case_sensitivity(connection: &SSHConnection, dir: &str) -> Result<bool> {
+        let sftp = connection.sftp()?;
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let lower = Path::new(dir).join(format!(".circle9-case-probe-{}", token));
+        let upper = Path::new(dir).join(format!(".CIRCLE9-CASE-PROBE-{}", token.to_uppercase()));
+
+        let result = (|| -> Result<bool> {
+            sftp.create(&lower)
+                .context("Failed to create case probe file")?
+                .write_all(b"lower")?;
+            sftp.create(&upper)
+                .context("Failed to create case probe file")?
+                .write_all(b"upper")?;
+
+            // If both names refer to one file, the second write replaced the first
+            let mut contents = String::new();
+            sftp.open(&lower)
+                .context("Failed to reopen case probe file")?
+                .read_to_string(&mut contents)?;
+            Ok(contents == "lower")
+        })();
+
+        sftp.unlink(&upper).ok();
+        sftp.unlink(&lower).ok();
+        result
+    }
+
     /// Normalize filename for case-insensitive comparison
     pub fn normalize_filename(filename: &str) -> String {
         filename.to_lowercase()
@@ -205,6 +394,7 @@ pub async fn check_case_conflict(
     source_path: String,
     dest_path: String,
     direction: String, // "windows_to_linux" or "linux_to_windows"
+    connection_id: Option<String>,
 ) -> Result<Option<CaseConflict>, String> {
     let source = Path::new(&source_path);
     let dest = Path::new(&dest_path);
@@ -213,7 +403,14 @@ pub async fn check_case_conflict(
     
     match direction.as_str() {
         "windows_to_linux" => {
-            agent.check_windows_to_linux_conflict(source, dest)
+            // Linux is assumed case-sensitive unless the destination has been probed
+            let remote_case_sensitive = connection_id
+                .and_then(|id| {
+                    let dir = dest.parent()?.to_string_lossy().to_string();
+                    agent.remote_case_sensitivity(&id, &dir)
+                })
+                .unwrap_or(true);
+            agent.check_windows_to_linux_conflict(source, dest, remote_case_sensitive)
                 .map_err(|e| e.to_string())
         }
         "linux_to_windows" => {
@@ -224,6 +421,12 @@ pub async fn check_case_conflict(
     }
 }
 
+#[tauri::command]
+pub async fn find_trailing_name_collisions(names: Vec<String>) -> Result<Vec<CaseConflict>, String> {
+    let mut agent = CASE_AGENT.lock().unwrap();
+    Ok(agent.find_trailing_collisions(&names))
+}
+
 #[tauri::command]
 pub async fn resolve_case_conflict(
     original_name: String,
@@ -261,3 +464,80 @@ pub async fn normalize_filename(filename: String) -> Result<String, String> {
 pub async fn filenames_equal_ignore_case(name1: String, name2: String) -> Result<bool, String> {
     Ok(CaseAgent::filenames_equal_ignore_case(&name1, &name2))
 }
+
+#[tauri::command]
+pub async fn probe_remote_case_sensitivity(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+) -> Result<RemoteCaseProbe, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    detect_remote_case_sensitivity(&connection, &connection_id, dir)
+        .map_err(|e| e.to_string())
+}
+
+/// Case sensitivity of the filesystem holding a remote directory, probing it unless its mount
+/// point has been probed before
+pub fn detect_remote_case_sensitivity(connection: &SSHConnection, connection_id: &str, dir: String) -> Result<RemoteCaseProbe> {
+    // Results are cached per mount point; fall back to the directory itself if df is unavailable
+    let mount_point = exec_command(connection, &format!("df -P {} | tail -n 1", shell_quote(&dir)))
+        .ok()
+        .filter(|output| output.success())
+        .and_then(|output| output.stdout.split_whitespace().last().map(str::to_string))
+        .unwrap_or_else(|| dir.clone());
+
+    let cached = lock_or_error(&CASE_AGENT)?.mount_case_sensitivity(connection_id, &mount_point);
+    if let Some(case_sensitive) = cached {
+        lock_or_error(&CASE_AGENT)?
+            .record_remote_case_sensitivity(connection_id, &dir, &mount_point, case_sensitive);
+        return Ok(RemoteCaseProbe { dir, mount_point, case_sensitive, cached: true });
+    }
+
+    let case_sensitive = CaseAgent::probe_remote_case_sensitivity(connection, &dir)?;
+    lock_or_error(&CASE_AGENT)?
+        .record_remote_case_sensitivity(connection_id, &dir, &mount_point, case_sensitive);
+
+    Ok(RemoteCaseProbe { dir, mount_point, case_sensitive, cached: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_dots_and_spaces_round_trip() {
+        for name in ["file.", "file ", "file...", "file. .", "archive.tar.gz."] {
+            let encoded = CaseAgent::encode_trailing(name);
+            assert!(!CaseAgent::has_trailing_dot_or_space(&encoded), "{:?} encoded as {:?}", name, encoded);
+            assert_eq!(CaseAgent::decode_trailing(&encoded), name);
+        }
+        assert_eq!(CaseAgent::encode_trailing("file."), "file\u{F02E}");
+        assert_eq!(CaseAgent::encode_trailing("file "), "file\u{F020}");
+        assert_eq!(CaseAgent::encode_trailing("file. "), "file\u{F02E}\u{F020}");
+    }
+
+    #[test]
+    fn plain_names_decode_unchanged() {
+        assert_eq!(CaseAgent::decode_trailing("file"), "file");
+        assert_eq!(CaseAgent::decode_trailing("file.txt"), "file.txt");
+    }
+
+    #[test]
+    fn percent_escapes_in_real_names_survive() {
+        for name in ["Q3%20", "page%2E", "100%", "Q3%20 "] {
+            assert_eq!(CaseAgent::decode_trailing(&CaseAgent::encode_trailing(name)), name);
+        }
+        assert_eq!(CaseAgent::decode_trailing("Q3%20"), "Q3%20");
+        assert_eq!(CaseAgent::decode_trailing("page%2E"), "page%2E");
+    }
+
+    #[test]
+    fn upload_paths_get_every_component_restored() {
+        // Settings default to AutoRename
+        assert_eq!(
+            CaseAgent::restore_trailing_names(Path::new("dir\u{F02E}/sub/file\u{F020}")),
+            PathBuf::from("dir./sub/file "),
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::{DateTime, Duration, Utc};
+use tauri::{AppHandle, Manager};
+use crate::copy_agent::CopyAgent;
+use crate::error::{Circle9Error, Result};
+use crate::settings;
+
+/// When repeated transfer failures stop a connection's queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the breaker
+    pub failure_threshold: u32,
+    /// Failures further apart than this don't count as consecutive
+    pub window_secs: u64,
+    /// How long an open breaker blocks the queue before one task is let through to probe
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window_secs: 300,
+            cooldown_secs: 300,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.failure_threshold == 0 || self.cooldown_secs == 0 {
+            return Err(Circle9Error::InvalidValue(
+                "Circuit breaker threshold and cooldown must be greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CircuitState {
+    pub consecutive_failures: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Set while open, and kept once the cooldown passes so a single failure reopens it
+    pub open_until: Option<DateTime<Utc>>,
+}
+
+impl CircuitState {
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.open_until.map_or(false, |until| now < until)
+    }
+}
+
+/// Payload of `connection_circuit_open`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitOpenEvent {
+    pub connection_id: String,
+    pub consecutive_failures: u32,
+    pub last_error: String,
+    pub retry_at: DateTime<Utc>,
+    /// Queued transfers now Blocked until the breaker closes
+    pub blocked_tasks: usize,
+}
+
+impl CircuitOpenEvent {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("connection_circuit_open", self) {
+            tracing::error!("Failed to emit circuit open event: {}", e);
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CIRCUITS: Mutex<HashMap<String, CircuitState>> = Mutex::new(HashMap::new());
+}
+
+/// Whether transfers through `connection_id` are currently held back
+pub fn is_open(connection_id: &str) -> bool {
+    CIRCUITS.lock()
+        .map(|circuits| circuits.get(connection_id).map_or(false, |c| c.is_open(Utc::now())))
+        .unwrap_or(false)
+}
+
+/// Count a failed transfer, returning when the breaker reopens if this failure opened it
+pub fn record_failure(connection_id: &str) -> Option<(u32, DateTime<Utc>)> {
+    let config = settings::current().circuit_breaker;
+    let now = Utc::now();
+    let mut circuits = CIRCUITS.lock().ok()?;
+    let state = circuits.entry(connection_id.to_string()).or_default();
+
+    let within_window = state.last_failure
+        .map_or(false, |last| now - last <= Duration::seconds(config.window_secs as i64));
+    state.consecutive_failures = if within_window { state.consecutive_failures + 1 } else { 1 };
+    state.last_failure = Some(now);
+
+    // After a cooldown the first task through is a probe; failing it reopens straight away
+    let probing = state.open_until.is_some();
+    if state.is_open(now) || !(probing || state.consecutive_failures >= config.failure_threshold) {
+        return None;
+    }
+    let retry_at = now + Duration::seconds(config.cooldown_secs as i64);
+    state.open_until = Some(retry_at);
+    Some((state.consecutive_failures, retry_at))
+}
+
+pub fn record_success(connection_id: &str) {
+    if let Ok(mut circuits) = CIRCUITS.lock() {
+        circuits.remove(connection_id);
+    }
+}
+
+/// Close the breaker after the connection proved healthy and release its blocked transfers
+pub fn close(app_handle: &AppHandle, connection_id: &str) -> Result<usize> {
+    let was_tripped = CIRCUITS.lock()
+        .map_err(|_| Circle9Error::MutexPoisoned)?
+        .remove(connection_id)
+        .map_or(false, |state| state.open_until.is_some());
+    if !was_tripped {
+        return Ok(0);
+    }
+
+    let released = app_handle.state::<CopyAgent>().release_blocked(Some(connection_id))?;
+    tracing::info!("Closed circuit breaker for {}, released {} transfers", connection_id, released);
+    if let Err(e) = app_handle.emit_all("connection_circuit_closed", connection_id) {
+        tracing::error!("Failed to emit connection_circuit_closed: {}", e);
+    }
+    Ok(released)
+}
+
+pub fn state(connection_id: &str) -> CircuitState {
+    CIRCUITS.lock()
+        .ok()
+        .and_then(|circuits| circuits.get(connection_id).cloned())
+        .unwrap_or_default()
+}
+
+// Tauri commands for the circuit breaker
+
+#[tauri::command]
+pub async fn get_circuit_state(connection_id: String) -> Result<CircuitState, String> {
+    Ok(state(&connection_id))
+}
+
@@ -0,0 +1,348 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Bumped when the export format changes incompatibly
+const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// A saved server definition. It has no secret fields: passwords and passphrases are
+/// entered at connect time or kept in secure storage, and keys are referenced by path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// Path of the private key on the machine using the profile, never its contents
+    #[serde(default)]
+    pub key_path: Option<String>,
+    #[serde(default)]
+    pub expected_host_key_fingerprint: Option<String>,
+    #[serde(default)]
+    pub session_label: Option<String>,
+}
+
+impl ConnectionProfile {
+    fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(Circle9Error::InvalidValue("Profile name is required".to_string()));
+        }
+        if self.host.trim().is_empty() || self.username.trim().is_empty() || self.port == 0 {
+            return Err(Circle9Error::InvalidValue(format!("Profile {} needs a host, port and username", self.name)));
+        }
+        // Catch a pasted key where a path belongs, so it can't end up in an export
+        if self.key_path.as_deref().map_or(false, |k| k.contains("PRIVATE KEY") || k.contains('\n')) {
+            return Err(Circle9Error::InvalidValue(format!("Profile {} has key contents instead of a key path", self.name)));
+        }
+        Ok(())
+    }
+}
+
+/// One field whose saved value differs from the connection's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileFieldDiff {
+    pub field: String,
+    pub profile: Option<String>,
+    pub live: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileDiff {
+    pub profile_name: String,
+    pub connection_id: String,
+    pub differences: Vec<ProfileFieldDiff>,
+}
+
+/// The profile `name` would be if saved from `connection` as it is now
+fn profile_from_connection(name: &str, connection: &SSHConnection) -> ConnectionProfile {
+    let config = &connection.config;
+    ConnectionProfile {
+        name: name.to_string(),
+        host: config.host.clone(),
+        port: config.port,
+        username: config.username.clone(),
+        key_path: config.key_path.clone(),
+        expected_host_key_fingerprint: config.expected_host_key_fingerprint.clone(),
+        session_label: connection.label.clone(),
+    }
+}
+
+/// Blank optional fields mean the same as missing ones
+fn normalized(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// The fields of `saved` that differ from `live`, ignoring the name
+fn differences(saved: &ConnectionProfile, live: &ConnectionProfile) -> Vec<ProfileFieldDiff> {
+    let fields = [
+        ("host", Some(saved.host.clone()), Some(live.host.clone())),
+        ("port", Some(saved.port.to_string()), Some(live.port.to_string())),
+        ("username", Some(saved.username.clone()), Some(live.username.clone())),
+        ("key_path", normalized(&saved.key_path), normalized(&live.key_path)),
+        (
+            "expected_host_key_fingerprint",
+            normalized(&saved.expected_host_key_fingerprint),
+            normalized(&live.expected_host_key_fingerprint),
+        ),
+        ("session_label", normalized(&saved.session_label), normalized(&live.session_label)),
+    ];
+    fields.into_iter()
+        .filter(|(_, profile, live)| profile != live)
+        .map(|(field, profile, live)| ProfileFieldDiff { field: field.to_string(), profile, live })
+        .collect()
+}
+
+fn find_profile(profiles: &[ConnectionProfile], name: &str) -> Result<usize> {
+    profiles.iter()
+        .position(|p| p.name == name)
+        .ok_or_else(|| Circle9Error::NotFound(format!("Profile {}", name)))
+}
+
+/// How the live connection differs from the saved profile `name`
+pub fn diff_against_connection(name: &str, connection_id: &str, connection: &SSHConnection) -> Result<ProfileDiff> {
+    let profiles = {
+        let _guard = lock_profiles()?;
+        load_profiles()?
+    };
+    let saved = &profiles[find_profile(&profiles, name)?];
+    Ok(ProfileDiff {
+        profile_name: name.to_string(),
+        connection_id: connection_id.to_string(),
+        differences: differences(saved, &profile_from_connection(name, connection)),
+    })
+}
+
+/// Overwrite the saved profile `name` with the live connection's settings, returning what changed
+pub fn update_from_connection(name: &str, connection_id: &str, connection: &SSHConnection) -> Result<ProfileDiff> {
+    let live = profile_from_connection(name, connection);
+    live.validate()?;
+
+    let _guard = lock_profiles()?;
+    let mut profiles = load_profiles()?;
+    let index = find_profile(&profiles, name)?;
+    let diff = ProfileDiff {
+        profile_name: name.to_string(),
+        connection_id: connection_id.to_string(),
+        differences: differences(&profiles[index], &live),
+    };
+    if !diff.differences.is_empty() {
+        profiles[index] = live;
+        save_profiles(&profiles)?;
+    }
+    Ok(diff)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileExport {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    profiles: Vec<ConnectionProfile>,
+}
+
+/// What to do with an imported profile whose name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ProfileMergeStrategy {
+    Skip,
+    Overwrite,
+    /// Import under the first free name of the form "name (2)"
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileImportReport {
+    pub imported: Vec<String>,
+    pub overwritten: Vec<String>,
+    /// (name in the file, name it was imported as)
+    pub renamed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+lazy_static::lazy_static! {
+    /// Serialises read-modify-write cycles on the profiles file
+    static ref PROFILES_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn profiles_file() -> Result<PathBuf> {
+    Ok(crate::utils::app_data_dir()?.join("connection_profiles.json"))
+}
+
+fn load_profiles() -> Result<Vec<ConnectionProfile>> {
+    let path = profiles_file()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+fn save_profiles(profiles: &[ConnectionProfile]) -> Result<()> {
+    let path = profiles_file()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    crate::utils::write_atomic(&path, serde_json::to_string_pretty(profiles)?.as_bytes())
+}
+
+fn lock_profiles() -> Result<std::sync::MutexGuard<'static, ()>> {
+    PROFILES_LOCK.lock().map_err(|_| Circle9Error::MutexPoisoned)
+}
+
+/// Add a profile, replacing any with the same name
+pub fn upsert_profile(profile: ConnectionProfile) -> Result<()> {
+    profile.validate()?;
+    let _guard = lock_profiles()?;
+    let mut profiles = load_profiles()?;
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    save_profiles(&profiles)
+}
+
+pub fn remove_profile(name: &str) -> Result<bool> {
+    let _guard = lock_profiles()?;
+    let mut profiles = load_profiles()?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+    let removed = profiles.len() != before;
+    if removed {
+        save_profiles(&profiles)?;
+    }
+    Ok(removed)
+}
+
+/// Write every saved profile to a portable file, returning how many were written
+pub fn export_to(path: &Path) -> Result<usize> {
+    let profiles = {
+        let _guard = lock_profiles()?;
+        load_profiles()?
+    };
+    let export = ProfileExport {
+        version: EXPORT_FORMAT_VERSION,
+        exported_at: Utc::now(),
+        profiles,
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&export)?)?;
+    Ok(export.profiles.len())
+}
+
+fn free_name(name: &str, taken: &HashMap<String, usize>) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken.contains_key(candidate))
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Merge the profiles in an exported file into the saved ones
+pub fn import_from(path: &Path, strategy: ProfileMergeStrategy) -> Result<ProfileImportReport> {
+    let export: ProfileExport = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if export.version > EXPORT_FORMAT_VERSION {
+        return Err(Circle9Error::InvalidValue(format!(
+            "Profile file is version {}, newer than this version of Circle9 supports", export.version
+        )));
+    }
+    for profile in &export.profiles {
+        profile.validate()?;
+    }
+
+    let _guard = lock_profiles()?;
+    let mut profiles = load_profiles()?;
+    let mut taken: HashMap<String, usize> = profiles.iter()
+        .enumerate()
+        .map(|(index, p)| (p.name.clone(), index))
+        .collect();
+    let mut report = ProfileImportReport::default();
+
+    for mut profile in export.profiles {
+        match (taken.get(&profile.name).copied(), strategy) {
+            (None, _) => {
+                report.imported.push(profile.name.clone());
+            }
+            (Some(_), ProfileMergeStrategy::Skip) => {
+                report.skipped.push(profile.name);
+                continue;
+            }
+            (Some(index), ProfileMergeStrategy::Overwrite) => {
+                report.overwritten.push(profile.name.clone());
+                profiles[index] = profile;
+                continue;
+            }
+            (Some(_), ProfileMergeStrategy::Rename) => {
+                let renamed = free_name(&profile.name, &taken);
+                report.renamed.push((profile.name.clone(), renamed.clone()));
+                profile.name = renamed;
+            }
+        }
+        taken.insert(profile.name.clone(), profiles.len());
+        profiles.push(profile);
+    }
+
+    save_profiles(&profiles)?;
+    Ok(report)
+}
+
+// Tauri commands for connection profiles
+
+#[tauri::command]
+pub async fn list_connection_profiles() -> Result<Vec<ConnectionProfile>, String> {
+    let _guard = lock_profiles().map_err(|e| e.to_string())?;
+    load_profiles().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn save_connection_profile(profile: ConnectionProfile) -> Result<(), String> {
+    upsert_profile(profile).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_connection_profile(name: String) -> Result<bool, String> {
+    remove_profile(&name).map_err(|e| e.to_string())
+}
+
+/// Export all profiles for sharing; the file holds no passwords or key contents
+#[tauri::command]
+pub async fn export_profiles(path: String) -> Result<usize, String> {
+    export_to(Path::new(&path))
+        .map_err(|e| format!("Failed to export profiles: {}", e))
+}
+
+#[tauri::command]
+pub async fn import_profiles(
+    path: String,
+    merge_strategy: Option<ProfileMergeStrategy>,
+) -> Result<ProfileImportReport, String> {
+    import_from(Path::new(&path), merge_strategy.unwrap_or(ProfileMergeStrategy::Skip))
+        .map_err(|e| format!("Failed to import profiles: {}", e))
+}
+
+/// Fields of the live connection that no longer match the saved profile
+#[tauri::command]
+pub async fn diff_profile(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    profile_name: String,
+) -> Result<ProfileDiff, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    diff_against_connection(&profile_name, &connection_id, &connection)
+        .map_err(|e| e.to_string())
+}
+
+/// Save the live connection's settings back into the profile, returning the fields that changed
+#[tauri::command]
+pub async fn update_profile_from_connection(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    profile_name: String,
+) -> Result<ProfileDiff, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    update_from_connection(&profile_name, &connection_id, &connection)
+        .map_err(|e| e.to_string())
+}
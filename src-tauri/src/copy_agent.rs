@@ -1,14 +1,32 @@
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use crate::error::{Circle9Error, Result};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use tokio::sync::mpsc;
-use tauri::{AppHandle, State};
-use crate::utils::lock_or_error;
+use tauri::{AppHandle, Manager, State};
+use crate::audit_log::{AuditOperation, AUDIT_LOGGER};
+use crate::case_agent::{detect_remote_case_sensitivity, CaseAgent, CaseConflict, CASE_AGENT};
+use crate::circuit_breaker::{self, CircuitOpenEvent};
+use crate::manifest;
+use crate::remote_clock;
+use crate::remote_access::check_writable;
+use crate::remote_names::{decode_remote_path, display_name};
+use crate::remote_users::expand_tilde;
+use crate::scheduler::{AllowedHours, TransferScheduler};
+use crate::settings::{self, SettingsPatch};
+use crate::scp_transfer::{scp_download, scp_upload};
+use crate::remote_dirs::{create_remote_dirs, create_remote_file, ensure_parent_dirs, plan_remote_dirs};
+use crate::ssh_client::{SSHClient, SSHConnection, TransferProtocol};
+use crate::transfer_hooks::{require_connection, run_hook, HookRun, HookStage, TransferHooks};
+use crate::transform::{line_ending_transform, read_head, TransferTransform, TransformStage};
+use crate::types::{DiskFullEvent, FdBackoffEvent, NotifyOn, TransferNotification, TransferProgress};
+use crate::utils::{device_id, lock_or_error, ProgressThrottle, ProgressThrottleConfig};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferTask {
@@ -23,6 +41,166 @@ pub struct TransferTask {
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
     pub error: Option<String>,
+    pub parent_id: Option<String>,
+    pub children: Vec<String>,
+    /// Destination of an earlier file sharing this file's inode; linked instead of copied
+    pub link_target: Option<String>,
+    pub warning: Option<String>,
+    /// User-chosen label for managing related transfers together
+    pub group: Option<String>,
+    /// SSH connection the remote side of the transfer goes through; after a failover, the
+    /// alternate that took over and so handled the transfer. An upload without one writes
+    /// to a local path, e.g. a mounted share.
+    #[serde(default)]
+    pub connection_id: Option<String>,
+    /// Connections still to fail over to, in order, once `connection_id` fails or its breaker opens
+    #[serde(default)]
+    pub alternate_connections: Vec<String>,
+    /// Tasks that must complete before this one may start
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Recurring window this task may run in, overriding the global setting
+    #[serde(default)]
+    pub allowed_hours: Option<AllowedHours>,
+    /// Paused or scheduled by a closed allowed-hours window rather than by the user
+    #[serde(default)]
+    pub held_by_window: bool,
+    /// Write a checksum manifest into the destination once a recursive transfer completes
+    #[serde(default)]
+    pub write_manifest: bool,
+    /// Files of a recursive transfer last modified before this are left out
+    #[serde(default)]
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Still unfinished when the previous session ended; cleared once resumed
+    #[serde(default)]
+    pub interrupted: bool,
+    /// Offset the next run continues from instead of rewriting the destination
+    #[serde(default)]
+    pub resume_from: u64,
+    /// Events that raise a `transfer_notification`
+    #[serde(default)]
+    pub notify_on: Vec<NotifyOn>,
+    /// `Percent` thresholds that have already fired
+    #[serde(default)]
+    pub notified_percents: Vec<u8>,
+    /// Times the task was requeued after the server ran out of file handles
+    #[serde(default)]
+    pub fd_retries: u32,
+    /// Paused by `defer_transfer` until nothing else is queued or running
+    #[serde(default)]
+    pub deferred: bool,
+    /// Rewrite applied to the bytes between reading the source and writing the destination
+    #[serde(default)]
+    pub transform: TransferTransform,
+    /// Remote commands run before and after the transfer on `connection_id`
+    #[serde(default)]
+    pub hooks: TransferHooks,
+    /// Every hook run so far, with its output
+    #[serde(default)]
+    pub hook_runs: Vec<HookRun>,
+}
+
+impl TransferTask {
+    /// A transformed destination doesn't line up byte for byte with the source, so those
+    /// transfers restart rather than continue from a checkpoint
+    fn resumable(&self) -> bool {
+        self.transform == TransferTransform::None
+    }
+
+    fn display_name(&self) -> String {
+        decode_remote_path(&self.source_path)
+            .map(|path| display_name(&path))
+            .unwrap_or_else(|_| self.source_path.clone())
+    }
+
+    /// The lowest `Percent` threshold just reached for the first time, marking every
+    /// threshold now passed as fired so each is reported once
+    fn percent_notification(&mut self) -> Option<TransferNotification> {
+        if self.total_bytes == 0 {
+            return None;
+        }
+        let reached = (self.transferred_bytes.saturating_mul(100) / self.total_bytes).min(100) as u8;
+        let mut crossed: Vec<u8> = self.notify_on.iter()
+            .filter_map(|n| match n {
+                NotifyOn::Percent(p) if *p <= reached && !self.notified_percents.contains(p) => Some(*p),
+                _ => None,
+            })
+            .collect();
+        crossed.sort_unstable();
+        let highest = *crossed.last()?;
+        self.notified_percents.extend(crossed);
+        Some(TransferNotification {
+            task_id: self.id.clone(),
+            trigger: NotifyOn::Percent(highest),
+            title: format!("Transfer {}% done", highest),
+            body: format!("{}: {} of {} bytes", self.display_name(), self.transferred_bytes, self.total_bytes),
+        })
+    }
+
+    /// The notification for a task that just completed or failed, if it asked for one
+    fn finished_notification(&self) -> Option<TransferNotification> {
+        let (trigger, title, body) = match self.status {
+            TransferStatus::Completed => (
+                NotifyOn::Complete,
+                "Transfer complete".to_string(),
+                format!("{} ({} bytes)", self.display_name(), self.total_bytes),
+            ),
+            TransferStatus::HookFailed => (
+                NotifyOn::Failed,
+                "Transfer succeeded but hook failed".to_string(),
+                format!("{}: {}", self.display_name(), self.error.as_deref().unwrap_or("unknown error")),
+            ),
+            TransferStatus::Failed => (
+                NotifyOn::Failed,
+                "Transfer failed".to_string(),
+                format!("{}: {}", self.display_name(), self.error.as_deref().unwrap_or("unknown error")),
+            ),
+            _ => return None,
+        };
+        self.notify_on.contains(&trigger).then(|| TransferNotification {
+            task_id: self.id.clone(),
+            trigger,
+            title,
+            body,
+        })
+    }
+
+    pub fn new(source_path: String, dest_path: String, direction: TransferDirection, total_bytes: u64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            source_path,
+            dest_path,
+            direction,
+            status: TransferStatus::Pending,
+            total_bytes,
+            transferred_bytes: 0,
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+            error: None,
+            parent_id: None,
+            children: Vec::new(),
+            link_target: None,
+            warning: None,
+            group: None,
+            connection_id: None,
+            alternate_connections: Vec::new(),
+            depends_on: Vec::new(),
+            allowed_hours: None,
+            held_by_window: false,
+            write_manifest: false,
+            modified_since: None,
+            interrupted: false,
+            resume_from: 0,
+            notify_on: Vec::new(),
+            notified_percents: Vec::new(),
+            fd_retries: 0,
+            deferred: false,
+            transform: TransferTransform::None,
+            hooks: TransferHooks::default(),
+            hook_runs: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,27 +212,187 @@ pub enum TransferDirection {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransferStatus {
     Pending,
+    Scheduled,
     InProgress,
+    Paused,
     Completed,
     Failed,
     Cancelled,
+    /// Held while the connection's circuit breaker is open
+    Blocked,
+    /// The data arrived but the post-transfer hook failed
+    HookFailed,
 }
 
+/// Why a task hasn't started yet
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransferProgress {
-    pub task_id: String,
-    pub filename: String,
-    pub direction: String,
+pub enum PendingReason {
+    /// All transfer slots are busy; `position` is 1 for the next task to start
+    WaitingForSlot { position: usize },
+    Scheduled { at: Option<DateTime<Utc>> },
+    /// A `depends_on` task, or for a hard link the file it links to, hasn't completed
+    WaitingForDependency { task_id: String },
+    Blocked { reason: String },
+}
+
+/// Optional settings for a new transfer, shared by the single-file, download and recursive
+/// creation calls. Recursive transfers use `group`, `connection_id`, `allow_oversize`,
+/// `cross_filesystems` and `write_manifest`; the rest apply to single files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferOptions {
+    pub group: Option<String>,
+    /// Id for the new task, so a caller that already announced it can keep using it for events
+    pub task_id: Option<String>,
+    /// Tasks that must complete before this one starts
+    pub depends_on: Vec<String>,
+    /// Send files over `max_transfer_file_size` anyway
+    pub allow_oversize: bool,
+    pub notify_on: Vec<NotifyOn>,
+    pub transform: TransferTransform,
+    /// Pick a line-ending conversion for text files when `transform` is None
+    pub auto_line_endings: bool,
+    /// Connection the remote side goes through; required for downloads
+    pub connection_id: Option<String>,
+    /// Mirrors of `connection_id` to fail over to, in order
+    pub alternate_connections: Vec<String>,
+    pub hooks: TransferHooks,
+    /// Descend into directories on other filesystems
+    pub cross_filesystems: bool,
+    /// Store a manifest of the destination once the transfer completes
+    pub write_manifest: bool,
+}
+
+impl TransferOptions {
+    fn require_connection_id(&self) -> Result<String> {
+        self.connection_id.clone()
+            .ok_or_else(|| Circle9Error::InvalidValue("A download needs a connection".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileTransferResult {
+    pub path: String,
+    pub status: TransferStatus,
+    pub error: Option<String>,
     pub bytes_transferred: u64,
+    pub linked: bool,
+    pub warning: Option<String>,
+}
+
+/// A queued recursive transfer and how many files its `modified_since` cutoff left out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecursiveTransferCreated {
+    pub task_id: String,
+    pub included_files: usize,
+    pub skipped_unmodified: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecursiveTransferResults {
+    pub parent_task_id: String,
+    pub total_files: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub pending: usize,
+    pub hardlinks_deduplicated: usize,
+    pub results: Vec<FileTransferResult>,
+}
+
+/// Progress of the tasks sharing one group (or no group)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupSummary {
+    pub group: Option<String>,
+    pub total_tasks: usize,
+    pub pending: usize,
+    pub in_progress: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub transferred_bytes: u64,
     pub total_bytes: u64,
-    pub percentage: f64,
-    pub speed_bytes_per_sec: u64,
-    pub estimated_remaining_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSummary {
+    pub total_tasks: usize,
+    pub transferred_bytes: u64,
+    pub total_bytes: u64,
+    pub groups: Vec<GroupSummary>,
+}
+
+/// Outcome of `resume_persisted_transfers`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResumeReport {
+    pub resumed: Vec<ResumedTransfer>,
+    /// Left paused, with why
+    pub failed: Vec<ResumeFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumedTransfer {
+    pub task_id: String,
+    pub source_path: String,
+    /// Bytes kept from the previous session; 0 when the transfer had to start over
+    pub resumed_from: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeFailure {
+    pub task_id: String,
+    pub source_path: String,
+    pub reason: String,
+}
+
+/// A file found while walking a local source tree
+struct LocalFile {
+    path: PathBuf,
+    size: u64,
+    /// (device, inode) for files with more than one hard link
+    inode: Option<(u64, u64)>,
+}
+
+/// Counts a transfer as in flight for as long as it lives, however the dispatcher leaves
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn start(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Requeues after running out of server file handles before a task fails for good
+const MAX_FD_RETRIES: u32 = 5;
+/// How long the reduced concurrency lasts; each retry of a task also waits this long per attempt
+const FD_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Concurrency cap in force while the server recovers from running out of file handles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FdBackoff {
+    pub concurrency: usize,
+    pub until: DateTime<Utc>,
+}
+
+/// On-disk form of the queue written at shutdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedQueue {
+    tasks: Vec<TransferTask>,
+    recursive_results: HashMap<String, Vec<FileTransferResult>>,
 }
 
 pub struct CopyAgent {
     active_transfers: Arc<Mutex<HashMap<String, TransferTask>>>,
-    max_concurrent_transfers: usize,
+    recursive_results: Arc<Mutex<HashMap<String, Vec<FileTransferResult>>>>,
+    in_flight: Arc<AtomicUsize>,
+    /// (connection, directory) pairs that passed the upload pre-flight check
+    writable_dirs: Mutex<HashMap<(String, String), Option<String>>>,
+    fd_backoff: Mutex<Option<FdBackoff>>,
     sender: mpsc::UnboundedSender<String>,
     receiver: mpsc::UnboundedReceiver<String>,
     app_handle: Arc<AppHandle>,
@@ -65,41 +403,213 @@ impl CopyAgent {
         let (sender, receiver) = mpsc::unbounded_channel();
         Self {
             active_transfers: Arc::new(Mutex::new(HashMap::new())),
-            max_concurrent_transfers: 3,
+            recursive_results: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            writable_dirs: Mutex::new(HashMap::new()),
+            fd_backoff: Mutex::new(None),
             sender,
             receiver,
             app_handle,
         }
     }
 
-    /// Create a new transfer task
+    /// Create a new transfer task. `alternate_connections` are mirrors of `connection_id`
+    /// the task moves to if that connection fails.
     pub fn create_transfer_task(
         &self,
         source_path: String,
         dest_path: String,
         direction: TransferDirection,
+        options: TransferOptions,
     ) -> Result<String> {
-        let task_id = Uuid::new_v4().to_string();
-        tracing::info!("Creating transfer task {}: {} -> {}", task_id, source_path, dest_path);
+        let TransferOptions {
+            group,
+            task_id,
+            depends_on,
+            allow_oversize,
+            notify_on,
+            transform,
+            auto_line_endings,
+            connection_id,
+            alternate_connections,
+            hooks,
+            ..
+        } = options;
+        validate_notify_on(&notify_on)?;
+        let hooks = hooks.validated()?;
+        require_connection(&hooks, connection_id.as_deref())?;
+        if connection_id.is_none() && !alternate_connections.is_empty() {
+            return Err(Circle9Error::InvalidValue(
+                "Alternate connections need a primary connection to fail over from".to_string(),
+            ));
+        }
         let total_bytes = self.get_file_size(&source_path)?;
+        // An explicit transform wins over detection
+        let transform = if auto_line_endings && transform == TransferTransform::None {
+            let head = read_head(std::fs::File::open(&source_path)?)?;
+            let to_linux = matches!(direction, TransferDirection::WindowsToLinux);
+            line_ending_transform(&source_path, &head, to_linux, &settings::current().line_ending_overrides)
+        } else {
+            transform
+        };
+        check_size_limit(&source_path, total_bytes, allow_oversize)?;
+        let dest_path = match direction {
+            TransferDirection::WindowsToLinux => {
+                let dest = Path::new(&dest_path);
+                match dest.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => dest.with_file_name(CaseAgent::restore_trailing_names(Path::new(name))).to_string_lossy().to_string(),
+                    None => dest_path,
+                }
+            }
+            TransferDirection::LinuxToWindows => dest_path,
+        };
+        let mut task = TransferTask {
+            group,
+            depends_on,
+            notify_on,
+            transform,
+            connection_id,
+            alternate_connections,
+            hooks,
+            ..TransferTask::new(source_path, dest_path, direction, total_bytes)
+        };
+        if let Some(task_id) = task_id {
+            task.id = task_id;
+        }
+        self.check_dependencies(&task.id, &task.depends_on)?;
+        self.queue_task(task)
+    }
 
-        let task = TransferTask {
-            id: task_id.clone(),
-            source_path,
-            dest_path,
-            direction,
-            status: TransferStatus::Pending,
-            total_bytes,
-            transferred_bytes: 0,
-            created_at: Utc::now(),
-            started_at: None,
-            completed_at: None,
-            error: None,
+    /// Every dependency must exist, and following them must never lead back to `task_id`
+    fn check_dependencies(&self, task_id: &str, depends_on: &[String]) -> Result<()> {
+        let transfers = lock_or_error(&self.active_transfers)?;
+        let mut stack: Vec<&str> = depends_on.iter().map(String::as_str).collect();
+        let mut seen = HashSet::new();
+
+        while let Some(id) = stack.pop() {
+            if id == task_id {
+                return Err(Circle9Error::TransferError(format!("Dependency cycle through task {}", task_id)));
+            }
+            if !seen.insert(id) {
+                continue;
+            }
+            let dependency = transfers.get(id)
+                .ok_or_else(|| Circle9Error::TransferError(format!("Dependency {} not found", id)))?;
+            stack.extend(dependency.depends_on.iter().map(String::as_str));
+        }
+        Ok(())
+    }
+
+    /// Outcome of a task's dependencies: Ok(true) once all completed, Ok(false) while
+    /// some are still outstanding, Err with the reason if one failed or was cancelled
+    fn dependencies_ready(&self, task: &TransferTask) -> Result<std::result::Result<bool, String>> {
+        let transfers = lock_or_error(&self.active_transfers)?;
+        Ok(Self::outstanding_dependency(&transfers, task).map(|outstanding| outstanding.is_none()))
+    }
+
+    /// The first of `task`'s dependencies that hasn't completed yet, or Err with the reason
+    /// if one can no longer complete. A dependency whose post-transfer hook failed counts as
+    /// failed: whatever depends on it expected the hook to have run.
+    fn outstanding_dependency<'a>(
+        transfers: &HashMap<String, TransferTask>,
+        task: &'a TransferTask,
+    ) -> std::result::Result<Option<&'a String>, String> {
+        let mut outstanding = None;
+        for id in &task.depends_on {
+            match transfers.get(id).map(|t| &t.status) {
+                Some(TransferStatus::Completed) => {}
+                Some(status @ (TransferStatus::Failed | TransferStatus::Cancelled | TransferStatus::HookFailed)) => {
+                    return Err(format!("Dependency {} {:?}", id, status).to_lowercase());
+                }
+                Some(_) => {
+                    outstanding.get_or_insert(id);
+                }
+                None => return Err(format!("Dependency {} no longer exists", id)),
+            }
+        }
+        Ok(outstanding)
+    }
+
+    /// Re-queue the pending tasks waiting on `finished_id` so the dispatcher re-checks them
+    fn release_dependents(&self, finished_id: &str) -> Result<()> {
+        let dependents: Vec<String> = lock_or_error(&self.active_transfers)?
+            .values()
+            .filter(|t| matches!(t.status, TransferStatus::Pending) && t.depends_on.iter().any(|d| d == finished_id))
+            .map(|t| t.id.clone())
+            .collect();
+        for id in dependents {
+            self.sender.send(id)
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Create a task downloading `remote_path` over the options' `connection_id` into `local_path`
+    pub fn create_download_task(
+        &self,
+        remote_path: String,
+        local_path: String,
+        options: TransferOptions,
+    ) -> Result<String> {
+        let connection_id = options.require_connection_id()?;
+        let TransferOptions {
+            group,
+            task_id,
+            depends_on,
+            allow_oversize,
+            notify_on,
+            transform,
+            auto_line_endings,
+            alternate_connections,
+            hooks,
+            ..
+        } = options;
+        validate_notify_on(&notify_on)?;
+        let hooks = hooks.validated()?;
+        let connection = self.app_handle.state::<SSHClient>()
+            .get_connection(&connection_id)
+            .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+        let remote_path = expand_tilde(&connection, &remote_path)?;
+        let total_bytes = connection.sftp()?
+            .stat(&decode_remote_path(&remote_path)?)?
+            .size
+            .unwrap_or(0);
+        check_size_limit(&remote_path, total_bytes, allow_oversize)?;
+        let transform = if auto_line_endings && transform == TransferTransform::None {
+            let head = read_head(connection.sftp()?.open(&decode_remote_path(&remote_path)?)?)?;
+            line_ending_transform(&remote_path, &head, false, &settings::current().line_ending_overrides)
+        } else {
+            transform
+        };
+
+        let mut task = TransferTask {
+            group,
+            depends_on,
+            notify_on,
+            connection_id: Some(connection_id),
+            alternate_connections,
+            transform,
+            hooks,
+            ..TransferTask::new(remote_path, local_path, TransferDirection::LinuxToWindows, total_bytes)
         };
+        if let Some(task_id) = task_id {
+            task.id = task_id;
+        }
+        self.check_dependencies(&task.id, &task.depends_on)?;
+        self.queue_task(task)
+    }
 
+    /// Add a task to the queue, returning its id. A caller-chosen id already in use is
+    /// refused rather than replacing the task that has it.
+    fn queue_task(&self, task: TransferTask) -> Result<String> {
+        let task_id = task.id.clone();
         {
             let mut transfers = self.active_transfers.lock()
                 .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
+            if transfers.contains_key(&task_id) {
+                return Err(Circle9Error::TransferError(format!("Transfer task {} already exists", task_id)));
+            }
+            tracing::info!("Creating transfer task {}: {} -> {}", task_id, task.source_path, task.dest_path);
             transfers.insert(task_id.clone(), task);
         }
 
@@ -111,6 +621,403 @@ impl CopyAgent {
         Ok(task_id)
     }
 
+    /// Create a recursive transfer: one parent task plus a child task per file
+    pub fn create_recursive_transfer_task(
+        &self,
+        source_dir: String,
+        dest_dir: String,
+        direction: TransferDirection,
+        options: TransferOptions,
+    ) -> Result<String> {
+        self.create_recursive_transfer(source_dir, dest_dir, direction, None, options)
+            .map(|created| created.task_id)
+    }
+
+    /// Create a recursive transfer of only the files modified at or after `modified_since`,
+    /// give or take `mtime_tolerance_secs`. Every file is held to the size limit unless
+    /// `allow_oversize` is set.
+    pub fn create_recursive_transfer(
+        &self,
+        source_dir: String,
+        dest_dir: String,
+        direction: TransferDirection,
+        modified_since: Option<DateTime<Utc>>,
+        options: TransferOptions,
+    ) -> Result<RecursiveTransferCreated> {
+        let TransferOptions { group, allow_oversize, connection_id, cross_filesystems, write_manifest, .. } = options;
+        if matches!(direction, TransferDirection::LinuxToWindows) {
+            return Err(Circle9Error::TransferError(
+                "Recursive Linux to Windows transfer not implemented yet".to_string(),
+            ));
+        }
+        let connection = match &connection_id {
+            Some(id) => Some(self.app_handle.state::<SSHClient>()
+                .get_connection(id)
+                .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?),
+            None => None,
+        };
+
+        let source_root = Path::new(&source_dir);
+        let mut files = Vec::new();
+        let mut skipped_mounts = Vec::new();
+        let root_device = if cross_filesystems {
+            None
+        } else {
+            device_id(&std::fs::metadata(source_root)?)
+        };
+        let cutoff = modified_since.map(|since| {
+            let tolerance = settings::current().mtime_tolerance_secs;
+            SystemTime::from(since) - Duration::from_secs(tolerance)
+        });
+        let mut skipped_unmodified = 0;
+        Self::collect_local_files(source_root, root_device, cutoff, &mut files, &mut skipped_mounts, &mut skipped_unmodified)?;
+        // Refuse before anything is created remotely or queued
+        for file in &files {
+            check_size_limit(&file.path.to_string_lossy(), file.size, allow_oversize)?;
+        }
+        let mut warnings = Vec::new();
+        if !skipped_mounts.is_empty() {
+            warnings.push(format!("Skipped mount points: {}", skipped_mounts.join(", ")));
+        }
+
+        // Make the whole remote tree up front so each file's preflight finds its directory
+        if let Some(connection) = &connection {
+            let dest_root = decode_remote_path(&dest_dir)?;
+            create_remote_dirs(connection, &plan_remote_dirs(source_root, &dest_root)?)?;
+            // Children only see their own directory, so a symlinked root is reported here
+            if settings::current().preflight_write_check {
+                let check = check_writable(connection, &dest_root.to_string_lossy())?;
+                if let Some(error) = check.error {
+                    return Err(Circle9Error::TransferError(error));
+                }
+                if let Some(symlink_warning) = check.warning {
+                    tracing::warn!("{}", symlink_warning);
+                    warnings.push(symlink_warning);
+                }
+            }
+        }
+        let warning = (!warnings.is_empty()).then(|| warnings.join("; "));
+
+        let parent_id = Uuid::new_v4().to_string();
+        tracing::info!(
+            "Creating recursive transfer task {}: {} -> {} ({} files, {} unmodified skipped)",
+            parent_id, source_dir, dest_dir, files.len(), skipped_unmodified
+        );
+
+        let mut children = Vec::with_capacity(files.len());
+        let mut total_bytes = 0u64;
+        // First destination seen for each multiply-linked inode
+        let mut first_links: HashMap<(u64, u64), String> = HashMap::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            for file in &files {
+                let relative = file.path.strip_prefix(source_root)
+                    .map_err(|_| Circle9Error::InvalidPath(file.path.to_string_lossy().to_string()))?;
+                let relative = match direction {
+                    TransferDirection::WindowsToLinux => CaseAgent::restore_trailing_names(relative),
+                    TransferDirection::LinuxToWindows => relative.to_path_buf(),
+                };
+                let dest_path = Path::new(&dest_dir).join(relative).to_string_lossy().to_string();
+
+                let link_target = file.inode.and_then(|inode| {
+                    match first_links.get(&inode) {
+                        Some(first) => Some(first.clone()),
+                        None => {
+                            first_links.insert(inode, dest_path.clone());
+                            None
+                        }
+                    }
+                });
+
+                // Linked files move no data, so they don't count towards the byte total
+                let size = if link_target.is_some() { 0 } else { file.size };
+                let child = TransferTask {
+                    parent_id: Some(parent_id.clone()),
+                    link_target,
+                    group: group.clone(),
+                    connection_id: connection_id.clone(),
+                    ..TransferTask::new(
+                        file.path.to_string_lossy().to_string(),
+                        dest_path,
+                        direction.clone(),
+                        size,
+                    )
+                };
+
+                total_bytes += size;
+                children.push(child.id.clone());
+                transfers.insert(child.id.clone(), child);
+            }
+
+            let status = if children.is_empty() {
+                TransferStatus::Completed
+            } else {
+                TransferStatus::InProgress
+            };
+
+            transfers.insert(parent_id.clone(), TransferTask {
+                id: parent_id.clone(),
+                status,
+                started_at: Some(Utc::now()),
+                children: children.clone(),
+                group,
+                warning,
+                write_manifest,
+                modified_since,
+                connection_id,
+                ..TransferTask::new(source_dir, dest_dir, direction, total_bytes)
+            });
+        }
+
+        lock_or_error(&self.recursive_results)?.insert(parent_id.clone(), Vec::new());
+
+        for child_id in children {
+            if self.sender.send(child_id).is_err() {
+                return Err(Circle9Error::TransferError("Failed to queue transfer task".to_string()));
+            }
+        }
+
+        Ok(RecursiveTransferCreated {
+            task_id: parent_id,
+            included_files: files.len(),
+            skipped_unmodified,
+        })
+    }
+
+    /// Recursively collect every regular file below `dir` together with its size.
+    /// With `root_device` set, directories on another device are recorded and not entered.
+    /// With `cutoff` set, files last modified before it are counted and left out.
+    fn collect_local_files(
+        dir: &Path,
+        root_device: Option<u64>,
+        cutoff: Option<SystemTime>,
+        files: &mut Vec<LocalFile>,
+        skipped_mounts: &mut Vec<String>,
+        skipped_unmodified: &mut usize,
+    ) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                if root_device.is_some() && device_id(&metadata) != root_device {
+                    skipped_mounts.push(entry.path().to_string_lossy().to_string());
+                    continue;
+                }
+                Self::collect_local_files(&entry.path(), root_device, cutoff, files, skipped_mounts, skipped_unmodified)?;
+            } else if metadata.is_file() {
+                // A file whose mtime can't be read is kept rather than silently left behind
+                let unmodified = match (cutoff, metadata.modified()) {
+                    (Some(cutoff), Ok(mtime)) => mtime < cutoff,
+                    _ => false,
+                };
+                if unmodified {
+                    *skipped_unmodified += 1;
+                    continue;
+                }
+                files.push(LocalFile {
+                    path: entry.path(),
+                    size: metadata.len(),
+                    inode: hardlink_key(&entry.path(), &metadata),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a finished child task on its recursive parent and update the aggregate
+    fn record_child_result(&self, child: &TransferTask) -> Result<()> {
+        let parent_id = match &child.parent_id {
+            Some(parent_id) => parent_id.clone(),
+            None => return Ok(()),
+        };
+        if matches!(child.status, TransferStatus::Pending | TransferStatus::InProgress | TransferStatus::Paused | TransferStatus::Blocked) {
+            return Ok(());
+        }
+
+        let finished = {
+            let mut results = lock_or_error(&self.recursive_results)?;
+            let entries = results.entry(parent_id.clone()).or_insert_with(Vec::new);
+            entries.retain(|r| r.path != child.source_path);
+            entries.push(FileTransferResult {
+                path: child.source_path.clone(),
+                status: child.status.clone(),
+                error: child.error.clone(),
+                bytes_transferred: child.transferred_bytes,
+                linked: child.link_target.is_some() && matches!(child.status, TransferStatus::Completed),
+                warning: child.warning.clone(),
+            });
+            entries.clone()
+        };
+
+        let transferred: u64 = finished.iter()
+            .filter(|r| matches!(r.status, TransferStatus::Completed))
+            .map(|r| r.bytes_transferred)
+            .sum();
+
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let mut finished_parent = None;
+        let mut notification = None;
+        if let Some(parent) = transfers.get_mut(&parent_id) {
+            parent.transferred_bytes = transferred;
+            notification = parent.percent_notification();
+
+            if finished.len() >= parent.children.len() {
+                let failed = finished.iter()
+                    .filter(|r| !matches!(r.status, TransferStatus::Completed))
+                    .count();
+                parent.completed_at = Some(Utc::now());
+                if matches!(parent.status, TransferStatus::Cancelled) {
+                    // A cancelled transfer stays cancelled however its last files ended
+                } else if failed == 0 {
+                    parent.status = TransferStatus::Completed;
+                    parent.error = None;
+                } else {
+                    parent.status = TransferStatus::Failed;
+                    parent.error = Some(format!(
+                        "{} of {} files failed",
+                        failed,
+                        parent.children.len()
+                    ));
+                }
+                finished_parent = Some(parent.clone());
+            }
+        }
+        drop(transfers);
+
+        if let Some(notification) = notification {
+            notification.emit(&self.app_handle);
+        }
+        // Logged unbatched, which also flushes the per-file entries before it
+        if let Some(parent) = finished_parent {
+            Self::audit_finished(&parent);
+            if let Some(notification) = parent.finished_notification() {
+                notification.emit(&self.app_handle);
+            }
+            if parent.write_manifest && matches!(parent.status, TransferStatus::Completed) {
+                self.spawn_manifest_write(parent);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash the destination of a completed recursive transfer in the background and store
+    /// the manifest there. A failure is reported as a warning on the parent task.
+    fn spawn_manifest_write(&self, parent: TransferTask) {
+        let app_handle = self.app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let written = match parent.direction {
+                TransferDirection::WindowsToLinux => Self::write_upload_manifest(&app_handle, &parent).await,
+                TransferDirection::LinuxToWindows => {
+                    let dest_path = parent.dest_path.clone();
+                    tauri::async_runtime::spawn_blocking(move || {
+                        let dest_dir = Path::new(&dest_path);
+                        manifest::build_local_manifest(dest_dir)
+                            .and_then(|m| manifest::write_local_manifest(dest_dir, &m).map(|_| m.entries.len()))
+                    })
+                        .await
+                        .unwrap_or_else(|e| Err(Circle9Error::TransferError(format!("Manifest task failed: {}", e))))
+                }
+            };
+            match written {
+                Ok(files) => {
+                    tracing::info!("Wrote manifest of {} files to {}", files, parent.dest_path);
+                    let event = manifest::ManifestWrittenEvent {
+                        task_id: parent.id.clone(),
+                        dir: parent.dest_path.clone(),
+                        files,
+                    };
+                    if let Err(e) = app_handle.emit_all("transfer_manifest_written", &event) {
+                        tracing::error!("Failed to emit manifest event: {}", e);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to write manifest for {}: {}", parent.dest_path, e);
+                    let copy_agent = app_handle.state::<CopyAgent>();
+                    if let Ok(mut transfers) = lock_or_error(&copy_agent.active_transfers) {
+                        if let Some(task) = transfers.get_mut(&parent.id) {
+                            let message = format!("Manifest not written: {}", e);
+                            task.warning = Some(match task.warning.take() {
+                                Some(existing) => format!("{}; {}", existing, message),
+                                None => message,
+                            });
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Hash the remote destination of a completed upload over its own connection and store
+    /// the manifest next to the files
+    async fn write_upload_manifest(app_handle: &AppHandle, parent: &TransferTask) -> Result<usize> {
+        let connection_id = parent.connection_id.as_deref()
+            .ok_or_else(|| Circle9Error::TransferError("Upload has no connection".to_string()))?;
+        let ssh_client = app_handle.state::<SSHClient>();
+        let connection = ssh_client.get_connection(connection_id)
+            .ok_or_else(|| Circle9Error::SSHError(format!("Connection {} not found", connection_id)))?;
+
+        let (manifest, skipped) = manifest::build_remote_manifest(&connection, &parent.dest_path).await?;
+        if let Some(first) = skipped.first() {
+            return Err(Circle9Error::TransferError(format!(
+                "Could not read {} entries, e.g. {}", skipped.len(), first.path
+            )));
+        }
+        manifest::write_remote_manifest(&connection, &parent.dest_path, &manifest)?;
+        ssh_client.listing_cache.invalidate(connection_id, &parent.dest_path);
+        Ok(manifest.entries.len())
+    }
+
+    /// Record a completed or failed task in the audit log. The files of a recursive
+    /// transfer are batched rather than flushed one at a time.
+    fn audit_finished(task: &TransferTask) {
+        let (operation, success) = match task.status {
+            TransferStatus::Completed | TransferStatus::HookFailed => (AuditOperation::TransferCompleted, true),
+            TransferStatus::Failed => (AuditOperation::TransferFailed, false),
+            _ => return,
+        };
+        let source_path = Some(task.source_path.clone());
+        let dest_path = Some(task.dest_path.clone());
+        let result = if task.parent_id.is_some() {
+            AUDIT_LOGGER.log_operation_batched(operation, source_path, dest_path, Some(task.total_bytes), success, task.error.clone())
+        } else {
+            AUDIT_LOGGER.log_operation(operation, source_path, dest_path, Some(task.total_bytes), success, task.error.clone())
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to audit transfer {}: {}", task.id, e);
+        }
+    }
+
+    /// Get the per-file results of a recursive transfer
+    pub fn get_recursive_transfer_results(&self, parent_task_id: &str) -> Result<RecursiveTransferResults> {
+        let total_files = {
+            let transfers = lock_or_error(&self.active_transfers)?;
+            transfers.get(parent_task_id)
+                .map(|t| t.children.len())
+                .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", parent_task_id)))?
+        };
+
+        let results = lock_or_error(&self.recursive_results)?
+            .get(parent_task_id)
+            .cloned()
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} is not recursive", parent_task_id)))?;
+
+        let succeeded = results.iter()
+            .filter(|r| matches!(r.status, TransferStatus::Completed))
+            .count();
+        let failed = results.len() - succeeded;
+        let hardlinks_deduplicated = results.iter().filter(|r| r.linked).count();
+
+        Ok(RecursiveTransferResults {
+            parent_task_id: parent_task_id.to_string(),
+            total_files,
+            succeeded,
+            failed,
+            pending: total_files.saturating_sub(results.len()),
+            hardlinks_deduplicated,
+            results,
+        })
+    }
+
     /// Start processing the transfer queue
     pub async fn process_queue(&self) -> Result<()> {
         loop {
@@ -126,12 +1033,60 @@ impl CopyAgent {
                     .count()
             };
 
-            if current_transfers < self.max_concurrent_transfers {
+            if current_transfers < self.max_concurrent_transfers() {
                 self.start_transfer(task_id).await?;
             }
         }
     }
 
+    /// The concurrency setting, lowered while an fd backoff is in force
+    fn max_concurrent_transfers(&self) -> usize {
+        let configured = settings::current().max_concurrent_transfers;
+        match self.fd_backoff() {
+            Some(backoff) => configured.min(backoff.concurrency),
+            None => configured,
+        }
+    }
+
+    /// The fd backoff in force, if it hasn't expired
+    pub fn fd_backoff(&self) -> Option<FdBackoff> {
+        let mut backoff = lock_or_error(&self.fd_backoff).ok()?;
+        if backoff.as_ref().map_or(false, |b| b.until <= Utc::now()) {
+            *backoff = None;
+        }
+        backoff.clone()
+    }
+
+    /// Halve concurrency for `FD_BACKOFF`, tell the UI why, and requeue the task once the
+    /// delay for its attempt has passed
+    fn back_off_for_fd_exhaustion(&self, task: &TransferTask, message: String) -> Result<()> {
+        let concurrency = (self.max_concurrent_transfers() / 2).max(1);
+        let backoff_secs = FD_BACKOFF.as_secs() * task.fd_retries as u64;
+        *lock_or_error(&self.fd_backoff)? = Some(FdBackoff {
+            concurrency,
+            until: Utc::now() + chrono::Duration::seconds(backoff_secs as i64),
+        });
+        tracing::warn!(
+            "Server out of file handles during {}; running {} transfers at once, retrying in {}s: {}",
+            task.id, concurrency, backoff_secs, message
+        );
+        FdBackoffEvent {
+            task_id: task.id.clone(),
+            connection_id: task.connection_id.clone(),
+            concurrency,
+            backoff_secs,
+            message,
+        }.emit(&self.app_handle);
+
+        let sender = self.sender.clone();
+        let task_id = task.id.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(backoff_secs)).await;
+            let _ = sender.send(task_id);
+        });
+        Ok(())
+    }
+
 
     /// Start a transfer task
     async fn start_transfer(&self, task_id: String) -> Result<()> {
@@ -141,6 +1096,59 @@ impl CopyAgent {
             transfers.get_mut(&task_id).cloned()
         };
 
+        // Scheduled, paused or cancelled tasks stay put until released back to Pending
+        let task = task.filter(|t| matches!(t.status, TransferStatus::Pending));
+
+        // Tasks with outstanding dependencies wait to be re-queued by release_dependents;
+        // a failed dependency fails the task, which in turn releases its own dependents
+        if let Some(waiting) = &task {
+            match self.dependencies_ready(waiting)? {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(reason) => {
+                    {
+                        let mut transfers = lock_or_error(&self.active_transfers)?;
+                        if let Some(task) = transfers.get_mut(&task_id) {
+                            task.status = TransferStatus::Failed;
+                            task.error = Some(reason);
+                        }
+                    }
+                    return self.release_dependents(&task_id);
+                }
+            }
+        }
+
+        // Outside its allowed hours the task waits as Scheduled until the window opens
+        if let Some(waiting) = &task {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let window_closed = Self::allowed_hours_for(&transfers, waiting)
+                .map_or(false, |window| !window.is_open(Utc::now()));
+            if window_closed {
+                if let Some(task) = transfers.get_mut(&task_id) {
+                    task.status = TransferStatus::Scheduled;
+                    task.held_by_window = true;
+                }
+                return Ok(());
+            }
+        }
+
+        // A connection that keeps failing isn't tried again until its breaker closes,
+        // unless the task can move to one of its alternates
+        if let Some(waiting) = &task {
+            let breaker_open = waiting.connection_id.as_deref().map_or(false, circuit_breaker::is_open);
+            if breaker_open && self.try_failover(waiting)? {
+                return Ok(());
+            }
+        }
+        if let Some(connection_id) = task.as_ref().and_then(|t| t.connection_id.as_deref()) {
+            if circuit_breaker::is_open(connection_id) {
+                if let Some(task) = lock_or_error(&self.active_transfers)?.get_mut(&task_id) {
+                    task.status = TransferStatus::Blocked;
+                }
+                return Ok(());
+            }
+        }
+
         if let Some(mut task) = task {
             task.status = TransferStatus::InProgress;
             task.started_at = Some(Utc::now());
@@ -151,55 +1159,567 @@ impl CopyAgent {
                 transfers.insert(task_id.clone(), task.clone());
             }
 
-            // Execute the transfer based on direction
-            let result = match task.direction {
-                TransferDirection::WindowsToLinux => {
-                    self.transfer_windows_to_linux(&task).await
-                }
-                TransferDirection::LinuxToWindows => {
-                    self.transfer_linux_to_windows(&task).await
-                }
-            };
-
+            // Recreate hard links instead of copying the same inode again
+            let mut link_fallback = None;
+            if let Some(link_target) = &task.link_target {
+                match self.create_hard_link(link_target, &task.dest_path) {
+                    Ok(()) => {
+                        let mut transfers = lock_or_error(&self.active_transfers)?;
+                        if let Some(task) = transfers.get_mut(&task_id) {
+                            task.status = TransferStatus::Completed;
+                            task.completed_at = Some(Utc::now());
+                        }
+                        let finished = transfers.get(&task_id).cloned();
+                        drop(transfers);
+                        if let Some(finished) = finished {
+                            Self::audit_finished(&finished);
+                            self.record_child_result(&finished)?;
+                        }
+                        return self.release_dependents(&task_id);
+                    }
+                    Err(e) => {
+                        let warning = format!("Could not hard link to {} ({}); copied instead", link_target, e);
+                        tracing::warn!("{}: {}", task.dest_path, warning);
+                        link_fallback = Some(warning);
+                    }
+                }
+            }
+
+            if let Some(warning) = link_fallback {
+                task.link_target = None;
+                task.warning = Some(warning);
+                task.total_bytes = self.get_file_size(&task.source_path)?;
+                let mut transfers = lock_or_error(&self.active_transfers)?;
+                if let Some(parent) = task.parent_id.as_ref().and_then(|id| transfers.get_mut(id)) {
+                    parent.total_bytes += task.total_bytes;
+                }
+                transfers.insert(task_id.clone(), task.clone());
+            }
+
+            // Execute the transfer based on direction
+            let in_flight = InFlight::start(&self.in_flight);
+            let preflight = self.preflight_write_check(&task);
+            if let Ok(Some(warning)) = &preflight {
+                if let Some(task) = lock_or_error(&self.active_transfers)?.get_mut(&task_id) {
+                    task.warning = Some(match task.warning.take() {
+                        Some(existing) => format!("{}; {}", existing, warning),
+                        None => warning.clone(),
+                    });
+                }
+            }
+            let result = match preflight {
+                Err(e) => Err(e),
+                Ok(_) => match self.run_task_hook(&task, HookStage::Pre) {
+                    Err(e) => Err(e),
+                    Ok(Some(run)) if !run.success() => Err(Circle9Error::PreHookFailed(run.failure().unwrap_or_default())),
+                    Ok(_) => match task.direction {
+                        TransferDirection::WindowsToLinux => {
+                            self.transfer_windows_to_linux(&task).await
+                        }
+                        TransferDirection::LinuxToWindows => {
+                            self.transfer_linux_to_windows(&task).await
+                        }
+                    },
+                },
+            };
+            drop(in_flight);
+            let result = result.map_err(Circle9Error::classify_fd_exhaustion);
+
+            // The post hook is only for a transfer that ran to the end, not one paused or cancelled
+            let still_running = lock_or_error(&self.active_transfers)?
+                .get(&task_id)
+                .map_or(false, |t| matches!(t.status, TransferStatus::InProgress));
+            let post_hook = match &result {
+                // A hook that couldn't even be started still leaves the task HookFailed
+                Ok(_) if still_running => self.run_task_hook(&task, HookStage::Post).unwrap_or_else(|e| {
+                    let command = task.hooks.post_hook.as_deref().unwrap_or_default();
+                    Some(HookRun::not_run(HookStage::Post, command, e.to_string()))
+                }),
+                _ => None,
+            };
+
+            // Running out of file handles, a failing hook or a pause/cancel says nothing about
+            // the connection's health. Uploads and downloads both count once they carry a connection.
+            let mut tripped = None;
+            if let Some(connection_id) = &task.connection_id {
+                match &result {
+                    Ok(_) => circuit_breaker::record_success(connection_id),
+                    Err(_) if !still_running => {}
+                    Err(Circle9Error::TooManyOpenFiles(_) | Circle9Error::PreHookFailed(_)) => {}
+                    Err(e) => {
+                        tripped = circuit_breaker::record_failure(connection_id)
+                            .map(|(failures, retry_at)| (connection_id.clone(), failures, retry_at, e.to_string()));
+                    }
+                }
+            }
+
+            let connection_failed = matches!(&result, Err(e) if e.is_connection_failure());
+
             // Update task status
-            {
+            let mut fd_retry = None;
+            let finished = {
                 let mut transfers = self.active_transfers.lock()
                     .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
                 if let Some(task) = transfers.get_mut(&task_id) {
+                    task.resume_from = 0;
                     match result {
+                        // Paused or cancelled mid-flight: keep the status that stopped it
+                        _ if matches!(task.status, TransferStatus::Paused | TransferStatus::Cancelled) => {}
                         Ok(_) => {
-                            task.status = TransferStatus::Completed;
                             task.completed_at = Some(Utc::now());
+                            match post_hook.as_ref().and_then(HookRun::failure) {
+                                Some(failure) => {
+                                    task.status = TransferStatus::HookFailed;
+                                    task.error = Some(format!("Transfer succeeded but post-transfer hook failed: {}", failure));
+                                }
+                                None => task.status = TransferStatus::Completed,
+                            }
+                            if matches!(task.direction, TransferDirection::WindowsToLinux) {
+                                // Tasks are not bound to a connection, so drop the directory everywhere
+                                self.app_handle.state::<SSHClient>()
+                                    .listing_cache
+                                    .invalidate_parent_everywhere(&task.dest_path);
+                            }
+                        }
+                        Err(Circle9Error::TooManyOpenFiles(message)) if task.fd_retries < MAX_FD_RETRIES => {
+                            task.status = TransferStatus::Pending;
+                            task.transferred_bytes = 0;
+                            task.fd_retries += 1;
+                            task.warning = Some(format!(
+                                "Server ran out of file handles; retry {} of {}", task.fd_retries, MAX_FD_RETRIES
+                            ));
+                            fd_retry = Some(message);
                         }
                         Err(e) => {
+                            if let Circle9Error::DiskFull { path, bytes_written } = &e {
+                                DiskFullEvent {
+                                    task_id: task.id.clone(),
+                                    path: path.clone(),
+                                    bytes_written: *bytes_written,
+                                }.emit(&self.app_handle);
+                            }
                             task.status = TransferStatus::Failed;
                             task.error = Some(e.to_string());
                         }
                     }
+                    Some(task.clone())
+                } else {
+                    None
+                }
+            };
+
+            if let (Some(message), Some(requeued)) = (fd_retry, &finished) {
+                self.back_off_for_fd_exhaustion(requeued, message)?;
+            }
+
+            let failed_over = match &finished {
+                Some(failed) if connection_failed && matches!(failed.status, TransferStatus::Failed) => {
+                    self.try_failover(failed)?
+                }
+                _ => false,
+            };
+            if !failed_over {
+                if let Some(finished) = finished {
+                    Self::audit_finished(&finished);
+                    if let Some(notification) = finished.finished_notification() {
+                        notification.emit(&self.app_handle);
+                    }
+                    self.record_child_result(&finished)?;
+                }
+                self.release_dependents(&task_id)?;
+            }
+
+            if let Some((connection_id, consecutive_failures, retry_at, last_error)) = tripped {
+                let blocked_tasks = self.block_connection_transfers(&connection_id)?;
+                tracing::warn!(
+                    "Circuit breaker open for {} after {} failures, {} transfers blocked until {}",
+                    connection_id, consecutive_failures, blocked_tasks, retry_at
+                );
+                CircuitOpenEvent {
+                    connection_id,
+                    consecutive_failures,
+                    last_error,
+                    retry_at,
+                    blocked_tasks,
+                }.emit(&self.app_handle);
+            }
+
+            self.resume_deferred_if_idle()?;
+        }
+
+        Ok(())
+    }
+
+    /// Move a transfer whose connection failed, or whose breaker is open, onto the first
+    /// alternate that is connected, not itself tripped, and can serve it: a download needs
+    /// the source file there, an upload a writable destination directory. Then requeue it.
+    /// Returns false when there is nothing to fail over to.
+    fn try_failover(&self, task: &TransferTask) -> Result<bool> {
+        let current = match &task.connection_id {
+            Some(current) if !task.alternate_connections.is_empty() => current.clone(),
+            _ => return Ok(false),
+        };
+        let ssh_client = self.app_handle.state::<SSHClient>();
+
+        let mut alternates = task.alternate_connections.clone();
+        let mut chosen = None;
+        while !alternates.is_empty() {
+            let candidate = alternates.remove(0);
+            if circuit_breaker::is_open(&candidate) {
+                continue;
+            }
+            let connection = match ssh_client.get_connection(&candidate) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            match Self::failover_candidate_size(&connection, task) {
+                Ok(total_bytes) => {
+                    chosen = Some((candidate, total_bytes));
+                    break;
+                }
+                Err(e) => tracing::warn!("Not failing {} over to {}: {}", task.id, candidate, e),
+            }
+        }
+
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let stored = match transfers.get_mut(&task.id) {
+            Some(stored) if matches!(stored.status, TransferStatus::Pending | TransferStatus::Failed) => stored,
+            _ => return Ok(false),
+        };
+        stored.alternate_connections = alternates;
+        let (candidate, total_bytes) = match chosen {
+            Some(chosen) => chosen,
+            None => return Ok(false),
+        };
+
+        let note = format!("Failed over from {} to {}", current, candidate);
+        tracing::warn!("Transfer {}: {}", task.id, note);
+        stored.warning = Some(match stored.warning.take() {
+            Some(existing) => format!("{}; {}", existing, note),
+            None => note,
+        });
+        stored.connection_id = Some(candidate);
+        stored.status = TransferStatus::Pending;
+        stored.error = None;
+        stored.completed_at = None;
+        stored.transferred_bytes = 0;
+        stored.total_bytes = total_bytes;
+        drop(transfers);
+
+        self.sender.send(task.id.clone())
+            .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        Ok(true)
+    }
+
+    /// Check that `connection` can take over `task`, returning the size to transfer
+    fn failover_candidate_size(connection: &SSHConnection, task: &TransferTask) -> Result<u64> {
+        match task.direction {
+            TransferDirection::LinuxToWindows => {
+                let source = decode_remote_path(&task.source_path)?;
+                let stat = connection.sftp()?.stat(&source)?;
+                Ok(stat.size.unwrap_or(0))
+            }
+            TransferDirection::WindowsToLinux => {
+                let dest = decode_remote_path(&task.dest_path)?;
+                let dir = dest.parent()
+                    .ok_or_else(|| Circle9Error::InvalidPath(format!("{} has no parent directory", task.dest_path)))?;
+                ensure_parent_dirs(connection, &dest)?;
+                let check = check_writable(connection, &dir.to_string_lossy())?;
+                match check.error {
+                    Some(error) => Err(Circle9Error::TransferError(error)),
+                    None => Ok(task.total_bytes),
+                }
+            }
+        }
+    }
+
+    /// Hold the queued transfers through a connection whose breaker just opened. Those with
+    /// alternates stay queued and fail over when they come up.
+    fn block_connection_transfers(&self, connection_id: &str) -> Result<usize> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let mut blocked = 0;
+        for task in transfers.values_mut() {
+            if task.connection_id.as_deref() == Some(connection_id)
+                && task.alternate_connections.is_empty()
+                && matches!(task.status, TransferStatus::Pending)
+            {
+                task.status = TransferStatus::Blocked;
+                blocked += 1;
+            }
+        }
+        Ok(blocked)
+    }
+
+    /// Re-queue Blocked transfers: those through `connection_id`, or with None every one
+    /// whose breaker has cooled down. Returns how many were released.
+    pub fn release_blocked(&self, connection_id: Option<&str>) -> Result<usize> {
+        let released: Vec<String> = {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            transfers.values_mut()
+                .filter(|t| matches!(t.status, TransferStatus::Blocked))
+                .filter(|t| match (connection_id, t.connection_id.as_deref()) {
+                    (Some(id), task_connection) => task_connection == Some(id),
+                    (None, Some(task_connection)) => !circuit_breaker::is_open(task_connection),
+                    (None, None) => true,
+                })
+                .map(|t| {
+                    t.status = TransferStatus::Pending;
+                    t.id.clone()
+                })
+                .collect()
+        };
+
+        for task_id in &released {
+            self.sender.send(task_id.clone())
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+        Ok(released.len())
+    }
+
+    /// The window a task runs in: its own, then its recursive parent's, then the global setting
+    fn allowed_hours_for(transfers: &HashMap<String, TransferTask>, task: &TransferTask) -> Option<AllowedHours> {
+        task.allowed_hours.clone()
+            .or_else(|| {
+                task.parent_id.as_ref()
+                    .and_then(|id| transfers.get(id))
+                    .and_then(|parent| parent.allowed_hours.clone())
+            })
+            .or_else(|| settings::current().allowed_hours)
+    }
+
+    /// Pause running and queued transfers whose allowed-hours window has closed, and
+    /// release the ones it held once the window reopens. Released transfers continue from
+    /// what was written before the window closed. Returns (held, released).
+    pub fn enforce_allowed_hours(&self) -> Result<(usize, usize)> {
+        let now = Utc::now();
+        let mut releasing = Vec::new();
+        let mut held = 0;
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let windows: Vec<(String, Option<AllowedHours>)> = transfers.values()
+                .filter(|t| t.children.is_empty())
+                .map(|t| (t.id.clone(), Self::allowed_hours_for(&transfers, t)))
+                .collect();
+
+            for (task_id, window) in windows {
+                let open = window.map_or(true, |w| w.is_open(now));
+                let task = match transfers.get_mut(&task_id) {
+                    Some(task) => task,
+                    None => continue,
+                };
+                match window_change(&task.status, task.held_by_window, open) {
+                    Some(WindowChange::Hold(status)) => {
+                        task.status = status;
+                        task.held_by_window = true;
+                        held += 1;
+                    }
+                    Some(WindowChange::Release) => releasing.push(task.clone()),
+                    None => {}
                 }
             }
         }
 
+        // Measured before relocking, since an upload's destination is statted over SFTP
+        let checkpoints: Vec<(String, u64)> = releasing.iter()
+            .map(|task| {
+                let checkpoint = if task.resumable() { self.written_length(task).min(task.transferred_bytes) } else { 0 };
+                (task.id.clone(), checkpoint)
+            })
+            .collect();
+
+        let mut released = Vec::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            for (task_id, checkpoint) in checkpoints {
+                let task = match transfers.get_mut(&task_id) {
+                    Some(task) if task.held_by_window
+                        && matches!(task.status, TransferStatus::Paused | TransferStatus::Scheduled) => task,
+                    _ => continue,
+                };
+                task.status = TransferStatus::Pending;
+                task.held_by_window = false;
+                task.transferred_bytes = checkpoint;
+                task.resume_from = checkpoint;
+                released.push(task_id);
+            }
+        }
+
+        if held > 0 {
+            tracing::info!("Allowed hours closed, held {} transfers", held);
+        }
+        for task_id in &released {
+            self.sender.send(task_id.clone())
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+        Ok((held, released.len()))
+    }
+
+    /// Set or clear the allowed-hours window of a task (and so of a recursive task's children)
+    pub fn set_allowed_hours(&self, task_id: &str, window: Option<AllowedHours>) -> Result<()> {
+        if let Some(window) = &window {
+            window.validate()?;
+        }
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let task = transfers.get_mut(task_id)
+                .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+            task.allowed_hours = window;
+        }
+        self.enforce_allowed_hours()?;
         Ok(())
     }
 
-    /// Transfer file from Windows to Linux
+    /// Choose when a task raises `transfer_notification`; thresholds already passed don't fire
+    pub fn set_notifications(&self, task_id: &str, notify_on: Vec<NotifyOn>) -> Result<()> {
+        validate_notify_on(&notify_on)?;
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let task = transfers.get_mut(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+        task.notify_on = notify_on;
+        task.notified_percents.clear();
+        if task.total_bytes > 0 {
+            let reached = (task.transferred_bytes.saturating_mul(100) / task.total_bytes).min(100) as u8;
+            let passed: Vec<u8> = task.notify_on.iter()
+                .filter_map(|n| match n {
+                    NotifyOn::Percent(p) if *p <= reached => Some(*p),
+                    _ => None,
+                })
+                .collect();
+            task.notified_percents = passed;
+        }
+        Ok(())
+    }
+
+    /// Run the task's hook for `stage`, if it has one, on its connection and record it on the task
+    fn run_task_hook(&self, task: &TransferTask, stage: HookStage) -> Result<Option<HookRun>> {
+        let command = match stage {
+            HookStage::Pre => task.hooks.pre_hook.as_deref(),
+            HookStage::Post => task.hooks.post_hook.as_deref(),
+        };
+        let command = match command {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        let connection = task.connection_id.as_deref()
+            .and_then(|id| self.app_handle.state::<SSHClient>().get_connection(id));
+        let run = match connection {
+            Some(connection) => run_hook(&connection, stage, command),
+            None => HookRun::not_run(stage, command, "Connection not found".to_string()),
+        };
+        if let Some(stored) = lock_or_error(&self.active_transfers)?.get_mut(&task.id) {
+            stored.hook_runs.push(run.clone());
+        }
+        Ok(Some(run))
+    }
+
+    /// Fail an upload up front if its remote target directory isn't writable.
+    /// Each directory is probed once, so the children of a recursive upload share the check.
+    /// Returns a warning to attach to the task, e.g. when the directory is a symlink.
+    fn preflight_write_check(&self, task: &TransferTask) -> Result<Option<String>> {
+        let connection_id = match (&task.direction, &task.connection_id) {
+            (TransferDirection::WindowsToLinux, Some(connection_id)) => connection_id,
+            _ => return Ok(None),
+        };
+        if !settings::current().preflight_write_check {
+            return Ok(None);
+        }
+        let dest = decode_remote_path(&task.dest_path)?;
+        let dir = match dest.parent() {
+            Some(dir) => dir.to_string_lossy().to_string(),
+            None => return Ok(None),
+        };
+        let key = (connection_id.clone(), dir.clone());
+        if let Some(warning) = lock_or_error(&self.writable_dirs)?.get(&key) {
+            return Ok(warning.clone());
+        }
+
+        let connection = self.app_handle.state::<SSHClient>()
+            .get_connection(connection_id)
+            .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+        // The probe needs SFTP; an SCP-only server reports a bad destination when the upload starts
+        if !connection.has_sftp() {
+            return Ok(None);
+        }
+        // A directory the upload would create can't be probed until it exists
+        ensure_parent_dirs(&connection, &dest)?;
+        let check = check_writable(&connection, &dir)?;
+        if let Some(error) = check.error {
+            return Err(Circle9Error::TransferError(error));
+        }
+        if let Some(warning) = &check.warning {
+            tracing::warn!("{}", warning);
+        }
+        lock_or_error(&self.writable_dirs)?.insert(key, check.warning.clone());
+        Ok(check.warning)
+    }
+
+    /// Transfer file from Windows to Linux: over SFTP on the task's connection, or to a
+    /// local path such as a mounted share when it has none
     async fn transfer_windows_to_linux(&self, task: &TransferTask) -> Result<()> {
         let source_file = std::fs::File::open(&task.source_path)?;
         let mut reader = std::io::BufReader::new(source_file);
-        
-        // Create destination directory if it doesn't exist
-        if let Some(parent) = Path::new(&task.dest_path).parent() {
-            std::fs::create_dir_all(parent)?;
+
+        let connection = match task.connection_id.as_deref() {
+            Some(connection_id) => Some(self.app_handle.state::<SSHClient>()
+                .get_connection(connection_id)
+                .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?),
+            None => None,
+        };
+
+        // Same limits as SCP downloads: whole files only, untransformed, UTF-8 names
+        if let Some(connection) = &connection {
+            let tuning = connection.tuning();
+            let use_scp = tuning.transfer_protocol == TransferProtocol::Scp
+                && task.resume_from == 0
+                && task.transform == TransferTransform::None;
+            let dest_path = decode_remote_path(&task.dest_path)?;
+            if let Some(scp_dest) = dest_path.to_str().filter(|_| use_scp) {
+                let start_time = std::time::Instant::now();
+                let mut throttle = ProgressThrottle::new(self.progress_throttle());
+                let mut stopped = false;
+                let result = scp_upload(connection, &task.source_path, scp_dest, tuning.chunk_size, |transferred, total| {
+                    if let Some(status) = self.record_scp_progress(task, transferred)? {
+                        stopped = true;
+                        return Err(Circle9Error::TransferError(format!("Transfer {:?}", status)));
+                    }
+                    if throttle.should_emit(transferred, total) {
+                        self.emit_progress(task, "upload", transferred, start_time.elapsed());
+                    }
+                    Ok(())
+                });
+                match result {
+                    Ok(()) => return Ok(()),
+                    // Paused or cancelled between chunks, which is no reason to retry over SFTP
+                    Err(e) if stopped => return Err(e),
+                    Err(e) => tracing::warn!("SCP upload of {} failed, falling back to SFTP: {}", task.source_path, e),
+                }
+            }
         }
 
-        let dest_file = std::fs::File::create(&task.dest_path)?;
-        let mut writer = std::io::BufWriter::new(dest_file);
+        let mut stage = TransformStage::new(task.transform);
+        let (mut writer, mut transferred): (Box<dyn Write>, u64) = match &connection {
+            Some(connection) => {
+                let (dest_file, offset) = open_remote_destination(connection, &task.dest_path, task.resume_from)?;
+                let capacity = connection.tuning().sftp_buffer_size();
+                (Box::new(std::io::BufWriter::with_capacity(capacity, dest_file)), offset)
+            }
+            None => {
+                if let Some(parent) = Path::new(&task.dest_path).parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let (dest_file, offset) = open_destination(&task.dest_path, task.resume_from)?;
+                (Box::new(std::io::BufWriter::new(dest_file)), offset)
+            }
+        };
+        if transferred > 0 {
+            reader.seek(SeekFrom::Start(transferred))?;
+        }
 
         let chunk_size = 8192;
         let mut buffer = vec![0u8; chunk_size];
-        let mut transferred = 0u64;
+        let mut transformed = Vec::new();
         let start_time = std::time::Instant::now();
+        let mut throttle = ProgressThrottle::new(self.progress_throttle());
 
         loop {
             let bytes_read = reader.read(&mut buffer)?;
@@ -207,126 +1727,1005 @@ impl CopyAgent {
                 break;
             }
 
-            writer.write_all(&buffer[..bytes_read])?;
-            transferred += bytes_read as u64;
+            if let Some(connection) = &connection {
+                connection.bandwidth.acquire(bytes_read);
+            }
+            write_transformed(&mut writer, &mut stage, &buffer[..bytes_read], &mut transformed)
+                .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+            // Progress counts source bytes consumed, whatever the transform wrote
+            transferred += bytes_read as u64;
+
+            // Update task progress on every chunk so polling stays accurate
+            let mut notification = None;
+            {
+                let mut transfers = self.active_transfers.lock()
+                    .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
+                if let Some(task) = transfers.get_mut(&task.id) {
+                    task.transferred_bytes = transferred;
+                    notification = task.percent_notification();
+                    if matches!(task.status, TransferStatus::Paused | TransferStatus::Cancelled) {
+                        writer.flush()
+                            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+                        return Err(Circle9Error::TransferError(format!("Transfer {:?}", task.status)));
+                    }
+                }
+            }
+            if let Some(notification) = notification {
+                notification.emit(&self.app_handle);
+            }
+
+            if throttle.should_emit(transferred, task.total_bytes) {
+                self.emit_progress(task, "upload", transferred, start_time.elapsed());
+            }
+        }
+
+        finish_transform(&mut writer, stage)
+            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+        writer.flush()
+            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+        if throttle.needs_final(transferred) {
+            self.emit_progress(task, "upload", transferred, start_time.elapsed());
+        }
+        Ok(())
+    }
+
+    /// Emit a `transfer_progress` event for a task
+    fn emit_progress(&self, task: &TransferTask, direction: &str, transferred: u64, elapsed: std::time::Duration) {
+        // Download sources may be `raw:` encoded remote paths
+        let filename = decode_remote_path(&task.source_path)
+            .map(|path| display_name(&path))
+            .unwrap_or_else(|_| "unknown".to_string());
+        TransferProgress::new(&task.id, &filename, direction, transferred, task.total_bytes, elapsed)
+            .emit(&self.app_handle);
+    }
+
+    /// Transfer file from Linux to Windows
+    async fn transfer_linux_to_windows(&self, task: &TransferTask) -> Result<()> {
+        let connection_id = task.connection_id.as_deref()
+            .ok_or_else(|| Circle9Error::TransferError("Download task has no connection".to_string()))?;
+        let connection = self.app_handle.state::<SSHClient>()
+            .get_connection(connection_id)
+            .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+
+        if let Some(parent) = Path::new(&task.dest_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let tuning = connection.tuning();
+        let start_time = std::time::Instant::now();
+        let mut throttle = ProgressThrottle::new(self.progress_throttle());
+        let source_path = decode_remote_path(&task.source_path)?;
+
+        // scp quotes the name through the remote shell, so only UTF-8 names can go that way,
+        // and it can't start part way through or transform, so those downloads use SFTP
+        let use_scp = tuning.transfer_protocol == TransferProtocol::Scp
+            && task.resume_from == 0
+            && task.transform == TransferTransform::None;
+        if let Some(scp_source) = source_path.to_str().filter(|_| use_scp) {
+            let mut stopped = false;
+            let result = scp_download(&connection, scp_source, &task.dest_path, tuning.chunk_size, |transferred, total| {
+                if let Some(status) = self.record_scp_progress(task, transferred)? {
+                    stopped = true;
+                    return Err(Circle9Error::TransferError(format!("Transfer {:?}", status)));
+                }
+                if throttle.should_emit(transferred, total) {
+                    self.emit_progress(task, "download", transferred, start_time.elapsed());
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => return Ok(()),
+                // Paused or cancelled between chunks, which is no reason to retry over SFTP
+                Err(e) if stopped => return Err(e),
+                Err(e) => tracing::warn!("SCP download of {} failed, falling back to SFTP: {}", task.source_path, e),
+            }
+        }
+
+        let sftp = connection.sftp()?;
+        let mut remote_file = sftp.open(&source_path)?;
+        let mut stage = TransformStage::new(task.transform);
+        let (dest_file, mut transferred) = open_destination(&task.dest_path, task.resume_from)?;
+        if transferred > 0 {
+            remote_file.seek(SeekFrom::Start(transferred))?;
+        }
+        let mut writer = std::io::BufWriter::new(dest_file);
+
+        // A buffer spanning several SFTP requests keeps libssh2's read-ahead pipeline full
+        let mut buffer = vec![0u8; tuning.sftp_buffer_size()];
+        let mut transformed = Vec::new();
+
+        loop {
+            let bytes_read = remote_file.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            connection.bandwidth.acquire(bytes_read);
+            write_transformed(&mut writer, &mut stage, &buffer[..bytes_read], &mut transformed)
+                .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+            transferred += bytes_read as u64;
+
+            let mut notification = None;
+            {
+                let mut transfers = lock_or_error(&self.active_transfers)?;
+                if let Some(task) = transfers.get_mut(&task.id) {
+                    task.transferred_bytes = transferred;
+                    notification = task.percent_notification();
+                    if matches!(task.status, TransferStatus::Paused | TransferStatus::Cancelled) {
+                        writer.flush()
+                            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+                        return Err(Circle9Error::TransferError(format!("Transfer {:?}", task.status)));
+                    }
+                }
+            }
+            if let Some(notification) = notification {
+                notification.emit(&self.app_handle);
+            }
+
+            if throttle.should_emit(transferred, task.total_bytes) {
+                self.emit_progress(task, "download", transferred, start_time.elapsed());
+            }
+        }
+
+        finish_transform(&mut writer, stage)
+            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+        writer.into_inner()
+            .map_err(|e| Circle9Error::from_write(e.into_error(), &task.dest_path, transferred))?
+            .sync_all()
+            .map_err(|e| Circle9Error::from_write(e, &task.dest_path, transferred))?;
+        if throttle.needs_final(transferred) {
+            self.emit_progress(task, "download", transferred, start_time.elapsed());
+        }
+        Ok(())
+    }
+
+    /// Record an SCP transfer's progress and send any percent notification. Returns the task's
+    /// status once it has been paused or cancelled, so the transfer can stop between chunks.
+    fn record_scp_progress(&self, task: &TransferTask, transferred: u64) -> Result<Option<TransferStatus>> {
+        let notification = {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let active = match transfers.get_mut(&task.id) {
+                Some(active) => active,
+                None => return Ok(None),
+            };
+            active.transferred_bytes = transferred;
+            if matches!(active.status, TransferStatus::Paused | TransferStatus::Cancelled) {
+                return Ok(Some(active.status.clone()));
+            }
+            active.percent_notification()
+        };
+        if let Some(notification) = notification {
+            notification.emit(&self.app_handle);
+        }
+        Ok(None)
+    }
+
+    /// Link `dest` to the already-transferred `target`
+    fn create_hard_link(&self, target: &str, dest: &str) -> Result<()> {
+        if let Some(parent) = Path::new(dest).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::hard_link(target, dest)?;
+        Ok(())
+    }
+
+    /// Get file size
+    fn get_file_size(&self, path: &str) -> Result<u64> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(metadata.len())
+    }
+
+    /// Get transfer progress
+    pub fn get_transfer_progress(&self, task_id: &str) -> Option<TransferProgress> {
+        let transfers = lock_or_error(&self.active_transfers).ok()?;
+        let task = transfers.get(task_id)?;
+
+        let filename = Path::new(&task.source_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+        let direction = match task.direction {
+            TransferDirection::WindowsToLinux => "upload",
+            TransferDirection::LinuxToWindows => "download",
+        };
+        let elapsed = task.started_at
+            .and_then(|started| (task.completed_at.unwrap_or_else(Utc::now) - started).to_std().ok())
+            .unwrap_or_default();
+
+        Some(TransferProgress::new(&task.id, filename, direction, task.transferred_bytes, task.total_bytes, elapsed))
+    }
+
+    /// Why `task_id` is still waiting, or None if it isn't Pending or Scheduled
+    pub fn pending_reason(&self, task_id: &str) -> Result<Option<PendingReason>> {
+        let transfers = lock_or_error(&self.active_transfers)?;
+        let task = transfers.get(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+
+        match task.status {
+            TransferStatus::Scheduled if task.held_by_window => {
+                let at = Self::allowed_hours_for(&transfers, task)
+                    .map(|window| window.next_open(Utc::now()));
+                return Ok(Some(PendingReason::Scheduled { at }));
+            }
+            TransferStatus::Scheduled => {
+                // Children of a scheduled recursive transfer are held under the parent's schedule
+                let held_id = task.parent_id.as_deref().unwrap_or(task_id);
+                let at = self.app_handle.state::<TransferScheduler>()
+                    .list()
+                    .into_iter()
+                    .find(|schedule| schedule.task.id == task_id || schedule.task.id == held_id)
+                    .map(|schedule| schedule.next_run);
+                return Ok(Some(PendingReason::Scheduled { at }));
+            }
+            TransferStatus::Blocked => {
+                let retry_at = task.connection_id.as_deref()
+                    .and_then(|id| circuit_breaker::state(id).open_until)
+                    .map(|at| format!(" until {}", at.to_rfc3339()))
+                    .unwrap_or_default();
+                return Ok(Some(PendingReason::Blocked {
+                    reason: format!("Connection keeps failing; transfers are held{}", retry_at),
+                }));
+            }
+            TransferStatus::Pending => {}
+            _ => return Ok(None),
+        }
+
+        if let Some(connection_id) = &task.connection_id {
+            if !self.app_handle.state::<SSHClient>().is_connected(connection_id) {
+                return Ok(Some(PendingReason::Blocked {
+                    reason: format!("Connection {} is not open", connection_id),
+                }));
+            }
+        }
+
+        match Self::outstanding_dependency(&transfers, task) {
+            Ok(Some(id)) => return Ok(Some(PendingReason::WaitingForDependency { task_id: id.clone() })),
+            // The dispatcher fails the task when it next picks it up
+            Err(reason) => return Ok(Some(PendingReason::Blocked { reason })),
+            Ok(None) => {}
+        }
+
+        if let Some(link_target) = &task.link_target {
+            let source = transfers.values().find(|t| {
+                t.parent_id == task.parent_id
+                    && &t.dest_path == link_target
+                    && !matches!(t.status, TransferStatus::Completed)
+            });
+            if let Some(source) = source {
+                return Ok(Some(PendingReason::WaitingForDependency { task_id: source.id.clone() }));
+            }
+        }
+
+        // Leaf tasks start in the order they were queued
+        let position = transfers.values()
+            .filter(|t| t.children.is_empty() && matches!(t.status, TransferStatus::Pending))
+            .filter(|t| (t.created_at, &t.id) < (task.created_at, &task.id))
+            .count() + 1;
+        Ok(Some(PendingReason::WaitingForSlot { position }))
+    }
+
+    /// Get all active transfers
+    pub fn get_active_transfers(&self) -> Vec<TransferTask> {
+        let transfers = lock_or_error(&self.active_transfers).unwrap_or_else(|_| return Vec::new());
+        transfers.values().cloned().collect()
+    }
+
+    /// Cancel a transfer
+    pub fn cancel_transfer(&self, task_id: &str) -> Result<()> {
+        let cancelled = match lock_or_error(&self.active_transfers)?.get_mut(task_id) {
+            Some(task) => {
+                task.status = TransferStatus::Cancelled;
+                true
+            }
+            None => false,
+        };
+        // A task cancelled before the worker picked it up never reaches the completion path
+        if cancelled {
+            self.release_dependents(task_id)?;
+        }
+        Ok(())
+    }
+
+    /// All tasks in `group`, parents and children alike
+    pub fn get_transfers_by_group(&self, group: &str) -> Vec<TransferTask> {
+        let transfers = match lock_or_error(&self.active_transfers) {
+            Ok(transfers) => transfers,
+            Err(_) => return Vec::new(),
+        };
+        transfers.values()
+            .filter(|t| t.group.as_deref() == Some(group))
+            .cloned()
+            .collect()
+    }
+
+    /// Cancel every unfinished task in `group`, returning how many were cancelled
+    pub fn cancel_group(&self, group: &str) -> Result<usize> {
+        let mut cancelled = Vec::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            for task in transfers.values_mut().filter(|t| t.group.as_deref() == Some(group)) {
+                if matches!(
+                    task.status,
+                    TransferStatus::Pending | TransferStatus::Scheduled | TransferStatus::InProgress
+                        | TransferStatus::Paused | TransferStatus::Blocked
+                ) {
+                    task.status = TransferStatus::Cancelled;
+                    cancelled.push(task.clone());
+                }
+            }
+        }
+
+        // Scheduled tasks would otherwise keep firing from their schedules
+        let ids: Vec<String> = cancelled.iter().map(|t| t.id.clone()).collect();
+        self.app_handle.state::<TransferScheduler>().cancel_for_tasks(&ids)?;
+        for task in &cancelled {
+            self.record_child_result(task)?;
+            self.release_dependents(&task.id)?;
+        }
+        tracing::info!("Cancelled {} transfers in group {}", cancelled.len(), group);
+        Ok(cancelled.len())
+    }
+
+    /// Overall queue progress broken down by group.
+    /// Recursive parents are skipped since their children already carry the bytes.
+    pub fn get_queue_summary(&self) -> Result<QueueSummary> {
+        let transfers = lock_or_error(&self.active_transfers)?;
+        let mut groups: HashMap<Option<String>, GroupSummary> = HashMap::new();
+
+        for task in transfers.values().filter(|t| t.children.is_empty()) {
+            let summary = groups.entry(task.group.clone()).or_insert_with(|| GroupSummary {
+                group: task.group.clone(),
+                total_tasks: 0,
+                pending: 0,
+                in_progress: 0,
+                completed: 0,
+                failed: 0,
+                transferred_bytes: 0,
+                total_bytes: 0,
+            });
+            summary.total_tasks += 1;
+            summary.transferred_bytes += task.transferred_bytes;
+            summary.total_bytes += task.total_bytes;
+            match task.status {
+                TransferStatus::Pending | TransferStatus::Scheduled | TransferStatus::Paused | TransferStatus::Blocked => summary.pending += 1,
+                TransferStatus::InProgress => summary.in_progress += 1,
+                TransferStatus::Completed | TransferStatus::HookFailed => summary.completed += 1,
+                TransferStatus::Failed | TransferStatus::Cancelled => summary.failed += 1,
+            }
+        }
+
+        let mut groups: Vec<GroupSummary> = groups.into_values().collect();
+        groups.sort_by(|a, b| a.group.cmp(&b.group));
+        Ok(QueueSummary {
+            total_tasks: groups.iter().map(|g| g.total_tasks).sum(),
+            transferred_bytes: groups.iter().map(|g| g.transferred_bytes).sum(),
+            total_bytes: groups.iter().map(|g| g.total_bytes).sum(),
+            groups,
+        })
+    }
+
+    /// Pause a pending or running transfer; the chunk loop stops at the next chunk
+    pub fn pause_transfer(&self, task_id: &str) -> Result<()> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        if let Some(task) = transfers.get_mut(task_id) {
+            if matches!(task.status, TransferStatus::Pending | TransferStatus::InProgress | TransferStatus::Blocked) {
+                task.status = TransferStatus::Paused;
+                // A user pause isn't lifted when an allowed-hours window opens, or when the queue drains
+                task.held_by_window = false;
+                task.deferred = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pause a pending or running transfer, keeping what it has written, and hold it until
+    /// every other transfer has finished, when it resumes from that point on its own.
+    /// This is a hold until the queue is idle; the queue has no priorities to lower.
+    pub fn defer_transfer(&self, task_id: &str) -> Result<()> {
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let task = transfers.get_mut(task_id)
+                .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+            if !task.children.is_empty() {
+                return Err(Circle9Error::TransferError("Recursive transfers can't be deferred as a whole".to_string()));
+            }
+            if !matches!(task.status, TransferStatus::Pending | TransferStatus::InProgress) {
+                return Err(Circle9Error::TransferError(format!("Transfer is {:?}", task.status)));
+            }
+            // The chunk loop sees the pause and stops after flushing what it has written
+            task.status = TransferStatus::Paused;
+            task.held_by_window = false;
+            task.deferred = true;
+        }
+        tracing::info!("Deferred transfer {}", task_id);
+
+        // Otherwise the transfer that stops next does the check
+        if !self.has_in_flight_transfers() {
+            self.resume_deferred_if_idle()?;
+        }
+        Ok(())
+    }
+
+    /// How much of a task's destination exists, read over SFTP for an upload with a
+    /// connection; 0 when it can't be read
+    fn written_length(&self, task: &TransferTask) -> u64 {
+        match (&task.direction, task.connection_id.as_deref()) {
+            (TransferDirection::WindowsToLinux, Some(connection_id)) => {
+                let connection = match self.app_handle.state::<SSHClient>().get_connection(connection_id) {
+                    Some(connection) => connection,
+                    None => return 0,
+                };
+                let path = match decode_remote_path(&task.dest_path) {
+                    Ok(path) => path,
+                    Err(_) => return 0,
+                };
+                connection.sftp()
+                    .ok()
+                    .and_then(|sftp| sftp.stat(&path).ok())
+                    .and_then(|stat| stat.size)
+                    .unwrap_or(0)
+            }
+            _ => std::fs::metadata(&task.dest_path).map(|m| m.len()).unwrap_or(0),
+        }
+    }
+
+    /// Requeue deferred transfers, from their checkpoints, once nothing else is waiting or running.
+    /// Tasks still waiting on a dependency don't count, since that may be the deferred task itself.
+    fn resume_deferred_if_idle(&self) -> Result<()> {
+        let deferred: Vec<TransferTask> = {
+            let transfers = lock_or_error(&self.active_transfers)?;
+            let waiting_on_dependency = |t: &TransferTask| t.depends_on.iter().any(|id| {
+                !matches!(transfers.get(id).map(|d| &d.status), Some(TransferStatus::Completed))
+            });
+            let busy = transfers.values().any(|t| {
+                t.children.is_empty() && match t.status {
+                    TransferStatus::InProgress => true,
+                    TransferStatus::Pending => !waiting_on_dependency(t),
+                    _ => false,
+                }
+            });
+            if busy || self.has_in_flight_transfers() {
+                return Ok(());
+            }
+            transfers.values()
+                .filter(|t| t.deferred && matches!(t.status, TransferStatus::Paused))
+                .cloned()
+                .collect()
+        };
+
+        // Measured before relocking, since an upload's destination is statted over SFTP
+        let checkpoints: Vec<(String, u64)> = deferred.iter()
+            .map(|task| {
+                let checkpoint = if task.resumable() { self.written_length(task).min(task.transferred_bytes) } else { 0 };
+                (task.id.clone(), checkpoint)
+            })
+            .collect();
+
+        let mut resumed = Vec::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            for (task_id, checkpoint) in checkpoints {
+                let task = match transfers.get_mut(&task_id) {
+                    Some(task) if task.deferred && matches!(task.status, TransferStatus::Paused) => task,
+                    _ => continue,
+                };
+                task.status = TransferStatus::Pending;
+                task.deferred = false;
+                task.transferred_bytes = checkpoint;
+                task.resume_from = checkpoint;
+                resumed.push(task_id);
+            }
+        }
+
+        for task_id in resumed {
+            tracing::info!("Queue drained, resuming deferred transfer {}", task_id);
+            self.sender.send(task_id)
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the remote directory `dest` goes into is case-sensitive, from the probe cache or a
+    /// fresh probe. Linux is assumed case-sensitive when there's no connection to ask.
+    fn remote_case_sensitivity(&self, connection_id: Option<&str>, dest: &Path) -> bool {
+        let (connection_id, dir) = match (connection_id, dest.parent()) {
+            (Some(connection_id), Some(dir)) => (connection_id, dir),
+            _ => return true,
+        };
+        let dir = dir.to_string_lossy().to_string();
+        if let Some(cached) = lock_or_error(&CASE_AGENT).ok().and_then(|agent| agent.remote_case_sensitivity(connection_id, &dir)) {
+            return cached;
+        }
+        self.app_handle.state::<SSHClient>()
+            .get_connection(connection_id)
+            .and_then(|connection| detect_remote_case_sensitivity(&connection, connection_id, dir)
+                .map_err(|e| tracing::warn!("Could not probe case sensitivity for {}: {}", dest.display(), e))
+                .ok())
+            .map_or(true, |probe| probe.case_sensitive)
+    }
+
+    /// Point a queued or paused transfer at a new destination.
+    /// Returns any case conflict the new name introduces so the caller can resolve it.
+    pub fn update_transfer_destination(&self, task_id: &str, new_dest: String) -> Result<Option<CaseConflict>> {
+        let new_path = Path::new(&new_dest);
+        if new_dest.trim().is_empty() {
+            return Err(Circle9Error::InvalidPath("Destination is empty".to_string()));
+        }
+        if new_path.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+            return Err(Circle9Error::InvalidPath("Path traversal detected".to_string()));
+        }
+
+        // Probing may touch the remote, so it happens before the queue is locked for the update
+        let connection_id = lock_or_error(&self.active_transfers)?
+            .get(task_id)
+            .filter(|task| matches!(task.direction, TransferDirection::WindowsToLinux))
+            .and_then(|task| task.connection_id.clone());
+        let remote_case_sensitive = self.remote_case_sensitivity(connection_id.as_deref(), new_path);
+
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let task = transfers.get_mut(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer {} not found", task_id)))?;
+
+        if !matches!(task.status, TransferStatus::Pending | TransferStatus::Paused) {
+            return Err(Circle9Error::TransferError(format!(
+                "Destination can only be changed while a transfer is pending or paused (currently {:?})",
+                task.status
+            )));
+        }
+        if !task.children.is_empty() {
+            return Err(Circle9Error::TransferError(
+                "Destination of a recursive transfer can't be changed once its files are queued".to_string()
+            ));
+        }
+
+        let source = Path::new(&task.source_path);
+        let conflict = {
+            let mut case_agent = lock_or_error(&CASE_AGENT)?;
+            match task.direction {
+                TransferDirection::WindowsToLinux => case_agent.check_windows_to_linux_conflict(source, new_path, remote_case_sensitive),
+                TransferDirection::LinuxToWindows => case_agent.check_linux_to_windows_conflict(source, new_path),
+            }
+        }?;
+
+        tracing::info!("Moving destination of {}: {} -> {}", task_id, task.dest_path, new_dest);
+        task.dest_path = new_dest;
+        // Anything already written went to the old destination
+        task.transferred_bytes = 0;
+        Ok(conflict)
+    }
+
+    /// Resume a paused transfer by sending it back through the queue. It continues from
+    /// what was already written, as a deferred transfer does.
+    pub fn resume_transfer(&self, task_id: &str) -> Result<()> {
+        let paused = match lock_or_error(&self.active_transfers)?.get(task_id) {
+            Some(task) if matches!(task.status, TransferStatus::Paused) => task.clone(),
+            _ => return Ok(()),
+        };
+        // Measured before relocking, since an upload's destination is statted over SFTP
+        let checkpoint = if paused.resumable() { self.written_length(&paused).min(paused.transferred_bytes) } else { 0 };
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            match transfers.get_mut(task_id) {
+                Some(task) if matches!(task.status, TransferStatus::Paused) => {
+                    task.status = TransferStatus::Pending;
+                    task.transferred_bytes = checkpoint;
+                    task.resume_from = checkpoint;
+                    task.deferred = false;
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        self.sender.send(task_id.to_string())
+            .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        Ok(())
+    }
+
+    /// Pause every pending or running transfer, returning how many were paused
+    pub fn pause_all_transfers(&self) -> Result<usize> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let mut paused = 0;
+        for task in transfers.values_mut() {
+            // Recursive parents only aggregate their children
+            if !task.children.is_empty() {
+                continue;
+            }
+            if matches!(task.status, TransferStatus::Pending | TransferStatus::InProgress) {
+                task.status = TransferStatus::Paused;
+                paused += 1;
+            }
+        }
+        Ok(paused)
+    }
+
+    pub fn progress_throttle(&self) -> ProgressThrottleConfig {
+        settings::current().progress_throttle
+    }
+
+    /// Whether any chunk loop is still running
+    pub fn has_in_flight_transfers(&self) -> bool {
+        self.in_flight.load(Ordering::SeqCst) > 0
+    }
+
+    fn queue_file() -> Result<PathBuf> {
+        Ok(crate::utils::app_data_dir()?.join("transfer_queue.json"))
+    }
+
+    /// Persist all unfinished transfers so they survive a restart
+    pub fn persist_queue(&self) -> Result<usize> {
+        let tasks: Vec<TransferTask> = {
+            let transfers = lock_or_error(&self.active_transfers)?;
+            let unfinished = |t: &TransferTask| {
+                !matches!(t.status, TransferStatus::Completed | TransferStatus::HookFailed | TransferStatus::Cancelled)
+            };
+            // Keep every child of an unfinished recursive parent so its aggregate stays whole
+            transfers.values()
+                .filter(|t| {
+                    unfinished(t) || t.parent_id.as_ref()
+                        .and_then(|p| transfers.get(p))
+                        .map_or(false, |p| unfinished(p))
+                })
+                .cloned()
+                .collect()
+        };
+
+        let recursive_results: HashMap<String, Vec<FileTransferResult>> = {
+            let results = lock_or_error(&self.recursive_results)?;
+            tasks.iter()
+                .filter_map(|t| results.get(&t.id).map(|r| (t.id.clone(), r.clone())))
+                .collect()
+        };
+
+        let path = Self::queue_file()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let queue = PersistedQueue { tasks, recursive_results };
+        crate::utils::write_atomic(&path, serde_json::to_string_pretty(&queue)?.as_bytes())?;
+
+        tracing::info!("Persisted {} transfers to {}", queue.tasks.len(), path.display());
+        Ok(queue.tasks.len())
+    }
+
+    /// Load transfers persisted by a previous session; interrupted ones come back paused
+    pub fn restore_persisted_queue(&self) -> Result<usize> {
+        let path = Self::queue_file()?;
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let queue: PersistedQueue = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let restored = queue.tasks.len();
+        let mut requeue = Vec::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            for mut task in queue.tasks {
+                match task.status {
+                    TransferStatus::InProgress if task.children.is_empty() => {
+                        task.status = TransferStatus::Paused;
+                        task.interrupted = true;
+                    }
+                    TransferStatus::Paused if task.children.is_empty() => {
+                        task.interrupted = true;
+                    }
+                    // Breaker state isn't persisted, so blocked tasks get another try
+                    TransferStatus::Pending | TransferStatus::Blocked if task.children.is_empty() => {
+                        task.status = TransferStatus::Pending;
+                        requeue.push(task.id.clone());
+                    }
+                    _ => {}
+                }
+                transfers.insert(task.id.clone(), task);
+            }
+        }
+
+        lock_or_error(&self.recursive_results)?.extend(queue.recursive_results);
+
+        for task_id in requeue {
+            self.sender.send(task_id)
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+
+        tracing::info!("Restored {} persisted transfers", restored);
+        Ok(restored)
+    }
+
+    /// Size of a task's source now, or why it can't be read
+    fn current_source_size(&self, task: &TransferTask) -> std::result::Result<u64, String> {
+        match task.direction {
+            TransferDirection::WindowsToLinux => std::fs::metadata(&task.source_path)
+                .map(|m| m.len())
+                .map_err(|e| format!("Source file is no longer readable: {}", e)),
+            TransferDirection::LinuxToWindows => {
+                let connection_id = task.connection_id.as_deref()
+                    .ok_or_else(|| "Download has no connection".to_string())?;
+                let connection = self.app_handle.state::<SSHClient>()
+                    .get_connection(connection_id)
+                    .ok_or_else(|| format!("Connection {} is not open; reconnect and resume again", connection_id))?;
+                let source = decode_remote_path(&task.source_path).map_err(|e| e.to_string())?;
+                let stat = connection.sftp()
+                    .and_then(|sftp| Ok(sftp.stat(&source)?))
+                    .map_err(|e| format!("Source file is no longer readable: {}", e))?;
+                Ok(stat.size.unwrap_or(0))
+            }
+        }
+    }
+
+    /// Requeue the transfers that were running or paused when the previous session ended.
+    /// Each continues from the bytes already in its destination when the source is unchanged
+    /// in size, and starts over otherwise. Ones whose source can't be reached stay paused.
+    pub fn resume_persisted_transfers(&self) -> Result<ResumeReport> {
+        let interrupted: Vec<TransferTask> = lock_or_error(&self.active_transfers)?
+            .values()
+            .filter(|t| t.interrupted && t.children.is_empty() && matches!(t.status, TransferStatus::Paused))
+            .cloned()
+            .collect();
 
-            // Calculate progress
-            let elapsed = start_time.elapsed().as_secs_f64();
-            let speed = if elapsed > 0.0 {
-                (transferred as f64 / elapsed) as u64
-            } else {
-                0
+        let mut report = ResumeReport::default();
+        let mut requeue = Vec::new();
+        for task in interrupted {
+            let source_size = match self.current_source_size(&task) {
+                Ok(size) => size,
+                Err(reason) => {
+                    tracing::warn!("Cannot resume transfer {}: {}", task.id, reason);
+                    report.failed.push(ResumeFailure { task_id: task.id, source_path: task.source_path, reason });
+                    continue;
+                }
             };
-            let remaining_bytes = task.total_bytes.saturating_sub(transferred);
-            let estimated_remaining = if speed > 0 {
-                remaining_bytes / speed
+            // The destination length is what actually reached disk before shutdown
+            let written = self.written_length(&task);
+            let checkpoint = if source_size == task.total_bytes && task.resumable() {
+                written.min(task.transferred_bytes)
             } else {
                 0
             };
 
-            // Update task progress
-            {
-                let mut transfers = self.active_transfers.lock()
-                    .map_err(|e| anyhow::anyhow!("Mutex poisoned: {}", e))?;
-                if let Some(task) = transfers.get_mut(&task.id) {
-                    task.transferred_bytes = transferred;
-                }
-            }
-
-            // Emit progress event (this would be handled by the Tauri app)
-            let progress = TransferProgress {
-                task_id: task.id.clone(),
-                filename: Path::new(&task.source_path)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown")
-                    .to_string(),
-                direction: "upload".to_string(),
-                bytes_transferred: transferred,
-                total_bytes: task.total_bytes,
-                percentage: (transferred as f64 / task.total_bytes as f64) * 100.0,
-                speed_bytes_per_sec: speed,
-                estimated_remaining_secs: estimated_remaining,
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let stored = match transfers.get_mut(&task.id) {
+                Some(stored) if matches!(stored.status, TransferStatus::Paused) => stored,
+                _ => continue,
             };
+            stored.status = TransferStatus::Pending;
+            stored.interrupted = false;
+            stored.total_bytes = source_size;
+            stored.transferred_bytes = checkpoint;
+            stored.resume_from = checkpoint;
+            requeue.push(task.id.clone());
+            report.resumed.push(ResumedTransfer { task_id: task.id, source_path: task.source_path, resumed_from: checkpoint });
+        }
 
-            // Emit the progress event to the frontend
-            if let Err(e) = self.app_handle.emit_all("transfer_progress", &progress) {
-                eprintln!("Failed to emit transfer progress: {}", e);
-            }
+        for task_id in requeue {
+            self.sender.send(task_id)
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
         }
 
-        writer.flush()?;
-        Ok(())
+        tracing::info!("Resumed {} persisted transfers, {} could not be resumed", report.resumed.len(), report.failed.len());
+        Ok(report)
     }
 
-    /// Transfer file from Linux to Windows
-    async fn transfer_linux_to_windows(&self, task: &TransferTask) -> Result<()> {
-        // This would use the SSH client to download the file
-        // For now, this is a placeholder implementation
-        Err(anyhow::anyhow!("Linux to Windows transfer not implemented yet"))
+    /// Get a copy of a single task
+    pub fn get_task(&self, task_id: &str) -> Option<TransferTask> {
+        lock_or_error(&self.active_transfers).ok()?.get(task_id).cloned()
     }
 
-    /// Get file size
-    fn get_file_size(&self, path: &str) -> Result<u64> {
-        let metadata = std::fs::metadata(path)?;
-        Ok(metadata.len())
+    /// Hold a pending task (and its children, if recursive) until a schedule releases it.
+    /// A recursive parent is marked in progress as soon as it's created, so it qualifies as
+    /// long as none of its files have started.
+    pub fn hold_for_schedule(&self, task_id: &str) -> Result<TransferTask> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let task = transfers.get(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+
+        let not_started = match task.status {
+            TransferStatus::Pending => true,
+            TransferStatus::InProgress if !task.children.is_empty() => task.children.iter()
+                .all(|id| transfers.get(id).map_or(false, |child| matches!(child.status, TransferStatus::Pending))),
+            _ => false,
+        };
+        if !not_started {
+            return Err(Circle9Error::TransferError(format!(
+                "Only transfers that haven't started can be scheduled (task is {:?})",
+                task.status
+            )));
+        }
+        let task = transfers.get_mut(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+        task.status = TransferStatus::Scheduled;
+        let task = task.clone();
+
+        for child_id in &task.children {
+            if let Some(child) = transfers.get_mut(child_id) {
+                if matches!(child.status, TransferStatus::Pending) {
+                    child.status = TransferStatus::Scheduled;
+                }
+            }
+        }
+
+        Ok(task)
     }
 
-    /// Get transfer progress
-    pub fn get_transfer_progress(&self, task_id: &str) -> Option<TransferProgress> {
-        let transfers = lock_or_error(&self.active_transfers).ok()?;
-        if let Some(task) = transfers.get(task_id) {
-            let percentage = if task.total_bytes > 0 {
-                (task.transferred_bytes as f64 / task.total_bytes as f64) * 100.0
-            } else {
-                0.0
+    /// Move a scheduled task (and its scheduled children) back to Pending and queue it
+    pub fn release_scheduled(&self, task_id: &str) -> Result<()> {
+        let mut queued = Vec::new();
+        {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let children = match transfers.get_mut(task_id) {
+                Some(task) if matches!(task.status, TransferStatus::Scheduled) => {
+                    if task.children.is_empty() {
+                        task.status = TransferStatus::Pending;
+                        queued.push(task_id.to_string());
+                    } else {
+                        task.status = TransferStatus::InProgress;
+                        task.started_at = Some(Utc::now());
+                    }
+                    task.children.clone()
+                }
+                _ => return Ok(()),
             };
 
-            Some(TransferProgress {
-                task_id: task.id.clone(),
-                filename: Path::new(&task.source_path)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown")
-                    .to_string(),
-                direction: match task.direction {
-                    TransferDirection::WindowsToLinux => "upload".to_string(),
-                    TransferDirection::LinuxToWindows => "download".to_string(),
-                },
-                bytes_transferred: task.transferred_bytes,
-                total_bytes: task.total_bytes,
-                percentage,
-                speed_bytes_per_sec: 0, // Would need to calculate this
-                estimated_remaining_secs: 0, // Would need to calculate this
-            })
+            for child_id in children {
+                if let Some(child) = transfers.get_mut(&child_id) {
+                    if matches!(child.status, TransferStatus::Scheduled) {
+                        child.status = TransferStatus::Pending;
+                        queued.push(child_id);
+                    }
+                }
+            }
+        }
+
+        for task_id in queued {
+            self.sender.send(task_id)
+                .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Queue a fresh run of a scheduled template task, returning the new task id
+    pub fn run_from_template(&self, template: &TransferTask) -> Result<String> {
+        if let (TransferDirection::LinuxToWindows, Some(connection_id)) = (&template.direction, &template.connection_id) {
+        // The size limit and any line-ending detection were settled when the transfer was first created
+        let options = TransferOptions {
+            group: template.group.clone(),
+            allow_oversize: true,
+            notify_on: template.notify_on.clone(),
+            transform: template.transform,
+            connection_id: template.connection_id.clone(),
+            alternate_connections: template.alternate_connections.clone(),
+            hooks: template.hooks.clone(),
+            write_manifest: template.write_manifest,
+            ..TransferOptions::default()
+        };
+        if let (TransferDirection::LinuxToWindows, Some(_)) = (&template.direction, &template.connection_id) {
+            self.create_download_task(template.source_path.clone(), template.dest_path.clone(), options)
+        } else if template.children.is_empty() {
+            self.create_transfer_task(
+                template.source_path.clone(),
+                template.dest_path.clone(),
+                template.direction.clone(),
+                options,
+            )
         } else {
-            None
+            // Re-walk the source so files added since the schedule was made are included
+            self.create_recursive_transfer(
+                template.source_path.clone(),
+                template.dest_path.clone(),
+                template.direction.clone(),
+                template.modified_since,
+                options,
+            ).map(|created| created.task_id)
         }
     }
 
-    /// Get all active transfers
-    pub fn get_active_transfers(&self) -> Vec<TransferTask> {
-        let transfers = lock_or_error(&self.active_transfers).unwrap_or_else(|_| return Vec::new());
-        transfers.values().cloned().collect()
+    /// Put back a scheduled task that is missing from the queue (e.g. after a crash)
+    pub fn restore_scheduled_task(&self, task: TransferTask) -> Result<()> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        transfers.entry(task.id.clone()).or_insert(TransferTask {
+            status: TransferStatus::Scheduled,
+            ..task
+        });
+        Ok(())
     }
 
-    /// Cancel a transfer
-    pub fn cancel_transfer(&self, task_id: &str) -> Result<()> {
+    /// Mark a task as cancelled, e.g. when its one-off schedule was missed
+    pub fn cancel_with_reason(&self, task_id: &str, reason: &str) -> Result<()> {
         let mut transfers = lock_or_error(&self.active_transfers)?;
         if let Some(task) = transfers.get_mut(task_id) {
             task.status = TransferStatus::Cancelled;
+            task.error = Some(reason.to_string());
         }
         Ok(())
     }
 
-    /// Retry a failed transfer
+    /// Cancel the queued, running and paused tasks reading or writing through a connection
+    /// that has closed, returning them. Scheduled tasks are kept for when it reconnects.
+    pub fn cancel_connection_transfers(&self, connection_id: &str, reason: &str) -> Result<Vec<TransferTask>> {
+        let mut transfers = lock_or_error(&self.active_transfers)?;
+        let mut cancelled = Vec::new();
+        for task in transfers.values_mut() {
+            if task.connection_id.as_deref() != Some(connection_id) {
+                continue;
+            }
+            if matches!(task.status, TransferStatus::Pending | TransferStatus::InProgress | TransferStatus::Paused | TransferStatus::Blocked) {
+                task.status = TransferStatus::Cancelled;
+                task.error = Some(reason.to_string());
+                cancelled.push(task.clone());
+            }
+        }
+        Ok(cancelled)
+    }
+
+    /// Delete the partial file a failed or cancelled task left at its destination.
+    /// Returns false when there was nothing to remove, e.g. for completed tasks. A file
+    /// whose size doesn't match what the task wrote is left alone, since it may predate the task.
+    pub fn cleanup_partial_transfer(&self, task_id: &str) -> Result<bool> {
+        let task = self.get_task(task_id)
+            .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", task_id)))?;
+
+        match task.status {
+            TransferStatus::Failed | TransferStatus::Cancelled => {}
+            TransferStatus::InProgress | TransferStatus::Paused => {
+                return Err(Circle9Error::TransferError(format!(
+                    "Transfer is {:?}; cancel it before cleaning up", task.status
+                )));
+            }
+            _ => return Ok(false),
+        }
+        if task.started_at.is_none() || task.link_target.is_some() {
+            return Ok(false);
+        }
+
+        let remote_connection = match task.direction {
+            TransferDirection::WindowsToLinux => task.connection_id.as_deref(),
+            TransferDirection::LinuxToWindows => None,
+        };
+        let removed = match remote_connection {
+            Some(connection_id) => {
+                let connection = self.app_handle.state::<SSHClient>()
+                    .get_connection(connection_id)
+                    .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+                let dest = decode_remote_path(&task.dest_path)?;
+                let sftp = connection.sftp()?;
+                match sftp.lstat(&dest) {
+                    Ok(stat) if stat.is_file() && stat.size == Some(task.transferred_bytes) => {
+                        sftp.unlink(&dest)?;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            None => match std::fs::metadata(&task.dest_path) {
+                Ok(metadata) if metadata.is_file() && metadata.len() == task.transferred_bytes => {
+                    std::fs::remove_file(&task.dest_path)?;
+                    true
+                }
+                _ => false,
+            },
+        };
+
+        if removed {
+            tracing::info!("Removed partial file {} left by task {}", task.dest_path, task_id);
+        }
+        Ok(removed)
+    }
+
+    /// Retry a failed transfer. A task whose post-transfer hook failed is sent again in
+    /// full, with its hooks run afresh.
     pub fn retry_transfer(&self, task_id: &str) -> Result<()> {
         let mut transfers = lock_or_error(&self.active_transfers)?;
         if let Some(task) = transfers.get_mut(task_id) {
+            if !matches!(task.status, TransferStatus::Failed | TransferStatus::Cancelled | TransferStatus::HookFailed) {
+                return Err(Circle9Error::TransferError(format!(
+                    "Transfer task {} is {:?} and can't be retried", task_id, task.status
+                )));
+            }
             task.status = TransferStatus::Pending;
             task.error = None;
             task.transferred_bytes = 0;
+            task.started_at = None;
+            task.completed_at = None;
+            task.hook_runs.clear();
         }
+        drop(transfers);
 
         // Send task to queue via channel
         if let Err(e) = self.sender.send(task_id.to_string()) {
@@ -337,26 +2736,329 @@ impl CopyAgent {
 
         Ok(())
     }
+
+    /// Re-queue only the failed children of a recursive transfer
+    pub fn retry_failed_children(&self, parent_task_id: &str) -> Result<usize> {
+        let retried: Vec<String> = {
+            let mut transfers = lock_or_error(&self.active_transfers)?;
+            let children = transfers.get(parent_task_id)
+                .map(|t| t.children.clone())
+                .ok_or_else(|| Circle9Error::TransferError(format!("Transfer task {} not found", parent_task_id)))?;
+
+            let mut retried = Vec::new();
+            for child_id in children {
+                if let Some(child) = transfers.get_mut(&child_id) {
+                    if matches!(child.status, TransferStatus::Failed | TransferStatus::HookFailed) {
+                        child.status = TransferStatus::Pending;
+                        child.error = None;
+                        child.transferred_bytes = 0;
+                        child.started_at = None;
+                        child.completed_at = None;
+                        child.hook_runs.clear();
+                        retried.push(child.source_path.clone());
+                        self.sender.send(child_id.clone())
+                            .map_err(|_| Circle9Error::TransferError("Failed to queue transfer task".to_string()))?;
+                    }
+                }
+            }
+
+            if !retried.is_empty() {
+                if let Some(parent) = transfers.get_mut(parent_task_id) {
+                    parent.status = TransferStatus::InProgress;
+                    parent.error = None;
+                    parent.completed_at = None;
+                }
+            }
+
+            retried
+        };
+
+        // Drop the stale failure records so the aggregate counts them as pending again
+        if let Some(results) = lock_or_error(&self.recursive_results)?.get_mut(parent_task_id) {
+            results.retain(|r| !retried.contains(&r.path));
+        }
+
+        tracing::info!("Retrying {} failed files of transfer {}", retried.len(), parent_task_id);
+        Ok(retried.len())
+    }
+}
+
+/// (device, inode) identifying a file with more than one hard link
+#[cfg(unix)]
+fn hardlink_key(_path: &Path, metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// (volume serial number, file index) identifying a file with more than one hard link.
+/// std doesn't expose these on stable, so they're read with `GetFileInformationByHandle`.
+#[cfg(windows)]
+fn hardlink_key(path: &Path, _metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ByHandleFileInformation {
+        file_attributes: u32,
+        creation_time: [u32; 2],
+        last_access_time: [u32; 2],
+        last_write_time: [u32; 2],
+        volume_serial_number: u32,
+        file_size_high: u32,
+        file_size_low: u32,
+        number_of_links: u32,
+        file_index_high: u32,
+        file_index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(file: RawHandle, info: *mut ByHandleFileInformation) -> i32;
+    }
+
+    let file = std::fs::File::open(path).ok()?;
+    let mut info = ByHandleFileInformation::default();
+    // SAFETY: the handle is open for the duration of the call and `info` matches
+    // BY_HANDLE_FILE_INFORMATION's layout
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        return None;
+    }
+    let index = (u64::from(info.file_index_high) << 32) | u64::from(info.file_index_low);
+    (info.number_of_links > 1).then(|| (u64::from(info.volume_serial_number), index))
+}
+
+/// No file identity to go on, so every file is copied
+#[cfg(not(any(unix, windows)))]
+fn hardlink_key(_path: &Path, _metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// What an allowed-hours window does to a leaf task
+#[derive(Debug)]
+enum WindowChange {
+    /// The window closed: hold the task in this status until it reopens
+    Hold(TransferStatus),
+    /// The window reopened on a task it was holding
+    Release,
+}
+
+fn window_change(status: &TransferStatus, held_by_window: bool, open: bool) -> Option<WindowChange> {
+    match (status, open) {
+        (TransferStatus::InProgress, false) => Some(WindowChange::Hold(TransferStatus::Paused)),
+        (TransferStatus::Pending, false) => Some(WindowChange::Hold(TransferStatus::Scheduled)),
+        (TransferStatus::Paused | TransferStatus::Scheduled, true) if held_by_window => Some(WindowChange::Release),
+        _ => None,
+    }
+}
+
+/// Refuse a file over `max_transfer_file_size` unless the caller opted out
+fn check_size_limit(path: &str, size: u64, allow_oversize: bool) -> Result<()> {
+    match settings::current().max_transfer_file_size {
+        Some(limit) if size > limit && !allow_oversize => {
+            Err(Circle9Error::FileTooLarge { path: path.to_string(), size, limit })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_notify_on(notify_on: &[NotifyOn]) -> Result<()> {
+    if notify_on.iter().any(|n| matches!(n, NotifyOn::Percent(p) if *p == 0 || *p > 100)) {
+        return Err(Circle9Error::TransferError("Notification percentages must be between 1 and 100".to_string()));
+    }
+    Ok(())
+}
+
+/// Write `chunk` through the task's transform, skipping the copy when there is none
+fn write_transformed(writer: &mut impl Write, stage: &mut TransformStage, chunk: &[u8], scratch: &mut Vec<u8>) -> std::io::Result<()> {
+    if stage.is_identity() {
+        return writer.write_all(chunk);
+    }
+    scratch.clear();
+    stage.apply(chunk, scratch)?;
+    writer.write_all(scratch)
+}
+
+/// Write out whatever the transform held back until the end of the source
+fn finish_transform(writer: &mut impl Write, stage: TransformStage) -> std::io::Result<()> {
+    let mut tail = Vec::new();
+    stage.finish(&mut tail)?;
+    writer.write_all(&tail)
+}
+
+/// `open_destination` for an upload's remote file, creating any missing parent directories
+fn open_remote_destination(connection: &SSHConnection, dest: &str, offset: u64) -> Result<(ssh2::File, u64)> {
+    let path = decode_remote_path(dest)?;
+    ensure_parent_dirs(connection, &path)?;
+    let sftp = connection.sftp()?;
+    let existing = sftp.stat(&path).ok().and_then(|stat| stat.size).unwrap_or(0);
+    if offset == 0 || existing < offset {
+        return Ok((create_remote_file(&sftp, &path)?, 0));
+    }
+    let mut file = sftp.open_mode(&path, ssh2::OpenFlags::WRITE, 0o644, ssh2::OpenType::File)?;
+    file.setstat(ssh2::FileStat { size: Some(offset), uid: None, gid: None, perm: None, atime: None, mtime: None })?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok((file, offset))
+}
+
+/// Open `dest` for writing, keeping its first `offset` bytes when it has at least that many.
+/// Returns the file positioned where writing should continue, and that position.
+fn open_destination(dest: &str, offset: u64) -> Result<(std::fs::File, u64)> {
+    let existing = std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+    if offset == 0 || existing < offset {
+        return Ok((std::fs::File::create(dest)?, 0));
+    }
+    let mut file = std::fs::OpenOptions::new().write(true).open(dest)?;
+    file.set_len(offset)?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok((file, offset))
 }
 
 // Global copy agent instance removed - using Tauri managed state instead
 
 // Tauri commands for copy operations
 
+fn parse_direction(direction: &str) -> Result<TransferDirection, String> {
+    match direction {
+        "windows_to_linux" => Ok(TransferDirection::WindowsToLinux),
+        "linux_to_windows" => Ok(TransferDirection::LinuxToWindows),
+        _ => Err("Invalid direction".to_string()),
+    }
+}
+
 #[tauri::command]
 pub async fn create_transfer_task(
     copy_agent: State<'_, CopyAgent>,
     source_path: String,
     dest_path: String,
     direction: String,
+    options: Option<TransferOptions>,
+) -> Result<String, String> {
+    let direction = parse_direction(&direction)?;
+
+    copy_agent.create_transfer_task(source_path, dest_path, direction, options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Raise `transfer_notification` when the task completes, fails or reaches a percentage
+#[tauri::command]
+pub async fn set_transfer_notifications(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String,
+    notify_on: Vec<NotifyOn>,
+) -> Result<(), String> {
+    copy_agent.set_notifications(&task_id, notify_on)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_transfer_allowed_hours(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String,
+    allowed_hours: Option<AllowedHours>,
+) -> Result<(), String> {
+    copy_agent.set_allowed_hours(&task_id, allowed_hours)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cleanup_partial_transfer(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String
+) -> Result<bool, String> {
+    copy_agent.cleanup_partial_transfer(&task_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn retry_failed_children(
+    copy_agent: State<'_, CopyAgent>,
+    parent_task_id: String
+) -> Result<usize, String> {
+    copy_agent.retry_failed_children(&parent_task_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_recursive_transfer_task(
+    copy_agent: State<'_, CopyAgent>,
+    source_dir: String,
+    dest_dir: String,
+    direction: String,
+    options: Option<TransferOptions>,
 ) -> Result<String, String> {
-    let direction = match direction.as_str() {
-        "windows_to_linux" => crate::copy_agent::TransferDirection::WindowsToLinux,
-        "linux_to_windows" => crate::copy_agent::TransferDirection::LinuxToWindows,
-        _ => return Err("Invalid direction".to_string()),
+    let direction = parse_direction(&direction)?;
+
+    copy_agent.create_recursive_transfer_task(source_dir, dest_dir, direction, options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Recursively transfer only the files modified since `modified_since`. The options'
+/// `connection_id` is the server uploaded to; a cutoff read off it, e.g. the time of its
+/// last backup, is shifted onto the local clock.
+#[tauri::command]
+pub async fn create_incremental_transfer_task(
+    copy_agent: State<'_, CopyAgent>,
+    ssh_client: State<'_, SSHClient>,
+    source_dir: String,
+    dest_dir: String,
+    direction: String,
+    modified_since: DateTime<Utc>,
+    options: Option<TransferOptions>,
+) -> Result<RecursiveTransferCreated, String> {
+    let direction = parse_direction(&direction)?;
+    let options = options.unwrap_or_default();
+    let modified_since = match &options.connection_id {
+        Some(connection_id) => {
+            let connection = ssh_client.get_connection(connection_id)
+                .ok_or("Connection not found")?;
+            modified_since - chrono::Duration::seconds(remote_clock::clock_skew(&connection))
+        }
+        None => modified_since,
     };
 
-    copy_agent.create_transfer_task(source_path, dest_path, direction)
+    copy_agent.create_recursive_transfer(source_dir, dest_dir, direction, Some(modified_since), options)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_transfers_by_group(
+    copy_agent: State<'_, CopyAgent>,
+    group: String
+) -> Result<Vec<TransferTask>, String> {
+    Ok(copy_agent.get_transfers_by_group(&group))
+}
+
+#[tauri::command]
+pub async fn cancel_group(
+    copy_agent: State<'_, CopyAgent>,
+    group: String
+) -> Result<usize, String> {
+    copy_agent.cancel_group(&group)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_queue_summary(
+    copy_agent: State<'_, CopyAgent>
+) -> Result<QueueSummary, String> {
+    copy_agent.get_queue_summary()
+        .map_err(|e| e.to_string())
+}
+
+/// The reduced concurrency in force after the server ran out of file handles, if any
+#[tauri::command]
+pub async fn get_fd_backoff(
+    copy_agent: State<'_, CopyAgent>
+) -> Result<Option<FdBackoff>, String> {
+    Ok(copy_agent.fd_backoff())
+}
+
+#[tauri::command]
+pub async fn get_recursive_transfer_results(
+    copy_agent: State<'_, CopyAgent>,
+    parent_task_id: String
+) -> Result<RecursiveTransferResults, String> {
+    copy_agent.get_recursive_transfer_results(&parent_task_id)
         .map_err(|e| e.to_string())
 }
 
@@ -368,6 +3070,15 @@ pub async fn get_transfer_progress(
     Ok(copy_agent.get_transfer_progress(&task_id))
 }
 
+#[tauri::command]
+pub async fn get_pending_reason(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String
+) -> Result<Option<PendingReason>, String> {
+    copy_agent.pending_reason(&task_id)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_active_transfers(
     copy_agent: State<'_, CopyAgent>
@@ -384,6 +3095,89 @@ pub async fn cancel_transfer(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn pause_transfer(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String
+) -> Result<(), String> {
+    copy_agent.pause_transfer(&task_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_transfer(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String
+) -> Result<(), String> {
+    copy_agent.resume_transfer(&task_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Resume the transfers left running or paused by the previous session from their checkpoints
+#[tauri::command]
+pub async fn resume_persisted_transfers(
+    copy_agent: State<'_, CopyAgent>,
+) -> Result<ResumeReport, String> {
+    copy_agent.resume_persisted_transfers()
+        .map_err(|e| e.to_string())
+}
+
+/// Step a transfer aside for everything else in the queue; it resumes where it stopped
+/// once the queue is empty. It is held rather than reprioritised.
+#[tauri::command]
+pub async fn defer_transfer(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String,
+) -> Result<(), String> {
+    copy_agent.defer_transfer(&task_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_transfer_destination(
+    copy_agent: State<'_, CopyAgent>,
+    task_id: String,
+    new_dest: String,
+) -> Result<Option<CaseConflict>, String> {
+    copy_agent.update_transfer_destination(&task_id, new_dest)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_progress_throttle(
+    app_handle: AppHandle,
+    interval_ms: u64,
+    min_bytes: u64,
+) -> Result<(), String> {
+    let patch = SettingsPatch {
+        progress_throttle: Some(ProgressThrottleConfig { interval_ms, min_bytes }),
+        ..SettingsPatch::default()
+    };
+    settings::update(&app_handle, patch)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Set the largest file a transfer accepts without `allow_oversize`; None removes the limit
+#[tauri::command]
+pub async fn set_max_transfer_file_size(
+    app_handle: AppHandle,
+    bytes: Option<u64>,
+) -> Result<(), String> {
+    let patch = SettingsPatch {
+        max_transfer_file_size: Some(bytes),
+        ..SettingsPatch::default()
+    };
+    settings::update(&app_handle, patch)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_max_transfer_file_size() -> Result<Option<u64>, String> {
+    Ok(settings::current().max_transfer_file_size)
+}
+
 #[tauri::command]
 pub async fn retry_transfer(
     copy_agent: State<'_, CopyAgent>,
@@ -392,3 +3186,33 @@ pub async fn retry_transfer(
     copy_agent.retry_transfer(&task_id)
         .map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_window_holds_running_and_queued_tasks() {
+        assert!(matches!(
+            window_change(&TransferStatus::InProgress, false, false),
+            Some(WindowChange::Hold(TransferStatus::Paused))
+        ));
+        assert!(matches!(
+            window_change(&TransferStatus::Pending, false, false),
+            Some(WindowChange::Hold(TransferStatus::Scheduled))
+        ));
+        assert!(window_change(&TransferStatus::Completed, false, false).is_none());
+        // Already held
+        assert!(window_change(&TransferStatus::Paused, true, false).is_none());
+    }
+
+    #[test]
+    fn opening_window_releases_only_what_it_held() {
+        assert!(matches!(window_change(&TransferStatus::Paused, true, true), Some(WindowChange::Release)));
+        assert!(matches!(window_change(&TransferStatus::Scheduled, true, true), Some(WindowChange::Release)));
+        // Paused by the user or held by a schedule
+        assert!(window_change(&TransferStatus::Paused, false, true).is_none());
+        assert!(window_change(&TransferStatus::Scheduled, false, true).is_none());
+        assert!(window_change(&TransferStatus::InProgress, false, true).is_none());
+    }
+}
@@ -0,0 +1,558 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use crate::error::{Circle9Error, Result};
+use crate::remote_dirs::create_remote_file;
+use crate::remote_env::environment;
+use crate::remote_exec::{exec_command_with_timeout, shell_quote};
+use crate::ssh_client::SSHConnection;
+use crate::utils::lock_or_error;
+
+/// Size of the blocks the remote file is split into for matching
+pub const DELTA_BLOCK_SIZE: usize = 256 * 1024;
+
+/// Hashing a multi-gigabyte remote file block by block can take a while
+const SIGNATURE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Exit status the signature script uses when the remote file doesn't exist
+const REMOTE_MISSING: i32 = 3;
+
+/// Unmatched local data is sent once this much has built up
+const MAX_LITERAL: usize = 1024 * 1024;
+
+/// Prints `weak length sha256` for each block of argv[1] in one pass. The weak checksum is
+/// the same rolling checksum as `Rolling`. Exits with `REMOTE_MISSING` when there's no file.
+const SIGNATURE_SCRIPT: &str = r#"
+import hashlib, itertools, sys
+try:
+    f = open(sys.argv[1], 'rb')
+except FileNotFoundError:
+    sys.exit(3)
+size = int(sys.argv[2])
+with f:
+    while True:
+        block = f.read(size)
+        if not block:
+            break
+        a = sum(block) & 0xffff
+        b = sum(itertools.accumulate(block)) & 0xffff
+        print(a | b << 16, len(block), hashlib.sha256(block).hexdigest())
+"#;
+
+/// Rebuilds argv[1] into the temp file argv[2] from the ops on stdin, then renames it into
+/// place with the original's mode. Each op is a kind byte and two big-endian u64s:
+/// `L len` followed by that many literal bytes, `C offset len` copying from the old file,
+/// and `E` to finish.
+const APPLY_SCRIPT: &str = r#"
+import os, shutil, struct, sys
+target, temp = sys.argv[1], sys.argv[2]
+ops = sys.stdin.buffer
+def pump(read, new, remaining):
+    while remaining:
+        chunk = read(min(remaining, 1 << 20))
+        if not chunk:
+            sys.exit(4)
+        new.write(chunk)
+        remaining -= len(chunk)
+try:
+    with open(target, 'rb') as old, open(temp, 'wb') as new:
+        while True:
+            header = ops.read(17)
+            if len(header) < 17:
+                sys.exit(4)
+            kind, a, b = header[:1], *struct.unpack('>QQ', header[1:])
+            if kind == b'E':
+                break
+            elif kind == b'L':
+                pump(ops.read, new, a)
+            elif kind == b'C':
+                old.seek(a)
+                pump(old.read, new, b)
+            else:
+                sys.exit(4)
+        new.flush()
+        os.fsync(new.fileno())
+    shutil.copymode(target, temp)
+    os.replace(temp, target)
+except BaseException:
+    if os.path.exists(temp):
+        os.remove(temp)
+    raise
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaStats {
+    pub total_bytes: u64,
+    pub bytes_sent: u64,
+    /// Blocks in the previous remote copy
+    pub blocks_total: usize,
+    /// Of those, the ones no longer found anywhere in the new file
+    pub blocks_changed: usize,
+    /// False when the whole file had to be sent, e.g. because no remote copy existed
+    pub used_delta: bool,
+}
+
+/// One block of the remote file
+#[derive(Debug, Clone, PartialEq)]
+struct BlockSignature {
+    weak: u32,
+    len: usize,
+    strong: String,
+}
+
+/// rsync's rolling checksum: `a` sums the window's bytes and `b` weights each by its
+/// distance from the end, so sliding the window one byte is O(1)
+#[derive(Debug, Clone, Copy)]
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(window: &[u8]) -> Self {
+        let len = window.len() as u32;
+        let (mut a, mut b) = (0u32, 0u32);
+        for (i, &byte) in window.iter().enumerate() {
+            a = a.wrapping_add(u32::from(byte));
+            b = b.wrapping_add((len - i as u32).wrapping_mul(u32::from(byte)));
+        }
+        Self { a, b, len }
+    }
+
+    /// Slide the window past `outgoing` to take in `incoming`
+    fn roll(&mut self, outgoing: u8, incoming: u8) {
+        self.a = self.a.wrapping_sub(u32::from(outgoing)).wrapping_add(u32::from(incoming));
+        self.b = self.b.wrapping_sub(self.len.wrapping_mul(u32::from(outgoing))).wrapping_add(self.a);
+    }
+
+    fn value(&self) -> u32 {
+        (self.a & 0xffff) | (self.b & 0xffff) << 16
+    }
+}
+
+fn strong_hash(block: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(block))
+}
+
+/// Signatures of the remote file's blocks, or None if it doesn't exist. They're computed on
+/// the remote host in a single python3 process, so only the signature crosses the wire.
+fn remote_signature(connection: &SSHConnection, remote_path: &str) -> Result<Option<Vec<BlockSignature>>> {
+    if !environment(connection)?.has_tool("python3") {
+        return Err(Circle9Error::TransferError("Delta uploads need python3 on the remote host".to_string()));
+    }
+    let command = format!(
+        "python3 -c {} {} {}",
+        shell_quote(SIGNATURE_SCRIPT),
+        shell_quote(remote_path),
+        DELTA_BLOCK_SIZE,
+    );
+
+    let output = exec_command_with_timeout(connection, &command, SIGNATURE_TIMEOUT)?;
+    match output.exit_status {
+        0 => output.stdout.lines().map(parse_signature_line).collect::<Option<Vec<_>>>()
+            .map(Some)
+            .ok_or_else(|| Circle9Error::TransferError("Unreadable remote block signature".to_string())),
+        REMOTE_MISSING => Ok(None),
+        status => Err(Circle9Error::TransferError(format!(
+            "Failed to checksum remote file (status {}): {}", status, output.stderr.trim()
+        ))),
+    }
+}
+
+fn parse_signature_line(line: &str) -> Option<BlockSignature> {
+    let mut fields = line.split_whitespace();
+    Some(BlockSignature {
+        weak: fields.next()?.parse().ok()?,
+        len: fields.next()?.parse().ok()?,
+        strong: fields.next()?.to_string(),
+    })
+}
+
+/// Writes ops for the remote apply script, merging back-to-back copies into one
+struct OpWriter<'a, W: Write> {
+    out: W,
+    pending_copy: Option<(u64, u64)>,
+    stats: &'a mut DeltaStats,
+    connection: Option<&'a SSHConnection>,
+}
+
+impl<W: Write> OpWriter<'_, W> {
+    fn header(&mut self, kind: u8, a: u64, b: u64) -> Result<()> {
+        let mut header = [0u8; 17];
+        header[0] = kind;
+        header[1..9].copy_from_slice(&a.to_be_bytes());
+        header[9..].copy_from_slice(&b.to_be_bytes());
+        self.out.write_all(&header)?;
+        Ok(())
+    }
+
+    fn flush_copy(&mut self) -> Result<()> {
+        if let Some((offset, len)) = self.pending_copy.take() {
+            self.header(b'C', offset, len)?;
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, offset: u64, len: u64) -> Result<()> {
+        // Nothing is sent, but the connection is still busy with this transfer
+        if let Some(connection) = self.connection {
+            connection.bandwidth.acquire(0);
+        }
+        match &mut self.pending_copy {
+            Some((start, run)) if *start + *run == offset => *run += len,
+            _ => {
+                self.flush_copy()?;
+                self.pending_copy = Some((offset, len));
+            }
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.flush_copy()?;
+        if let Some(connection) = self.connection {
+            connection.bandwidth.acquire(data.len());
+        }
+        self.header(b'L', data.len() as u64, 0)?;
+        self.out.write_all(data)?;
+        self.stats.bytes_sent += data.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.flush_copy()?;
+        self.header(b'E', 0, 0)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Read from `local` until `buffer` holds `wanted` bytes or the file ends
+fn fill(local: &mut impl Read, buffer: &mut Vec<u8>, wanted: usize) -> Result<()> {
+    let mut chunk = [0u8; 64 * 1024];
+    while buffer.len() < wanted {
+        let n = local.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    Ok(())
+}
+
+/// Write the ops turning the remote file described by `signature` into `local`. Blocks are
+/// found wherever they now sit in `local`, not just at their old offsets, so data inserted
+/// or removed near the start doesn't force the rest of the file to be resent.
+fn write_delta<R: Read, W: Write>(
+    mut local: R,
+    signature: &[BlockSignature],
+    block_size: usize,
+    ops: W,
+    connection: Option<&SSHConnection>,
+    stats: &mut DeltaStats,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<()> {
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, block) in signature.iter().enumerate().filter(|(_, block)| block.len == block_size) {
+        index.entry(block.weak).or_default().push(i);
+    }
+    // A short final block can only match the very end of the local file
+    let short_tail = signature.last().filter(|block| block.len < block_size);
+    let mut reused = HashSet::new();
+
+    let total_bytes = stats.total_bytes;
+    let mut ops = OpWriter { out: ops, pending_copy: None, stats, connection };
+    // Local bytes from `consumed` on; the window being matched starts at `pos`
+    let mut buffer = Vec::with_capacity(MAX_LITERAL + block_size);
+    let mut consumed = 0u64;
+    let mut pos = 0;
+    let mut rolling: Option<Rolling> = None;
+
+    loop {
+        fill(&mut local, &mut buffer, pos + block_size)?;
+        if buffer.len() - pos < block_size {
+            // Tail: either the remote's short last block, or literal data
+            let tail_match = short_tail.filter(|block| {
+                buffer.len() - pos >= block.len && block.strong == strong_hash(&buffer[buffer.len() - block.len..])
+            });
+            match tail_match {
+                Some(block) => {
+                    let split = buffer.len() - block.len;
+                    ops.literal(&buffer[..split])?;
+                    ops.copy(((signature.len() - 1) * block_size) as u64, block.len as u64)?;
+                    reused.insert(signature.len() - 1);
+                }
+                None => ops.literal(&buffer)?,
+            }
+            consumed += buffer.len() as u64;
+            on_progress(consumed, total_bytes);
+            break;
+        }
+
+        let window = &buffer[pos..pos + block_size];
+        let weak = rolling.get_or_insert_with(|| Rolling::new(window)).value();
+        let matched = index.get(&weak).and_then(|candidates| {
+            let strong = strong_hash(window);
+            candidates.iter().copied().find(|&i| signature[i].strong == strong)
+        });
+
+        match matched {
+            Some(i) => {
+                ops.literal(&buffer[..pos])?;
+                ops.copy((i * block_size) as u64, block_size as u64)?;
+                reused.insert(i);
+                buffer.drain(..pos + block_size);
+                consumed += (pos + block_size) as u64;
+                pos = 0;
+                rolling = None;
+                on_progress(consumed, total_bytes);
+            }
+            None => {
+                let outgoing = buffer[pos];
+                pos += 1;
+                fill(&mut local, &mut buffer, pos + block_size)?;
+                rolling = match (rolling, buffer.get(pos + block_size - 1)) {
+                    (Some(mut rolling), Some(&incoming)) => {
+                        rolling.roll(outgoing, incoming);
+                        Some(rolling)
+                    }
+                    _ => None,
+                };
+                if pos >= MAX_LITERAL {
+                    ops.literal(&buffer[..pos])?;
+                    buffer.drain(..pos);
+                    consumed += pos as u64;
+                    pos = 0;
+                    on_progress(consumed, total_bytes);
+                }
+            }
+        }
+    }
+
+    ops.finish()?;
+    stats.blocks_changed = signature.len() - reused.len();
+    Ok(())
+}
+
+/// Send `local_path` as ops to the apply script, which rebuilds the remote file beside it
+/// and swaps it in. The channel is opened under the session lock, which is then released so
+/// other operations on the connection can run while the data goes across.
+fn apply_delta(
+    connection: &SSHConnection,
+    local_path: &str,
+    remote_path: &str,
+    signature: &[BlockSignature],
+    stats: &mut DeltaStats,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<()> {
+    let temp = format!("{}.circle9-delta-{}", remote_path, uuid::Uuid::new_v4());
+    let command = format!(
+        "python3 -c {} {} {}",
+        shell_quote(APPLY_SCRIPT),
+        shell_quote(remote_path),
+        shell_quote(&temp),
+    );
+    let mut channel = {
+        let session = lock_or_error(&connection.session)?;
+        let mut channel = session.channel_session()?;
+        channel.exec(&command)?;
+        channel
+    };
+
+    let local = std::io::BufReader::new(File::open(local_path)?);
+    write_delta(local, signature, DELTA_BLOCK_SIZE, &mut channel, Some(connection), stats, on_progress)?;
+    channel.send_eof()?;
+
+    let mut stderr = String::new();
+    channel.stderr().read_to_string(&mut stderr)?;
+    channel.wait_close()?;
+    match channel.exit_status()? {
+        0 => Ok(()),
+        status => Err(Circle9Error::TransferError(format!(
+            "Remote delta apply failed (status {}): {}", status, stderr.trim()
+        ))),
+    }
+}
+
+/// Send the whole file, taking the SFTP lock for each chunk rather than the whole upload
+fn full_upload(
+    connection: &SSHConnection,
+    local_path: &str,
+    remote_path: &str,
+    stats: &mut DeltaStats,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<()> {
+    let mut local_file = File::open(local_path)?;
+    let mut remote_file = create_remote_file(&connection.sftp()?, Path::new(remote_path))?;
+    let mut buffer = vec![0u8; DELTA_BLOCK_SIZE];
+    loop {
+        let n = local_file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        connection.bandwidth.acquire(n);
+        {
+            let _sftp = connection.sftp()?;
+            remote_file.write_all(&buffer[..n])?;
+        }
+        stats.bytes_sent += n as u64;
+        on_progress(stats.bytes_sent, stats.total_bytes);
+    }
+    // fsync is an OpenSSH extension, so not every server supports it
+    let _sftp = connection.sftp()?;
+    if let Err(e) = remote_file.fsync() {
+        tracing::debug!("fsync of {} not supported: {}", remote_path, e);
+    }
+    Ok(())
+}
+
+/// Upload `local_path`, sending only the data the existing remote file doesn't already have.
+/// Falls back to a full upload when there is no remote copy or no python3 to compute and
+/// apply the delta with.
+pub fn delta_upload<F>(
+    connection: &SSHConnection,
+    local_path: &str,
+    remote_path: &str,
+    mut on_progress: F,
+) -> Result<DeltaStats>
+where
+    F: FnMut(u64, u64),
+{
+    let total_bytes = std::fs::metadata(local_path)?.len();
+    let signature = match remote_signature(connection, remote_path) {
+        Ok(signature) => signature,
+        Err(e) => {
+            tracing::warn!("Delta signature failed for {}, sending whole file: {}", remote_path, e);
+            None
+        }
+    };
+
+    let mut stats = DeltaStats {
+        total_bytes,
+        bytes_sent: 0,
+        blocks_total: signature.as_ref().map_or(0, Vec::len),
+        blocks_changed: 0,
+        used_delta: signature.is_some(),
+    };
+    match &signature {
+        Some(signature) => apply_delta(connection, local_path, remote_path, signature, &mut stats, &mut on_progress)?,
+        None => full_upload(connection, local_path, remote_path, &mut stats, &mut on_progress)?,
+    }
+
+    tracing::info!(
+        "Uploaded {} ({} of {} remote blocks changed, {} bytes sent)",
+        remote_path, stats.blocks_changed, stats.blocks_total, stats.bytes_sent
+    );
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: usize = 64;
+
+    /// What the signature script prints, computed locally
+    fn signature_of(data: &[u8]) -> Vec<BlockSignature> {
+        data.chunks(BLOCK)
+            .map(|block| BlockSignature { weak: Rolling::new(block).value(), len: block.len(), strong: strong_hash(block) })
+            .collect()
+    }
+
+    /// What the apply script does with the ops
+    fn apply(old: &[u8], mut ops: &[u8]) -> Vec<u8> {
+        let mut new = Vec::new();
+        loop {
+            let kind = ops[0];
+            let a = u64::from_be_bytes(ops[1..9].try_into().unwrap()) as usize;
+            let b = u64::from_be_bytes(ops[9..17].try_into().unwrap()) as usize;
+            ops = &ops[17..];
+            match kind {
+                b'E' => return new,
+                b'L' => {
+                    new.extend_from_slice(&ops[..a]);
+                    ops = &ops[a..];
+                }
+                b'C' => new.extend_from_slice(&old[a..a + b]),
+                _ => panic!("unknown op {}", kind),
+            }
+        }
+    }
+
+    fn data(len: usize, seed: u8) -> Vec<u8> {
+        (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed) ^ (i / 7) as u8).collect()
+    }
+
+    fn delta(old: &[u8], new: &[u8]) -> (Vec<u8>, DeltaStats) {
+        let mut stats = DeltaStats {
+            total_bytes: new.len() as u64,
+            bytes_sent: 0,
+            blocks_total: 0,
+            blocks_changed: 0,
+            used_delta: true,
+        };
+        let mut ops = Vec::new();
+        write_delta(new, &signature_of(old), BLOCK, &mut ops, None, &mut stats, &mut |_, _| {}).unwrap();
+        (ops, stats)
+    }
+
+    #[test]
+    fn rolling_matches_a_fresh_checksum() {
+        let bytes = data(300, 5);
+        let mut rolling = Rolling::new(&bytes[..BLOCK]);
+        for start in 1..bytes.len() - BLOCK {
+            rolling.roll(bytes[start - 1], bytes[start + BLOCK - 1]);
+            assert_eq!(rolling.value(), Rolling::new(&bytes[start..start + BLOCK]).value());
+        }
+    }
+
+    #[test]
+    fn insertion_at_the_start_only_sends_the_insertion() {
+        let old = data(BLOCK * 20 + 10, 1);
+        let mut new = b"inserted header".to_vec();
+        new.extend_from_slice(&old);
+
+        let (ops, stats) = delta(&old, &new);
+        assert_eq!(apply(&old, &ops), new);
+        assert_eq!(stats.bytes_sent, 15);
+        assert_eq!(stats.blocks_changed, 0);
+    }
+
+    #[test]
+    fn edits_and_truncation_rebuild_exactly() {
+        let old = data(BLOCK * 10 + 3, 2);
+        let mut new = old[..BLOCK * 6].to_vec();
+        new[BLOCK * 2 + 5] ^= 0xff;
+        new.extend_from_slice(&data(40, 9));
+
+        let (ops, stats) = delta(&old, &new);
+        assert_eq!(apply(&old, &ops), new);
+        assert!(stats.bytes_sent < new.len() as u64);
+    }
+
+    #[test]
+    fn unrelated_content_is_sent_whole() {
+        let old = data(BLOCK * 4, 3);
+        let new = data(BLOCK * 3 + 1, 200);
+        let (ops, stats) = delta(&old, &new);
+        assert_eq!(apply(&old, &ops), new);
+        assert_eq!(stats.bytes_sent, new.len() as u64);
+    }
+
+    #[test]
+    fn signature_lines_parse() {
+        assert_eq!(
+            parse_signature_line("123 65536 abcd"),
+            Some(BlockSignature { weak: 123, len: 65536, strong: "abcd".to_string() }),
+        );
+        assert_eq!(parse_signature_line("garbage"), None);
+    }
+}
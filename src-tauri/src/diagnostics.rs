@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use sysinfo::{System, SystemExt};
+use tauri::State;
+use crate::audit_log::{AuditEntry, AUDIT_LOGGER};
+use crate::copy_agent::{CopyAgent, TransferTask};
+use crate::settings::{self, Settings};
+use crate::ssh_client::SSHClient;
+
+/// Log lines kept in memory for diagnostics exports
+const LOG_TAIL_LINES: usize = 500;
+
+/// Audit entries included in an export
+const RECENT_AUDIT_ENTRIES: usize = 200;
+
+lazy_static::lazy_static! {
+    static ref LOG_TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::with_capacity(LOG_TAIL_LINES));
+}
+
+/// Writer for the tracing subscriber: prints to stdout and keeps the last lines for export
+pub struct TailWriter;
+
+pub fn log_writer() -> TailWriter {
+    TailWriter
+}
+
+impl Write for TailWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(mut tail) = LOG_TAIL.lock() {
+            for line in String::from_utf8_lossy(buf).lines().filter(|l| !l.is_empty()) {
+                if tail.len() == LOG_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line.to_string());
+            }
+        }
+        std::io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+/// A connection with its credentials stripped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactedConnection {
+    pub connection_id: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub label: Option<String>,
+    pub uses_key: bool,
+    pub uses_password: bool,
+    pub bandwidth_limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub circle9_version: String,
+    pub os: String,
+    pub os_version: Option<String>,
+    pub arch: String,
+    pub generated_at: DateTime<Utc>,
+    pub settings: Settings,
+    pub connections: Vec<RedactedConnection>,
+    pub transfers: Vec<TransferTask>,
+    pub recent_audit_entries: Vec<AuditEntry>,
+    pub log_tail: Vec<String>,
+}
+
+/// Snapshot of app state for a bug report. Passwords and key contents are never included;
+/// a key is only reported as present.
+pub fn collect(ssh_client: &SSHClient, copy_agent: &CopyAgent) -> Diagnostics {
+    let connections = ssh_client.list_sessions(None).into_iter()
+        .filter_map(|session| {
+            let connection = ssh_client.get_connection(&session.connection_id)?;
+            Some(RedactedConnection {
+                connection_id: session.connection_id,
+                host: connection.config.host.clone(),
+                port: connection.config.port,
+                username: connection.config.username.clone(),
+                label: session.label,
+                uses_key: connection.config.key_path.is_some(),
+                uses_password: connection.config.password.is_some(),
+                bandwidth_limit: connection.bandwidth.limit(),
+            })
+        })
+        .collect();
+
+    let recent_audit_entries = match AUDIT_LOGGER.read_entries(None) {
+        Ok(entries) => {
+            let skip = entries.len().saturating_sub(RECENT_AUDIT_ENTRIES);
+            entries.into_iter().skip(skip).collect()
+        }
+        Err(e) => {
+            tracing::warn!("Could not read audit log for diagnostics: {}", e);
+            Vec::new()
+        }
+    };
+
+    Diagnostics {
+        circle9_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        os_version: System::new().long_os_version(),
+        arch: std::env::consts::ARCH.to_string(),
+        generated_at: Utc::now(),
+        settings: settings::current(),
+        connections,
+        transfers: copy_agent.get_active_transfers(),
+        recent_audit_entries,
+        log_tail: LOG_TAIL.lock()
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+// Tauri commands for diagnostics
+
+/// Write a diagnostics bundle as JSON to `path`
+#[tauri::command]
+pub async fn export_diagnostics(
+    ssh_client: State<'_, SSHClient>,
+    copy_agent: State<'_, CopyAgent>,
+    path: String,
+) -> Result<String, String> {
+    let diagnostics = collect(&ssh_client, &copy_agent);
+    let json = serde_json::to_string_pretty(&diagnostics)
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    tracing::info!("Exported diagnostics to {}", path);
+    Ok(path)
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use ssh2::FileType;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, State};
+use crate::case_agent::CaseAgent;
+use crate::error::Result;
+use crate::remote_clock::{clock_skew, mtimes_match, warn_if_excessive};
+use crate::remote_users::expand_tilde;
+use crate::remote_walk::{walk_remote, SkippedEntry};
+use crate::settings;
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::device_id;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DiffKind {
+    OnlyLocal,
+    OnlyRemote,
+    SizeDiffers,
+    MtimeDiffers,
+    TypeDiffers,
+    /// Same path apart from letter case, e.g. `Readme.md` locally and `README.md` remotely
+    CaseDiffers,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffEntry {
+    /// Path relative to the compared roots, `/`-separated; the local spelling when both exist
+    pub path: String,
+    /// Remote spelling, set only for `CaseDiffers`
+    pub remote_path: Option<String>,
+    pub kind: DiffKind,
+    pub local_size: Option<u64>,
+    pub remote_size: Option<u64>,
+    pub local_mtime: Option<u64>,
+    pub remote_mtime: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryDiff {
+    pub local_dir: String,
+    pub remote_dir: String,
+    pub differences: Vec<DiffEntry>,
+    pub identical: usize,
+    /// Remote directories that couldn't be read, so their contents weren't compared
+    pub skipped: Vec<SkippedEntry>,
+    /// Mount points on either side whose contents weren't compared
+    pub skipped_mounts: Vec<String>,
+    /// Server clock minus local clock; remote mtimes are corrected by this before comparing
+    pub clock_skew_secs: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EntryType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EntryInfo {
+    entry_type: EntryType,
+    size: u64,
+    /// Seconds since the epoch, the precision SFTP offers
+    mtime: Option<u64>,
+}
+
+/// Compare two trees without transferring anything. Sizes and mtimes are only compared for files,
+/// with remote mtimes corrected for the server's clock skew.
+pub async fn diff_trees(
+    connection: &SSHConnection,
+    local_dir: &str,
+    remote_dir: &str,
+    cross_filesystems: bool,
+) -> Result<DirectoryDiff> {
+    let mut local = BTreeMap::new();
+    let mut skipped_mounts = Vec::new();
+    let root_device = if cross_filesystems {
+        None
+    } else {
+        device_id(&std::fs::metadata(local_dir)?)
+    };
+    walk_local(Path::new(local_dir), Path::new(local_dir), root_device, &mut local, &mut skipped_mounts)?;
+
+    let mut remote = BTreeMap::new();
+    let remote_root = Path::new(remote_dir);
+    let outcome = walk_remote(connection, remote_dir, cross_filesystems, &AtomicBool::new(false), |path, stat| {
+        if let Ok(relative) = path.strip_prefix(remote_root) {
+            let entry_type = match stat.file_type() {
+                FileType::RegularFile => EntryType::File,
+                FileType::Directory => EntryType::Directory,
+                FileType::Symlink => EntryType::Symlink,
+                _ => EntryType::Other,
+            };
+            remote.insert(relative_key(relative), EntryInfo {
+                entry_type,
+                size: stat.size.unwrap_or(0),
+                mtime: stat.mtime,
+            });
+        }
+    }).await?;
+
+    let skew = clock_skew(connection);
+    let tolerance = settings::current().mtime_tolerance_secs;
+    let mut differences = Vec::new();
+    let mut identical = 0;
+    let mut only_local = Vec::new();
+    let mut remote_unmatched: HashMap<String, String> = HashMap::new();
+
+    for path in remote.keys() {
+        if !local.contains_key(path) {
+            remote_unmatched.insert(CaseAgent::normalize_filename(path), path.clone());
+        }
+    }
+
+    for (path, local_info) in &local {
+        let remote_info = match remote.get(path) {
+            Some(info) => info,
+            None => {
+                only_local.push(path.clone());
+                continue;
+            }
+        };
+        match compare(local_info, remote_info, skew, tolerance) {
+            Some(kind) => differences.push(entry(path, None, kind, Some(local_info), Some(remote_info))),
+            None => identical += 1,
+        }
+    }
+
+    // Pair up what's left on each side when the names match ignoring case
+    for path in only_local {
+        let local_info = &local[&path];
+        match remote_unmatched.remove(&CaseAgent::normalize_filename(&path)) {
+            Some(remote_path) => {
+                let remote_info = &remote[&remote_path];
+                differences.push(entry(&path, Some(remote_path.clone()), DiffKind::CaseDiffers, Some(local_info), Some(remote_info)));
+            }
+            None => differences.push(entry(&path, None, DiffKind::OnlyLocal, Some(local_info), None)),
+        }
+    }
+    for remote_path in remote_unmatched.into_values() {
+        let remote_info = &remote[&remote_path];
+        differences.push(entry(&remote_path, None, DiffKind::OnlyRemote, None, Some(remote_info)));
+    }
+    differences.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(DirectoryDiff {
+        local_dir: local_dir.to_string(),
+        remote_dir: remote_dir.to_string(),
+        differences,
+        identical,
+        skipped: outcome.skipped,
+        skipped_mounts: skipped_mounts.into_iter().chain(outcome.skipped_mounts).collect(),
+        clock_skew_secs: skew,
+    })
+}
+
+fn compare(local: &EntryInfo, remote: &EntryInfo, skew_secs: i64, tolerance_secs: u64) -> Option<DiffKind> {
+    if local.entry_type != remote.entry_type {
+        return Some(DiffKind::TypeDiffers);
+    }
+    if local.entry_type != EntryType::File {
+        return None;
+    }
+    if local.size != remote.size {
+        return Some(DiffKind::SizeDiffers);
+    }
+    if !mtimes_match(local.mtime, remote.mtime, skew_secs, tolerance_secs) {
+        return Some(DiffKind::MtimeDiffers);
+    }
+    None
+}
+
+fn entry(path: &str, remote_path: Option<String>, kind: DiffKind, local: Option<&EntryInfo>, remote: Option<&EntryInfo>) -> DiffEntry {
+    DiffEntry {
+        path: path.to_string(),
+        remote_path,
+        kind,
+        local_size: local.map(|i| i.size),
+        remote_size: remote.map(|i| i.size),
+        local_mtime: local.and_then(|i| i.mtime),
+        remote_mtime: remote.and_then(|i| i.mtime),
+    }
+}
+
+/// Record everything below `dir` keyed by its path relative to `root`; symlinks are not followed,
+/// and with `root_device` set, directories on another device aren't entered
+fn walk_local(
+    root: &Path,
+    dir: &Path,
+    root_device: Option<u64>,
+    entries: &mut BTreeMap<String, EntryInfo>,
+    skipped_mounts: &mut Vec<String>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = std::fs::symlink_metadata(entry.path())?;
+        let entry_type = if metadata.file_type().is_symlink() {
+            EntryType::Symlink
+        } else if metadata.is_dir() {
+            EntryType::Directory
+        } else if metadata.is_file() {
+            EntryType::File
+        } else {
+            EntryType::Other
+        };
+        let mtime = metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+
+        let path = entry.path();
+        if let Ok(relative) = path.strip_prefix(root) {
+            entries.insert(relative_key(relative), EntryInfo { entry_type, size: metadata.len(), mtime });
+        }
+        if entry_type == EntryType::Directory {
+            if root_device.is_some() && device_id(&metadata) != root_device {
+                skipped_mounts.push(path.to_string_lossy().to_string());
+                continue;
+            }
+            walk_local(root, &path, root_device, entries, skipped_mounts)?;
+        }
+    }
+    Ok(())
+}
+
+/// Relative path with `/` separators so local and remote keys line up on Windows
+fn relative_key(relative: &Path) -> String {
+    relative.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Tauri commands for directory comparison
+
+#[tauri::command]
+pub async fn diff_directories(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    local_dir: String,
+    remote_dir: String,
+    cross_filesystems: Option<bool>,
+) -> Result<DirectoryDiff, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let remote_dir = expand_tilde(&connection, &remote_dir)
+        .map_err(|e| e.to_string())?;
+
+    let diff = diff_trees(&connection, &local_dir, &remote_dir, cross_filesystems.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())?;
+    warn_if_excessive(&app_handle, &connection_id, diff.clock_skew_secs);
+    Ok(diff)
+}
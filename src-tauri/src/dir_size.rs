@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use ssh2::FileType;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use crate::background_jobs::{self, BackgroundJobKind};
+use crate::error::Result;
+use crate::listing_cache::DirSizeCache;
+use crate::remote_walk::{walk_remote, SkippedEntry};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Minimum gap between `dir_size_progress` events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteDirSize {
+    pub path: String,
+    pub total_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub skipped: Vec<SkippedEntry>,
+    /// Mount points below `path` that weren't counted
+    pub skipped_mounts: Vec<String>,
+    pub cancelled: bool,
+    /// Served from the cache rather than walked again
+    #[serde(default)]
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirSizeProgress {
+    pub scan_id: String,
+    pub path: String,
+    pub total_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_SCANS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+/// Recursively total the size of a remote directory, reporting progress as it goes
+pub async fn compute_dir_size<F>(
+    connection: &SSHConnection,
+    path: &str,
+    cross_filesystems: bool,
+    cancel: &AtomicBool,
+    mut on_progress: F,
+) -> Result<RemoteDirSize>
+where
+    F: FnMut(&RemoteDirSize),
+{
+    let mut size = RemoteDirSize {
+        path: path.to_string(),
+        total_bytes: 0,
+        file_count: 0,
+        dir_count: 0,
+        skipped: Vec::new(),
+        skipped_mounts: Vec::new(),
+        cancelled: false,
+        cached: false,
+    };
+    let mut last_progress = Instant::now();
+
+    let outcome = walk_remote(connection, path, cross_filesystems, cancel, |_, stat| {
+        if stat.file_type() == FileType::Directory {
+            size.dir_count += 1;
+        } else {
+            size.file_count += 1;
+            size.total_bytes += stat.size.unwrap_or(0);
+        }
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            on_progress(&size);
+            last_progress = Instant::now();
+        }
+    }).await?;
+
+    size.skipped = outcome.skipped;
+    size.skipped_mounts = outcome.skipped_mounts;
+    size.cancelled = outcome.cancelled;
+    Ok(size)
+}
+
+/// `compute_dir_size`, answered from `cache` when a fresh result is there
+pub async fn cached_dir_size<F>(
+    cache: &DirSizeCache,
+    connection: &SSHConnection,
+    connection_id: &str,
+    path: &str,
+    cross_filesystems: bool,
+    cancel: &AtomicBool,
+    on_progress: F,
+) -> Result<RemoteDirSize>
+where
+    F: FnMut(&RemoteDirSize),
+{
+    if let Some(size) = cache.get(connection_id, path, cross_filesystems) {
+        return Ok(size);
+    }
+    let size = compute_dir_size(connection, path, cross_filesystems, cancel, on_progress).await?;
+    cache.insert(connection_id, cross_filesystems, &size);
+    Ok(size)
+}
+
+// Tauri commands for directory sizes
+
+#[tauri::command]
+pub async fn get_remote_dir_size(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    path: String,
+    scan_id: Option<String>,
+    cross_filesystems: Option<bool>,
+    refresh: Option<bool>,
+) -> Result<RemoteDirSize, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let cross_filesystems = cross_filesystems.unwrap_or(false);
+    if refresh.unwrap_or(false) {
+        ssh_client.listing_cache.dir_sizes.invalidate_containing(Some(&connection_id), &path);
+    }
+
+    let scan_id = scan_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancel = Arc::new(AtomicBool::new(false));
+    ACTIVE_SCANS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?
+        .insert(scan_id.clone(), Arc::clone(&cancel));
+    let job_cancel = Arc::clone(&cancel);
+    background_jobs::register(&scan_id, BackgroundJobKind::DirSizeScan, &path, Some(&connection_id), move || {
+        job_cancel.store(true, Ordering::SeqCst);
+    });
+
+    let result = cached_dir_size(&ssh_client.listing_cache.dir_sizes, &connection, &connection_id, &path, cross_filesystems, &cancel, |size| {
+        let progress = DirSizeProgress {
+            scan_id: scan_id.clone(),
+            path: size.path.clone(),
+            total_bytes: size.total_bytes,
+            file_count: size.file_count,
+            dir_count: size.dir_count,
+        };
+        if let Err(e) = app_handle.emit_all("dir_size_progress", &progress) {
+            tracing::error!("Failed to emit dir size progress: {}", e);
+        }
+    }).await;
+
+    if let Ok(mut scans) = ACTIVE_SCANS.lock() {
+        scans.remove(&scan_id);
+    }
+    background_jobs::unregister(&scan_id);
+
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cancel_remote_dir_size(scan_id: String) -> Result<bool, String> {
+    let scans = ACTIVE_SCANS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?;
+    match scans.get(&scan_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use ssh2::FileType;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_env::environment;
+use crate::remote_exec::{exec_command_dedicated, shell_quote};
+use crate::remote_users::expand_tilde;
+use crate::remote_walk::{walk_remote, SkippedEntry};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Files hashed per exec channel, and the quoted-argument budget for one command line
+const HASH_BATCH_FILES: usize = 200;
+const HASH_BATCH_BYTES: usize = 64 * 1024;
+
+/// A batch of large files can take a while to read
+const HASH_BATCH_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub sha256: String,
+    pub paths: Vec<String>,
+    /// Space freed by keeping only one copy
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateReport {
+    pub dir: String,
+    /// Largest `wasted_bytes` first
+    pub groups: Vec<DuplicateGroup>,
+    pub files_scanned: usize,
+    pub files_hashed: usize,
+    pub wasted_bytes: u64,
+    /// Unreadable directories and files that couldn't be hashed
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// SHA-256 of each of `paths`, computed on the remote host; None where a file couldn't be read.
+/// The loop prints one line per file, so results line up with `paths` even when some fail.
+async fn hash_batch(connection: &SSHConnection, sha256: &str, paths: &[String]) -> Result<Vec<Option<String>>> {
+    let quoted: Vec<String> = paths.iter().map(|p| shell_quote(p)).collect();
+    let script = format!(
+        "for f in {files}; do h=$({sha256} < \"$f\" 2>/dev/null) && echo \"${{h%% *}}\" || echo -; done",
+        files = quoted.join(" "),
+        sha256 = sha256,
+    );
+
+    let output = exec_command_dedicated(connection, &script, HASH_BATCH_TIMEOUT).await?;
+    let hashes: Vec<Option<String>> = output.stdout.lines()
+        .map(|line| (line != "-" && !line.is_empty()).then(|| line.to_string()))
+        .collect();
+    if !output.success() || hashes.len() != paths.len() {
+        return Err(Circle9Error::SSHError(format!(
+            "Checksum batch failed (status {}): {}", output.exit_status, output.stderr.trim()
+        )));
+    }
+    Ok(hashes)
+}
+
+/// Walk `dir`, group its files by size and hash only the sizes that occur more than once.
+/// Empty files are left out, as are names that aren't valid UTF-8.
+pub async fn find_duplicates(connection: &SSHConnection, dir: &str) -> Result<DuplicateReport> {
+    let environment = environment(connection)?;
+    let sha256 = environment.sha256_command()
+        .ok_or_else(|| Circle9Error::SSHError("No SHA-256 tool on the remote host".to_string()))?;
+
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut files_scanned = 0;
+    let outcome = walk_remote(connection, dir, false, &AtomicBool::new(false), |path, stat| {
+        if stat.file_type() != FileType::RegularFile {
+            return;
+        }
+        files_scanned += 1;
+        match (stat.size, path.to_str()) {
+            (Some(size), Some(path)) if size > 0 => by_size.entry(size).or_default().push(path.to_string()),
+            _ => {}
+        }
+    }).await?;
+
+    let candidates: Vec<(String, u64)> = by_size.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (path, size)))
+        .collect();
+
+    let mut report = DuplicateReport {
+        dir: dir.to_string(),
+        files_scanned,
+        skipped: outcome.skipped,
+        ..DuplicateReport::default()
+    };
+    let mut by_content: HashMap<(u64, String), Vec<String>> = HashMap::new();
+    let mut batch: Vec<(String, u64)> = Vec::new();
+    let mut batch_bytes = 0;
+    for (index, candidate) in candidates.iter().enumerate() {
+        batch_bytes += candidate.0.len() + 3;
+        batch.push(candidate.clone());
+        let last = index + 1 == candidates.len();
+        if !last && batch.len() < HASH_BATCH_FILES && batch_bytes < HASH_BATCH_BYTES {
+            continue;
+        }
+
+        let paths: Vec<String> = batch.iter().map(|(path, _)| path.clone()).collect();
+        let hashes = hash_batch(connection, sha256, &paths).await?;
+        for ((path, size), hash) in batch.drain(..).zip(hashes) {
+            match hash {
+                Some(hash) => {
+                    report.files_hashed += 1;
+                    by_content.entry((size, hash)).or_default().push(path);
+                }
+                None => report.skipped.push(SkippedEntry { path, reason: "Could not read file".to_string() }),
+            }
+        }
+        batch_bytes = 0;
+    }
+
+    report.groups = by_content.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, sha256), mut paths)| {
+            paths.sort();
+            let wasted_bytes = size * (paths.len() as u64 - 1);
+            DuplicateGroup { size, sha256, paths, wasted_bytes }
+        })
+        .collect();
+    report.groups.sort_by(|a, b| b.wasted_bytes.cmp(&a.wasted_bytes).then_with(|| a.paths.cmp(&b.paths)));
+    report.wasted_bytes = report.groups.iter().map(|g| g.wasted_bytes).sum();
+    Ok(report)
+}
+
+// Tauri commands for duplicate detection
+
+/// Find files under `dir` with identical contents; nothing is changed
+#[tauri::command]
+pub async fn find_duplicate_files(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+) -> Result<DuplicateReport, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let dir = expand_tilde(&connection, &dir).map_err(|e| e.to_string())?;
+
+    let report = find_duplicates(&connection, &dir).await.map_err(|e| e.to_string())?;
+    tracing::info!("Found {} duplicate groups in {} ({} bytes reclaimable)", report.groups.len(), dir, report.wasted_bytes);
+    Ok(report)
+}
@@ -13,10 +13,34 @@ pub enum Circle9Error {
     
     #[error("Invalid path: {0}")]
     InvalidPath(String),
+
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Skipped: {0} already exists")]
+    Skipped(String),
     
     #[error("Operation timeout")]
     Timeout,
     
+    #[error("Command rejected: {0}")]
+    CommandRejected(String),
+
+    #[error("Disk full writing {path} after {bytes_written} bytes; free some space and retry")]
+    DiskFull { path: String, bytes_written: u64 },
+
+    #[error("File too large: {path} is {size} bytes, over the {limit}-byte transfer limit; allow oversize files to send it anyway")]
+    FileTooLarge { path: String, size: u64, limit: u64 },
+    
+    #[error("Remote server ran out of file handles: {0}")]
+    TooManyOpenFiles(String),
+
+    #[error("Pre-transfer hook failed: {0}")]
+    PreHookFailed(String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
@@ -30,4 +54,74 @@ pub enum Circle9Error {
     AnyhowError(#[from] anyhow::Error),
 }
 
+impl Circle9Error {
+    /// Wrap a failed write, singling out a full disk so the partial file can be resumed later
+    pub fn from_write(error: std::io::Error, path: &str, bytes_written: u64) -> Self {
+        if is_disk_full(&error) {
+            Circle9Error::DiskFull { path: path.to_string(), bytes_written }
+        } else {
+            Circle9Error::IoError(error)
+        }
+    }
+
+    /// Reclassify an error whose message shows the server hit its open file limit. SFTP
+    /// servers report EMFILE/ENFILE as a generic failure, so only the text gives it away.
+    pub fn classify_fd_exhaustion(self) -> Self {
+        let message = match &self {
+            Circle9Error::SSHError(m) | Circle9Error::TransferError(m) => m.clone(),
+            Circle9Error::IoError(e) => e.to_string(),
+            Circle9Error::Ssh2Error(e) => e.to_string(),
+            _ => return self,
+        };
+        if is_fd_exhaustion(&message) {
+            Circle9Error::TooManyOpenFiles(message)
+        } else {
+            self
+        }
+    }
+
+    /// Whether the error says the connection itself failed rather than the file or the
+    /// request, so the same transfer could succeed through another host
+    pub fn is_connection_failure(&self) -> bool {
+        /// SSH_FX_NO_CONNECTION and SSH_FX_CONNECTION_LOST
+        const SFTP_CONNECTION_CODES: &[i32] = &[6, 7];
+        match self {
+            Circle9Error::SSHError(_) | Circle9Error::Timeout => true,
+            Circle9Error::Ssh2Error(e) => match e.code() {
+                ssh2::ErrorCode::Session(_) => true,
+                ssh2::ErrorCode::SFTP(code) => SFTP_CONNECTION_CODES.contains(&code),
+            },
+            Circle9Error::IoError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// EMFILE/ENFILE as worded by sftp-server and libc, or a channel refused for the same reason
+pub fn is_fd_exhaustion(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["too many open files", "emfile", "enfile", "file table overflow"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// ENOSPC/EDQUOT on Unix, ERROR_DISK_FULL/ERROR_HANDLE_DISK_FULL on Windows
+pub fn is_disk_full(error: &std::io::Error) -> bool {
+    #[cfg(windows)]
+    const DISK_FULL_CODES: &[i32] = &[39, 112];
+    #[cfg(target_os = "linux")]
+    const DISK_FULL_CODES: &[i32] = &[28, 122];
+    #[cfg(all(unix, not(target_os = "linux")))]
+    const DISK_FULL_CODES: &[i32] = &[28, 69];
+    error.raw_os_error().map_or(false, |code| DISK_FULL_CODES.contains(&code))
+}
+
 pub type Result<T> = std::result::Result<T, Circle9Error>;
@@ -1,63 +1,223 @@
-use crate::ssh_client::{SSHClient, SSHConfig};
-use ssh2::{FileType, Permissions};
-use std::path::Path;
+use crate::ssh_client::{ConfigFieldError, ConnectionTestResult, ConnectionTuning, HostKeyFingerprints, SessionInfo, SSHClient, SSHConfig, SSHConnection, TransferProtocol};
+use ssh2::{FileStat, FileType};
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use crate::error::{Circle9Error, Result};
+use crate::copy_agent::{CopyAgent, TransferOptions};
+use crate::delta_transfer::delta_upload;
+use crate::scp_transfer::scp_upload;
+use crate::overwrite_policy::{resolve_destination, resolve_local_destination, OverwritePolicy};
+use crate::remote_dirs::create_remote_file;
+use crate::remote_env::command_available;
+use crate::remote_exec::{exec_command_dedicated, shell_quote, COMMAND_NOT_FOUND};
+use crate::remote_mounts::{out_of_space, same_device};
+use crate::remote_names::{decode_remote_path, display_name, encode_remote_path};
+use crate::remote_users::expand_tilde;
+use crate::settings::{self, SettingsPatch};
+use crate::types::{DiskFullEvent, TransferProgress, TransferRenamedEvent, TransferSkippedEvent};
+use crate::utils::{lock_or_error, ProgressThrottle};
 use std::time::SystemTime;
 use chrono::{DateTime, Utc};
-use tauri::State;
+use tauri::{Manager, State};
+
+/// Server-side `cp` of a large file can run well past the default command timeout
+const REMOTE_COPY_TIMEOUT: Duration = Duration::from_secs(3600);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LinuxFileInfo {
+    /// Display name; bytes that aren't valid UTF-8 show as U+FFFD
     pub name: String,
+    /// Path to pass back to other commands; `raw:` plus base64 when the real name isn't UTF-8
     pub path: String,
+    /// False when `name` is only an approximation of the real name
+    pub name_is_utf8: bool,
     pub size: u64,
     pub is_dir: bool,
+    /// Dot-prefixed name; the Linux counterpart of `WindowsFileAttributes::hidden`
+    pub hidden: bool,
+    /// Left empty, and out of the JSON, when the listing didn't ask for it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub file_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub permissions: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub owner: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub group: String,
     pub modified: DateTime<Utc>,
     pub accessed: DateTime<Utc>,
 }
 
+/// Access and modification times of a remote file; SFTP carries whole seconds only
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransferProgress {
-    pub bytes_transferred: u64,
-    pub total_bytes: u64,
-    pub filename: String,
-    pub direction: String, // "upload" or "download"
+pub struct LinuxFileTimes {
+    pub atime: Option<DateTime<Utc>>,
+    pub mtime: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Name,
+    Size,
+    Mtime,
+    Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Server-side sorting, filtering and paging for `list_linux_dir`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListingOptions {
+    pub sort_by: SortBy,
+    pub sort_order: SortOrder,
+    pub directories_first: bool,
+    /// Case-insensitive substring the name must contain
+    pub name_filter: Option<String>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// Optional columns of a `list_linux_dir` entry; name, path, size, `is_dir`, `hidden` and the
+/// times are always filled in. Everything is on unless turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListingFields {
+    /// `file_type` and `permissions`
+    pub permissions: bool,
+    /// `owner` and `group`
+    pub ownership: bool,
+}
+
+impl Default for ListingFields {
+    fn default() -> Self {
+        Self { permissions: true, ownership: true }
+    }
+}
+
+impl ListingFields {
+    /// Whether entries read with these fields have everything `other` asks for
+    pub(crate) fn covers(&self, other: &ListingFields) -> bool {
+        (self.permissions || !other.permissions) && (self.ownership || !other.ownership)
+    }
+
+    /// Clear the columns that weren't asked for from a fuller entry
+    pub(crate) fn project(&self, mut file: LinuxFileInfo) -> LinuxFileInfo {
+        if !self.permissions {
+            file.file_type.clear();
+            file.permissions.clear();
+        }
+        if !self.ownership {
+            file.owner.clear();
+            file.group.clear();
+        }
+        file
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinuxPermissionInfo {
+    pub permissions: String,
+    pub file_type: String,
+    pub mode: u32,
 }
 
 // Tauri commands for Linux file operations
 
+/// Callback emitting throttled `transfer_progress` events for `path` under `task_id`
+fn progress_emitter<'a>(
+    app_handle: &'a tauri::AppHandle,
+    task_id: &'a str,
+    path: &str,
+    direction: &'a str,
+) -> impl FnMut(u64, u64) + 'a {
+    let filename = Path::new(path).file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let mut throttle = ProgressThrottle::new(app_handle.state::<CopyAgent>().progress_throttle());
+    let start_time = Instant::now();
+
+    move |transferred, total| {
+        if throttle.should_emit(transferred, total) {
+            TransferProgress::new(task_id, &filename, direction, transferred, total, start_time.elapsed())
+                .emit(app_handle);
+        }
+    }
+}
+
 fn validate_path(path: &str) -> Result<(), String> {
     crate::utils::validate_path(path)
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// Give an uploaded file the mode its extension has in the permission profile, if any
+fn apply_permission_profile(connection: &SSHConnection, local_path: &str, remote_path: &Path) -> Result<(), String> {
+    let mode = match settings::current().permission_profile.mode_for(Path::new(local_path)) {
+        Some(mode) => mode,
+        None => return Ok(()),
+    };
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: Some(mode),
+        atime: None,
+        mtime: None,
+    };
+    connection.sftp().map_err(|e| e.to_string())?
+        .setstat(remote_path, stat)
+        .map_err(|e| format!("Uploaded but failed to set permissions: {}", e))
+}
+
+/// Expand `~` and `~user` against the connection's home directories
+fn expand_path(connection: &SSHConnection, path: &str) -> Result<String, String> {
+    expand_tilde(connection, path)
+        .map_err(|e| e.to_string())
+}
+
+/// The real remote path behind a path from the frontend, which may be `raw:` encoded
+fn real_path(connection: &SSHConnection, path: &str) -> Result<PathBuf, String> {
+    decode_remote_path(&expand_path(connection, path)?)
+        .map_err(|e| e.to_string())
+}
+
+/// `path` as text for the delta and SCP uploads, which name the file in a remote command.
+/// None for a name that isn't UTF-8; SFTP takes its raw bytes instead
+fn shell_path(path: &Path) -> Option<&str> {
+    path.to_str()
+}
+
 #[tauri::command]
 pub async fn connect_ssh(
     ssh_client: State<'_, SSHClient>,
-    host: String,
-    port: u16,
-    username: String,
-    key_path: Option<String>,
-    password: Option<String>,
+    config: SSHConfig,
+    session_label: Option<String>,
 ) -> Result<String, String> {
-    let config = SSHConfig {
-        host,
-        port,
-        username,
-        key_path,
-        password,
-    };
-
-    ssh_client.connect(config).await
+    ssh_client.connect(config, session_label).await
         .map(|id| id.as_str().to_string())
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn validate_ssh_config(config: SSHConfig) -> Result<Vec<ConfigFieldError>, String> {
+    Ok(config.validate())
+}
+
+#[tauri::command]
+pub async fn test_ssh_connection(config: SSHConfig) -> Result<ConnectionTestResult, String> {
+    Ok(SSHClient::test_connection(config).await)
+}
+
 #[tauri::command]
 pub async fn disconnect_ssh(
     ssh_client: State<'_, SSHClient>,
@@ -71,56 +231,86 @@ pub async fn disconnect_ssh(
 pub async fn list_linux_dir(
     ssh_client: State<'_, SSHClient>,
     connection_id: String, 
-    path: String
+    path: String,
+    show_hidden: Option<bool>,
+    options: Option<ListingOptions>,
+    fields: Option<ListingFields>,
 ) -> Result<Vec<LinuxFileInfo>, String> {
-    validate_path(&path)?;
     let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
+    let path = expand_path(&connection, &path)?;
+    validate_path(&path)?;
+    let dir = decode_remote_path(&path).map_err(|e| e.to_string())?;
+    let options = options.unwrap_or_default();
+    let mut fields = fields.unwrap_or_default();
+    // Sorting by type needs the type column
+    fields.permissions |= options.sort_by == SortBy::Type;
 
-    let mut files = Vec::new();
-    
-    match connection.sftp.readdir(Path::new(&path)) {
-        Ok(entries) => {
-            for (path, stat) in entries {
-                let file_name = path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown")
-                    .to_string();
-
-                let is_dir = stat.file_type() == FileType::Directory;
-                let size = stat.size().unwrap_or(0);
-                let permissions = format_permissions(stat.permissions());
-                let owner = stat.uid().unwrap_or(0).to_string();
-                let group = stat.gid().unwrap_or(0).to_string();
-                
-                // Convert timestamps
-                let modified = stat.mtime().and_then(|mtime| {
-                    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(mtime as u64))
-                }).map(|st| DateTime::<Utc>::from(st))
-                .unwrap_or_else(|| Utc::now());
-
-                let accessed = stat.atime().and_then(|atime| {
-                    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(atime as u64))
-                }).map(|st| DateTime::<Utc>::from(st))
-                .unwrap_or_else(|| Utc::now());
-
-                files.push(LinuxFileInfo {
-                    name: file_name,
-                    path: path.to_string_lossy().to_string(),
-                    size,
-                    is_dir,
-                    permissions,
-                    owner,
-                    group,
-                    modified,
-                    accessed,
-                });
-            }
+    // Hidden files are filtered after the cache, so toggling them doesn't refetch; a listing
+    // read with more fields also serves requests for fewer
+    let files = match ssh_client.listing_cache.get(&connection_id, &path, fields) {
+        Some(cached) => cached,
+        None => {
+            let files = {
+                let _permit = connection.acquire_metadata_permit().await
+                    .map_err(|e| e.to_string())?;
+                read_remote_dir_fields(&connection, &dir, fields)?
+            };
+            ssh_client.listing_cache.insert(&connection_id, &path, fields, files.clone());
+            files
         }
-        Err(e) => return Err(format!("Failed to read directory: {}", e)),
-    }
+    };
 
-    Ok(files)
+    let show_hidden = show_hidden.unwrap_or(true);
+    let files = files.into_iter().filter(|f| show_hidden || !f.hidden).collect();
+    Ok(apply_listing_options(files, &options))
+}
+
+#[tauri::command]
+pub async fn invalidate_listing_cache(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String
+) -> Result<(), String> {
+    ssh_client.listing_cache.invalidate(&connection_id, &path);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_listing_cache_ttl(
+    app_handle: tauri::AppHandle,
+    ttl_secs: u64
+) -> Result<(), String> {
+    let patch = SettingsPatch {
+        listing_cache_ttl_secs: Some(ttl_secs),
+        ..SettingsPatch::default()
+    };
+    settings::update(&app_handle, patch)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Optional settings for `copy_to_linux`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadOptions {
+    /// Send only the blocks that differ from the existing remote file
+    pub delta: bool,
+    /// Defaults to Overwrite
+    pub overwrite_policy: Option<OverwritePolicy>,
+    /// Lets the caller match progress events to this upload
+    pub task_id: Option<String>,
+}
+
+/// Optional settings for `copy_from_linux`: the overwrite policy for the local file plus
+/// the copy agent's options for the queued download
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadOptions {
+    /// Defaults to Overwrite
+    pub overwrite_policy: Option<OverwritePolicy>,
+    #[serde(flatten)]
+    pub transfer: TransferOptions,
 }
 
 #[tauri::command]
@@ -129,111 +319,346 @@ pub async fn copy_to_linux(
     connection_id: String,
     local_path: String,
     remote_path: String,
+    options: Option<UploadOptions>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    let connection = SSH_CLIENT.get_connection(&connection_id)
+    let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
+    let UploadOptions { delta, overwrite_policy, task_id } = options.unwrap_or_default();
+    let task_id = task_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let remote_path = real_path(&connection, &remote_path)?;
+
+    // Delta uploads patch the existing file, so they only apply where the policy lets it be
+    // overwritten; Rename never does
+    let policy = overwrite_policy.unwrap_or(OverwritePolicy::Overwrite);
+    if delta && matches!(policy, OverwritePolicy::Rename) {
+        return Err("Delta uploads patch the existing file and can't be combined with the Rename policy".to_string());
+    }
+    let requested_path = remote_path;
+    let remote_path = match resolve_destination(&app_handle, &connection, &local_path, &requested_path, &policy).await {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            TransferSkippedEvent { task_id, path: encode_remote_path(&requested_path) }.emit(&app_handle);
+            return Err(Circle9Error::Skipped(requested_path.display().to_string()).to_string());
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    if remote_path != requested_path {
+        TransferRenamedEvent {
+            task_id: task_id.clone(),
+            requested_path: encode_remote_path(&requested_path),
+            path: encode_remote_path(&remote_path),
+        }.emit(&app_handle);
+    }
+    let cache_path = remote_path.to_string_lossy().to_string();
+
+    // A prompt answered with Rename leaves nothing to patch, so that upload is sent in full.
+    // A name that isn't UTF-8 can't be handed to the remote delta tool, so it's sent in full too
+    if delta && remote_path == requested_path {
+        if let Some(text_path) = shell_path(&remote_path) {
+            delta_upload(&connection, &local_path, text_path, progress_emitter(&app_handle, &task_id, &local_path, "upload"))
+                .map_err(|e| e.to_string())?;
+            apply_permission_profile(&connection, &local_path, &remote_path)?;
+
+            ssh_client.listing_cache.invalidate_parent(&connection_id, &cache_path);
+            return Ok(());
+        }
+    }
+
+    let tuning = connection.tuning();
+    if tuning.transfer_protocol == TransferProtocol::Scp {
+        if let Some(text_path) = shell_path(&remote_path) {
+            let mut progress = progress_emitter(&app_handle, &task_id, &local_path, "upload");
+            let result = scp_upload(&connection, &local_path, text_path, tuning.chunk_size, |sent, total| {
+                progress(sent, total);
+                Ok(())
+            });
+            match result {
+                Ok(()) => {
+                    apply_permission_profile(&connection, &local_path, &remote_path)?;
+                    ssh_client.listing_cache.invalidate_parent(&connection_id, &cache_path);
+                    return Ok(());
+                }
+                Err(e) => tracing::warn!("SCP upload of {} failed, falling back to SFTP: {}", local_path, e),
+            }
+        }
+    }
 
     // Read local file
     let local_file = std::fs::read(&local_path)
         .map_err(|e| format!("Failed to read local file: {}", e))?;
 
-    // Create remote file
-    let mut remote_file = connection.sftp.create(Path::new(&remote_path))
+    let sftp = connection.sftp().map_err(|e| e.to_string())?;
+    let mut remote_file = create_remote_file(&sftp, &remote_path)
         .map_err(|e| format!("Failed to create remote file: {}", e))?;
 
     // Write file in chunks for progress tracking
-    let chunk_size = 8192;
     let total_size = local_file.len() as u64;
     let mut bytes_written = 0;
+    let mut progress = progress_emitter(&app_handle, &task_id, &local_path, "upload");
+
+    for chunk in local_file.chunks(tuning.sftp_buffer_size()) {
+        connection.bandwidth.acquire(chunk.len());
+        if let Err(e) = remote_file.write_all(chunk) {
+            if out_of_space(&mut remote_file, chunk.len()) {
+                DiskFullEvent { task_id: task_id.clone(), path: encode_remote_path(&remote_path), bytes_written }.emit(&app_handle);
+                return Err(Circle9Error::DiskFull { path: cache_path, bytes_written }.to_string());
+            }
+            return Err(format!("Failed to write to remote file: {}", e));
+        }
 
-    for chunk in local_file.chunks(chunk_size) {
-        remote_file.write_all(chunk)
-            .map_err(|e| format!("Failed to write to remote file: {}", e))?;
-        
         bytes_written += chunk.len() as u64;
-        
-        // Emit progress event
-        let progress = TransferProgress {
-            bytes_transferred: bytes_written,
-            total_bytes: total_size,
-            filename: Path::new(&local_path).file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
-            direction: "upload".to_string(),
-        };
+        progress(bytes_written, total_size);
+    }
 
-        app_handle.emit_all("transfer_progress", &progress)
-            .unwrap_or_default();
+    // fsync is an OpenSSH extension, so not every server supports it
+    if let Err(e) = remote_file.fsync() {
+        tracing::debug!("fsync of {} not supported: {}", remote_path.display(), e);
     }
+    drop(remote_file);
+    drop(sftp);
+    apply_permission_profile(&connection, &local_path, &remote_path)?;
 
-    remote_file.sync_all()
-        .map_err(|e| format!("Failed to sync remote file: {}", e))?;
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &cache_path);
 
     Ok(())
 }
 
+/// Queue a download on the copy agent so it gets a task id, pause/resume, cancellation and retry.
+/// Returns the task id; progress arrives as `transfer_progress` events under it. If the
+/// overwrite prompt is answered Skip, no task is queued: `transfer_skipped` is emitted under
+/// the id and the command fails with a `Skipped` error. With
+/// `alternate_connections`, mirrors of the same server, the download moves to the next one
+/// when the primary's connection fails or its circuit breaker opens.
 #[tauri::command]
 pub async fn copy_from_linux(
     ssh_client: State<'_, SSHClient>,
+    copy_agent: State<'_, CopyAgent>,
     connection_id: String,
     remote_path: String,
     local_path: String,
+    options: Option<DownloadOptions>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if ssh_client.get_connection(&connection_id).is_none() {
+        return Err("Connection not found".to_string());
+    }
+    let DownloadOptions { overwrite_policy, transfer } = options.unwrap_or_default();
+    let task_id = transfer.task_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let policy = overwrite_policy.unwrap_or(OverwritePolicy::Overwrite);
+    let local_path = match resolve_local_destination(&app_handle, &remote_path, &local_path, &policy).await {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            TransferSkippedEvent { task_id, path: local_path.clone() }.emit(&app_handle);
+            return Err(Circle9Error::Skipped(local_path).to_string());
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let options = TransferOptions {
+        task_id: Some(task_id),
+        connection_id: Some(connection_id),
+        ..transfer
+    };
+    copy_agent.create_download_task(remote_path, local_path, options)
+        .map_err(|e| e.to_string())
+}
+
+/// Copy a file to another path on the same host without routing it through this machine.
+/// Uses `cp` when the server has it, otherwise streams SFTP read → write within the connection.
+#[tauri::command]
+pub async fn copy_linux_to_linux(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    src: String,
+    dst: String,
+    task_id: Option<String>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
     let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
+    let task_id = task_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let src = expand_path(&connection, &src)?;
+    let dst = expand_path(&connection, &dst)?;
+    validate_path(&src)?;
+    validate_path(&dst)?;
+    let src_path = decode_remote_path(&src).map_err(|e| e.to_string())?;
+    let dst_path = decode_remote_path(&dst).map_err(|e| e.to_string())?;
+
+    let command = match cp_command(&src_path, &dst_path) {
+        Some(command) => command,
+        None => {
+            tracing::info!("{} isn't valid UTF-8, streaming it over SFTP", src);
+            return stream_and_invalidate(&ssh_client, &connection, &connection_id, &task_id, &src_path, &dst_path, &app_handle);
+        }
+    };
+    match command_available(&connection, "cp") {
+        Ok(true) => match exec_command_dedicated(&connection, &command, REMOTE_COPY_TIMEOUT).await {
+            Ok(output) if output.success() => {
+                ssh_client.listing_cache.invalidate_parent(&connection_id, &dst_path.to_string_lossy());
+                return Ok(());
+            }
+            Ok(output) if output.exit_status != COMMAND_NOT_FOUND => {
+                return Err(format!("Remote copy failed: {}", output.stderr.trim()));
+            }
+            Ok(_) => tracing::info!("cp not available on remote host, streaming {} over SFTP", src),
+            Err(e) => tracing::warn!("Could not run cp for {}, streaming over SFTP: {}", src, e),
+        },
+        Ok(false) => tracing::info!("cp not available on remote host, streaming {} over SFTP", src),
+        Err(e) => tracing::warn!("Could not check for cp, streaming {} over SFTP: {}", src, e),
+    }
 
-    // Open remote file
-    let mut remote_file = connection.sftp.open(Path::new(&remote_path))
-        .map_err(|e| format!("Failed to open remote file: {}", e))?;
+    stream_and_invalidate(&ssh_client, &connection, &connection_id, &task_id, &src_path, &dst_path, &app_handle)
+}
 
-    // Get file size for progress tracking
-    let stat = connection.sftp.stat(Path::new(&remote_path))
-        .map_err(|e| format!("Failed to get file stats: {}", e))?;
-    let total_size = stat.size().unwrap_or(0);
+/// The `cp -p` command line for a same-host copy. Exec commands are UTF-8, so names that
+/// aren't have no command line and are streamed over SFTP instead.
+fn cp_command(src: &Path, dst: &Path) -> Option<String> {
+    Some(format!("cp -p -- {} {}", shell_quote(src.to_str()?), shell_quote(dst.to_str()?)))
+}
 
-    // Create local file
-    let mut local_file = std::fs::File::create(&local_path)
-        .map_err(|e| format!("Failed to create local file: {}", e))?;
+/// The SFTP leg of `copy_linux_to_linux`
+#[allow(clippy::too_many_arguments)]
+fn stream_and_invalidate(
+    ssh_client: &SSHClient,
+    connection: &SSHConnection,
+    connection_id: &str,
+    task_id: &str,
+    src_path: &Path,
+    dst_path: &Path,
+    app_handle: &tauri::AppHandle,
+) -> Result<(), String> {
+    let mut progress = progress_emitter(app_handle, task_id, &src_path.to_string_lossy(), "remote");
+    stream_remote_copy(connection, src_path, dst_path, false, &mut progress)?;
+    ssh_client.listing_cache.invalidate_parent(connection_id, &dst_path.to_string_lossy());
+    Ok(())
+}
 
-    // Read file in chunks
-    let chunk_size = 8192;
-    let mut buffer = vec![0u8; chunk_size];
-    let mut bytes_read = 0;
+/// Copy a remote file to another remote path over SFTP, keeping its mode and optionally its times
+fn stream_remote_copy(
+    connection: &SSHConnection,
+    src: &Path,
+    dst: &Path,
+    preserve_times: bool,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), String> {
+    let sftp = connection.sftp().map_err(|e| e.to_string())?;
+    let mut src_file = sftp.open(src)
+        .map_err(|e| format!("Failed to open source file: {}", e))?;
+    let stat = src_file.stat()
+        .map_err(|e| format!("Failed to get file stats: {}", e))?;
+    let total_size = stat.size.unwrap_or(0);
+    let mut dst_file = sftp.create(dst)
+        .map_err(|e| format!("Failed to create destination file: {}", e))?;
+
+    let mut buffer = vec![0u8; connection.tuning().sftp_buffer_size()];
+    let mut bytes_copied = 0;
 
     loop {
-        let bytes = remote_file.read(&mut buffer)
-            .map_err(|e| format!("Failed to read from remote file: {}", e))?;
-        
+        let bytes = src_file.read(&mut buffer)
+            .map_err(|e| format!("Failed to read from source file: {}", e))?;
+
         if bytes == 0 {
             break;
         }
 
-        local_file.write_all(&buffer[..bytes])
-            .map_err(|e| format!("Failed to write to local file: {}", e))?;
-        
-        bytes_read += bytes as u64;
-        
-        // Emit progress event
-        let progress = TransferProgress {
-            bytes_transferred: bytes_read,
-            total_bytes,
-            filename: Path::new(&remote_path).file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
-            direction: "download".to_string(),
-        };
+        connection.bandwidth.acquire(bytes);
+        dst_file.write_all(&buffer[..bytes])
+            .map_err(|e| format!("Failed to write to destination file: {}", e))?;
 
-        app_handle.emit_all("transfer_progress", &progress)
-            .unwrap_or_default();
+        bytes_copied += bytes as u64;
+        progress(bytes_copied, total_size);
     }
 
-    local_file.sync_all()
-        .map_err(|e| format!("Failed to sync local file: {}", e))?;
+    drop(dst_file);
+
+    // Carry the mode (and times, when asked) over like `cp -p` would
+    let times_known = preserve_times && stat.atime.is_some() && stat.mtime.is_some();
+    let attrs = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: stat.perm.map(|perm| perm & 0o7777),
+        atime: if times_known { stat.atime } else { None },
+        mtime: if times_known { stat.mtime } else { None },
+    };
+    if let Err(e) = sftp.setstat(Path::new(dst), attrs) {
+        tracing::debug!("Could not copy attributes to {}: {}", dst.display(), e);
+    }
+
+    Ok(())
+}
+
+/// Whether a failed SFTP rename could be a cross-filesystem move. OpenSSH reports `EXDEV` as a
+/// generic failure, but also an existing target and other refusals, so this alone isn't enough.
+fn is_cross_device_error(error: &ssh2::Error) -> bool {
+    const SSH_FX_FAILURE: i32 = 4;
+    const SSH_FX_OP_UNSUPPORTED: i32 = 8;
+    matches!(error.code(), ssh2::ErrorCode::SFTP(SSH_FX_FAILURE) | ssh2::ErrorCode::SFTP(SSH_FX_OP_UNSUPPORTED))
+}
+
+#[tauri::command]
+pub async fn move_linux_file(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    src: String,
+    dst: String,
+    task_id: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let task_id = task_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let src = expand_path(&connection, &src)?;
+    let dst = expand_path(&connection, &dst)?;
+    validate_path(&src)?;
+    validate_path(&dst)?;
+    let src_path = decode_remote_path(&src).map_err(|e| e.to_string())?;
+    let dst_path = decode_remote_path(&dst).map_err(|e| e.to_string())?;
+
+    // The destination may not exist yet, so compare against its parent directory
+    let dst_dir = dst_path.parent()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "/".to_string());
+    // The device check goes through the shell, which only gets the names as text
+    let device_check = src_path.to_str()
+        .and_then(|src| same_device(&connection, &connection_id, src, &dst_dir));
+
+    let fallback_reason = if device_check == Some(false) {
+        Some(format!("{} and {} are on different filesystems", src, dst_dir))
+    } else {
+        let sftp = connection.sftp().map_err(|e| e.to_string())?;
+        match sftp.rename(&src_path, &dst_path, None) {
+            Ok(()) => None,
+            // Copy and delete only when the filesystems couldn't be compared and nothing is in the
+            // way; a generic failure onto an existing target must not turn into an overwrite
+            Err(e) if device_check.is_none() && is_cross_device_error(&e) && sftp.lstat(&dst_path).is_err() => {
+                Some(e.to_string())
+            }
+            Err(e) => return Err(format!("Failed to move file: {}", e)),
+        }
+    };
+
+    if let Some(reason) = fallback_reason {
+        let is_dir = connection.sftp().map_err(|e| e.to_string())?
+            .stat(&src_path)
+            .map(|stat| stat.is_dir())
+            .map_err(|e| format!("Failed to get file stats: {}", e))?;
+        if is_dir {
+            return Err(format!("Cannot move directory {} across filesystems: {}", src, reason));
+        }
+
+        tracing::info!("Moving {} by copy and delete: {}", src, reason);
+        let mut progress = progress_emitter(&app_handle, &task_id, &src, "remote");
+        stream_remote_copy(&connection, &src_path, &dst_path, true, &mut progress)?;
+        connection.sftp().map_err(|e| e.to_string())?
+            .unlink(&src_path)
+            .map_err(|e| format!("Copied to {} but failed to remove source: {}", dst, e))?;
+    }
 
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &src_path.to_string_lossy());
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &dst_path.to_string_lossy());
     Ok(())
 }
 
@@ -246,19 +671,29 @@ pub async fn delete_linux_file(
     let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
 
-    let path = Path::new(&path);
+    let path = real_path(&connection, &path)?;
+    let path = path.as_path();
     
     // Check if it's a directory or file
-    let stat = connection.sftp.stat(path)
+    let _permit = connection.acquire_metadata_permit().await
+        .map_err(|e| e.to_string())?;
+    let sftp = connection.sftp().map_err(|e| e.to_string())?;
+    let stat = sftp.stat(path)
         .map_err(|e| format!("Failed to get file stats: {}", e))?;
 
     if stat.file_type() == FileType::Directory {
-        connection.sftp.rmdir(path)
+        sftp.rmdir(path)
             .map_err(|e| format!("Failed to remove directory: {}", e))?;
     } else {
-        connection.sftp.unlink(path)
+        sftp.unlink(path)
             .map_err(|e| format!("Failed to remove file: {}", e))?;
     }
+    drop(sftp);
+
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &path.to_string_lossy());
+    if stat.file_type() == FileType::Directory {
+        ssh_client.listing_cache.invalidate(&connection_id, &path.to_string_lossy());
+    }
 
     Ok(())
 }
@@ -268,14 +703,23 @@ pub async fn get_linux_permissions(
     ssh_client: State<'_, SSHClient>,
     connection_id: String, 
     path: String
-) -> Result<String, String> {
+) -> Result<LinuxPermissionInfo, String> {
     let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
 
-    let stat = connection.sftp.stat(Path::new(&path))
+    let path = real_path(&connection, &path)?;
+    let _permit = connection.acquire_metadata_permit().await
+        .map_err(|e| e.to_string())?;
+    let stat = connection.sftp().map_err(|e| e.to_string())?
+        .stat(&path)
         .map_err(|e| format!("Failed to get file stats: {}", e))?;
 
-    Ok(format_permissions(stat.permissions()))
+    let mode = stat.perm.unwrap_or(0);
+    Ok(LinuxPermissionInfo {
+        permissions: format_permissions(mode),
+        file_type: format_file_type(mode),
+        mode,
+    })
 }
 
 #[tauri::command]
@@ -285,13 +729,92 @@ pub async fn set_linux_permissions(
     path: String,
     permissions: u32,
 ) -> Result<(), String> {
-    let connection = SSH_CLIENT.get_connection(&connection_id)
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = real_path(&connection, &path)?;
+
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: Some(permissions & 0o7777),
+        atime: None,
+        mtime: None,
+    };
+    connection.sftp().map_err(|e| e.to_string())?
+        .setstat(&path, stat)
+        .map_err(|e| format!("Failed to set permissions: {}", e))?;
+
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &path.to_string_lossy());
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_linux_file_times(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<LinuxFileTimes, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = real_path(&connection, &path)?;
+
+    let _permit = connection.acquire_metadata_permit().await
+        .map_err(|e| e.to_string())?;
+    let stat = connection.sftp().map_err(|e| e.to_string())?
+        .stat(&path)
+        .map_err(|e| format!("Failed to get file stats: {}", e))?;
+
+    Ok(LinuxFileTimes {
+        atime: stat.atime.and_then(unix_to_datetime),
+        mtime: stat.mtime.and_then(unix_to_datetime),
+    })
+}
+
+/// Set a remote file's access and modification times. SFTP sets both together,
+/// so a time left as None keeps its current value.
+#[tauri::command]
+pub async fn set_linux_file_times(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+    atime: Option<DateTime<Utc>>,
+    mtime: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    let connection = ssh_client.get_connection(&connection_id)
         .ok_or("Connection not found")?;
+    let path = real_path(&connection, &path)?;
 
-    connection.sftp.setstat(Path::new(&path), |stat| {
-        stat.set_permissions(Permissions::from_bits(permissions).unwrap_or_default());
-    }).map_err(|e| format!("Failed to set permissions: {}", e))?;
+    let to_secs = |time: DateTime<Utc>| -> Result<u64, String> {
+        u64::try_from(time.timestamp())
+            .map_err(|_| format!("{} is before the Unix epoch", time))
+    };
+    let atime = atime.map(to_secs).transpose()?;
+    let mtime = mtime.map(to_secs).transpose()?;
 
+    let _permit = connection.acquire_metadata_permit().await
+        .map_err(|e| e.to_string())?;
+    let sftp = connection.sftp().map_err(|e| e.to_string())?;
+    let (atime, mtime) = if atime.is_none() || mtime.is_none() {
+        let current = sftp.stat(&path)
+            .map_err(|e| format!("Failed to get file stats: {}", e))?;
+        (atime.or(current.atime), mtime.or(current.mtime))
+    } else {
+        (atime, mtime)
+    };
+
+    sftp.setstat(&path, FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: None,
+        atime,
+        mtime,
+    }).map_err(|e| format!("Failed to set file times: {}", e))?;
+    drop(sftp);
+
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &path.to_string_lossy());
     Ok(())
 }
 
@@ -303,6 +826,42 @@ pub async fn is_ssh_connected(
     Ok(ssh_client.is_connected(&connection_id))
 }
 
+/// Round-trip to the server, returning the latency in milliseconds. Success closes the
+/// connection's circuit breaker and releases the transfers it blocked.
+#[tauri::command]
+pub async fn ping_ssh_connection(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: tauri::AppHandle,
+    connection_id: String
+) -> Result<u64, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    let started = std::time::Instant::now();
+    connection.sftp().map_err(|e| e.to_string())?
+        .realpath(Path::new("."))
+        .map_err(|e| format!("Ping failed: {}", e))?;
+    let latency = started.elapsed().as_millis() as u64;
+
+    crate::circuit_breaker::close(&app_handle, &connection_id)
+        .map_err(|e| e.to_string())?;
+    Ok(latency)
+}
+
+/// The connected server's host key fingerprints, for pinning in a profile
+#[tauri::command]
+pub async fn get_host_key_fingerprint(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String
+) -> Result<HostKeyFingerprints, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    let session = lock_or_error(&connection.session).map_err(|e| e.to_string())?;
+    HostKeyFingerprints::of(&session)
+        .ok_or_else(|| "Server did not present a host key".to_string())
+}
+
 #[tauri::command]
 pub async fn list_ssh_connections(
     ssh_client: State<'_, SSHClient>
@@ -310,12 +869,229 @@ pub async fn list_ssh_connections(
     Ok(ssh_client.list_connections())
 }
 
+#[tauri::command]
+pub async fn list_ssh_sessions(
+    ssh_client: State<'_, SSHClient>,
+    target: Option<String>
+) -> Result<Vec<SessionInfo>, String> {
+    Ok(ssh_client.list_sessions(target.as_deref()))
+}
+
+#[tauri::command]
+pub async fn get_connection_tuning(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String
+) -> Result<ConnectionTuning, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    Ok(connection.tuning())
+}
+
+#[tauri::command]
+pub async fn set_connection_tuning(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    tuning: ConnectionTuning
+) -> Result<(), String> {
+    ssh_client.set_tuning(&connection_id, tuning)
+        .map_err(|e| e.to_string())
+}
+
+/// Cap the combined throughput of all transfers on a connection; 0 removes the cap
+#[tauri::command]
+pub async fn set_connection_bandwidth_limit(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    bytes_per_sec: u64,
+) -> Result<(), String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    connection.bandwidth.set_limit(Some(bytes_per_sec));
+    Ok(())
+}
+
 // Helper functions
 
-fn format_permissions(permissions: Permissions) -> String {
-    let mode = permissions.bits();
-    format!("{:o}", mode)
+/// Read a remote directory over SFTP into `LinuxFileInfo` entries
+pub(crate) fn read_remote_dir(connection: &SSHConnection, path: &Path) -> Result<Vec<LinuxFileInfo>, String> {
+    read_remote_dir_fields(connection, path, ListingFields::default())
+}
+
+/// List a remote directory, formatting only the optional columns in `fields`
+fn read_remote_dir_fields(connection: &SSHConnection, path: &Path, fields: ListingFields) -> Result<Vec<LinuxFileInfo>, String> {
+    let entries = {
+        let sftp = connection.sftp().map_err(|e| e.to_string())?;
+        sftp.readdir(path)
+            .map_err(|e| format!("Failed to read directory: {}", e))?
+    };
+
+    let mut files = Vec::with_capacity(entries.len());
+    for (path, stat) in entries {
+        let file_name = display_name(&path);
+        let name_is_utf8 = path.file_name().map_or(true, |n| n.to_str().is_some());
+
+        let is_dir = stat.file_type() == FileType::Directory;
+        let hidden = file_name.starts_with('.');
+        let size = stat.size.unwrap_or(0);
+        let mode = stat.perm.unwrap_or(0);
+        let (file_type, permissions) = if fields.permissions {
+            (format_file_type(mode), format_permissions(mode))
+        } else {
+            (String::new(), String::new())
+        };
+        let (owner, group) = if fields.ownership {
+            (stat.uid.unwrap_or(0).to_string(), stat.gid.unwrap_or(0).to_string())
+        } else {
+            (String::new(), String::new())
+        };
+
+        // Convert timestamps
+        let modified = stat.mtime.and_then(unix_to_datetime)
+            .unwrap_or_else(|| Utc::now());
+
+        let accessed = stat.atime.and_then(unix_to_datetime)
+            .unwrap_or_else(|| Utc::now());
+
+        files.push(LinuxFileInfo {
+            name: file_name,
+            path: encode_remote_path(&path),
+            name_is_utf8,
+            size,
+            is_dir,
+            hidden,
+            file_type,
+            permissions,
+            owner,
+            group,
+            modified,
+            accessed,
+        });
+    }
+
+    Ok(files)
+}
+
+/// SFTP timestamp (seconds since the epoch) as a `DateTime`
+fn unix_to_datetime(secs: u64) -> Option<DateTime<Utc>> {
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+        .map(DateTime::<Utc>::from)
+}
+
+/// Filter, sort and page a directory listing
+pub(crate) fn apply_listing_options(mut files: Vec<LinuxFileInfo>, options: &ListingOptions) -> Vec<LinuxFileInfo> {
+    if let Some(filter) = options.name_filter.as_deref().filter(|f| !f.is_empty()) {
+        let filter = filter.to_lowercase();
+        files.retain(|f| f.name.to_lowercase().contains(&filter));
+    }
+
+    files.sort_by(|a, b| {
+        let ordering = match options.sort_by {
+            SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortBy::Size => a.size.cmp(&b.size),
+            SortBy::Mtime => a.modified.cmp(&b.modified),
+            SortBy::Type => a.file_type.cmp(&b.file_type),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = match options.sort_order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        if options.directories_first {
+            b.is_dir.cmp(&a.is_dir).then(ordering)
+        } else {
+            ordering
+        }
+    });
+
+    files.into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Octal permission string from a full st_mode, without the file type bits
+/// (a directory with mode 0o40755 formats as "755")
+pub(crate) fn format_permissions(mode: u32) -> String {
+    format!("{:o}", mode & 0o7777)
+}
+
+/// File type name from the S_IFMT bits of a full st_mode
+pub(crate) fn format_file_type(mode: u32) -> String {
+    match mode & 0o170000 {
+        0o040000 => "directory",
+        0o100000 => "file",
+        0o120000 => "symlink",
+        0o020000 => "char_device",
+        0o060000 => "block_device",
+        0o010000 => "fifo",
+        0o140000 => "socket",
+        _ => "unknown",
+    }.to_string()
 }
 
 use std::io::{Read, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_permissions_drops_file_type_bits() {
+        assert_eq!(format_permissions(0o040755), "755");
+        assert_eq!(format_permissions(0o100644), "644");
+        assert_eq!(format_permissions(0o104755), "4755");
+        assert_eq!(format_permissions(0o120777), "777");
+        assert_eq!(format_permissions(0o100000), "0");
+    }
+
+    #[test]
+    fn format_file_type_names_each_s_ifmt() {
+        assert_eq!(format_file_type(0o040755), "directory");
+        assert_eq!(format_file_type(0o100644), "file");
+        assert_eq!(format_file_type(0o120777), "symlink");
+        assert_eq!(format_file_type(0o020666), "char_device");
+        assert_eq!(format_file_type(0o060660), "block_device");
+        assert_eq!(format_file_type(0o010644), "fifo");
+        assert_eq!(format_file_type(0o140755), "socket");
+        assert_eq!(format_file_type(0o644), "unknown");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn latin1_names_round_trip_and_skip_cp() {
+        use std::os::unix::ffi::OsStrExt;
+        let latin1 = Path::new(std::ffi::OsStr::from_bytes(b"/srv/caf\xe9.txt"));
+
+        let encoded = encode_remote_path(latin1);
+        assert!(encoded.starts_with("raw:"));
+        let decoded = decode_remote_path(&encoded).unwrap();
+        assert_eq!(decoded.as_os_str().as_bytes(), b"/srv/caf\xe9.txt");
+
+        assert_eq!(cp_command(&decoded, Path::new("/srv/copy.txt")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn uploads_to_latin1_names_keep_the_raw_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        let latin1 = Path::new(std::ffi::OsStr::from_bytes(b"/srv/caf\xe9.txt"));
+
+        // The frontend sends back the name it was given in the listing
+        let destination = decode_remote_path(&encode_remote_path(latin1)).unwrap();
+        assert_eq!(destination.as_os_str().as_bytes(), b"/srv/caf\xe9.txt");
+        assert!(!destination.to_string_lossy().starts_with("raw:"));
+
+        // so the upload skips delta and SCP and creates exactly those bytes over SFTP
+        assert_eq!(shell_path(&destination), None);
+        assert_eq!(shell_path(Path::new("/srv/café.txt")), Some("/srv/café.txt"));
+    }
+
+    #[test]
+    fn cp_command_quotes_utf8_names() {
+        assert_eq!(
+            cp_command(Path::new("/srv/it's.txt"), Path::new("/srv/café.txt")).as_deref(),
+            Some(r"cp -p -- '/srv/it'\''s.txt' '/srv/café.txt'"),
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::dir_size::RemoteDirSize;
+use crate::linux_files::{LinuxFileInfo, ListingFields};
+
+pub const DEFAULT_LISTING_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// How long a computed size is trusted when nothing we did has written below it;
+/// other processes on the server can still change the tree
+const DIR_SIZE_CACHE_TTL: Duration = Duration::from_secs(600);
+
+struct CachedListing {
+    entries: Vec<LinuxFileInfo>,
+    /// Optional columns the entries were read with
+    fields: ListingFields,
+    fetched_at: Instant,
+}
+
+/// Short-lived cache of remote directory listings, keyed by connection and path, along with
+/// the sizes of directories whose trees have been walked
+pub struct ListingCache {
+    listings: Mutex<HashMap<(String, String), CachedListing>>,
+    ttl: Mutex<Duration>,
+    pub dir_sizes: DirSizeCache,
+}
+
+impl ListingCache {
+    pub fn new() -> Self {
+        Self {
+            listings: Mutex::new(HashMap::new()),
+            ttl: Mutex::new(DEFAULT_LISTING_CACHE_TTL),
+            dir_sizes: DirSizeCache::new(),
+        }
+    }
+
+    /// Get a cached listing if it is still fresh and was read with at least `fields`; columns
+    /// beyond those are cleared
+    pub fn get(&self, connection_id: &str, path: &str, fields: ListingFields) -> Option<Vec<LinuxFileInfo>> {
+        let ttl = self.ttl();
+        if ttl.is_zero() {
+            return None;
+        }
+
+        let mut listings = self.listings.lock().ok()?;
+        let key = (connection_id.to_string(), normalize(path));
+        match listings.get(&key) {
+            Some(cached) if cached.fetched_at.elapsed() < ttl => {
+                if !cached.fields.covers(&fields) {
+                    return None;
+                }
+                Some(match fields == cached.fields {
+                    true => cached.entries.clone(),
+                    false => cached.entries.iter().cloned().map(|f| fields.project(f)).collect(),
+                })
+            }
+            Some(_) => {
+                listings.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, connection_id: &str, path: &str, fields: ListingFields, entries: Vec<LinuxFileInfo>) {
+        if self.ttl().is_zero() {
+            return;
+        }
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.insert(
+                (connection_id.to_string(), normalize(path)),
+                CachedListing { entries, fields, fetched_at: Instant::now() },
+            );
+        }
+    }
+
+    /// Drop the cached listing of a directory, and the cached sizes of it and its ancestors
+    pub fn invalidate(&self, connection_id: &str, path: &str) {
+        self.dir_sizes.invalidate_containing(Some(connection_id), path);
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.remove(&(connection_id.to_string(), normalize(path)));
+        }
+    }
+
+    /// Drop the cached listing of the directory containing `path`
+    pub fn invalidate_parent(&self, connection_id: &str, path: &str) {
+        if let Some(parent) = parent_dir(path) {
+            self.invalidate(connection_id, &parent);
+        }
+    }
+
+    /// Drop the directory containing `path` from every connection's cache
+    pub fn invalidate_parent_everywhere(&self, path: &str) {
+        self.dir_sizes.invalidate_containing(None, path);
+        if let (Some(parent), Ok(mut listings)) = (parent_dir(path), self.listings.lock()) {
+            listings.retain(|(_, cached_path), _| *cached_path != parent);
+        }
+    }
+
+    /// Drop everything cached for a connection
+    pub fn clear_connection(&self, connection_id: &str) {
+        self.dir_sizes.clear_connection(connection_id);
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.retain(|(id, _), _| id != connection_id);
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl.lock().map(|ttl| *ttl).unwrap_or(DEFAULT_LISTING_CACHE_TTL)
+    }
+
+    /// Set the cache lifetime; zero disables caching
+    pub fn set_ttl(&self, ttl: Duration) {
+        if let Ok(mut current) = self.ttl.lock() {
+            *current = ttl;
+        }
+        if ttl.is_zero() {
+            if let Ok(mut listings) = self.listings.lock() {
+                listings.clear();
+            }
+        }
+    }
+}
+
+/// Completed directory sizes, keyed by connection, path and whether mounts were crossed.
+/// Writes drop the size of every directory above the written path.
+pub struct DirSizeCache {
+    sizes: Mutex<HashMap<(String, String, bool), (RemoteDirSize, Instant)>>,
+}
+
+impl DirSizeCache {
+    fn new() -> Self {
+        Self { sizes: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn get(&self, connection_id: &str, path: &str, cross_filesystems: bool) -> Option<RemoteDirSize> {
+        let mut sizes = self.sizes.lock().ok()?;
+        let key = (connection_id.to_string(), normalize(path), cross_filesystems);
+        match sizes.get(&key) {
+            Some((size, computed_at)) if computed_at.elapsed() < DIR_SIZE_CACHE_TTL => {
+                Some(RemoteDirSize { cached: true, ..size.clone() })
+            }
+            Some(_) => {
+                sizes.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remember a finished scan; cancelled ones are partial and aren't kept
+    pub fn insert(&self, connection_id: &str, cross_filesystems: bool, size: &RemoteDirSize) {
+        if size.cancelled {
+            return;
+        }
+        if let Ok(mut sizes) = self.sizes.lock() {
+            sizes.insert(
+                (connection_id.to_string(), normalize(&size.path), cross_filesystems),
+                (size.clone(), Instant::now()),
+            );
+        }
+    }
+
+    /// Drop the sizes of `path` and every directory containing it, on one connection or all
+    pub fn invalidate_containing(&self, connection_id: Option<&str>, path: &str) {
+        let path = normalize(path);
+        if let Ok(mut sizes) = self.sizes.lock() {
+            sizes.retain(|(id, dir, _), _| {
+                connection_id.map_or(false, |c| c != id) || !is_within(&path, dir)
+            });
+        }
+    }
+
+    pub fn clear_connection(&self, connection_id: &str) {
+        if let Ok(mut sizes) = self.sizes.lock() {
+            sizes.retain(|(id, _, _), _| id != connection_id);
+        }
+    }
+}
+
+/// Whether `path` is `dir` or below it
+fn is_within(path: &str, dir: &str) -> bool {
+    dir == "/" || path == dir || path.strip_prefix(dir).map_or(false, |rest| rest.starts_with('/'))
+}
+
+fn normalize(path: &str) -> String {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() && path.starts_with('/') {
+        "/".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn parent_dir(path: &str) -> Option<String> {
+    Path::new(&normalize(path))
+        .parent()
+        .map(|p| normalize(&p.to_string_lossy().replace('\\', "/")))
+}
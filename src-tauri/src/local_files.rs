@@ -0,0 +1,166 @@
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use crate::error::{Circle9Error, Result};
+use crate::linux_files::{apply_listing_options, LinuxFileInfo, ListingOptions};
+use crate::permission_agent::PermissionAgent;
+
+/// The user's home directory: USERPROFILE on Windows, HOME elsewhere
+fn home_dir() -> Result<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var(var)
+        .map(PathBuf::from)
+        .map_err(|_| Circle9Error::InvalidPath(format!("{} environment variable not found", var)))
+}
+
+/// Replace `%NAME%` with environment variables, leaving unknown names as they are
+fn expand_env_vars(path: &str) -> String {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        let after = &rest[start + 1..];
+        match after.find('%').map(|end| (&after[..end], end)) {
+            Some((name, end)) if !name.is_empty() => {
+                expanded.push_str(&rest[..start]);
+                match std::env::var(name) {
+                    Ok(value) => expanded.push_str(&value),
+                    Err(_) => expanded.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                expanded.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Expand `~` and `%VAR%`, then resolve symlinks and `..` the way `realpath` does remotely
+pub fn resolve_local_path(path: &str) -> Result<PathBuf> {
+    let expanded = expand_env_vars(path.trim());
+    let expanded = match expanded.strip_prefix('~') {
+        Some("") => home_dir()?,
+        Some(rest) if rest.starts_with('/') || rest.starts_with('\\') => home_dir()?.join(&rest[1..]),
+        _ => PathBuf::from(expanded),
+    };
+    let canonical = expanded.canonicalize()
+        .map_err(|e| Circle9Error::InvalidPath(format!("{}: {}", expanded.display(), e)))?;
+    Ok(strip_verbatim_prefix(canonical))
+}
+
+/// canonicalize on Windows returns `\\?\C:\...`, which isn't for display
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(stripped) if !stripped.starts_with("UNC\\") => PathBuf::from(stripped),
+        _ => path,
+    }
+}
+
+/// Mode bits as they would read on Linux: the real mode on Unix, the permission agent's
+/// mapping of the file attributes on Windows
+#[cfg(unix)]
+fn local_mode(_path: &Path, metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn local_mode(path: &Path, metadata: &Metadata) -> u32 {
+    let type_bits = if metadata.is_dir() { 0o040000 } else { 0o100000 };
+    let permissions = PermissionAgent::get_windows_attributes(path)
+        .map(|attrs| PermissionAgent::linux_to_octal(&PermissionAgent::windows_to_linux_for_path(&attrs, path, true)))
+        .unwrap_or(0o644);
+    type_bits | permissions
+}
+
+#[cfg(unix)]
+fn ownership(metadata: &Metadata) -> (String, String) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.uid().to_string(), metadata.gid().to_string())
+}
+
+/// Windows owners are SIDs, which std doesn't expose; left empty like an unrequested column
+#[cfg(not(unix))]
+fn ownership(_metadata: &Metadata) -> (String, String) {
+    (String::new(), String::new())
+}
+
+/// Dot-prefixed everywhere, plus the hidden attribute on Windows
+fn is_hidden(path: &Path, name: &str) -> bool {
+    name.starts_with('.')
+        || (cfg!(windows) && PermissionAgent::get_windows_attributes(path).map_or(false, |attrs| attrs.hidden))
+}
+
+fn local_file_info(path: &Path) -> Result<LinuxFileInfo> {
+    let link_metadata = std::fs::symlink_metadata(path)?;
+    // Follow links for size and type so a linked directory can be entered, but report them as links
+    let metadata = std::fs::metadata(path).unwrap_or_else(|_| link_metadata.clone());
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+    let mode = local_mode(path, &metadata);
+    let file_type = if link_metadata.file_type().is_symlink() {
+        "symlink".to_string()
+    } else {
+        crate::linux_files::format_file_type(mode)
+    };
+    let (owner, group) = ownership(&metadata);
+    let now = Utc::now();
+
+    Ok(LinuxFileInfo {
+        hidden: is_hidden(path, &name),
+        name_is_utf8: path.file_name().map_or(true, |n| n.to_str().is_some()),
+        path: path.to_string_lossy().to_string(),
+        name,
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        is_dir: metadata.is_dir(),
+        file_type,
+        permissions: crate::linux_files::format_permissions(mode),
+        owner,
+        group,
+        modified: metadata.modified().map(DateTime::<Utc>::from).unwrap_or(now),
+        accessed: metadata.accessed().map(DateTime::<Utc>::from).unwrap_or(now),
+    })
+}
+
+/// List a local directory in the same shape, and with the same options, as a remote one
+pub fn list_local(path: &Path, show_hidden: bool, options: &ListingOptions) -> Result<Vec<LinuxFileInfo>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        match local_file_info(&entry.path()) {
+            Ok(info) if show_hidden || !info.hidden => files.push(info),
+            Ok(_) => {}
+            // Entries removed or locked mid-listing are left out rather than failing it
+            Err(e) => tracing::debug!("Skipping {}: {}", entry.path().display(), e),
+        }
+    }
+    Ok(apply_listing_options(files, options))
+}
+
+// Tauri commands for local files
+
+/// The canonical form of a local path, after `~` and `%VAR%` expansion
+#[tauri::command]
+pub async fn resolve_local_dir(path: String) -> Result<String, String> {
+    resolve_local_path(&path)
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_local_dir(
+    path: String,
+    show_hidden: Option<bool>,
+    options: Option<ListingOptions>,
+) -> Result<Vec<LinuxFileInfo>, String> {
+    let dir = resolve_local_path(&path).map_err(|e| e.to_string())?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    list_local(&dir, show_hidden.unwrap_or(true), &options.unwrap_or_default())
+        .map_err(|e| format!("Failed to read directory: {}", e))
+}
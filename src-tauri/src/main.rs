@@ -5,14 +5,56 @@
 
 mod ssh_client;
 mod linux_files;
+mod remote_walk;
+mod remote_dirs;
+mod dir_size;
+mod dir_diff;
+mod tar_transfer;
+mod delta_transfer;
+mod scp_transfer;
+mod stream_download;
+mod overwrite_policy;
+mod listing_cache;
 mod permission_agent;
+mod permission_snapshot;
+mod batch_rename;
+mod manifest;
+mod duplicates;
+mod remote_archive;
+mod connection_profiles;
+mod remote_checksum;
+mod remote_watch;
 mod case_agent;
+mod circuit_breaker;
 mod copy_agent;
+mod scheduler;
+mod background_jobs;
 mod audit_log;
+mod remote_exec;
+mod remote_env;
+mod remote_users;
+mod remote_access;
+mod rate_limit;
+mod ssh_keys;
+mod remote_passwd;
+mod remote_attrs;
+mod remote_mounts;
+mod remote_names;
+mod remote_clock;
+mod remote_file_type;
 mod error;
 mod types;
 mod utils;
 mod secure_storage;
+mod shutdown;
+mod settings;
+mod diagnostics;
+mod benchmark;
+mod local_files;
+mod remote_range;
+mod same_host;
+mod transform;
+mod transfer_hooks;
 
 use clap::{Arg, ArgMatches, Command as ClapCommand};
 use std::env;
@@ -55,7 +97,9 @@ lazy_static::lazy_static! {
 
 fn main() {
     // Initialize logging
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt()
+        .with_writer(diagnostics::log_writer)
+        .init();
     
     tauri::Builder::default()
         .setup(|app| {
@@ -73,56 +117,190 @@ fn main() {
             app.manage(ssh_client::SSHClient::new(app.handle()));
             app.manage(copy_agent::CopyAgent::new(app.handle()));
             app.manage(case_agent::CaseAgent::new());
+            app.manage(scheduler::TransferScheduler::new(app.handle()));
+
+            if let Err(e) = settings::load(&app.handle()) {
+                tracing::error!("Failed to load settings: {}", e);
+            }
+
+            let copy_agent = app.state::<copy_agent::CopyAgent>();
+            if let Err(e) = copy_agent.restore_persisted_queue() {
+                tracing::error!("Failed to restore persisted transfers: {}", e);
+            } else if settings::current().auto_resume_transfers {
+                if let Err(e) = copy_agent.resume_persisted_transfers() {
+                    tracing::error!("Failed to resume persisted transfers: {}", e);
+                }
+            }
+
+            let scheduler = app.state::<scheduler::TransferScheduler>();
+            if let Err(e) = scheduler.restore() {
+                tracing::error!("Failed to restore transfer schedules: {}", e);
+            }
+            scheduler.start();
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // SSH connection commands
             linux_files::connect_ssh,
+            connection_profiles::list_connection_profiles,
+            connection_profiles::save_connection_profile,
+            connection_profiles::delete_connection_profile,
+            connection_profiles::export_profiles,
+            connection_profiles::import_profiles,
+            connection_profiles::diff_profile,
+            connection_profiles::update_profile_from_connection,
             linux_files::disconnect_ssh,
+            linux_files::validate_ssh_config,
+            linux_files::test_ssh_connection,
             linux_files::is_ssh_connected,
+            linux_files::ping_ssh_connection,
+            benchmark::benchmark_connection,
+            benchmark::recommend_concurrency,
+            local_files::list_local_dir,
+            local_files::resolve_local_dir,
+            linux_files::get_host_key_fingerprint,
+            same_host::are_connections_same_host,
+            remote_clock::get_remote_time,
+            circuit_breaker::get_circuit_state,
             linux_files::list_ssh_connections,
+            linux_files::list_ssh_sessions,
+            linux_files::get_connection_tuning,
+            linux_files::set_connection_tuning,
+            linux_files::set_connection_bandwidth_limit,
+            ssh_keys::generate_ssh_keypair,
+            ssh_keys::install_public_key,
+            remote_passwd::rotate_ssh_password,
             
             // Linux file operations
             linux_files::list_linux_dir,
+            linux_files::invalidate_listing_cache,
+            linux_files::set_listing_cache_ttl,
             linux_files::copy_to_linux,
+            overwrite_policy::resolve_overwrite,
             linux_files::copy_from_linux,
+            stream_download::stream_remote_file,
+            remote_range::read_remote_range,
+            linux_files::copy_linux_to_linux,
+            linux_files::move_linux_file,
             linux_files::delete_linux_file,
             linux_files::get_linux_permissions,
             linux_files::set_linux_permissions,
+            remote_access::check_remote_writable,
+            remote_access::get_effective_access,
+            linux_files::get_linux_file_times,
+            linux_files::set_linux_file_times,
+            dir_size::get_remote_dir_size,
+            dir_diff::diff_directories,
+            dir_size::cancel_remote_dir_size,
+            tar_transfer::download_remote_dir_as_tar,
+            tar_transfer::upload_local_dir_as_tar,
+            remote_attrs::get_linux_xattrs,
+            remote_attrs::set_linux_xattr,
+            remote_mounts::list_remote_mounts,
+            remote_mounts::get_path_device,
+            remote_file_type::detect_remote_file_type,
+            remote_exec::run_remote_command,
+            remote_env::detect_remote_environment,
+            remote_env::check_remote_command_available,
+            remote_exec::run_remote_command_streaming,
+            remote_exec::send_command_input,
+            remote_exec::kill_command,
             
             // Permission mapping commands
             permission_agent::map_windows_to_linux_attrs,
             permission_agent::map_linux_to_windows_attrs,
+            permission_agent::preview_permission_mapping,
+            permission_snapshot::snapshot_permissions,
+            permission_snapshot::list_permission_snapshots,
+            permission_snapshot::restore_permissions,
+            permission_snapshot::delete_permission_snapshot,
+            batch_rename::batch_rename,
+            manifest::write_directory_manifest,
+            manifest::verify_against_manifest,
+            duplicates::find_duplicate_files,
+            remote_archive::list_remote_archive,
+            remote_archive::extract_remote_archive_entry,
+            remote_checksum::remote_file_checksum,
+            remote_watch::watch_remote_dir,
+            remote_watch::stop_remote_watch,
+            permission_agent::set_permission_profile,
             permission_agent::get_windows_file_attrs,
             permission_agent::set_windows_file_attrs,
             permission_agent::preserve_file_timestamps,
             
             // Case conflict handling
             case_agent::check_case_conflict,
+            case_agent::find_trailing_name_collisions,
             case_agent::resolve_case_conflict,
             case_agent::get_case_conflict_log,
             case_agent::clear_case_conflict_log,
             case_agent::is_system_case_sensitive,
             case_agent::normalize_filename,
             case_agent::filenames_equal_ignore_case,
+            case_agent::probe_remote_case_sensitivity,
             
             // Copy operations
             copy_agent::create_transfer_task,
+            copy_agent::set_transfer_allowed_hours,
+            copy_agent::set_transfer_notifications,
             copy_agent::get_transfer_progress,
             copy_agent::get_active_transfers,
+            copy_agent::get_pending_reason,
             copy_agent::cancel_transfer,
             copy_agent::retry_transfer,
+            copy_agent::cleanup_partial_transfer,
+            copy_agent::pause_transfer,
+            copy_agent::resume_transfer,
+            copy_agent::resume_persisted_transfers,
+            copy_agent::defer_transfer,
+            copy_agent::update_transfer_destination,
+            copy_agent::set_progress_throttle,
+            copy_agent::set_max_transfer_file_size,
+            copy_agent::get_max_transfer_file_size,
+            copy_agent::create_recursive_transfer_task,
+            copy_agent::create_incremental_transfer_task,
+            copy_agent::get_transfers_by_group,
+            copy_agent::cancel_group,
+            copy_agent::get_queue_summary,
+            copy_agent::get_fd_backoff,
+            copy_agent::get_recursive_transfer_results,
+            copy_agent::retry_failed_children,
+            settings::get_settings,
+            settings::update_settings,
+            diagnostics::export_diagnostics,
+
+            // Transfer scheduling
+            scheduler::schedule_transfer,
+            scheduler::schedule_recurring_transfer,
+            scheduler::get_transfer_schedules,
+            scheduler::cancel_transfer_schedule,
+            background_jobs::list_background_jobs,
+            background_jobs::cancel_background_job,
             
             // Audit logging
             audit_log::log_file_operation,
+            audit_log::begin_audit_batch,
+            audit_log::end_audit_batch,
             audit_log::get_audit_entries,
+            audit_log::get_recent_transfers,
             audit_log::get_audit_statistics,
+            audit_log::get_audit_statistics_grouped,
             audit_log::clear_audit_log,
+            audit_log::repair_audit_log,
             audit_log::export_audit_log,
             audit_log::get_session_id,
             audit_log::get_current_user,
+            shutdown::shutdown_app,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                tauri::async_runtime::block_on(shutdown::graceful_shutdown(
+                    app_handle,
+                    shutdown::SHUTDOWN_GRACE_PERIOD,
+                ));
+            }
+        });
 }
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::FileType;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::UNIX_EPOCH;
+use chrono::{DateTime, Utc};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_users::expand_tilde;
+use crate::remote_walk::{walk_remote, SkippedEntry};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Name of the manifest written into a transferred directory; it doesn't list itself
+pub const MANIFEST_FILE: &str = ".circle9-manifest.json";
+
+const HASH_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the manifest's directory, `/`-separated
+    pub path: String,
+    pub size: u64,
+    /// Seconds since the epoch
+    pub mtime: Option<u64>,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub created_at: DateTime<Utc>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Payload of `transfer_manifest_written`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestWrittenEvent {
+    pub task_id: String,
+    pub dir: String,
+    pub files: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestDrift {
+    pub path: String,
+    pub expected: ManifestEntry,
+    pub actual_size: u64,
+    pub actual_sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestVerification {
+    pub dir: String,
+    pub manifest_created_at: Option<DateTime<Utc>>,
+    pub checked: usize,
+    pub intact: usize,
+    /// Files whose contents no longer match; an mtime change alone isn't drift
+    pub modified: Vec<ManifestDrift>,
+    pub missing: Vec<String>,
+    /// Files present now that the manifest doesn't list
+    pub unexpected: Vec<String>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl ManifestVerification {
+    pub fn is_intact(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.unexpected.is_empty() && self.skipped.is_empty()
+    }
+}
+
+fn hash_reader(reader: &mut dyn Read) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Relative path with `/` separators, so manifests read the same on either side
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(relative.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Hash every regular file below a local `dir`
+pub fn build_local_manifest(dir: &Path) -> Result<Manifest> {
+    let mut entries = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            let metadata = std::fs::symlink_metadata(entry.path())?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+                continue;
+            }
+            let path = match relative_key(dir, &entry.path()) {
+                Some(path) if metadata.is_file() && path != MANIFEST_FILE => path,
+                _ => continue,
+            };
+            let sha256 = hash_reader(&mut std::fs::File::open(entry.path())?)?;
+            entries.push(ManifestEntry {
+                path,
+                size: metadata.len(),
+                mtime: metadata.modified().ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+                sha256,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Manifest { created_at: Utc::now(), entries })
+}
+
+pub fn write_local_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    std::fs::write(dir.join(MANIFEST_FILE), serde_json::to_string_pretty(manifest)?)?;
+    Ok(())
+}
+
+/// Hash every regular file below a remote `dir`, returning the entries and unreadable directories
+pub async fn build_remote_manifest(connection: &SSHConnection, dir: &str) -> Result<(Manifest, Vec<SkippedEntry>)> {
+    let root = Path::new(dir);
+    let mut files = Vec::new();
+    let outcome = walk_remote(connection, dir, true, &AtomicBool::new(false), |path, stat| {
+        if stat.file_type() == FileType::RegularFile {
+            if let Some(key) = relative_key(root, path).filter(|key| key != MANIFEST_FILE) {
+                files.push((path.to_path_buf(), key, stat.size.unwrap_or(0), stat.mtime));
+            }
+        }
+    }).await?;
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut skipped = outcome.skipped;
+    for (path, key, size, mtime) in files {
+        let hashed = connection.sftp()
+            .and_then(|sftp| Ok(sftp.open(&path)?))
+            .and_then(|mut file| hash_reader(&mut file));
+        match hashed {
+            Ok(sha256) => entries.push(ManifestEntry { path: key, size, mtime, sha256 }),
+            Err(e) => skipped.push(SkippedEntry { path: path.to_string_lossy().to_string(), reason: e.to_string() }),
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((Manifest { created_at: Utc::now(), entries }, skipped))
+}
+
+pub fn write_remote_manifest(connection: &SSHConnection, dir: &str, manifest: &Manifest) -> Result<()> {
+    let contents = serde_json::to_string_pretty(manifest)?;
+    let mut file = connection.sftp()?.create(&Path::new(dir).join(MANIFEST_FILE))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+pub fn read_remote_manifest(connection: &SSHConnection, dir: &str) -> Result<Manifest> {
+    let path = Path::new(dir).join(MANIFEST_FILE);
+    let mut contents = String::new();
+    connection.sftp()?
+        .open(&path)
+        .map_err(|e| Circle9Error::NotFound(format!("Manifest at {}: {}", path.display(), e)))?
+        .read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Re-hash a remote directory and compare it with the manifest stored in it
+pub async fn verify_remote(connection: &SSHConnection, dir: &str) -> Result<ManifestVerification> {
+    let stored = read_remote_manifest(connection, dir)?;
+    let (current, skipped) = build_remote_manifest(connection, dir).await?;
+
+    let mut current: BTreeMap<String, ManifestEntry> = current.entries.into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    let mut verification = ManifestVerification {
+        dir: dir.to_string(),
+        manifest_created_at: Some(stored.created_at),
+        skipped,
+        ..ManifestVerification::default()
+    };
+
+    for expected in stored.entries {
+        verification.checked += 1;
+        match current.remove(&expected.path) {
+            None => verification.missing.push(expected.path),
+            Some(actual) if actual.size == expected.size && actual.sha256 == expected.sha256 => {
+                verification.intact += 1;
+            }
+            Some(actual) => verification.modified.push(ManifestDrift {
+                path: expected.path.clone(),
+                expected,
+                actual_size: actual.size,
+                actual_sha256: actual.sha256,
+            }),
+        }
+    }
+    verification.unexpected = current.into_keys().collect();
+    Ok(verification)
+}
+
+// Tauri commands for manifests
+
+/// Hash a remote directory and store the result as its manifest
+#[tauri::command]
+pub async fn write_directory_manifest(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+) -> Result<usize, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let dir = expand_tilde(&connection, &dir).map_err(|e| e.to_string())?;
+
+    let (manifest, skipped) = build_remote_manifest(&connection, &dir).await
+        .map_err(|e| e.to_string())?;
+    if !skipped.is_empty() {
+        return Err(format!("Could not read {} entries, e.g. {}", skipped.len(), skipped[0].path));
+    }
+    write_remote_manifest(&connection, &dir, &manifest).map_err(|e| e.to_string())?;
+    ssh_client.listing_cache.invalidate(&connection_id, &dir);
+    Ok(manifest.entries.len())
+}
+
+#[tauri::command]
+pub async fn verify_against_manifest(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+) -> Result<ManifestVerification, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let dir = expand_tilde(&connection, &dir).map_err(|e| e.to_string())?;
+
+    verify_remote(&connection, &dir).await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+use crate::case_agent::CaseAgent;
+use crate::error::{Circle9Error, Result};
+use crate::remote_names::encode_remote_path;
+use crate::ssh_client::SSHConnection;
+use crate::utils::lock_or_error;
+
+/// How long a transfer waits for the user to answer an overwrite prompt
+const OVERWRITE_PROMPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What to do when a transfer's destination already exists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    Fail,
+    Overwrite,
+    Rename,
+    Prompt,
+}
+
+/// The user's answer to an `overwrite_prompt` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OverwriteDecision {
+    Overwrite,
+    Rename,
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverwritePrompt {
+    pub prompt_id: String,
+    pub local_path: String,
+    pub remote_path: String,
+    pub existing_size: Option<u64>,
+    /// "upload" when the remote file would be replaced, "download" for the local one
+    pub direction: String,
+}
+
+lazy_static::lazy_static! {
+    static ref PENDING_PROMPTS: Mutex<HashMap<String, oneshot::Sender<OverwriteDecision>>> = Mutex::new(HashMap::new());
+}
+
+/// Work out where an upload should be written under `policy`.
+/// Returns None when the user chose to skip the file.
+pub async fn resolve_destination(
+    app_handle: &AppHandle,
+    connection: &SSHConnection,
+    local_path: &str,
+    remote_path: &Path,
+    policy: &OverwritePolicy,
+) -> Result<Option<PathBuf>> {
+    let existing = {
+        let sftp = connection.sftp()?;
+        sftp.stat(remote_path).ok()
+    };
+    let existing = match existing {
+        Some(stat) => stat,
+        None => return Ok(Some(remote_path.to_path_buf())),
+    };
+
+    let prompt = OverwritePrompt {
+        prompt_id: uuid::Uuid::new_v4().to_string(),
+        local_path: local_path.to_string(),
+        remote_path: encode_remote_path(remote_path),
+        existing_size: existing.size,
+        direction: "upload".to_string(),
+    };
+    match decide(app_handle, policy, &remote_path.display().to_string(), prompt).await? {
+        OverwriteDecision::Overwrite => Ok(Some(remote_path.to_path_buf())),
+        OverwriteDecision::Skip => Ok(None),
+        OverwriteDecision::Rename => {
+            let sftp = connection.sftp()?;
+            let unique_name = CaseAgent::generate_unique_name_with(remote_path, |candidate| sftp.stat(candidate).is_ok())?;
+            let renamed = remote_path.with_file_name(unique_name);
+            tracing::info!("{} exists, uploading as {}", remote_path.display(), renamed.display());
+            Ok(Some(renamed))
+        }
+    }
+}
+
+/// Work out where a download should be written under `policy`, the local counterpart of
+/// `resolve_destination`. Returns None when the user chose to skip the file.
+pub async fn resolve_local_destination(
+    app_handle: &AppHandle,
+    remote_path: &str,
+    local_path: &str,
+    policy: &OverwritePolicy,
+) -> Result<Option<String>> {
+    let existing = match std::fs::metadata(local_path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(Some(local_path.to_string())),
+    };
+
+    let prompt = OverwritePrompt {
+        prompt_id: uuid::Uuid::new_v4().to_string(),
+        local_path: local_path.to_string(),
+        remote_path: remote_path.to_string(),
+        existing_size: Some(existing.len()),
+        direction: "download".to_string(),
+    };
+    match decide(app_handle, policy, local_path, prompt).await? {
+        OverwriteDecision::Overwrite => Ok(Some(local_path.to_string())),
+        OverwriteDecision::Skip => Ok(None),
+        OverwriteDecision::Rename => {
+            let path = Path::new(local_path);
+            let unique_name = CaseAgent::generate_unique_name_with(path, |candidate| candidate.exists())?;
+            let renamed = path.with_file_name(unique_name).to_string_lossy().to_string();
+            tracing::info!("{} exists, downloading as {}", local_path, renamed);
+            Ok(Some(renamed))
+        }
+    }
+}
+
+/// Apply `policy` to an existing `destination`, asking the user under `Prompt`
+async fn decide(
+    app_handle: &AppHandle,
+    policy: &OverwritePolicy,
+    destination: &str,
+    prompt: OverwritePrompt,
+) -> Result<OverwriteDecision> {
+    match policy {
+        OverwritePolicy::Overwrite => Ok(OverwriteDecision::Overwrite),
+        OverwritePolicy::Rename => Ok(OverwriteDecision::Rename),
+        OverwritePolicy::Fail => {
+            Err(Circle9Error::TransferError(format!("{} already exists", destination)))
+        }
+        OverwritePolicy::Prompt => prompt_user(app_handle, prompt).await,
+    }
+}
+
+/// Emit `overwrite_prompt` and wait for the matching `resolve_overwrite` call
+async fn prompt_user(app_handle: &AppHandle, prompt: OverwritePrompt) -> Result<OverwriteDecision> {
+    let (sender, receiver) = oneshot::channel();
+    lock_or_error(&PENDING_PROMPTS)?.insert(prompt.prompt_id.clone(), sender);
+
+    if let Err(e) = app_handle.emit_all("overwrite_prompt", &prompt) {
+        lock_or_error(&PENDING_PROMPTS)?.remove(&prompt.prompt_id);
+        return Err(Circle9Error::TransferError(format!("Failed to emit overwrite prompt: {}", e)));
+    }
+
+    let answer = tokio::time::timeout(OVERWRITE_PROMPT_TIMEOUT, receiver).await;
+    lock_or_error(&PENDING_PROMPTS)?.remove(&prompt.prompt_id);
+    match answer {
+        Ok(Ok(decision)) => Ok(decision),
+        Ok(Err(_)) => Err(Circle9Error::TransferError("Overwrite prompt was abandoned".to_string())),
+        Err(_) => Err(Circle9Error::Timeout),
+    }
+}
+
+// Tauri commands for overwrite prompts
+
+#[tauri::command]
+pub async fn resolve_overwrite(prompt_id: String, decision: OverwriteDecision) -> Result<(), String> {
+    let sender = PENDING_PROMPTS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?
+        .remove(&prompt_id)
+        .ok_or("Overwrite prompt not found or already answered")?;
+    sender.send(decision)
+        .map_err(|_| "Transfer is no longer waiting for an answer".to_string())
+}
@@ -1,6 +1,49 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 use anyhow::{Result, Context};
+use std::io::Read;
+use crate::settings::{self, SettingsPatch};
+
+/// Extensions treated as executable when the execute bit is chosen by file type
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "sh", "bash", "zsh", "ksh", "csh", "fish", "py", "pl", "rb", "php", "run", "bin", "appimage", "out",
+];
+
+/// Modes applied to uploaded files by extension, e.g. `sh` → 0o755, `key` → 0o600
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionProfile {
+    /// Lowercase extension without the dot → mode
+    pub by_extension: HashMap<String, u32>,
+    /// Mode for files no extension entry matches; None leaves them as uploaded
+    pub default_mode: Option<u32>,
+}
+
+impl PermissionProfile {
+    /// Lowercase the extensions, strip leading dots and reject modes outside 0o7777
+    pub fn normalized(by_extension: HashMap<String, u32>, default_mode: Option<u32>) -> Result<Self> {
+        let modes = by_extension.values().copied().chain(default_mode);
+        if let Some(mode) = modes.into_iter().find(|mode| *mode > 0o7777) {
+            return Err(anyhow::anyhow!("Invalid mode {:o}", mode));
+        }
+        Ok(Self {
+            by_extension: by_extension.into_iter()
+                .map(|(ext, mode)| (ext.trim_start_matches('.').to_lowercase(), mode))
+                .collect(),
+            default_mode,
+        })
+    }
+
+    /// Mode the profile assigns to `path`, if any
+    pub fn mode_for(&self, path: &Path) -> Option<u32> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| self.by_extension.get(&e.to_lowercase()))
+            .copied()
+            .or(self.default_mode)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowsFileAttributes {
@@ -23,6 +66,24 @@ pub struct LinuxPermissions {
     pub other_execute: bool,
 }
 
+/// Permissions as given on the source side: a Linux mode or Windows attributes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SourcePermissions {
+    Octal(u32),
+    Attributes(WindowsFileAttributes),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionMappingPreview {
+    pub path: String,
+    pub source: SourcePermissions,
+    /// None when the source permissions don't fit the direction
+    pub mapped: Option<SourcePermissions>,
+    /// Information the mapping loses or adds, e.g. a dropped setuid bit
+    pub warnings: Vec<String>,
+}
+
 pub struct PermissionAgent;
 
 impl PermissionAgent {
@@ -41,6 +102,50 @@ impl PermissionAgent {
         }
     }
 
+    /// Map Windows attributes for a specific file or directory.
+    /// Directories always keep their traverse bits; with `smart_execute_bit` set, files only
+    /// get execute bits if they look executable and have them stripped otherwise.
+    pub fn windows_to_linux_for_path(
+        attrs: &WindowsFileAttributes,
+        path: &Path,
+        smart_execute_bit: bool,
+    ) -> LinuxPermissions {
+        let mut perms = Self::windows_to_linux(attrs);
+
+        if path.is_dir() {
+            // Without x a directory can't be entered, whatever its read bits say
+            perms.owner_execute = true;
+            perms.group_execute = perms.group_read;
+            perms.other_execute = perms.other_read;
+        } else if smart_execute_bit && !Self::looks_executable(path) {
+            perms.owner_execute = false;
+            perms.group_execute = false;
+            perms.other_execute = false;
+        }
+
+        perms
+    }
+
+    /// Whether a file is a script or binary, judged by extension, shebang or ELF header
+    pub fn looks_executable(path: &Path) -> bool {
+        let by_extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| EXECUTABLE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false);
+        if by_extension {
+            return true;
+        }
+
+        let mut magic = [0u8; 4];
+        let read = std::fs::File::open(path)
+            .and_then(|mut f| f.read(&mut magic));
+        match read {
+            Ok(n) if n >= 2 && magic.starts_with(b"#!") => true,
+            Ok(4) => magic == *b"\x7fELF",
+            _ => false,
+        }
+    }
+
     /// Map Linux permissions to Windows file attributes
     pub fn linux_to_windows(perms: &LinuxPermissions) -> WindowsFileAttributes {
         WindowsFileAttributes {
@@ -85,6 +190,73 @@ impl PermissionAgent {
         }
     }
 
+    /// Show what each entry's permissions would become on the other side, flagging lossy mappings.
+    /// Uploads use the permission profile first, as `copy_to_linux` does.
+    pub fn preview_mapping(path: &str, source: &SourcePermissions, to_linux: bool) -> PermissionMappingPreview {
+        let mut warnings = Vec::new();
+        let mapped = match (source, to_linux) {
+            (SourcePermissions::Attributes(attrs), true) => {
+                let path_ref = Path::new(path);
+                let mode = settings::current().permission_profile.mode_for(path_ref)
+                    .unwrap_or_else(|| Self::linux_to_octal(&Self::windows_to_linux_for_path(attrs, path_ref, true)));
+
+                if mode & 0o111 != 0 && !path_ref.is_dir() && !Self::looks_executable(path_ref) {
+                    warnings.push("Execute permission added to a file that doesn't look executable".to_string());
+                }
+                if mode & 0o002 != 0 {
+                    warnings.push("File will be world-writable".to_string());
+                }
+                if attrs.hidden {
+                    warnings.push("Hidden attribute becomes no group/other access; Linux hides only dot-files".to_string());
+                }
+                if attrs.system {
+                    warnings.push("System attribute has no Linux equivalent".to_string());
+                }
+                Some(SourcePermissions::Octal(mode))
+            }
+            (SourcePermissions::Octal(mode), false) => {
+                let perms = Self::octal_to_linux(*mode);
+                let attrs = Self::linux_to_windows(&perms);
+
+                if mode & 0o4000 != 0 {
+                    warnings.push("setuid bit will be dropped".to_string());
+                }
+                if mode & 0o2000 != 0 {
+                    warnings.push("setgid bit will be dropped".to_string());
+                }
+                if mode & 0o1000 != 0 {
+                    warnings.push("Sticky bit will be dropped".to_string());
+                }
+                if mode & 0o111 != 0 {
+                    warnings.push("Execute bits have no Windows equivalent".to_string());
+                }
+                let round_trip = Self::linux_to_octal(&Self::windows_to_linux(&attrs));
+                if round_trip != mode & 0o777 {
+                    warnings.push(format!(
+                        "Mode {:o} won't survive a round trip; it would come back as {:o}",
+                        mode & 0o777, round_trip
+                    ));
+                }
+                Some(SourcePermissions::Attributes(attrs))
+            }
+            (SourcePermissions::Octal(_), true) => {
+                warnings.push("Expected Windows attributes for a Windows to Linux mapping".to_string());
+                None
+            }
+            (SourcePermissions::Attributes(_), false) => {
+                warnings.push("Expected an octal mode for a Linux to Windows mapping".to_string());
+                None
+            }
+        };
+
+        PermissionMappingPreview {
+            path: path.to_string(),
+            source: source.clone(),
+            mapped,
+            warnings,
+        }
+    }
+
     /// Get Windows file attributes from a file path
     pub fn get_windows_attributes(path: &Path) -> Result<WindowsFileAttributes> {
         let metadata = std::fs::metadata(path)
@@ -169,6 +341,8 @@ pub async fn map_windows_to_linux_attrs(
     hidden: bool,
     system: bool,
     archive: bool,
+    path: Option<String>,
+    smart_execute_bit: Option<bool>,
 ) -> Result<u32, String> {
     let attrs = WindowsFileAttributes {
         read_only,
@@ -177,12 +351,52 @@ pub async fn map_windows_to_linux_attrs(
         archive,
     };
     
-    let linux_perms = PermissionAgent::windows_to_linux(&attrs);
+    // The execute bit can only be chosen by file type when there's a file to look at
+    let linux_perms = match path {
+        Some(path) => PermissionAgent::windows_to_linux_for_path(&attrs, Path::new(&path), smart_execute_bit.unwrap_or(true)),
+        None => PermissionAgent::windows_to_linux(&attrs),
+    };
     let octal = PermissionAgent::linux_to_octal(&linux_perms);
     
     Ok(octal)
 }
 
+/// Preview a bulk permission mapping before transferring; `direction` is
+/// "windows_to_linux" or "linux_to_windows"
+#[tauri::command]
+pub async fn preview_permission_mapping(
+    entries: Vec<(String, SourcePermissions)>,
+    direction: String,
+) -> Result<Vec<PermissionMappingPreview>, String> {
+    let to_linux = match direction.as_str() {
+        "windows_to_linux" => true,
+        "linux_to_windows" => false,
+        _ => return Err("Invalid direction. Use 'windows_to_linux' or 'linux_to_windows'".to_string()),
+    };
+
+    Ok(entries.iter()
+        .map(|(path, source)| PermissionAgent::preview_mapping(path, source, to_linux))
+        .collect())
+}
+
+/// Replace the per-extension upload modes kept in settings
+#[tauri::command]
+pub async fn set_permission_profile(
+    app_handle: tauri::AppHandle,
+    by_extension: HashMap<String, u32>,
+    default_mode: Option<u32>,
+) -> Result<PermissionProfile, String> {
+    let profile = PermissionProfile::normalized(by_extension, default_mode)
+        .map_err(|e| e.to_string())?;
+    let patch = SettingsPatch {
+        permission_profile: Some(profile.clone()),
+        ..SettingsPatch::default()
+    };
+    settings::update(&app_handle, patch)
+        .map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
 #[tauri::command]
 pub async fn map_linux_to_windows_attrs(octal_permissions: u32) -> Result<WindowsFileAttributes, String> {
     let linux_perms = PermissionAgent::octal_to_linux(octal_permissions);
@@ -219,3 +433,51 @@ pub async fn preserve_file_timestamps(
     PermissionAgent::preserve_timestamps(source, dest)
         .map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file under the temp dir holding `contents`, removed when dropped
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("circle9-{}-{}", uuid::Uuid::new_v4(), name));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn shebang_scripts_are_executable() {
+        let script = TempFile::new("deploy", b"#!/bin/sh\necho hi\n");
+        assert!(PermissionAgent::looks_executable(&script.0));
+    }
+
+    #[test]
+    fn elf_binaries_are_executable() {
+        let binary = TempFile::new("server", b"\x7fELF\x02\x01\x01\x00");
+        assert!(PermissionAgent::looks_executable(&binary.0));
+    }
+
+    #[test]
+    fn plain_text_is_not_executable() {
+        let text = TempFile::new("notes", b"just some notes\n");
+        assert!(!PermissionAgent::looks_executable(&text.0));
+        let short = TempFile::new("tiny", b"#");
+        assert!(!PermissionAgent::looks_executable(&short.0));
+    }
+
+    #[test]
+    fn executable_extensions_need_no_content_check() {
+        assert!(PermissionAgent::looks_executable(Path::new("/nonexistent/build.SH")));
+        assert!(!PermissionAgent::looks_executable(Path::new("/nonexistent/readme")));
+    }
+}
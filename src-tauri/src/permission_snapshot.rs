@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{FileStat, FileType};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use chrono::{DateTime, Utc};
+use tauri::State;
+use uuid::Uuid;
+use crate::error::{Circle9Error, Result};
+use crate::remote_users::expand_tilde;
+use crate::remote_walk::{walk_remote, SkippedEntry};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionEntry {
+    pub path: String,
+    /// Permission bits including setuid, setgid and sticky
+    pub mode: u32,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionSnapshot {
+    pub id: String,
+    pub label: String,
+    pub connection_id: String,
+    pub root: String,
+    pub created_at: DateTime<Utc>,
+    /// Symlinks are left out, since chmod on one changes its target
+    pub entries: Vec<PermissionEntry>,
+    /// Directories that couldn't be read, so their contents aren't covered
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// A stored snapshot without its entries, for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionSnapshotInfo {
+    pub id: String,
+    pub label: String,
+    pub connection_id: String,
+    pub root: String,
+    pub created_at: DateTime<Utc>,
+    pub entry_count: usize,
+}
+
+impl From<&PermissionSnapshot> for PermissionSnapshotInfo {
+    fn from(snapshot: &PermissionSnapshot) -> Self {
+        Self {
+            id: snapshot.id.clone(),
+            label: snapshot.label.clone(),
+            connection_id: snapshot.connection_id.clone(),
+            root: snapshot.root.clone(),
+            created_at: snapshot.created_at,
+            entry_count: snapshot.entries.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub unchanged: usize,
+    /// Entries that are gone or couldn't be changed, e.g. a chown without root
+    pub failed: Vec<SkippedEntry>,
+}
+
+fn entry_for(path: &Path, stat: &FileStat) -> Option<PermissionEntry> {
+    if stat.file_type() == FileType::Symlink {
+        return None;
+    }
+    Some(PermissionEntry {
+        path: path.to_string_lossy().to_string(),
+        mode: stat.perm? & 0o7777,
+        uid: stat.uid,
+        gid: stat.gid,
+    })
+}
+
+/// Record the mode, owner and group of `root` and everything below it
+pub async fn take_snapshot(connection: &SSHConnection, connection_id: &str, root: &str, label: String) -> Result<PermissionSnapshot> {
+    let root_stat = connection.sftp()?.lstat(Path::new(root))?;
+    let mut entries: Vec<PermissionEntry> = entry_for(Path::new(root), &root_stat).into_iter().collect();
+
+    let outcome = walk_remote(connection, root, true, &AtomicBool::new(false), |path, stat| {
+        entries.extend(entry_for(path, stat));
+    }).await?;
+
+    Ok(PermissionSnapshot {
+        id: Uuid::new_v4().to_string(),
+        label,
+        connection_id: connection_id.to_string(),
+        root: root.to_string(),
+        created_at: Utc::now(),
+        entries,
+        skipped: outcome.skipped,
+    })
+}
+
+/// Put every entry back the way it was. Ownership goes first because chown clears setuid/setgid.
+pub fn restore_snapshot(connection: &SSHConnection, snapshot: &PermissionSnapshot) -> Result<RestoreReport> {
+    let mut report = RestoreReport::default();
+    let sftp = connection.sftp()?;
+
+    for entry in &snapshot.entries {
+        let path = Path::new(&entry.path);
+        let current = match sftp.lstat(path) {
+            Ok(stat) => stat,
+            Err(e) => {
+                report.failed.push(SkippedEntry { path: entry.path.clone(), reason: e.to_string() });
+                continue;
+            }
+        };
+
+        let owner_changed = (entry.uid.is_some() && current.uid != entry.uid)
+            || (entry.gid.is_some() && current.gid != entry.gid);
+        let mode_changed = current.perm.map(|p| p & 0o7777) != Some(entry.mode);
+        if !owner_changed && !mode_changed {
+            report.unchanged += 1;
+            continue;
+        }
+
+        if owner_changed {
+            let owner = FileStat { size: None, uid: entry.uid, gid: entry.gid, perm: None, atime: None, mtime: None };
+            if let Err(e) = sftp.setstat(path, owner) {
+                report.failed.push(SkippedEntry {
+                    path: entry.path.clone(),
+                    reason: format!("Could not restore owner: {}", e),
+                });
+                continue;
+            }
+        }
+        let mode = FileStat { size: None, uid: None, gid: None, perm: Some(entry.mode), atime: None, mtime: None };
+        match sftp.setstat(path, mode) {
+            Ok(()) => report.restored += 1,
+            Err(e) => report.failed.push(SkippedEntry { path: entry.path.clone(), reason: e.to_string() }),
+        }
+    }
+
+    if !report.failed.is_empty() {
+        tracing::warn!("Could not restore {} entries from snapshot {}", report.failed.len(), snapshot.id);
+    }
+    Ok(report)
+}
+
+fn snapshots_dir() -> Result<PathBuf> {
+    Ok(crate::utils::app_data_dir()?.join("permission_snapshots"))
+}
+
+fn snapshot_file(snapshot_id: &str) -> Result<PathBuf> {
+    // Ids are uuids; anything else could point outside the directory
+    if Uuid::parse_str(snapshot_id).is_err() {
+        return Err(Circle9Error::InvalidPath(format!("Invalid snapshot id {}", snapshot_id)));
+    }
+    Ok(snapshots_dir()?.join(format!("{}.json", snapshot_id)))
+}
+
+pub fn save_snapshot(snapshot: &PermissionSnapshot) -> Result<()> {
+    std::fs::create_dir_all(snapshots_dir()?)?;
+    std::fs::write(snapshot_file(&snapshot.id)?, serde_json::to_string(snapshot)?)?;
+    Ok(())
+}
+
+pub fn load_snapshot(snapshot_id: &str) -> Result<PermissionSnapshot> {
+    let path = snapshot_file(snapshot_id)?;
+    if !path.exists() {
+        return Err(Circle9Error::NotFound(format!("Snapshot {}", snapshot_id)));
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Stored snapshots, newest first; unreadable files are skipped
+pub fn list_snapshots() -> Result<Vec<PermissionSnapshotInfo>> {
+    let dir = snapshots_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let snapshot = std::fs::read_to_string(&path).ok()
+            .and_then(|contents| serde_json::from_str::<PermissionSnapshot>(&contents).ok());
+        match snapshot {
+            Some(snapshot) => snapshots.push(PermissionSnapshotInfo::from(&snapshot)),
+            None => tracing::warn!("Ignoring unreadable permission snapshot {}", path.display()),
+        }
+    }
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(snapshots)
+}
+
+// Tauri commands for permission snapshots
+
+#[tauri::command]
+pub async fn snapshot_permissions(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    root: String,
+    label: Option<String>,
+) -> Result<PermissionSnapshotInfo, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let root = expand_tilde(&connection, &root).map_err(|e| e.to_string())?;
+    let label = label.unwrap_or_else(|| format!("{} {}", root, Utc::now().format("%Y-%m-%d %H:%M")));
+
+    let snapshot = take_snapshot(&connection, &connection_id, &root, label).await
+        .map_err(|e| e.to_string())?;
+    save_snapshot(&snapshot).map_err(|e| e.to_string())?;
+    tracing::info!("Saved permission snapshot {} of {} ({} entries)", snapshot.id, root, snapshot.entries.len());
+    Ok(PermissionSnapshotInfo::from(&snapshot))
+}
+
+#[tauri::command]
+pub async fn list_permission_snapshots() -> Result<Vec<PermissionSnapshotInfo>, String> {
+    list_snapshots().map_err(|e| e.to_string())
+}
+
+/// Reapply a stored snapshot; it may be taken on another connection to the same host
+#[tauri::command]
+pub async fn restore_permissions(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    snapshot_id: String,
+) -> Result<RestoreReport, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let snapshot = load_snapshot(&snapshot_id).map_err(|e| e.to_string())?;
+
+    let report = restore_snapshot(&connection, &snapshot).map_err(|e| e.to_string())?;
+    ssh_client.listing_cache.clear_connection(&connection_id);
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn delete_permission_snapshot(snapshot_id: String) -> Result<(), String> {
+    std::fs::remove_file(snapshot_file(&snapshot_id).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Failed to delete snapshot {}: {}", snapshot_id, e))
+}
@@ -0,0 +1,104 @@
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket shared by every transfer on one connection.
+/// The bucket holds at most one second's worth of bytes, so a long idle spell
+/// doesn't let the next transfer burst far above the limit.
+pub struct RateLimiter {
+    state: Mutex<Bucket>,
+}
+
+struct Bucket {
+    bytes_per_sec: Option<u64>,
+    tokens: f64,
+    last_refill: Instant,
+    /// When a transfer last drew from the bucket, limited or not
+    last_acquire: Instant,
+}
+
+impl RateLimiter {
+    pub fn unlimited() -> Self {
+        Self {
+            state: Mutex::new(Bucket {
+                bytes_per_sec: None,
+                tokens: 0.0,
+                last_refill: Instant::now(),
+                last_acquire: Instant::now(),
+            }),
+        }
+    }
+
+    /// Current limit, or None when transfers aren't throttled
+    pub fn limit(&self) -> Option<u64> {
+        self.state.lock().ok().and_then(|bucket| bucket.bytes_per_sec)
+    }
+
+    /// Time since a transfer last moved data through this limiter. Every transfer loop draws
+    /// from it per chunk, so this shows a connection is busy even when nothing else touches it.
+    pub fn idle_for(&self) -> Duration {
+        self.state.lock()
+            .map(|bucket| bucket.last_acquire.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Change the limit; None or 0 removes it. Transfers already running pick it up on their next chunk.
+    pub fn set_limit(&self, bytes_per_sec: Option<u64>) {
+        if let Ok(mut bucket) = self.state.lock() {
+            bucket.bytes_per_sec = bytes_per_sec.filter(|&rate| rate > 0);
+            bucket.tokens = bucket.bytes_per_sec.unwrap_or(0) as f64;
+            bucket.last_refill = Instant::now();
+        }
+    }
+
+    /// Block until `bytes` may be sent. Chunks larger than the bucket are let through
+    /// by running it into debt, which the following callers then wait out.
+    pub fn acquire(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = match self.state.lock() {
+                Ok(bucket) => bucket,
+                Err(_) => return,
+            };
+            bucket.last_acquire = Instant::now();
+            let rate = match bucket.bytes_per_sec {
+                Some(rate) => rate as f64,
+                None => return,
+            };
+
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last_refill).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(rate);
+            bucket.last_refill = now;
+
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        };
+        std::thread::sleep(wait);
+    }
+}
+
+/// Writer that draws from a `RateLimiter` before each write
+pub struct ThrottledWriter<'a, W: Write> {
+    inner: W,
+    limiter: &'a RateLimiter,
+}
+
+impl<'a, W: Write> ThrottledWriter<'a, W> {
+    pub fn new(inner: W, limiter: &'a RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl<W: Write> Write for ThrottledWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.limiter.acquire(buf.len());
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
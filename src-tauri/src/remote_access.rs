@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{ErrorCode, FileType, OpenFlags, OpenType};
+use std::path::Path;
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::exec_command;
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// SFTP status codes worth spelling out to the user
+const SSH_FX_NO_SUCH_FILE: i32 = 2;
+const SSH_FX_PERMISSION_DENIED: i32 = 3;
+const SSH_FX_NO_SPACE_ON_FILESYSTEM: i32 = 14;
+const SSH_FX_WRITE_PROTECT: i32 = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteCheck {
+    pub dir: String,
+    pub writable: bool,
+    pub error: Option<String>,
+    /// Where files really land when `dir` is a symlink
+    pub symlink_target: Option<String>,
+    /// Worth confirming with the user, but not a reason to refuse
+    pub warning: Option<String>,
+}
+
+/// Create and remove a scratch file in `dir` to find out whether uploads there will succeed.
+/// A symlinked `dir` is reported with its target, since that's where the files will go.
+pub fn check_writable(connection: &SSHConnection, dir: &str) -> Result<WriteCheck> {
+    let probe = Path::new(dir).join(format!(".circle9-write-test-{}", uuid::Uuid::new_v4()));
+    let sftp = connection.sftp()?;
+
+    let is_symlink = sftp.lstat(Path::new(dir))
+        .map_or(false, |stat| stat.file_type() == FileType::Symlink);
+    let symlink_target = if is_symlink {
+        sftp.realpath(Path::new(dir)).ok()
+            .map(|target| target.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    let warning = symlink_target.as_ref()
+        .map(|target| format!("{} is a symlink; files will be written to {}", dir, target));
+
+    let created = sftp.open_mode(
+        &probe,
+        OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+        0o600,
+        OpenType::File,
+    );
+    let error = match created {
+        Ok(file) => {
+            drop(file);
+            if let Err(e) = sftp.unlink(&probe) {
+                tracing::warn!("Could not remove write probe {}: {}", probe.display(), e);
+            }
+            None
+        }
+        Err(e) => Some(describe_write_error(dir, &e)),
+    };
+
+    Ok(WriteCheck {
+        dir: dir.to_string(),
+        writable: error.is_none(),
+        error,
+        symlink_target,
+        warning,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveAccess {
+    pub path: String,
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    /// Which permission class applied: "root", "owner", "group" or "other"
+    pub via: String,
+    pub file_uid: u32,
+    pub file_gid: u32,
+    pub mode: u32,
+}
+
+/// The connected user's uid and all group ids, from `id` on the remote host
+fn remote_identity(connection: &SSHConnection) -> Result<(u32, Vec<u32>)> {
+    let output = exec_command(connection, "id -u && id -G")?;
+    if !output.success() {
+        return Err(Circle9Error::SSHError(format!("Could not run id: {}", output.stderr.trim())));
+    }
+    let mut lines = output.stdout.lines();
+    let uid = lines.next()
+        .and_then(|l| l.trim().parse().ok())
+        .ok_or_else(|| Circle9Error::SSHError("Unexpected output from id -u".to_string()))?;
+    let groups = lines.next()
+        .map(|l| l.split_whitespace().filter_map(|g| g.parse().ok()).collect())
+        .unwrap_or_default();
+    Ok((uid, groups))
+}
+
+/// What the connected user may do with `path`, from its owner, group and mode bits.
+/// ACLs and mount options such as `noexec` aren't taken into account.
+pub fn effective_access(connection: &SSHConnection, path: &str) -> Result<EffectiveAccess> {
+    let stat = connection.sftp()?.stat(Path::new(path))?;
+    let (uid, groups) = remote_identity(connection)?;
+
+    let mode = stat.perm.unwrap_or(0);
+    let file_uid = stat.uid.unwrap_or(u32::MAX);
+    let file_gid = stat.gid.unwrap_or(u32::MAX);
+
+    let (read, write, execute, via) = if uid == 0 {
+        // root ignores rwx except that execute on a file needs at least one x bit
+        let any_execute = stat.is_dir() || mode & 0o111 != 0;
+        (true, true, any_execute, "root")
+    } else {
+        let (bits, via) = if uid == file_uid {
+            ((mode >> 6) & 0o7, "owner")
+        } else if groups.contains(&file_gid) {
+            ((mode >> 3) & 0o7, "group")
+        } else {
+            (mode & 0o7, "other")
+        };
+        (bits & 0o4 != 0, bits & 0o2 != 0, bits & 0o1 != 0, via)
+    };
+
+    Ok(EffectiveAccess {
+        path: path.to_string(),
+        read,
+        write,
+        execute,
+        via: via.to_string(),
+        file_uid,
+        file_gid,
+        mode: mode & 0o7777,
+    })
+}
+
+fn describe_write_error(dir: &str, error: &ssh2::Error) -> String {
+    match error.code() {
+        ErrorCode::SFTP(SSH_FX_PERMISSION_DENIED) => format!("Permission denied on target directory {}", dir),
+        ErrorCode::SFTP(SSH_FX_NO_SUCH_FILE) => format!("Target directory {} does not exist", dir),
+        ErrorCode::SFTP(SSH_FX_WRITE_PROTECT) => format!("Target directory {} is on a read-only filesystem", dir),
+        ErrorCode::SFTP(SSH_FX_NO_SPACE_ON_FILESYSTEM) => format!("No space left for {}", dir),
+        _ => format!("Cannot write to {}: {}", dir, error),
+    }
+}
+
+// Tauri commands for remote access checks
+
+#[tauri::command]
+pub async fn get_effective_access(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<EffectiveAccess, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path)
+        .map_err(|e| e.to_string())?;
+
+    effective_access(&connection, &path)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn check_remote_writable(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    dir: String,
+) -> Result<WriteCheck, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let dir = expand_tilde(&connection, &dir)
+        .map_err(|e| e.to_string())?;
+
+    check_writable(&connection, &dir)
+        .map_err(|e| e.to_string())
+}
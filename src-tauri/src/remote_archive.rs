@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Component, Path};
+use std::time::Duration;
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_env::command_available;
+use crate::remote_exec::{exec_command_dedicated, shell_quote, CommandOutput, COMMAND_NOT_FOUND};
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Listing reads the whole of a compressed archive
+const LIST_TIMEOUT: Duration = Duration::from_secs(300);
+const EXTRACT_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArchiveKind {
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveKind {
+    fn from_extension(path: &str) -> Option<Self> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if lower.ends_with(".tar.bz2") || lower.ends_with(".tbz2") {
+            Some(Self::TarBz2)
+        } else if lower.ends_with(".tar.xz") || lower.ends_with(".txz") {
+            Some(Self::TarXz)
+        } else if lower.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// Recognise an archive by its leading bytes. A bare gzip/bzip2/xz stream is taken to
+    /// wrap a tar, which tar itself will reject if it doesn't.
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if header.starts_with(b"BZh") {
+            Some(Self::TarBz2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::TarXz)
+        } else if header.len() >= 262 && &header[257..262] == b"ustar" {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    fn tool(self) -> &'static str {
+        match self {
+            Self::Zip => "unzip",
+            _ => "tar",
+        }
+    }
+
+    fn tar_flag(self) -> &'static str {
+        match self {
+            Self::TarGz => "z",
+            Self::TarBz2 => "j",
+            Self::TarXz => "J",
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size; None when the tool's output couldn't be parsed
+    pub size: Option<u64>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveListing {
+    pub path: String,
+    pub kind: ArchiveKind,
+    pub entries: Vec<ArchiveEntry>,
+    pub total_size: u64,
+}
+
+/// Work out the archive type from the name, falling back to the first bytes of the file
+pub fn detect_kind(connection: &SSHConnection, path: &str) -> Result<ArchiveKind> {
+    if let Some(kind) = ArchiveKind::from_extension(path) {
+        return Ok(kind);
+    }
+    let mut header = Vec::with_capacity(262);
+    connection.sftp()?
+        .open(Path::new(path))?
+        .take(262)
+        .read_to_end(&mut header)?;
+    ArchiveKind::from_magic(&header)
+        .ok_or_else(|| Circle9Error::InvalidPath(format!("{} is not a tar or zip archive", path)))
+}
+
+/// Fail early, naming the tool, when the server doesn't have what `kind` needs
+fn require_tool(connection: &SSHConnection, kind: ArchiveKind) -> Result<()> {
+    if !command_available(connection, kind.tool())? {
+        return Err(Circle9Error::SSHError(format!(
+            "{} is not installed on the remote host", kind.tool()
+        )));
+    }
+    Ok(())
+}
+
+/// Turn a failed run into an error, calling out a missing tool explicitly
+fn check_output(output: CommandOutput, kind: ArchiveKind, action: &str) -> Result<CommandOutput> {
+    if output.exit_status == COMMAND_NOT_FOUND {
+        return Err(Circle9Error::SSHError(format!(
+            "{} is not installed on the remote host", kind.tool()
+        )));
+    }
+    if !output.success() {
+        return Err(Circle9Error::SSHError(format!(
+            "Failed to {} archive (status {}): {}", action, output.exit_status, output.stderr.trim()
+        )));
+    }
+    Ok(output)
+}
+
+/// The first `count` whitespace-separated fields of `line` and the rest of it, which may
+/// itself contain spaces
+fn split_fields(line: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = line.trim_start();
+    for _ in 0..count {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest))
+}
+
+/// Parse a GNU `tar -tv` line: `-rw-r--r-- user/group 1234 2024-01-01 12:00 name`
+fn parse_tar_line(line: &str) -> ArchiveEntry {
+    let parsed = match split_fields(line, 5) {
+        Some((fields, name)) if !name.is_empty() => fields[2].parse().ok().map(|size| {
+            let mode = fields[0];
+            // Links are listed as "name -> target" / "name link to target"
+            let name = name.split(" -> ").next().unwrap_or(name);
+            let name = name.split(" link to ").next().unwrap_or(name);
+            ArchiveEntry { name: name.to_string(), size: Some(size), is_dir: mode.starts_with('d') }
+        }),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| ArchiveEntry {
+        name: line.to_string(),
+        size: None,
+        is_dir: line.ends_with('/'),
+    })
+}
+
+/// Parse the entry lines of `unzip -l`, which sit between two dashed rules:
+/// `     1234  2024-01-01 12:00   name`
+fn parse_unzip_listing(stdout: &str) -> Vec<ArchiveEntry> {
+    stdout.lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with("---"))
+        .filter_map(|line| {
+            let (fields, name) = split_fields(line, 3)?;
+            let size = fields[0].parse().ok()?;
+            Some(ArchiveEntry { is_dir: name.ends_with('/'), name: name.to_string(), size: Some(size) })
+        })
+        .collect()
+}
+
+pub async fn list_archive(connection: &SSHConnection, path: &str) -> Result<ArchiveListing> {
+    let kind = detect_kind(connection, path)?;
+    require_tool(connection, kind)?;
+    let command = match kind {
+        ArchiveKind::Zip => format!("LC_ALL=C unzip -l {}", shell_quote(path)),
+        _ => format!("LC_ALL=C tar -tv{}f {}", kind.tar_flag(), shell_quote(path)),
+    };
+    let output = check_output(exec_command_dedicated(connection, &command, LIST_TIMEOUT).await?, kind, "list")?;
+
+    let entries: Vec<ArchiveEntry> = match kind {
+        ArchiveKind::Zip => parse_unzip_listing(&output.stdout),
+        _ => output.stdout.lines().filter(|l| !l.is_empty()).map(parse_tar_line).collect(),
+    };
+    Ok(ArchiveListing {
+        path: path.to_string(),
+        kind,
+        total_size: entries.iter().filter_map(|e| e.size).sum(),
+        entries,
+    })
+}
+
+/// Extract one entry of the archive at `path` into `dest_dir` on the server, returning
+/// where it was written
+pub async fn extract_entry(connection: &SSHConnection, path: &str, entry: &str, dest_dir: &str) -> Result<String> {
+    let entry_path = Path::new(entry);
+    if entry.is_empty() || entry_path.components().any(|c| matches!(c, Component::ParentDir | Component::RootDir)) {
+        return Err(Circle9Error::InvalidPath(format!("Refusing to extract {}", entry)));
+    }
+
+    let kind = detect_kind(connection, path)?;
+    require_tool(connection, kind)?;
+    let command = match kind {
+        ArchiveKind::Zip => format!(
+            "unzip -o -q {} {} -d {}",
+            shell_quote(path), shell_quote(entry), shell_quote(dest_dir)
+        ),
+        _ => format!(
+            "tar -x{}f {} -C {} -- {}",
+            kind.tar_flag(), shell_quote(path), shell_quote(dest_dir), shell_quote(entry)
+        ),
+    };
+    check_output(exec_command_dedicated(connection, &command, EXTRACT_TIMEOUT).await?, kind, "extract from")?;
+    Ok(Path::new(dest_dir).join(entry_path).to_string_lossy().to_string())
+}
+
+// Tauri commands for remote archives
+
+/// List a tar or zip archive on the server without downloading it
+#[tauri::command]
+pub async fn list_remote_archive(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<ArchiveListing, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path).map_err(|e| e.to_string())?;
+
+    list_archive(&connection, &path).await.map_err(|e| e.to_string())
+}
+
+/// Extract a single entry next to the archive, or into `dest_dir`
+#[tauri::command]
+pub async fn extract_remote_archive_entry(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+    entry: String,
+    dest_dir: Option<String>,
+) -> Result<String, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path).map_err(|e| e.to_string())?;
+    let dest_dir = match dest_dir {
+        Some(dest_dir) => expand_tilde(&connection, &dest_dir).map_err(|e| e.to_string())?,
+        None => Path::new(&path).parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .ok_or("Archive has no parent directory")?,
+    };
+
+    let extracted = extract_entry(&connection, &path, &entry, &dest_dir).await.map_err(|e| e.to_string())?;
+    ssh_client.listing_cache.invalidate(&connection_id, &dest_dir);
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &extracted);
+    Ok(extracted)
+}
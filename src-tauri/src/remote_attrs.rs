@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_env::command_available;
+use crate::remote_exec::{exec_command, shell_quote, CommandOutput, COMMAND_NOT_FOUND};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub default: bool,
+    pub tag: String, // "user", "group", "mask" or "other"
+    pub qualifier: Option<String>,
+    pub permissions: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtendedAttribute {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteExtendedAttributes {
+    pub path: String,
+    pub acl: Vec<AclEntry>,
+    pub xattrs: Vec<ExtendedAttribute>,
+    pub acl_available: bool,
+    pub xattrs_available: bool,
+    pub warnings: Vec<String>,
+}
+
+/// Fetch POSIX ACLs and extended attributes with getfacl/getfattr
+pub fn get_extended_attributes(connection: &SSHConnection, path: &str) -> Result<RemoteExtendedAttributes> {
+    let mut result = RemoteExtendedAttributes {
+        path: path.to_string(),
+        acl: Vec::new(),
+        xattrs: Vec::new(),
+        acl_available: false,
+        xattrs_available: false,
+        warnings: Vec::new(),
+    };
+
+    if command_available(connection, "getfacl")? {
+        let acl_output = exec_command(connection, &format!("getfacl --absolute-names {}", shell_quote(path)))?;
+        match unavailable_reason("getfacl", &acl_output) {
+            Some(reason) => result.warnings.push(reason),
+            None => {
+                result.acl_available = true;
+                result.acl = parse_getfacl(&acl_output.stdout);
+            }
+        }
+    } else {
+        result.warnings.push(not_installed("getfacl"));
+    }
+
+    if command_available(connection, "getfattr")? {
+        let xattr_output = exec_command(connection, &format!("getfattr -d -m - --absolute-names {}", shell_quote(path)))?;
+        match unavailable_reason("getfattr", &xattr_output) {
+            Some(reason) => result.warnings.push(reason),
+            None => {
+                result.xattrs_available = true;
+                result.xattrs = parse_getfattr(&xattr_output.stdout);
+            }
+        }
+    } else {
+        result.warnings.push(not_installed("getfattr"));
+    }
+
+    Ok(result)
+}
+
+/// Set a single extended attribute with setfattr
+pub fn set_extended_attribute(connection: &SSHConnection, path: &str, name: &str, value: &str) -> Result<()> {
+    if !command_available(connection, "setfattr")? {
+        return Err(Circle9Error::SSHError(not_installed("setfattr")));
+    }
+    let output = exec_command(connection, &format!(
+        "setfattr -n {} -v {} {}",
+        shell_quote(name),
+        shell_quote(value),
+        shell_quote(path)
+    ))?;
+
+    match unavailable_reason("setfattr", &output) {
+        Some(reason) => Err(Circle9Error::SSHError(reason)),
+        None => Ok(()),
+    }
+}
+
+fn not_installed(tool: &str) -> String {
+    format!("{} is not installed on the remote host", tool)
+}
+
+/// Explain why a tool's output can't be used, if it can't
+fn unavailable_reason(tool: &str, output: &CommandOutput) -> Option<String> {
+    if output.exit_status == COMMAND_NOT_FOUND {
+        return Some(not_installed(tool));
+    }
+    if output.stderr.contains("Operation not supported") {
+        return Some(format!("The remote filesystem does not support {}", tool));
+    }
+    if !output.success() {
+        return Some(format!("{} failed: {}", tool, output.stderr.trim()));
+    }
+    None
+}
+
+fn parse_getfacl(output: &str) -> Vec<AclEntry> {
+    output.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            // Drop trailing "#effective:r--" annotations
+            let line = line.split_whitespace().next()?;
+            let (default, line) = match line.strip_prefix("default:") {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+
+            let mut parts = line.splitn(3, ':');
+            let tag = parts.next()?.to_string();
+            let qualifier = parts.next()?;
+            let permissions = parts.next()?.to_string();
+
+            Some(AclEntry {
+                default,
+                tag,
+                qualifier: if qualifier.is_empty() { None } else { Some(qualifier.to_string()) },
+                permissions,
+            })
+        })
+        .collect()
+}
+
+fn parse_getfattr(output: &str) -> Vec<ExtendedAttribute> {
+    output.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, value) = line.split_once('=').unwrap_or((line, ""));
+            ExtendedAttribute {
+                name: name.to_string(),
+                value: value.trim_matches('"').to_string(),
+            }
+        })
+        .collect()
+}
+
+// Tauri commands for extended attributes
+
+#[tauri::command]
+pub async fn get_linux_xattrs(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<RemoteExtendedAttributes, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    get_extended_attributes(&connection, &path)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_linux_xattr(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+    name: String,
+    value: String,
+) -> Result<(), String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    set_extended_attribute(&connection, &path, &name, &value)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use ssh2::Session;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use crate::background_jobs::{self, BackgroundJobKind};
+use crate::error::{Circle9Error, Result};
+use crate::remote_env::{environment, RemoteEnvironment};
+use crate::remote_exec::shell_quote;
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::SSHClient;
+
+/// How long the exec loop sleeps when the tool hasn't printed anything yet
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const READ_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    /// The first tool the server has that prints this digest as the first field
+    fn remote_command(self, environment: &RemoteEnvironment) -> Option<&'static str> {
+        let candidates: &[(&str, &'static str)] = match self {
+            Self::Md5 => &[("md5sum", "md5sum"), ("md5", "md5 -q")],
+            Self::Sha1 => &[("sha1sum", "sha1sum"), ("shasum", "shasum -a 1")],
+            Self::Sha256 => return environment.sha256_command(),
+            Self::Sha512 => &[("sha512sum", "sha512sum"), ("shasum", "shasum -a 512")],
+        };
+        candidates.iter()
+            .find(|(tool, _)| environment.has_tool(tool))
+            .map(|(_, command)| *command)
+    }
+
+    fn expected_len(self) -> usize {
+        match self {
+            Self::Md5 => 32,
+            Self::Sha1 => 40,
+            Self::Sha256 => 64,
+            Self::Sha512 => 128,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteChecksum {
+    pub job_id: String,
+    pub path: String,
+    pub algorithm: ChecksumAlgorithm,
+    /// Lowercase hex; None when cancelled
+    pub digest: Option<String>,
+    /// Hashed by a tool on the server rather than by reading the file over SFTP
+    pub computed_remotely: bool,
+    pub cancelled: bool,
+}
+
+/// Run the hashing tool with the file on stdin, so odd names can't change its output,
+/// polling so a cancel closes the channel. Returns None when cancelled.
+fn exec_digest(session: &Session, command: &str, path: &str, algorithm: ChecksumAlgorithm, cancel: &AtomicBool) -> Result<Option<String>> {
+    let mut channel = session.channel_session()?;
+    channel.exec(&format!("{} < {}", command, shell_quote(path)))?;
+
+    let mut stdout = Vec::new();
+    let mut buffer = [0u8; 4096];
+    session.set_blocking(false);
+    let outcome = loop {
+        if cancel.load(Ordering::SeqCst) {
+            break None;
+        }
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break Some(()),
+            Ok(0) => std::thread::sleep(POLL_INTERVAL),
+            Ok(n) => stdout.extend_from_slice(&buffer[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                session.set_blocking(true);
+                return Err(e.into());
+            }
+        }
+    };
+    session.set_blocking(true);
+    if outcome.is_none() {
+        channel.close()?;
+        return Ok(None);
+    }
+
+    let mut stderr = String::new();
+    channel.stderr().read_to_string(&mut stderr)?;
+    channel.wait_close()?;
+    let status = channel.exit_status()?;
+    let digest = String::from_utf8_lossy(&stdout)
+        .split_whitespace()
+        .next()
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    if status != 0 || digest.len() != algorithm.expected_len() {
+        return Err(Circle9Error::SSHError(format!("Checksum failed (status {}): {}", status, stderr.trim())));
+    }
+    Ok(Some(digest))
+}
+
+fn read_digest<D: Digest>(reader: &mut dyn Read, cancel: &AtomicBool) -> Result<Option<String>> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()))
+}
+
+/// Hash the file locally as it streams in over SFTP, for servers without the tool
+fn sftp_digest(session: &Session, path: &str, algorithm: ChecksumAlgorithm, cancel: &AtomicBool) -> Result<Option<String>> {
+    let mut file = session.sftp()?.open(Path::new(path))?;
+    match algorithm {
+        ChecksumAlgorithm::Md5 => read_digest::<md5::Md5>(&mut file, cancel),
+        ChecksumAlgorithm::Sha1 => read_digest::<sha1::Sha1>(&mut file, cancel),
+        ChecksumAlgorithm::Sha256 => read_digest::<sha2::Sha256>(&mut file, cancel),
+        ChecksumAlgorithm::Sha512 => read_digest::<sha2::Sha512>(&mut file, cancel),
+    }
+}
+
+// Tauri commands for remote checksums
+
+/// Digest of a remote file, computed on the server when it has a tool for the algorithm and
+/// by streaming the file over SFTP otherwise. Runs as a background job on its own session,
+/// so `cancel_background_job(job_id)` stops it.
+#[tauri::command]
+pub async fn remote_file_checksum(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+    algorithm: Option<ChecksumAlgorithm>,
+    job_id: Option<String>,
+) -> Result<RemoteChecksum, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path).map_err(|e| e.to_string())?;
+    let algorithm = algorithm.unwrap_or(ChecksumAlgorithm::Sha256);
+    let job_id = job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let command = environment(&connection).ok()
+        .and_then(|environment| algorithm.remote_command(&environment));
+    let session = SSHClient::open_dedicated_session(&connection.config).await
+        .map_err(|e| e.to_string())?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let job_cancel = Arc::clone(&cancel);
+    background_jobs::register(&job_id, BackgroundJobKind::Checksum, &path, Some(&connection_id), move || {
+        job_cancel.store(true, Ordering::SeqCst);
+    });
+
+    let target = path.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let digest = match command {
+            Some(command) => exec_digest(&session, command, &target, algorithm, &cancel),
+            None => sftp_digest(&session, &target, algorithm, &cancel),
+        };
+        let _ = session.disconnect(None, "Checksum finished", None);
+        digest
+    }).await;
+    background_jobs::unregister(&job_id);
+
+    let digest = result.map_err(|e| e.to_string())?.map_err(|e| e.to_string())?;
+    Ok(RemoteChecksum {
+        job_id,
+        path,
+        algorithm,
+        cancelled: digest.is_none(),
+        digest,
+        computed_remotely: command.is_some(),
+    })
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use tauri::{AppHandle, Manager, State};
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::exec_command;
+use crate::settings;
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockSkew {
+    pub connection_id: String,
+    pub remote_time: DateTime<Utc>,
+    pub local_time: DateTime<Utc>,
+    /// Remote minus local, in seconds; positive when the server is ahead
+    pub skew_secs: i64,
+    /// Skew is past `clock_skew_warning_secs`, which usually means the server's clock is misconfigured
+    pub excessive: bool,
+}
+
+/// Read the server's clock with `date` and compare it with ours, taking the local time
+/// halfway through the round-trip
+pub fn measure_skew(connection: &SSHConnection) -> Result<i64> {
+    let before = Utc::now();
+    let output = exec_command(connection, "date -u +%s")?;
+    let after = Utc::now();
+    if !output.success() {
+        return Err(Circle9Error::SSHError(format!("date failed: {}", output.stderr.trim())));
+    }
+
+    let remote_secs: i64 = output.stdout.trim().parse()
+        .map_err(|_| Circle9Error::SSHError(format!("Unexpected date output: {}", output.stdout.trim())))?;
+    let local_time = before + (after - before) / 2;
+    Ok(remote_secs - local_time.timestamp())
+}
+
+/// The connection's skew, measured on first use. A server whose clock can't be read counts as in sync.
+pub fn clock_skew(connection: &SSHConnection) -> i64 {
+    if let Ok(Some(skew)) = lock_or_error(&connection.clock_skew).map(|s| *s) {
+        return skew;
+    }
+    let skew = measure_skew(connection)
+        .map_err(|e| tracing::warn!("Could not read remote clock: {}", e))
+        .unwrap_or(0);
+    if let Ok(mut stored) = lock_or_error(&connection.clock_skew) {
+        *stored = Some(skew);
+    }
+    skew
+}
+
+pub fn is_excessive(skew_secs: i64) -> bool {
+    skew_secs.unsigned_abs() > settings::current().clock_skew_warning_secs
+}
+
+/// Whether a local and a remote mtime (epoch seconds) describe the same moment once the
+/// server's skew is taken out, allowing for `tolerance_secs` of slack
+pub fn mtimes_match(local: Option<u64>, remote: Option<u64>, skew_secs: i64, tolerance_secs: u64) -> bool {
+    match (local, remote) {
+        (Some(local), Some(remote)) => {
+            let adjusted = remote as i64 - skew_secs;
+            (adjusted - local as i64).unsigned_abs() <= tolerance_secs
+        }
+        (local, remote) => local == remote,
+    }
+}
+
+/// Emit `clock_skew_warning` when the skew is large enough to distrust timestamp comparisons
+pub fn warn_if_excessive(app_handle: &AppHandle, connection_id: &str, skew_secs: i64) {
+    if !is_excessive(skew_secs) {
+        return;
+    }
+    tracing::warn!("Clock on {} is off by {} seconds", connection_id, skew_secs);
+    let local_time = Utc::now();
+    let skew = ClockSkew {
+        connection_id: connection_id.to_string(),
+        remote_time: local_time + Duration::seconds(skew_secs),
+        local_time,
+        skew_secs,
+        excessive: true,
+    };
+    if let Err(e) = app_handle.emit_all("clock_skew_warning", &skew) {
+        tracing::error!("Failed to emit clock skew warning: {}", e);
+    }
+}
+
+// Tauri commands for the remote clock
+
+/// Measure the server's time afresh and store the skew on the connection
+#[tauri::command]
+pub async fn get_remote_time(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+) -> Result<ClockSkew, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    let skew_secs = measure_skew(&connection).map_err(|e| e.to_string())?;
+    *lock_or_error(&connection.clock_skew).map_err(|e| e.to_string())? = Some(skew_secs);
+    warn_if_excessive(&app_handle, &connection_id, skew_secs);
+
+    let local_time = Utc::now();
+    Ok(ClockSkew {
+        connection_id,
+        remote_time: local_time + Duration::seconds(skew_secs),
+        local_time,
+        skew_secs,
+        excessive: is_excessive(skew_secs),
+    })
+}
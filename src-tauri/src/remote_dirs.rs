@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{OpenFlags, OpenType, Sftp};
+use std::path::{Path, PathBuf};
+use crate::error::{Circle9Error, Result};
+use crate::remote_walk::SkippedEntry;
+use crate::settings;
+use crate::ssh_client::SSHConnection;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirCreationReport {
+    pub created: usize,
+    pub existing: usize,
+    /// Directories that couldn't be created, including those below a failed parent
+    pub failed: Vec<SkippedEntry>,
+}
+
+impl DirCreationReport {
+    /// Whether files destined for `remote_dir` can't be written because it wasn't created
+    pub fn is_failed(&self, remote_dir: &Path) -> bool {
+        self.failed.iter().any(|f| Path::new(&f.path) == remote_dir)
+    }
+}
+
+/// Every remote directory a recursive upload of `local_root` into `remote_root` needs,
+/// parents before children, including empty ones
+pub fn plan_remote_dirs(local_root: &Path, remote_root: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![remote_root.to_path_buf()];
+    let mut pending = vec![(local_root.to_path_buf(), remote_root.to_path_buf())];
+
+    // Breadth-first, so each directory comes after its parent
+    let mut index = 0;
+    while index < pending.len() {
+        let (local_dir, remote_dir) = pending[index].clone();
+        index += 1;
+        for entry in std::fs::read_dir(&local_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let remote_child = remote_dir.join(entry.file_name());
+                dirs.push(remote_child.clone());
+                pending.push((entry.path(), remote_child));
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+/// Create whatever is missing of the directories above `path`, like `mkdir -p` on its parent
+pub fn ensure_parent_dirs(connection: &SSHConnection, path: &Path) -> Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => return Ok(()),
+    };
+    if connection.sftp()?.stat(parent).map_or(false, |stat| stat.is_dir()) {
+        return Ok(());
+    }
+    let mut dirs: Vec<PathBuf> = parent.ancestors()
+        .filter(|dir| dir.parent().is_some() && !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect();
+    dirs.reverse();
+    let report = create_remote_dirs(connection, &dirs)?;
+    match report.failed.first() {
+        Some(failed) => Err(Circle9Error::TransferError(format!("Could not create {}: {}", failed.path, failed.reason))),
+        None => Ok(()),
+    }
+}
+
+/// Create or truncate a remote file, with the mode the settings' umask allows
+pub fn create_remote_file(sftp: &Sftp, path: &Path) -> std::result::Result<ssh2::File, ssh2::Error> {
+    sftp.open_mode(
+        path,
+        OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+        settings::current().file_mode(),
+        OpenType::File,
+    )
+}
+
+/// Create `dirs` (ordered parents first) in one pass under a single SFTP lock.
+/// A directory whose parent failed is reported without another round-trip.
+pub fn create_remote_dirs(connection: &SSHConnection, dirs: &[PathBuf]) -> Result<DirCreationReport> {
+    let mut report = DirCreationReport::default();
+    let sftp = connection.sftp()?;
+    let mode = settings::current().dir_mode();
+
+    for dir in dirs {
+        if let Some(parent) = dir.parent().filter(|p| report.is_failed(p)) {
+            report.failed.push(SkippedEntry {
+                path: dir.to_string_lossy().to_string(),
+                reason: format!("Parent directory {} could not be created", parent.display()),
+            });
+            continue;
+        }
+
+        match sftp.mkdir(dir, mode) {
+            Ok(()) => report.created += 1,
+            // mkdir fails on an existing path, so only then pay for a stat
+            Err(e) => match sftp.stat(dir) {
+                Ok(stat) if stat.is_dir() => report.existing += 1,
+                Ok(_) => report.failed.push(SkippedEntry {
+                    path: dir.to_string_lossy().to_string(),
+                    reason: "A file with this name already exists".to_string(),
+                }),
+                Err(_) => report.failed.push(SkippedEntry {
+                    path: dir.to_string_lossy().to_string(),
+                    reason: e.to_string(),
+                }),
+            },
+        }
+    }
+
+    if !report.failed.is_empty() {
+        tracing::warn!("Could not create {} remote directories", report.failed.len());
+    }
+    Ok(report)
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::{exec_command, shell_quote};
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+/// Tools probed for with `command -v`
+const PROBED_TOOLS: &[&str] = &[
+    "tar", "sha256sum", "shasum", "sha256", "md5sum", "md5", "sha1sum", "sha512sum",
+    "getfacl", "getfattr", "findmnt", "df", "stat", "python3",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RemoteOs {
+    Linux,
+    MacOs,
+    FreeBsd,
+    OpenBsd,
+    NetBsd,
+    Other(String),
+}
+
+/// Which `stat` option syntax the remote host understands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatFlavor {
+    /// GNU coreutils and BusyBox: `stat -c FORMAT`
+    Gnu,
+    /// BSD and macOS: `stat -f FORMAT`
+    Bsd,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteEnvironment {
+    pub uname: String,
+    pub os: RemoteOs,
+    pub busybox: bool,
+    pub shell: Option<String>,
+    pub stat_flavor: StatFlavor,
+    pub available_tools: Vec<String>,
+}
+
+impl RemoteEnvironment {
+    pub fn has_tool(&self, tool: &str) -> bool {
+        self.available_tools.iter().any(|t| t == tool)
+    }
+
+    /// Command printing the size in bytes of `path`
+    pub fn file_size_command(&self, path: &str) -> String {
+        match self.stat_flavor {
+            StatFlavor::Gnu => format!("stat -c %s {}", shell_quote(path)),
+            StatFlavor::Bsd => format!("stat -f %z {}", shell_quote(path)),
+        }
+    }
+
+    /// Filter that reads stdin and prints its SHA-256 as the first word of the output
+    pub fn sha256_command(&self) -> Option<&'static str> {
+        if self.has_tool("sha256sum") {
+            Some("sha256sum")
+        } else if self.has_tool("shasum") {
+            Some("shasum -a 256")
+        } else if self.has_tool("sha256") {
+            // BSD sha256 prints the digest alone with -q
+            Some("sha256 -q")
+        } else {
+            None
+        }
+    }
+}
+
+/// Probe the remote OS, shell and tools in a single exec round-trip
+pub fn detect_environment(connection: &SSHConnection) -> Result<RemoteEnvironment> {
+    let script = format!(
+        "uname -a; \
+         echo \"shell=$SHELL\"; \
+         for t in {tools}; do command -v $t >/dev/null 2>&1 && echo \"tool=$t\"; done; \
+         if stat -c %s / >/dev/null 2>&1; then echo stat=gnu; else echo stat=bsd; fi; \
+         ls --help 2>&1 | grep -q BusyBox && echo busybox=1; \
+         true",
+        tools = PROBED_TOOLS.join(" "),
+    );
+
+    let output = exec_command(connection, &script)?;
+    let mut lines = output.stdout.lines();
+    let uname = lines.next()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .ok_or_else(|| Circle9Error::SSHError(format!(
+            "Could not identify remote system: {}", output.stderr.trim()
+        )))?
+        .to_string();
+
+    let mut environment = RemoteEnvironment {
+        os: parse_os(&uname),
+        uname,
+        busybox: false,
+        shell: None,
+        stat_flavor: StatFlavor::Gnu,
+        available_tools: Vec::new(),
+    };
+
+    for line in lines {
+        match line.split_once('=') {
+            Some(("shell", shell)) if !shell.is_empty() => environment.shell = Some(shell.to_string()),
+            Some(("tool", tool)) => environment.available_tools.push(tool.to_string()),
+            Some(("stat", "bsd")) => environment.stat_flavor = StatFlavor::Bsd,
+            Some(("busybox", _)) => environment.busybox = true,
+            _ => {}
+        }
+    }
+
+    Ok(environment)
+}
+
+/// Whether `command` can be run on the remote host. Tools in the environment probe are
+/// answered from it; anything else is checked with `command -v` once per connection.
+pub fn command_available(connection: &SSHConnection, command: &str) -> Result<bool> {
+    let valid = !command.is_empty()
+        && command.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+'));
+    if !valid {
+        return Err(Circle9Error::InvalidPath(format!("{} is not a command name", command)));
+    }
+    if PROBED_TOOLS.contains(&command) {
+        return Ok(environment(connection)?.has_tool(command));
+    }
+    if let Some(available) = lock_or_error(&connection.available_commands)?.get(command) {
+        return Ok(*available);
+    }
+
+    let output = exec_command(connection, &format!("command -v {} >/dev/null 2>&1", shell_quote(command)))?;
+    let available = output.success();
+    lock_or_error(&connection.available_commands)?.insert(command.to_string(), available);
+    Ok(available)
+}
+
+/// The connection's cached environment, detecting it on first use
+pub fn environment(connection: &SSHConnection) -> Result<RemoteEnvironment> {
+    if let Some(environment) = lock_or_error(&connection.environment)?.clone() {
+        return Ok(environment);
+    }
+
+    let environment = detect_environment(connection)?;
+    tracing::info!("Detected remote environment: {}", environment.uname);
+    *lock_or_error(&connection.environment)? = Some(environment.clone());
+    Ok(environment)
+}
+
+fn parse_os(uname: &str) -> RemoteOs {
+    match uname.split_whitespace().next().unwrap_or("") {
+        "Linux" => RemoteOs::Linux,
+        "Darwin" => RemoteOs::MacOs,
+        "FreeBSD" => RemoteOs::FreeBsd,
+        "OpenBSD" => RemoteOs::OpenBsd,
+        "NetBSD" => RemoteOs::NetBsd,
+        other => RemoteOs::Other(other.to_string()),
+    }
+}
+
+// Tauri commands for remote environment detection
+
+#[tauri::command]
+pub async fn detect_remote_environment(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    refresh: Option<bool>,
+) -> Result<RemoteEnvironment, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    if refresh.unwrap_or(false) {
+        let environment = detect_environment(&connection).map_err(|e| e.to_string())?;
+        *lock_or_error(&connection.environment).map_err(|e| e.to_string())? = Some(environment.clone());
+        lock_or_error(&connection.available_commands).map_err(|e| e.to_string())?.clear();
+        return Ok(environment);
+    }
+
+    environment(&connection)
+        .map_err(|e| e.to_string())
+}
+
+/// Whether a tool is installed on the server, so the UI can enable features per connection
+#[tauri::command]
+pub async fn check_remote_command_available(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    command: String,
+) -> Result<bool, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    command_available(&connection, &command)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,454 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use ssh2::{Channel, Session};
+use tauri::{AppHandle, Manager, State};
+use crate::background_jobs::{self, BackgroundJobKind};
+use crate::error::{Circle9Error, Result};
+use crate::settings;
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+/// Exit status a POSIX shell uses when a command is not found
+pub const COMMAND_NOT_FOUND: i32 = 127;
+
+/// libssh2's error code for a blocking call that exceeded the session timeout
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+
+/// Patterns refused when a command is run in safe mode. This is a guard against slips, not a
+/// sandbox: variables, aliases, `eval` or a script file get past it.
+const DANGEROUS_PATTERNS: &[&str] = &[
+    "rm -rf /",
+    "rm -fr /",
+    "mkfs",
+    "dd if=",
+    "> /dev/sd",
+    ":(){",
+    "shutdown",
+    "reboot",
+    "halt",
+    "poweroff",
+    "chmod -r 777 /",
+];
+
+/// How long the streaming loop sleeps when neither stream has data
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: i32,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_status == 0
+    }
+}
+
+/// Run a command on an exec channel and collect its output
+pub fn exec_command(connection: &SSHConnection, command: &str) -> Result<CommandOutput> {
+    exec_command_with_timeout(connection, command, settings::current().command_timeout())
+}
+
+/// Run a command on the shared session, giving up with `Circle9Error::Timeout` once `timeout`
+/// has elapsed. The session stays locked, and in blocking mode, for the whole command, so this
+/// is for quick probes; anything long or chatty goes through `exec_command_dedicated`.
+pub fn exec_command_with_timeout(
+    connection: &SSHConnection,
+    command: &str,
+    timeout: Duration,
+) -> Result<CommandOutput> {
+    tracing::debug!("Running remote command: {}", command);
+    let session = lock_or_error(&connection.session)?;
+    let previous_timeout = session.timeout();
+    let deadline = Instant::now() + timeout;
+
+    let result = (|| -> Result<CommandOutput> {
+        session.set_timeout(remaining_ms(deadline)?);
+        let mut channel = session.channel_session()?;
+        channel.exec(command)?;
+
+        // Reading stdout to the end first stalls a command that fills the channel window with
+        // stderr, but the session timeout turns that into a Timeout rather than a hang
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        channel.read_to_end(&mut stdout)?;
+        channel.stderr().read_to_end(&mut stderr)?;
+
+        session.set_timeout(remaining_ms(deadline)?);
+        channel.wait_close()?;
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            exit_status: channel.exit_status()?,
+        })
+    })();
+
+    session.set_timeout(previous_timeout);
+    result.map_err(timeout_error)
+}
+
+/// Run a command on a session of its own, so the shared session stays free for listings and
+/// transfers however long it takes. Gives up with `Circle9Error::Timeout` after `timeout`.
+pub async fn exec_command_dedicated(
+    connection: &SSHConnection,
+    command: &str,
+    timeout: Duration,
+) -> Result<CommandOutput> {
+    tracing::debug!("Running remote command on a dedicated session: {}", command);
+    let deadline = Instant::now() + timeout;
+    let session = SSHClient::open_dedicated_session(&connection.config).await?;
+    let result = poll_command(&session, command, deadline);
+    let _ = session.disconnect(None, "Command finished", None);
+    result.map_err(timeout_error)
+}
+
+/// Run `command` on a session nobody else uses, which is what makes the switch to
+/// non-blocking mode safe
+fn poll_command(session: &Session, command: &str, deadline: Instant) -> Result<CommandOutput> {
+    session.set_timeout(remaining_ms(deadline)?);
+    let mut channel = session.channel_session()?;
+    channel.exec(command)?;
+
+    // Drain both streams as data arrives: reading one to the end first would deadlock once
+    // the command filled the other's window and blocked writing to it
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut buffer = [0u8; 8192];
+    session.set_blocking(false);
+    let drained = (|| -> Result<()> {
+        loop {
+            let read_out = read_available(&mut channel, &mut buffer, &mut stdout)?;
+            let read_err = read_available(&mut channel.stderr(), &mut buffer, &mut stderr)?;
+            if !read_out && !read_err {
+                if channel.eof() {
+                    return Ok(());
+                }
+                remaining_ms(deadline)?;
+                std::thread::sleep(STREAM_POLL_INTERVAL);
+            }
+        }
+    })();
+    session.set_blocking(true);
+    drained?;
+
+    session.set_timeout(remaining_ms(deadline)?);
+    channel.wait_close()?;
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_status: channel.exit_status()?,
+    })
+}
+
+/// Report libssh2's blocking-call timeout as `Circle9Error::Timeout`
+fn timeout_error(e: Circle9Error) -> Circle9Error {
+    match e {
+        Circle9Error::Ssh2Error(ref err) if err.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) => {
+            Circle9Error::Timeout
+        }
+        e => e,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutputLine {
+    pub session_id: String,
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandFinished {
+    pub session_id: String,
+    pub exit_code: Option<i32>,
+    pub killed: bool,
+    pub error: Option<String>,
+}
+
+/// Handles for talking to a streaming command from other commands
+struct RunningCommand {
+    input: Sender<Vec<u8>>,
+    kill: Arc<AtomicBool>,
+}
+
+lazy_static::lazy_static! {
+    static ref RUNNING_COMMANDS: Mutex<HashMap<String, RunningCommand>> = Mutex::new(HashMap::new());
+}
+
+/// Splits a byte stream into lines, holding back a trailing partial line
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, data: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(data);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            lines.push(String::from_utf8_lossy(&line[..line.len() - 1]).trim_end_matches('\r').to_string());
+        }
+        lines
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let line = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        Some(line)
+    }
+}
+
+/// Pump a streaming command until it exits or is killed, emitting its output as events
+fn stream_command(
+    app_handle: &AppHandle,
+    session_id: &str,
+    session: &Session,
+    channel: &mut Channel,
+    input: &Receiver<Vec<u8>>,
+    kill: &AtomicBool,
+) -> Result<Option<i32>> {
+    let emit_line = |stream: OutputStream, line: String| {
+        let event = CommandOutputLine { session_id: session_id.to_string(), stream, line };
+        if let Err(e) = app_handle.emit_all("command_output", &event) {
+            tracing::error!("Failed to emit command output: {}", e);
+        }
+    };
+
+    let mut stdout = LineBuffer::default();
+    let mut stderr = LineBuffer::default();
+    let mut buffer = [0u8; 8192];
+
+    session.set_blocking(false);
+    loop {
+        if kill.load(Ordering::SeqCst) {
+            session.set_blocking(true);
+            channel.close()?;
+            return Ok(None);
+        }
+
+        // Forward any input queued by send_command_input
+        while let Ok(data) = input.try_recv() {
+            session.set_blocking(true);
+            channel.write_all(&data)?;
+            channel.flush()?;
+            session.set_blocking(false);
+        }
+
+        let mut idle = true;
+        for (stream, lines) in [(OutputStream::Stdout, &mut stdout), (OutputStream::Stderr, &mut stderr)] {
+            let read = match stream {
+                OutputStream::Stdout => channel.read(&mut buffer),
+                OutputStream::Stderr => channel.stderr().read(&mut buffer),
+            };
+            match read {
+                Ok(0) => {}
+                Ok(n) => {
+                    idle = false;
+                    for line in lines.push(&buffer[..n]) {
+                        emit_line(stream.clone(), line);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if idle {
+            if channel.eof() {
+                break;
+            }
+            std::thread::sleep(STREAM_POLL_INTERVAL);
+        }
+    }
+
+    if let Some(line) = stdout.finish() {
+        emit_line(OutputStream::Stdout, line);
+    }
+    if let Some(line) = stderr.finish() {
+        emit_line(OutputStream::Stderr, line);
+    }
+
+    session.set_blocking(true);
+    channel.wait_close()?;
+    Ok(Some(channel.exit_status()?))
+}
+
+/// Refuse commands that match a known destructive pattern. Best-effort only: quotes and
+/// backslashes are stripped so `r''m -rf /` is still caught, but the shell has too many ways to
+/// spell a command for substring matching to be a security boundary.
+pub fn check_safe_command(command: &str) -> Result<()> {
+    let normalized = command.to_lowercase()
+        .replace(['\'', '"', '\\'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    match DANGEROUS_PATTERNS.iter().find(|pattern| normalized.contains(*pattern)) {
+        Some(pattern) => Err(Circle9Error::CommandRejected(format!(
+            "'{}' matches the blocked pattern '{}'", command, pattern
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Quote an argument for a POSIX shell
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Milliseconds left before `deadline`, as expected by `Session::set_timeout`
+fn remaining_ms(deadline: Instant) -> Result<u32> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(Circle9Error::Timeout);
+    }
+    // Zero means "no timeout" to libssh2, so never round down to it
+    Ok(remaining.as_millis().clamp(1, u32::MAX as u128) as u32)
+}
+
+/// Read whatever a non-blocking `stream` has ready, returning whether there was anything
+fn read_available(stream: &mut impl Read, buffer: &mut [u8], output: &mut Vec<u8>) -> Result<bool> {
+    match stream.read(buffer) {
+        Ok(n) => {
+            output.extend_from_slice(&buffer[..n]);
+            Ok(n > 0)
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Tauri commands
+
+#[tauri::command]
+pub async fn run_remote_command(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    command: String,
+    timeout_secs: Option<u64>,
+    safe_mode: Option<bool>,
+) -> Result<CommandOutput, String> {
+    if safe_mode.unwrap_or(false) {
+        check_safe_command(&command).map_err(|e| e.to_string())?;
+    }
+
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let timeout = timeout_secs.map(Duration::from_secs).unwrap_or_else(|| settings::current().command_timeout());
+
+    exec_command_dedicated(&connection, &command, timeout).await
+        .map_err(|e| e.to_string())
+}
+
+/// Start a command on its own session and stream its output as `command_output` events.
+/// Returns the session id used by `send_command_input` and `kill_command`.
+#[tauri::command]
+pub async fn run_remote_command_streaming(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    command: String,
+) -> Result<String, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    // A dedicated session keeps a long command from blocking transfers on the shared one
+    let session = SSHClient::open_dedicated_session(&connection.config).await
+        .map_err(|e| e.to_string())?;
+    let mut channel = session.channel_session().map_err(|e| e.to_string())?;
+    channel.exec(&command).map_err(|e| e.to_string())?;
+    tracing::debug!("Streaming remote command: {}", command);
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (input_tx, input_rx) = mpsc::channel();
+    let kill = Arc::new(AtomicBool::new(false));
+    RUNNING_COMMANDS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?
+        .insert(session_id.clone(), RunningCommand { input: input_tx, kill: Arc::clone(&kill) });
+    let job_kill = Arc::clone(&kill);
+    background_jobs::register(&session_id, BackgroundJobKind::ExecStream, &command, Some(&connection_id), move || {
+        job_kill.store(true, Ordering::SeqCst);
+    });
+
+    let id = session_id.clone();
+    std::thread::spawn(move || {
+        let result = stream_command(&app_handle, &id, &session, &mut channel, &input_rx, &kill);
+        if let Ok(mut running) = RUNNING_COMMANDS.lock() {
+            running.remove(&id);
+        }
+        background_jobs::unregister(&id);
+
+        let finished = match result {
+            Ok(exit_code) => CommandFinished {
+                session_id: id.clone(),
+                exit_code,
+                killed: exit_code.is_none(),
+                error: None,
+            },
+            Err(e) => CommandFinished {
+                session_id: id.clone(),
+                exit_code: None,
+                killed: false,
+                error: Some(e.to_string()),
+            },
+        };
+        if let Err(e) = app_handle.emit_all("command_finished", &finished) {
+            tracing::error!("Failed to emit command finished: {}", e);
+        }
+        let _ = session.disconnect(None, "Command finished", None);
+    });
+
+    Ok(session_id)
+}
+
+#[tauri::command]
+pub async fn send_command_input(session_id: String, data: String) -> Result<(), String> {
+    let running = RUNNING_COMMANDS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?;
+    let command = running.get(&session_id)
+        .ok_or("Command session not found")?;
+    command.input.send(data.into_bytes())
+        .map_err(|_| "Command has already finished".to_string())
+}
+
+#[tauri::command]
+pub async fn kill_command(session_id: String) -> Result<bool, String> {
+    let running = RUNNING_COMMANDS.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?;
+    match running.get(&session_id) {
+        Some(command) => {
+            command.kill.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_mode_sees_through_quoting_and_spacing() {
+        assert!(check_safe_command("r''m   -rf /").is_err());
+        assert!(check_safe_command("\"shutdown\" -h now").is_err());
+        assert!(check_safe_command("ls -la /var/log").is_ok());
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use tauri::State;
+use crate::error::Result;
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Bytes read from the start of the file for magic-number detection
+const SNIFF_LEN: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileCategory {
+    Image,
+    Text,
+    Archive,
+    Audio,
+    Video,
+    Document,
+    Binary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFileType {
+    pub path: String,
+    pub mime_type: String,
+    pub category: FileCategory,
+    /// True when the type came from the content rather than the extension
+    pub from_content: bool,
+}
+
+/// Identify a remote file from its first bytes, falling back to its extension
+pub fn detect_file_type(connection: &SSHConnection, path: &str) -> Result<RemoteFileType> {
+    let head = read_head(connection, path)?;
+
+    if let Some(kind) = infer::get(&head) {
+        return Ok(RemoteFileType {
+            path: path.to_string(),
+            mime_type: kind.mime_type().to_string(),
+            category: category_for_matcher(kind.matcher_type()),
+            from_content: true,
+        });
+    }
+
+    if let Some((mime_type, category)) = guess_from_extension(path) {
+        return Ok(RemoteFileType {
+            path: path.to_string(),
+            mime_type: mime_type.to_string(),
+            category,
+            from_content: false,
+        });
+    }
+
+    // No magic number and no known extension: decide between text and binary by content
+    let (mime_type, category) = if looks_like_text(&head) {
+        ("text/plain", FileCategory::Text)
+    } else {
+        ("application/octet-stream", FileCategory::Binary)
+    };
+    Ok(RemoteFileType {
+        path: path.to_string(),
+        mime_type: mime_type.to_string(),
+        category,
+        from_content: true,
+    })
+}
+
+fn read_head(connection: &SSHConnection, path: &str) -> Result<Vec<u8>> {
+    let sftp = connection.sftp()?;
+    let file = sftp.open(Path::new(path))?;
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn category_for_matcher(matcher: infer::MatcherType) -> FileCategory {
+    match matcher {
+        infer::MatcherType::Image => FileCategory::Image,
+        infer::MatcherType::Archive => FileCategory::Archive,
+        infer::MatcherType::Audio => FileCategory::Audio,
+        infer::MatcherType::Video => FileCategory::Video,
+        infer::MatcherType::Book | infer::MatcherType::Doc => FileCategory::Document,
+        infer::MatcherType::Text => FileCategory::Text,
+        _ => FileCategory::Binary,
+    }
+}
+
+fn guess_from_extension(path: &str) -> Option<(&'static str, FileCategory)> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    let guess = match extension.as_str() {
+        "txt" | "log" | "conf" | "cfg" | "ini" => ("text/plain", FileCategory::Text),
+        "md" => ("text/markdown", FileCategory::Text),
+        "csv" => ("text/csv", FileCategory::Text),
+        "json" => ("application/json", FileCategory::Text),
+        "yaml" | "yml" => ("application/yaml", FileCategory::Text),
+        "toml" => ("application/toml", FileCategory::Text),
+        "xml" => ("application/xml", FileCategory::Text),
+        "html" | "htm" => ("text/html", FileCategory::Text),
+        "css" => ("text/css", FileCategory::Text),
+        "js" => ("text/javascript", FileCategory::Text),
+        "sh" | "bash" | "py" | "rs" | "c" | "h" | "go" | "ts" => ("text/plain", FileCategory::Text),
+        "svg" => ("image/svg+xml", FileCategory::Image),
+        _ => return None,
+    };
+    Some(guess)
+}
+
+/// Heuristic: valid UTF-8 (allowing a character cut off at the end) with no NUL bytes
+pub(crate) fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+// Tauri commands for file type detection
+
+#[tauri::command]
+pub async fn detect_remote_file_type(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<RemoteFileType, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    detect_file_type(&connection, &path)
+        .map_err(|e| e.to_string())
+}
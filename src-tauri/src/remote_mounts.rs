@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+use crate::case_agent::CASE_AGENT;
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::{exec_command, shell_quote};
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+/// Free space below this fraction of the mount's size counts as nearly full
+const NEARLY_FULL_FRACTION: f64 = 0.05;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteMount {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: Vec<String>,
+    pub read_only: bool,
+    /// None for pseudo filesystems that `df` doesn't report
+    pub total_bytes: Option<u64>,
+    pub available_bytes: Option<u64>,
+    pub nearly_full: bool,
+    /// Result of an earlier case-sensitivity probe on this mount, if any
+    pub case_sensitive: Option<bool>,
+}
+
+lazy_static::lazy_static! {
+    /// Device ids keyed by (connection id, mount point)
+    static ref MOUNT_DEVICES: Mutex<HashMap<(String, String), u64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathDevice {
+    /// The path with symlinks resolved
+    pub resolved_path: String,
+    pub device_id: u64,
+    pub mount_point: String,
+}
+
+/// Space figures for one mount point, in bytes
+struct DiskUsage {
+    total: u64,
+    available: u64,
+}
+
+/// List the remote host's mounts with their type, options and free space
+pub fn list_mounts(connection: &SSHConnection, connection_id: &str) -> Result<Vec<RemoteMount>> {
+    let entries = match read_proc_mounts(connection) {
+        Ok(contents) => parse_mount_table(&contents),
+        Err(e) => {
+            tracing::debug!("/proc/mounts unavailable ({}), falling back to findmnt", e);
+            let output = exec_command(connection, "findmnt -rn -o SOURCE,TARGET,FSTYPE,OPTIONS")?;
+            if !output.success() {
+                return Err(Circle9Error::SSHError(format!(
+                    "Could not list mounts: {}", output.stderr.trim()
+                )));
+            }
+            parse_mount_table(&output.stdout)
+        }
+    };
+
+    // df can fail on a single stale mount and still print the rest, so ignore its status
+    let usage = exec_command(connection, "df -P -k")
+        .map(|output| parse_df(&output.stdout))
+        .unwrap_or_default();
+
+    let case_agent = lock_or_error(&CASE_AGENT)?;
+    Ok(entries.into_iter().map(|(device, mount_point, fs_type, options)| {
+        let usage = usage.get(&mount_point);
+        let read_only = options.iter().any(|o| o == "ro");
+        RemoteMount {
+            case_sensitive: case_agent.mount_case_sensitivity(connection_id, &mount_point),
+            total_bytes: usage.map(|u| u.total),
+            available_bytes: usage.map(|u| u.available),
+            nearly_full: usage.map_or(false, |u| {
+                u.total > 0 && (u.available as f64) < u.total as f64 * NEARLY_FULL_FRACTION
+            }),
+            device,
+            mount_point,
+            fs_type,
+            options,
+            read_only,
+        }
+    }).collect())
+}
+
+/// Find the device and mount point `path` lives on, resolving symlinks first.
+/// Device ids are looked up once per mount point and cached until disconnect.
+pub fn path_device(connection: &SSHConnection, connection_id: &str, path: &str) -> Result<PathDevice> {
+    let resolved = connection.sftp()?.realpath(Path::new(path))?;
+    let resolved_path = resolved.to_string_lossy().into_owned();
+
+    let mount_table = match read_proc_mounts(connection) {
+        Ok(contents) => contents,
+        Err(_) => exec_command(connection, "findmnt -rn -o SOURCE,TARGET,FSTYPE,OPTIONS")?.stdout,
+    };
+    // Later entries shadow earlier ones on the same mount point, so the last longest match wins
+    let mount_point = parse_mount_table(&mount_table).into_iter()
+        .map(|(_, mount_point, _, _)| mount_point)
+        .filter(|mount_point| resolved.starts_with(mount_point))
+        .fold(None::<String>, |best, mount_point| match best {
+            Some(best) if best.len() > mount_point.len() => Some(best),
+            _ => Some(mount_point),
+        })
+        .unwrap_or_else(|| "/".to_string());
+
+    let key = (connection_id.to_string(), mount_point.clone());
+    if let Some(device_id) = lock_or_error(&MOUNT_DEVICES)?.get(&key) {
+        return Ok(PathDevice { resolved_path, device_id: *device_id, mount_point });
+    }
+
+    let output = exec_command(connection, &format!("stat -c %d -- {}", shell_quote(&mount_point)))?;
+    let device_id = output.stdout.trim().parse::<u64>()
+        .ok()
+        .filter(|_| output.success())
+        .ok_or_else(|| Circle9Error::SSHError(format!(
+            "Could not stat {}: {}", mount_point, output.stderr.trim()
+        )))?;
+    lock_or_error(&MOUNT_DEVICES)?.insert(key, device_id);
+
+    Ok(PathDevice { resolved_path, device_id, mount_point })
+}
+
+/// Every mount point on the remote host, for stopping recursive walks at filesystem boundaries
+pub fn mount_points(connection: &SSHConnection) -> Result<std::collections::HashSet<String>> {
+    let mount_table = match read_proc_mounts(connection) {
+        Ok(contents) => contents,
+        Err(_) => exec_command(connection, "findmnt -rn -o SOURCE,TARGET,FSTYPE,OPTIONS")?.stdout,
+    };
+    Ok(parse_mount_table(&mount_table).into_iter()
+        .map(|(_, mount_point, _, _)| mount_point)
+        .collect())
+}
+
+/// Whether two remote paths share a filesystem, or None if either can't be resolved
+pub fn same_device(connection: &SSHConnection, connection_id: &str, a: &str, b: &str) -> Option<bool> {
+    let a = path_device(connection, connection_id, a).ok()?;
+    let b = path_device(connection, connection_id, b).ok()?;
+    Some(a.device_id == b.device_id)
+}
+
+/// Whether the filesystem holding an open remote file has less than `needed` bytes left.
+/// SFTP v3 servers report ENOSPC as a generic failure, so a failed write is checked this way.
+pub fn out_of_space(file: &mut ssh2::File, needed: usize) -> bool {
+    match file.statvfs() {
+        Ok(stats) => stats.f_bavail.saturating_mul(stats.f_frsize) < needed as u64,
+        Err(e) => {
+            tracing::debug!("statvfs not supported: {}", e);
+            false
+        }
+    }
+}
+
+/// Drop cached device ids for a connection
+pub fn forget_connection(connection_id: &str) {
+    if let Ok(mut devices) = MOUNT_DEVICES.lock() {
+        devices.retain(|(id, _), _| id != connection_id);
+    }
+}
+
+fn read_proc_mounts(connection: &SSHConnection) -> Result<String> {
+    let sftp = connection.sftp()?;
+    let mut file = sftp.open(Path::new("/proc/mounts"))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Parse `/proc/mounts` or `findmnt -rn` lines into (device, mount point, type, options)
+fn parse_mount_table(contents: &str) -> Vec<(String, String, String, Vec<String>)> {
+    contents.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape_mount_field(fields.next()?);
+            let mount_point = unescape_mount_field(fields.next()?);
+            let fs_type = fields.next()?.to_string();
+            let options = fields.next()
+                .map(|o| o.split(',').map(str::to_string).collect())
+                .unwrap_or_default();
+            Some((device, mount_point, fs_type, options))
+        })
+        .collect()
+}
+
+/// Decode the octal (`\040`, /proc/mounts) and hex (`\x20`, findmnt) escapes used for spaces etc.
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            let octal = field.get(i + 1..i + 4).and_then(|s| u8::from_str_radix(s, 8).ok());
+            let hex = field.get(i + 1..i + 2)
+                .filter(|x| *x == "x")
+                .and_then(|_| field.get(i + 2..i + 4))
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+            if let Some(byte) = octal {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+            if let Some(byte) = hex {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse POSIX `df -P -k` output keyed by mount point
+fn parse_df(output: &str) -> HashMap<String, DiskUsage> {
+    output.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let total = fields[1].parse::<u64>().ok()?;
+            let available = fields[3].parse::<u64>().ok()?;
+            // Mount points may contain spaces, which df prints unescaped
+            let mount_point = fields[5..].join(" ");
+            Some((mount_point, DiskUsage { total: total * 1024, available: available * 1024 }))
+        })
+        .collect()
+}
+
+// Tauri commands for remote mounts
+
+#[tauri::command]
+pub async fn list_remote_mounts(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+) -> Result<Vec<RemoteMount>, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    list_mounts(&connection, &connection_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_path_device(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+) -> Result<PathDevice, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    path_device(&connection, &connection_id, &path)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,65 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::path::{Component, Path, PathBuf};
+use crate::error::{Circle9Error, Result};
+
+/// Marks a remote path passed as base64 of its raw bytes, for names that aren't valid UTF-8
+pub const RAW_PATH_PREFIX: &str = "raw:";
+
+/// The bytes the server knows `path` by
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// ssh2 only produces UTF-8 paths on this platform, so the string is the real name
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+/// How to hand a remote path to the frontend so it comes back unchanged: as is when it's
+/// valid UTF-8, otherwise `raw:` and the base64 of its bytes
+pub fn encode_remote_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) if !path.starts_with(RAW_PATH_PREFIX) => path.to_string(),
+        _ => format!("{}{}", RAW_PATH_PREFIX, BASE64.encode(path_bytes(path))),
+    }
+}
+
+/// Inverse of `encode_remote_path`; plain strings are taken as they are
+pub fn decode_remote_path(path: &str) -> Result<PathBuf> {
+    let encoded = match path.strip_prefix(RAW_PATH_PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(PathBuf::from(path)),
+    };
+    let bytes = BASE64.decode(encoded)
+        .map_err(|e| Circle9Error::InvalidPath(format!("Invalid raw path: {}", e)))?;
+    let path = bytes_to_path(bytes)?;
+
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(Circle9Error::InvalidPath("Path traversal detected".to_string()));
+    }
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> Result<PathBuf> {
+    String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|_| Circle9Error::InvalidPath("Names that aren't valid UTF-8 can't be addressed on this platform".to_string()))
+}
+
+/// File name for display, with invalid bytes shown as U+FFFD
+pub fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
@@ -0,0 +1,160 @@
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::secure_storage::SecureStorage;
+use crate::ssh_client::{SSHClient, SSHConfig};
+
+/// How long `passwd` may take to prompt and answer, in total
+const PASSWD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prompts answered before a repeated prompt means the new password was rejected
+const PASSWD_PROMPTS: usize = 3;
+
+/// Change the login password on the remote host by driving `passwd` on a pseudo-terminal
+pub async fn change_password(config: &SSHConfig, old_password: &str, new_password: &str) -> Result<()> {
+    let session = SSHClient::open_dedicated_session(config).await?;
+    let deadline = Instant::now() + PASSWD_TIMEOUT;
+
+    let mut channel = session.channel_session()?;
+    channel.request_pty("dumb", None, None)?;
+    channel.exec("LC_ALL=C passwd")?;
+
+    let mut transcript = String::new();
+    let mut pending = String::new();
+    let mut answered = 0;
+    let mut buffer = [0u8; 1024];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Circle9Error::Timeout);
+        }
+        session.set_timeout(remaining.as_millis().clamp(1, u32::MAX as u128) as u32);
+
+        let n = match channel.read(&mut buffer) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => return Err(Circle9Error::Timeout),
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&buffer[..n]);
+        transcript.push_str(&text);
+        pending.push_str(&text);
+
+        if !pending.trim_end().to_lowercase().ends_with("password:") {
+            continue;
+        }
+        if answered == PASSWD_PROMPTS {
+            // passwd starts over after a policy rejection; stop rather than loop forever
+            channel.close()?;
+            return Err(rejection_error(&transcript));
+        }
+        let prompt = pending.to_lowercase();
+        let answer = if prompt.contains("current") || (answered == 0 && !prompt.contains("new")) {
+            old_password
+        } else {
+            new_password
+        };
+        channel.write_all(format!("{}\n", answer).as_bytes())?;
+        channel.flush()?;
+        answered += 1;
+        pending.clear();
+    }
+
+    channel.wait_close()?;
+    if channel.exit_status()? != 0 {
+        return Err(rejection_error(&transcript));
+    }
+    Ok(())
+}
+
+/// Pick the line that explains why `passwd` refused, e.g. a PAM `BAD PASSWORD:` message
+fn rejection_error(transcript: &str) -> Circle9Error {
+    let reason = transcript.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.to_lowercase().ends_with("password:"))
+        .filter(|line| !line.to_lowercase().starts_with("changing password"))
+        .find(|line| {
+            let line = line.to_lowercase();
+            line.contains("bad password") || line.contains("error") || line.contains("fail")
+                || line.contains("do not match") || line.contains("must") || line.contains("too")
+        })
+        .unwrap_or("passwd exited without changing the password");
+    Circle9Error::CommandRejected(format!("Password change refused: {}", reason))
+}
+
+// Tauri commands for password rotation
+
+#[tauri::command]
+pub async fn rotate_ssh_password(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    new_password: String,
+) -> Result<(), String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let config = &connection.config;
+
+    // A stored password is newer than the one the session logged in with if it was rotated before
+    let old_password = SecureStorage::get_password(&config.host, &config.username).ok()
+        .or_else(|| config.password.clone())
+        .ok_or("No current password is known for this connection")?;
+
+    change_password(&config.with_password(&old_password), &old_password, &new_password).await
+        .map_err(|e| e.to_string())?;
+    tracing::info!("Changed password for {}", connection_id);
+
+    // Sessions opened later from the live connection must log in with the new password
+    ssh_client.set_password(&connection_id, &new_password)
+        .map_err(|e| format!("Password changed on the server but the connection was not updated: {}", e))?;
+    SecureStorage::store_password(&config.host, &config.username, &new_password)
+        .map_err(|e| format!("Password changed on the server but could not be saved: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(password: &str) -> SSHConfig {
+        SSHConfig {
+            host: std::env::var("CIRCLE9_TEST_SSH_HOST").unwrap_or_else(|_| "localhost".to_string()),
+            port: std::env::var("CIRCLE9_TEST_SSH_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(22),
+            username: std::env::var("CIRCLE9_TEST_SSH_USER").unwrap_or_else(|_| "circle9".to_string()),
+            key_path: None,
+            password: Some(password.to_string()),
+            key_passphrase: None,
+            expected_host_key_fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn with_password_only_replaces_the_password() {
+        let rotated = config("old").with_password("new");
+        assert_eq!(rotated.password.as_deref(), Some("new"));
+        assert_eq!(rotated.host, config("old").host);
+        assert_eq!(rotated.username, config("old").username);
+        assert_eq!(rotated.key_path, None);
+    }
+
+    /// Needs a disposable account: CIRCLE9_TEST_SSH_HOST, _USER, _PASSWORD and _NEW_PASSWORD.
+    /// The password is changed back afterwards.
+    #[tokio::test]
+    #[ignore]
+    async fn dedicated_session_after_rotation_uses_new_password() {
+        let old = std::env::var("CIRCLE9_TEST_SSH_PASSWORD").expect("CIRCLE9_TEST_SSH_PASSWORD");
+        let new = std::env::var("CIRCLE9_TEST_SSH_NEW_PASSWORD").expect("CIRCLE9_TEST_SSH_NEW_PASSWORD");
+        let live = config(&old);
+
+        change_password(&live, &old, &new).await.expect("rotate");
+        let rotated = live.with_password(&new);
+        let with_new = SSHClient::open_dedicated_session(&rotated).await;
+        let with_old = SSHClient::open_dedicated_session(&live).await;
+        change_password(&rotated, &new, &old).await.expect("restore");
+
+        assert!(with_new.is_ok(), "dedicated session with the rotated config failed");
+        assert!(with_old.is_err(), "the old password still logs in");
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_names::decode_remote_path;
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Largest range returned by one call; bigger reads belong in a download
+const MAX_RANGE_LENGTH: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteRange {
+    pub path: String,
+    pub offset: u64,
+    /// Size of the file when it was read
+    pub file_size: u64,
+    pub data: Vec<u8>,
+    /// The file ended before `length` bytes could be read
+    pub eof: bool,
+}
+
+/// Read up to `length` bytes of a remote file starting at `offset`
+pub fn read_range(connection: &SSHConnection, path: &str, offset: u64, length: u64) -> Result<RemoteRange> {
+    if length == 0 || length > MAX_RANGE_LENGTH {
+        return Err(Circle9Error::InvalidValue(format!(
+            "Length must be between 1 and {} bytes", MAX_RANGE_LENGTH
+        )));
+    }
+    let remote_path = decode_remote_path(path)?;
+
+    let sftp = connection.sftp()?;
+    let mut file = sftp.open(&remote_path)?;
+    let file_size = file.stat()?.size.unwrap_or(0);
+    if offset > file_size {
+        return Err(Circle9Error::InvalidValue(format!(
+            "Offset {} is past the end of {} ({} bytes)", offset, path, file_size
+        )));
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::with_capacity(length.min(file_size - offset) as usize);
+    // A file that grew since the stat is read up to `length`, one that shrank ends early
+    file.take(length).read_to_end(&mut data)?;
+
+    Ok(RemoteRange {
+        path: path.to_string(),
+        offset,
+        file_size,
+        eof: (data.len() as u64) < length,
+        data,
+    })
+}
+
+// Tauri commands for ranged reads
+
+/// `length` bytes of a remote file from `offset`, for hex viewers, log tails and
+/// checking a partial download against the source
+#[tauri::command]
+pub async fn read_remote_range(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    path: String,
+    offset: u64,
+    length: u64,
+) -> Result<RemoteRange, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path).map_err(|e| e.to_string())?;
+
+    read_range(&connection, &path, offset, length)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use crate::error::{Circle9Error, Result};
+use crate::ssh_client::SSHConnection;
+use crate::utils::lock_or_error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswdEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+/// The remote `/etc/passwd`, read over SFTP once per connection
+pub fn passwd_entries(connection: &SSHConnection) -> Result<Vec<PasswdEntry>> {
+    if let Some(entries) = lock_or_error(&connection.passwd)?.clone() {
+        return Ok(entries);
+    }
+
+    let contents = {
+        let sftp = connection.sftp()?;
+        let mut file = sftp.open(Path::new("/etc/passwd"))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        contents
+    };
+    let entries = parse_passwd(&contents);
+    *lock_or_error(&connection.passwd)? = Some(entries.clone());
+    Ok(entries)
+}
+
+/// Resolve a leading `~` or `~user` the way a shell would; other paths are returned unchanged
+pub fn expand_tilde(connection: &SSHConnection, path: &str) -> Result<String> {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) => rest,
+        None => return Ok(path.to_string()),
+    };
+    let (user, tail) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, ""),
+    };
+
+    let home = if user.is_empty() {
+        connection.home_dir.clone()
+            .ok_or_else(|| Circle9Error::InvalidPath("Remote home directory is unknown".to_string()))?
+    } else {
+        passwd_entries(connection)?
+            .into_iter()
+            .find(|entry| entry.name == user)
+            .map(|entry| entry.home)
+            .ok_or_else(|| Circle9Error::InvalidPath(format!("Unknown remote user '{}'", user)))?
+    };
+
+    Ok(join_home(&home, tail))
+}
+
+/// `home` followed by the rest of a `~` path, which is empty or starts with `/`.
+/// Only the join is touched; the rest of the path is kept as given.
+fn join_home(home: &str, tail: &str) -> String {
+    match (home.trim_end_matches('/'), tail) {
+        ("", "") => "/".to_string(),
+        (home, tail) => format!("{}{}", home, tail),
+    }
+}
+
+fn parse_passwd(contents: &str) -> Vec<PasswdEntry> {
+    contents.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some(PasswdEntry {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().ok()?,
+                gid: fields[3].parse().ok()?,
+                home: fields[5].to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilde_paths_match_the_absolute_home_path() {
+        let absolute = "/home/alice/docs/report.txt";
+        assert_eq!(join_home("/home/alice", "/docs/report.txt"), absolute);
+        assert_eq!(join_home("/home/alice/", "/docs/report.txt"), absolute);
+        assert_eq!(join_home("/home/alice", ""), "/home/alice");
+    }
+
+    #[test]
+    fn join_home_keeps_the_rest_of_the_path() {
+        assert_eq!(join_home("/home/alice", "//docs//a"), "/home/alice//docs//a");
+        assert_eq!(join_home("/", "/etc"), "/etc");
+        assert_eq!(join_home("/", ""), "/");
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{FileStat, FileType};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::{Circle9Error, Result};
+use crate::remote_mounts::mount_points;
+use crate::ssh_client::SSHConnection;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalkOutcome {
+    pub skipped: Vec<SkippedEntry>,
+    /// Directories not descended into because another filesystem is mounted there
+    pub skipped_mounts: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// Depth-first walk of everything below `root`, calling `visit` for each entry.
+/// Unreadable subdirectories are recorded and skipped; symlinks are not followed.
+/// Unless `cross_filesystems` is set, mount points below `root` are visited but not entered.
+/// SFTP doesn't report device ids, so mount points come from the remote mount table.
+pub async fn walk_remote<F>(
+    connection: &SSHConnection,
+    root: &str,
+    cross_filesystems: bool,
+    cancel: &AtomicBool,
+    mut visit: F,
+) -> Result<WalkOutcome>
+where
+    F: FnMut(&Path, &FileStat),
+{
+    let root = PathBuf::from(root);
+    let mut outcome = WalkOutcome::default();
+    let mut pending = vec![root.clone()];
+
+    // Mount points are absolute and symlink-free, so compare against the resolved root
+    let boundaries = if cross_filesystems {
+        None
+    } else {
+        let resolved_root = connection.sftp()?.realpath(&root)
+            .unwrap_or_else(|_| root.clone());
+        match mount_points(connection) {
+            Ok(mounts) => Some((resolved_root, mounts)),
+            Err(e) => {
+                tracing::warn!("Could not read remote mounts, walk may cross filesystems: {}", e);
+                None
+            }
+        }
+    };
+
+    while let Some(dir) = pending.pop() {
+        if cancel.load(Ordering::SeqCst) {
+            outcome.cancelled = true;
+            break;
+        }
+
+        let entries = {
+            let _permit = connection.acquire_metadata_permit().await?;
+            let sftp = connection.sftp()?;
+            sftp.readdir(&dir)
+        };
+
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(e) if dir == root => {
+                return Err(Circle9Error::SSHError(format!("Failed to read directory {}: {}", root.display(), e)));
+            }
+            Err(e) => {
+                tracing::debug!("Skipping unreadable directory {}: {}", dir.display(), e);
+                outcome.skipped.push(SkippedEntry {
+                    path: dir.to_string_lossy().to_string(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        for (path, stat) in entries {
+            if stat.file_type() == FileType::Directory {
+                let is_mount = boundaries.as_ref().map_or(false, |(resolved_root, mounts)| {
+                    let resolved = path.strip_prefix(&root)
+                        .map(|relative| resolved_root.join(relative))
+                        .unwrap_or_else(|_| path.clone());
+                    mounts.contains(&*resolved.to_string_lossy())
+                });
+                if is_mount {
+                    tracing::debug!("Not descending into mount point {}", path.display());
+                    outcome.skipped_mounts.push(path.to_string_lossy().to_string());
+                } else {
+                    pending.push(path.clone());
+                }
+            }
+            visit(&path, &stat);
+        }
+    }
+
+    Ok(outcome)
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use crate::background_jobs::{self, BackgroundJobKind};
+use crate::linux_files::{read_remote_dir, LinuxFileInfo};
+use crate::remote_users::expand_tilde;
+use crate::ssh_client::SSHClient;
+use crate::utils::lock_or_error;
+
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Once the connection has seen no other use for this long, polling slows down
+const IDLE_BACKOFF_AFTER: Duration = Duration::from_secs(60);
+/// Longest gap between polls when backed off
+const MAX_WATCH_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Payload of `remote_change`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteChangeEvent {
+    pub watch_id: String,
+    pub connection_id: String,
+    pub path: String,
+    pub added: Vec<LinuxFileInfo>,
+    /// Paths of the entries that are gone
+    pub removed: Vec<String>,
+    /// Entries whose size, mtime or permissions changed
+    pub modified: Vec<LinuxFileInfo>,
+}
+
+impl RemoteChangeEvent {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref WATCHES: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+type Snapshot = HashMap<String, LinuxFileInfo>;
+
+fn snapshot(entries: Vec<LinuxFileInfo>) -> Snapshot {
+    entries.into_iter().map(|entry| (entry.path.clone(), entry)).collect()
+}
+
+fn changed(before: &LinuxFileInfo, after: &LinuxFileInfo) -> bool {
+    before.size != after.size
+        || before.modified != after.modified
+        || before.permissions != after.permissions
+        || before.is_dir != after.is_dir
+}
+
+/// What changed between two listings of the same directory
+fn diff(watch_id: &str, connection_id: &str, path: &str, previous: &Snapshot, current: &Snapshot) -> RemoteChangeEvent {
+    let mut event = RemoteChangeEvent {
+        watch_id: watch_id.to_string(),
+        connection_id: connection_id.to_string(),
+        path: path.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+    };
+    for (key, entry) in current {
+        match previous.get(key) {
+            None => event.added.push(entry.clone()),
+            Some(before) if changed(before, entry) => event.modified.push(entry.clone()),
+            Some(_) => {}
+        }
+    }
+    event.removed = previous.keys()
+        .filter(|key| !current.contains_key(*key))
+        .cloned()
+        .collect();
+    event
+}
+
+/// Poll `path` until stopped or the connection goes away. The interval doubles, up to
+/// `MAX_WATCH_INTERVAL`, while nothing else is using the connection, and drops back to
+/// `interval` once something does.
+async fn poll_loop(
+    app_handle: AppHandle,
+    watch_id: String,
+    connection_id: String,
+    path: String,
+    interval: Duration,
+    stop: Arc<AtomicBool>,
+    mut previous: Snapshot,
+) {
+    let mut current_interval = interval;
+    loop {
+        tokio::time::sleep(current_interval).await;
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let ssh_client = app_handle.state::<SSHClient>();
+        let connection = match ssh_client.peek_connection(&connection_id) {
+            Some(connection) => connection,
+            None => {
+                tracing::info!("Stopping remote watch {}: connection {} closed", watch_id, connection_id);
+                break;
+            }
+        };
+
+        let idle = lock_or_error(&connection.last_activity)
+            .map(|last| last.elapsed() > IDLE_BACKOFF_AFTER)
+            .unwrap_or(false);
+        current_interval = if idle {
+            (current_interval * 2).min(MAX_WATCH_INTERVAL.max(interval))
+        } else {
+            interval
+        };
+
+        let current = match read_remote_dir(&connection, Path::new(&path)) {
+            Ok(entries) => snapshot(entries),
+            Err(e) => {
+                tracing::warn!("Remote watch {} could not list {}: {}", watch_id, path, e);
+                continue;
+            }
+        };
+
+        let event = diff(&watch_id, &connection_id, &path, &previous, &current);
+        if !event.is_empty() {
+            ssh_client.listing_cache.invalidate(&connection_id, &path);
+            if let Err(e) = app_handle.emit_all("remote_change", &event) {
+                tracing::error!("Failed to emit remote change: {}", e);
+            }
+        }
+        previous = current;
+    }
+
+    if let Ok(mut watches) = WATCHES.lock() {
+        watches.remove(&watch_id);
+    }
+    background_jobs::unregister(&watch_id);
+}
+
+// Tauri commands for remote watches
+
+/// Poll a remote directory every `interval_secs` and emit `remote_change` events for
+/// added, removed and modified entries. Returns the watch id for `stop_remote_watch`.
+#[tauri::command]
+pub async fn watch_remote_dir(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    path: String,
+    interval_secs: Option<u64>,
+    watch_id: Option<String>,
+) -> Result<String, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let path = expand_tilde(&connection, &path).map_err(|e| e.to_string())?;
+    let interval = interval_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_WATCH_INTERVAL)
+        .max(MIN_WATCH_INTERVAL);
+    let watch_id = watch_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // The first listing is the baseline, and fails the command if the directory can't be read
+    let initial = snapshot(read_remote_dir(&connection, Path::new(&path))?);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let mut watches = WATCHES.lock().map_err(|_| "Mutex poisoned".to_string())?;
+        if watches.contains_key(&watch_id) {
+            return Err(format!("Watch {} already exists", watch_id));
+        }
+        watches.insert(watch_id.clone(), Arc::clone(&stop));
+    }
+    let job_stop = Arc::clone(&stop);
+    background_jobs::register(&watch_id, BackgroundJobKind::Watch, &path, Some(&connection_id), move || {
+        job_stop.store(true, Ordering::SeqCst);
+    });
+
+    tracing::info!("Watching {} on {} every {:?}", path, connection_id, interval);
+    tauri::async_runtime::spawn(poll_loop(app_handle, watch_id.clone(), connection_id, path, interval, stop, initial));
+    Ok(watch_id)
+}
+
+#[tauri::command]
+pub async fn stop_remote_watch(watch_id: String) -> Result<bool, String> {
+    let watches = WATCHES.lock()
+        .map_err(|_| "Mutex poisoned".to_string())?;
+    match watches.get(&watch_id) {
+        Some(stop) => {
+            stop.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
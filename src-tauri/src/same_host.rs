@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::ToSocketAddrs;
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::exec_command;
+use crate::ssh_client::{HostKeyFingerprints, SSHClient, SSHConnection};
+use crate::utils::lock_or_error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SameHostReport {
+    pub same_host: bool,
+    pub same_host_key: bool,
+    /// Addresses both hosts resolve to right now
+    pub shared_addresses: Vec<String>,
+    /// None when either server has no readable machine id
+    pub same_machine_id: Option<bool>,
+    pub reason: String,
+}
+
+fn resolved_addresses(connection: &SSHConnection) -> BTreeSet<String> {
+    (connection.config.host.as_str(), connection.config.port)
+        .to_socket_addrs()
+        .map(|addrs| addrs.map(|addr| addr.ip().to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn fingerprint(connection: &SSHConnection) -> Result<String> {
+    let session = lock_or_error(&connection.session)?;
+    HostKeyFingerprints::of(&session)
+        .map(|fingerprints| fingerprints.sha256)
+        .ok_or_else(|| Circle9Error::SSHError("Server did not present a host key".to_string()))
+}
+
+/// systemd's machine id, or the BSD host UUID
+fn machine_id(connection: &SSHConnection) -> Option<String> {
+    let output = exec_command(
+        connection,
+        "cat /etc/machine-id 2>/dev/null || cat /var/lib/dbus/machine-id 2>/dev/null || sysctl -n kern.hostuuid 2>/dev/null",
+    ).ok()?;
+    let id = output.stdout.trim();
+    (output.success() && !id.is_empty()).then(|| id.to_string())
+}
+
+/// Decide whether two connections reach the same machine. Matching host keys are required;
+/// a machine id can only turn a match into a mismatch (cloned servers share keys), never vouch
+/// for different keys. Matching addresses alone prove nothing, since one address can forward
+/// different ports to different machines.
+pub fn compare(a: &SSHConnection, b: &SSHConnection) -> Result<SameHostReport> {
+    let same_host_key = fingerprint(a)? == fingerprint(b)?;
+    let shared_addresses: Vec<String> = resolved_addresses(a)
+        .intersection(&resolved_addresses(b))
+        .cloned()
+        .collect();
+    let same_machine_id = match (machine_id(a), machine_id(b)) {
+        (Some(id_a), Some(id_b)) => Some(id_a == id_b),
+        _ => None,
+    };
+
+    let (same_host, reason) = decide(same_host_key, same_machine_id, !shared_addresses.is_empty());
+    Ok(SameHostReport { same_host, same_host_key, shared_addresses, same_machine_id, reason })
+}
+
+fn decide(same_host_key: bool, same_machine_id: Option<bool>, shared_address: bool) -> (bool, String) {
+    match (same_host_key, same_machine_id) {
+        (true, Some(true)) => (true, "Same host key and machine id".to_string()),
+        (true, Some(false)) => (false, "Same host key but different machine ids, e.g. cloned servers".to_string()),
+        (true, None) => (true, "Same host key".to_string()),
+        (false, Some(true)) => (false, "Same machine id but different host keys, so not trusted as one host".to_string()),
+        (false, _) if shared_address => (
+            false,
+            "Same address but different host keys, so different machines behind one address".to_string(),
+        ),
+        (false, _) => (false, "Different host keys".to_string()),
+    }
+}
+
+// Tauri commands for same-host detection
+
+/// Whether two open connections are to the same server, so a copy between them can run
+/// as a local `cp` instead
+#[tauri::command]
+pub async fn are_connections_same_host(
+    ssh_client: State<'_, SSHClient>,
+    id_a: String,
+    id_b: String,
+) -> Result<SameHostReport, String> {
+    let a = ssh_client.get_connection(&id_a)
+        .ok_or("Connection not found")?;
+    let b = ssh_client.get_connection(&id_b)
+        .ok_or("Connection not found")?;
+
+    compare(&a, &b).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_key_is_required() {
+        assert!(decide(true, None, false).0);
+        assert!(decide(true, Some(true), true).0);
+        assert!(!decide(false, Some(true), true).0);
+        assert!(!decide(false, None, true).0);
+    }
+
+    #[test]
+    fn machine_id_only_downgrades() {
+        assert!(!decide(true, Some(false), true).0);
+    }
+}
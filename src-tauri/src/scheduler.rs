@@ -0,0 +1,571 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Local, LocalResult, NaiveDate, TimeZone, Timelike, Utc};
+use tauri::{AppHandle, Manager, State};
+use uuid::Uuid;
+use crate::copy_agent::{CopyAgent, TransferTask};
+use crate::error::{Circle9Error, Result};
+use crate::utils::lock_or_error;
+
+/// How often the scheduler checks for due schedules
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Longest run of local times a DST change skips; no zone jumps by more than two hours
+const MAX_DST_GAP_MINUTES: i64 = 180;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ScheduleSpec {
+    /// Run once at the given time
+    Once { at: DateTime<Utc> },
+    /// Run every day at the given local wall-clock time
+    Daily { hour: u32, minute: u32 },
+    /// Run repeatedly with a fixed gap between runs
+    Interval { every_secs: u64 },
+}
+
+/// A recurring local wall-clock window transfers may run in, e.g. 22:00–06:00.
+/// The end is exclusive; an end before the start wraps past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllowedHours {
+    pub start_hour: u32,
+    pub start_minute: u32,
+    pub end_hour: u32,
+    pub end_minute: u32,
+}
+
+impl AllowedHours {
+    pub fn validate(&self) -> Result<()> {
+        if self.start_hour > 23 || self.end_hour > 23 || self.start_minute > 59 || self.end_minute > 59 {
+            return Err(Circle9Error::TransferError(format!(
+                "Invalid allowed hours {:02}:{:02}-{:02}:{:02}",
+                self.start_hour, self.start_minute, self.end_hour, self.end_minute
+            )));
+        }
+        if (self.start_hour, self.start_minute) == (self.end_hour, self.end_minute) {
+            return Err(Circle9Error::TransferError("Allowed hours window is empty".to_string()));
+        }
+        Ok(())
+    }
+
+    fn start_minutes(&self) -> u32 {
+        self.start_hour * 60 + self.start_minute
+    }
+
+    fn end_minutes(&self) -> u32 {
+        self.end_hour * 60 + self.end_minute
+    }
+
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        self.is_open_in(&Local, at)
+    }
+
+    /// `is_open` with the window read as wall-clock time in `tz`
+    fn is_open_in<Tz: TimeZone>(&self, tz: &Tz, at: DateTime<Utc>) -> bool {
+        let local = at.with_timezone(tz);
+        let minute = local.hour() * 60 + local.minute();
+        let (start, end) = (self.start_minutes(), self.end_minutes());
+        if start < end {
+            start <= minute && minute < end
+        } else {
+            minute >= start || minute < end
+        }
+    }
+
+    /// When the window next opens, or `at` itself if it's open now
+    pub fn next_open(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        if self.is_open(at) {
+            return at;
+        }
+        ScheduleSpec::Daily { hour: self.start_hour, minute: self.start_minute }
+            .next_after(at)
+            .unwrap_or(at)
+    }
+}
+
+/// `hour:minute` on `date` in `tz`. A time repeated when the clocks go back resolves to its
+/// first occurrence; one skipped when they go forward moves to the first valid minute after it.
+fn wall_clock_time<Tz: TimeZone>(tz: &Tz, date: NaiveDate, hour: u32, minute: u32) -> Option<DateTime<Utc>> {
+    let time = date.and_hms_opt(hour, minute, 0)?;
+    let resolved = match tz.from_local_datetime(&time) {
+        LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => at,
+        LocalResult::None => (1..=MAX_DST_GAP_MINUTES)
+            .find_map(|gap| tz.from_local_datetime(&(time + chrono::Duration::minutes(gap))).earliest())?,
+    };
+    Some(resolved.with_timezone(&Utc))
+}
+
+/// What to do with runs that were due while the app was closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CatchUpPolicy {
+    /// Run once as soon as the app starts
+    RunMissed,
+    /// Skip missed runs and wait for the next one
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferSchedule {
+    pub id: String,
+    pub task: TransferTask,
+    pub spec: ScheduleSpec,
+    pub catch_up: CatchUpPolicy,
+    pub next_run: DateTime<Utc>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TransferSchedule {
+    /// When the schedule should next fire after a restart at `now`, applying its catch-up
+    /// policy to a missed run; None when a missed one-off run is skipped
+    fn caught_up_next_run(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.next_run > now {
+            return Some(self.next_run);
+        }
+        match self.catch_up {
+            // Leaving next_run in the past makes the first tick fire it
+            CatchUpPolicy::RunMissed => Some(self.next_run),
+            CatchUpPolicy::Skip => self.spec.next_after(now),
+        }
+    }
+}
+
+impl ScheduleSpec {
+    fn validate(&self) -> Result<()> {
+        match self {
+            ScheduleSpec::Daily { hour, minute } if *hour > 23 || *minute > 59 => {
+                Err(Circle9Error::TransferError(format!("Invalid time of day {:02}:{:02}", hour, minute)))
+            }
+            ScheduleSpec::Interval { every_secs: 0 } => {
+                Err(Circle9Error::TransferError("Schedule interval must be greater than zero".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_recurring(&self) -> bool {
+        !matches!(self, ScheduleSpec::Once { .. })
+    }
+
+    /// The first run strictly after `after`, or `None` for a one-off schedule that has passed
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.next_after_in(&Local, after)
+    }
+
+    /// `next_after` with Daily times read as wall-clock time in `tz`. Each day's time is
+    /// resolved on its own, so a run stays at 02:00 across a DST change rather than
+    /// drifting by the hour the clocks moved.
+    fn next_after_in<Tz: TimeZone>(&self, tz: &Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            ScheduleSpec::Once { at } => (*at > after).then(|| *at),
+            ScheduleSpec::Daily { hour, minute } => {
+                let mut date = after.with_timezone(tz).date_naive();
+                // Today's time may have passed; tomorrow's can't have
+                for _ in 0..2 {
+                    let candidate = wall_clock_time(tz, date, *hour, *minute)?;
+                    if candidate > after {
+                        return Some(candidate);
+                    }
+                    date = date.succ_opt()?;
+                }
+                None
+            }
+            ScheduleSpec::Interval { every_secs } => {
+                Some(after + chrono::Duration::seconds(*every_secs as i64))
+            }
+        }
+    }
+
+    fn first_run(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            ScheduleSpec::Once { at } => *at,
+            _ => self.next_after(now).unwrap_or(now),
+        }
+    }
+}
+
+pub struct TransferScheduler {
+    schedules: Arc<Mutex<HashMap<String, TransferSchedule>>>,
+    app_handle: Arc<AppHandle>,
+}
+
+impl TransferScheduler {
+    pub fn new(app_handle: Arc<AppHandle>) -> Self {
+        Self {
+            schedules: Arc::new(Mutex::new(HashMap::new())),
+            app_handle,
+        }
+    }
+
+    /// Hold a pending task until the schedule fires
+    pub fn schedule(&self, task_id: &str, spec: ScheduleSpec, catch_up: CatchUpPolicy) -> Result<String> {
+        spec.validate()?;
+
+        let copy_agent = self.app_handle.state::<CopyAgent>();
+        let task = copy_agent.hold_for_schedule(task_id)?;
+
+        let now = Utc::now();
+        let schedule = TransferSchedule {
+            id: Uuid::new_v4().to_string(),
+            task,
+            next_run: spec.first_run(now),
+            spec,
+            catch_up,
+            last_run: None,
+            created_at: now,
+        };
+        tracing::info!("Scheduled transfer {} for {}", task_id, schedule.next_run);
+
+        let schedule_id = schedule.id.clone();
+        lock_or_error(&self.schedules)?.insert(schedule_id.clone(), schedule);
+        self.persist()?;
+
+        Ok(schedule_id)
+    }
+
+    pub fn list(&self) -> Vec<TransferSchedule> {
+        lock_or_error(&self.schedules)
+            .map(|s| s.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove a schedule; a one-off schedule's held task is cancelled with it
+    pub fn cancel(&self, schedule_id: &str) -> Result<()> {
+        let removed = lock_or_error(&self.schedules)?.remove(schedule_id);
+        if let Some(schedule) = removed {
+            self.app_handle.state::<CopyAgent>()
+                .cancel_with_reason(&schedule.task.id, "Schedule cancelled")?;
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the schedules holding any of `task_ids`, whose tasks were already cancelled
+    pub fn cancel_for_tasks(&self, task_ids: &[String]) -> Result<usize> {
+        let removed = {
+            let mut schedules = lock_or_error(&self.schedules)?;
+            let before = schedules.len();
+            schedules.retain(|_, s| !task_ids.contains(&s.task.id));
+            before - schedules.len()
+        };
+        if removed > 0 {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    /// Release every schedule whose time has come
+    pub fn fire_due(&self) -> Result<usize> {
+        let now = Utc::now();
+        let due: Vec<TransferSchedule> = lock_or_error(&self.schedules)?
+            .values()
+            .filter(|s| s.next_run <= now)
+            .cloned()
+            .collect();
+
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        let copy_agent = self.app_handle.state::<CopyAgent>();
+        for schedule in &due {
+            let next_run = if schedule.spec.is_recurring() {
+                match copy_agent.run_from_template(&schedule.task) {
+                    Ok(run_id) => tracing::info!("Schedule {} started transfer {}", schedule.id, run_id),
+                    Err(e) => tracing::error!("Schedule {} failed to start: {}", schedule.id, e),
+                }
+                schedule.spec.next_after(now)
+            } else {
+                copy_agent.release_scheduled(&schedule.task.id)?;
+                tracing::info!("Schedule {} released transfer {}", schedule.id, schedule.task.id);
+                None
+            };
+
+            let mut schedules = lock_or_error(&self.schedules)?;
+            match next_run {
+                Some(next_run) => {
+                    if let Some(s) = schedules.get_mut(&schedule.id) {
+                        s.last_run = Some(now);
+                        s.next_run = next_run;
+                    }
+                }
+                None => {
+                    schedules.remove(&schedule.id);
+                }
+            }
+        }
+
+        self.persist()?;
+        Ok(due.len())
+    }
+
+    /// Spawn the timer that fires due schedules and opens or closes allowed-hours windows
+    pub fn start(&self) {
+        let app_handle = Arc::clone(&self.app_handle);
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULER_TICK);
+            loop {
+                interval.tick().await;
+                if let Err(e) = app_handle.state::<TransferScheduler>().fire_due() {
+                    tracing::error!("Failed to fire scheduled transfers: {}", e);
+                }
+                if let Err(e) = app_handle.state::<CopyAgent>().enforce_allowed_hours() {
+                    tracing::error!("Failed to apply allowed transfer hours: {}", e);
+                }
+                // Let a probe through once a circuit breaker's cooldown has passed
+                if let Err(e) = app_handle.state::<CopyAgent>().release_blocked(None) {
+                    tracing::error!("Failed to release blocked transfers: {}", e);
+                }
+            }
+        });
+    }
+
+    fn schedules_file() -> Result<PathBuf> {
+        Ok(crate::utils::app_data_dir()?.join("schedules.json"))
+    }
+
+    fn persist(&self) -> Result<()> {
+        let schedules = self.list();
+        let path = Self::schedules_file()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::utils::write_atomic(&path, serde_json::to_string_pretty(&schedules)?.as_bytes())
+    }
+
+    /// Load persisted schedules, applying each one's catch-up policy to missed runs
+    pub fn restore(&self) -> Result<usize> {
+        let path = Self::schedules_file()?;
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let persisted: Vec<TransferSchedule> = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let copy_agent = self.app_handle.state::<CopyAgent>();
+        let now = Utc::now();
+        let mut restored = 0;
+
+        for mut schedule in persisted {
+            copy_agent.restore_scheduled_task(schedule.task.clone())?;
+
+            match schedule.caught_up_next_run(now) {
+                Some(next_run) => schedule.next_run = next_run,
+                None => {
+                    tracing::info!("Skipping missed one-off schedule {}", schedule.id);
+                    copy_agent.cancel_with_reason(&schedule.task.id, "Missed scheduled start")?;
+                    continue;
+                }
+            }
+
+            lock_or_error(&self.schedules)?.insert(schedule.id.clone(), schedule);
+            restored += 1;
+        }
+
+        self.persist()?;
+        Ok(restored)
+    }
+}
+
+// Tauri commands for transfer scheduling
+
+#[tauri::command]
+pub async fn schedule_transfer(
+    scheduler: State<'_, TransferScheduler>,
+    task_id: String,
+    start_at: DateTime<Utc>,
+    catch_up: Option<CatchUpPolicy>,
+) -> Result<String, String> {
+    scheduler.schedule(
+        &task_id,
+        ScheduleSpec::Once { at: start_at },
+        catch_up.unwrap_or(CatchUpPolicy::RunMissed),
+    ).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn schedule_recurring_transfer(
+    scheduler: State<'_, TransferScheduler>,
+    task_id: String,
+    spec: ScheduleSpec,
+    catch_up: Option<CatchUpPolicy>,
+) -> Result<String, String> {
+    scheduler.schedule(&task_id, spec, catch_up.unwrap_or(CatchUpPolicy::RunMissed))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_transfer_schedules(
+    scheduler: State<'_, TransferScheduler>
+) -> Result<Vec<TransferSchedule>, String> {
+    Ok(scheduler.list())
+}
+
+#[tauri::command]
+pub async fn cancel_transfer_schedule(
+    scheduler: State<'_, TransferScheduler>,
+    schedule_id: String
+) -> Result<(), String> {
+    scheduler.cancel(&schedule_id)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDateTime};
+    use crate::copy_agent::TransferDirection;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    /// UTC+1, moving to UTC+2 at 01:00 UTC on 2026-03-29: local 02:00–03:00 that night doesn't exist
+    #[derive(Debug, Clone)]
+    struct SpringForward;
+
+    impl SpringForward {
+        fn switch() -> NaiveDateTime {
+            utc("2026-03-29T01:00:00Z").naive_utc()
+        }
+
+        fn offset(hours: i32) -> FixedOffset {
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SpringForward
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let winter = *local - chrono::Duration::hours(1) < Self::switch();
+            let summer = *local - chrono::Duration::hours(2) >= Self::switch();
+            match (winter, summer) {
+                (true, true) => LocalResult::Ambiguous(Self::offset(1), Self::offset(2)),
+                (true, false) => LocalResult::Single(Self::offset(1)),
+                (false, true) => LocalResult::Single(Self::offset(2)),
+                (false, false) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset(if *utc < Self::switch() { 1 } else { 2 })
+        }
+    }
+
+    fn schedule(spec: ScheduleSpec, catch_up: CatchUpPolicy, next_run: DateTime<Utc>) -> TransferSchedule {
+        TransferSchedule {
+            id: "schedule".to_string(),
+            task: TransferTask::new("a".to_string(), "b".to_string(), TransferDirection::LinuxToWindows, 0),
+            spec,
+            catch_up,
+            next_run,
+            last_run: None,
+            created_at: next_run,
+        }
+    }
+
+    #[test]
+    fn once_only_fires_in_the_future() {
+        let at = utc("2026-05-01T12:00:00Z");
+        let spec = ScheduleSpec::Once { at };
+        assert_eq!(spec.next_after(utc("2026-05-01T11:59:00Z")), Some(at));
+        assert_eq!(spec.next_after(at), None);
+    }
+
+    #[test]
+    fn interval_adds_the_gap() {
+        let spec = ScheduleSpec::Interval { every_secs: 90 };
+        assert_eq!(spec.next_after(utc("2026-05-01T12:00:00Z")), Some(utc("2026-05-01T12:01:30Z")));
+    }
+
+    #[test]
+    fn daily_runs_today_or_tomorrow() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let spec = ScheduleSpec::Daily { hour: 3, minute: 0 };
+        // 02:30 local: 03:00 is still to come today
+        assert_eq!(spec.next_after_in(&tz, utc("2026-05-01T00:30:00Z")), Some(utc("2026-05-01T01:00:00Z")));
+        // Exactly at the run time moves to the next day
+        assert_eq!(spec.next_after_in(&tz, utc("2026-05-01T01:00:00Z")), Some(utc("2026-05-02T01:00:00Z")));
+        // Just before local midnight, the next run is on the following local date
+        assert_eq!(spec.next_after_in(&tz, utc("2026-05-01T21:59:00Z")), Some(utc("2026-05-02T01:00:00Z")));
+    }
+
+    #[test]
+    fn daily_keeps_wall_clock_time_across_dst() {
+        let spec = ScheduleSpec::Daily { hour: 1, minute: 30 };
+        // 01:30 at UTC+1 on the 29th, then 01:30 at UTC+2 on the 30th
+        assert_eq!(spec.next_after_in(&SpringForward, utc("2026-03-28T23:00:00Z")), Some(utc("2026-03-29T00:30:00Z")));
+        assert_eq!(spec.next_after_in(&SpringForward, utc("2026-03-29T00:30:00Z")), Some(utc("2026-03-29T23:30:00Z")));
+    }
+
+    #[test]
+    fn daily_time_skipped_by_dst_moves_to_the_next_valid_minute() {
+        let spec = ScheduleSpec::Daily { hour: 2, minute: 30 };
+        // 02:30 on the 29th doesn't exist; 03:00 at UTC+2 is the first minute that does
+        let next = spec.next_after_in(&SpringForward, utc("2026-03-28T01:30:00Z"));
+        assert_eq!(next, Some(utc("2026-03-29T01:00:00Z")));
+        assert_eq!(spec.next_after_in(&SpringForward, next.unwrap()), Some(utc("2026-03-30T00:30:00Z")));
+    }
+
+    fn window(start: (u32, u32), end: (u32, u32)) -> AllowedHours {
+        AllowedHours { start_hour: start.0, start_minute: start.1, end_hour: end.0, end_minute: end.1 }
+    }
+
+    #[test]
+    fn allowed_hours_within_a_day() {
+        let tz = FixedOffset::east_opt(0).unwrap();
+        let hours = window((9, 0), (17, 30));
+        assert!(!hours.is_open_in(&tz, utc("2026-05-01T08:59:00Z")));
+        assert!(hours.is_open_in(&tz, utc("2026-05-01T09:00:00Z")));
+        assert!(hours.is_open_in(&tz, utc("2026-05-01T17:29:00Z")));
+        assert!(!hours.is_open_in(&tz, utc("2026-05-01T17:30:00Z")));
+    }
+
+    #[test]
+    fn allowed_hours_across_midnight() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let hours = window((22, 0), (6, 0));
+        // Local 21:59, 22:00, 00:00, 05:59 and 06:00
+        assert!(!hours.is_open_in(&tz, utc("2026-05-01T19:59:00Z")));
+        assert!(hours.is_open_in(&tz, utc("2026-05-01T20:00:00Z")));
+        assert!(hours.is_open_in(&tz, utc("2026-05-01T22:00:00Z")));
+        assert!(hours.is_open_in(&tz, utc("2026-05-02T03:59:00Z")));
+        assert!(!hours.is_open_in(&tz, utc("2026-05-02T04:00:00Z")));
+        assert!(hours.validate().is_ok());
+        assert!(window((22, 0), (22, 0)).validate().is_err());
+    }
+
+    #[test]
+    fn catch_up_policies() {
+        let now = utc("2026-05-01T12:00:00Z");
+        let missed = utc("2026-05-01T11:00:00Z");
+        let interval = ScheduleSpec::Interval { every_secs: 3600 };
+
+        let upcoming = schedule(interval.clone(), CatchUpPolicy::Skip, utc("2026-05-01T13:00:00Z"));
+        assert_eq!(upcoming.caught_up_next_run(now), Some(upcoming.next_run));
+
+        let run_missed = schedule(interval.clone(), CatchUpPolicy::RunMissed, missed);
+        assert_eq!(run_missed.caught_up_next_run(now), Some(missed));
+
+        let skip = schedule(interval, CatchUpPolicy::Skip, missed);
+        assert_eq!(skip.caught_up_next_run(now), Some(utc("2026-05-01T13:00:00Z")));
+
+        let once = ScheduleSpec::Once { at: missed };
+        assert_eq!(schedule(once.clone(), CatchUpPolicy::Skip, missed).caught_up_next_run(now), None);
+        assert_eq!(schedule(once, CatchUpPolicy::RunMissed, missed).caught_up_next_run(now), Some(missed));
+    }
+}
@@ -0,0 +1,109 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use crate::error::{Circle9Error, Result};
+use crate::ssh_client::SSHConnection;
+use crate::utils::lock_or_error;
+
+/// Upload a file with SCP, reporting (bytes sent, total) after each chunk. An error from
+/// `on_progress`, e.g. because the transfer was paused, stops the upload and is returned.
+/// The session is locked per chunk rather than for the whole file.
+pub fn scp_upload<F>(
+    connection: &SSHConnection,
+    local_path: &str,
+    remote_path: &str,
+    chunk_size: usize,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(u64, u64) -> Result<()>,
+{
+    let mut local_file = File::open(local_path)?;
+    let metadata = local_file.metadata()?;
+    let total = metadata.len();
+
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        (metadata.permissions().mode() & 0o777) as i32
+    };
+    // No local permissions to carry over, so the file gets the settings' umask
+    #[cfg(not(unix))]
+    let mode = crate::settings::current().file_mode();
+
+    let mut channel = lock_or_error(&connection.session)?
+        .scp_send(Path::new(remote_path), mode, total, None)?;
+
+    let mut buffer = vec![0u8; chunk_size];
+    let mut sent = 0u64;
+    loop {
+        let n = local_file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        connection.bandwidth.acquire(n);
+        {
+            let _session = lock_or_error(&connection.session)?;
+            channel.write_all(&buffer[..n])?;
+        }
+        sent += n as u64;
+        on_progress(sent, total)?;
+    }
+
+    let _session = lock_or_error(&connection.session)?;
+    channel.send_eof()?;
+    channel.wait_eof()?;
+    channel.close()?;
+    channel.wait_close()?;
+    Ok(())
+}
+
+/// Download a file with SCP, reporting (bytes received, total) after each chunk. An error from
+/// `on_progress` stops the download and is returned. The session is locked per chunk rather
+/// than for the whole file.
+pub fn scp_download<F>(
+    connection: &SSHConnection,
+    remote_path: &str,
+    local_path: &str,
+    chunk_size: usize,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(u64, u64) -> Result<()>,
+{
+    let (mut channel, stat) = lock_or_error(&connection.session)?
+        .scp_recv(Path::new(remote_path))?;
+    let total = stat.size();
+
+    let mut local_file = File::create(local_path)?;
+    let mut buffer = vec![0u8; chunk_size];
+    let mut received = 0u64;
+    // The channel carries SCP protocol bytes after the file body, so stop at the advertised size
+    while received < total {
+        let want = buffer.len().min((total - received) as usize);
+        let n = {
+            let _session = lock_or_error(&connection.session)?;
+            channel.read(&mut buffer[..want])?
+        };
+        if n == 0 {
+            break;
+        }
+        connection.bandwidth.acquire(n);
+        local_file.write_all(&buffer[..n])?;
+        received += n as u64;
+        on_progress(received, total)?;
+    }
+    if received != total {
+        return Err(Circle9Error::TransferError(format!(
+            "SCP download of {} ended after {} of {} bytes", remote_path, received, total
+        )));
+    }
+    local_file.sync_all()?;
+
+    let _session = lock_or_error(&connection.session)?;
+    channel.send_eof()?;
+    channel.wait_eof()?;
+    channel.close()?;
+    channel.wait_close()?;
+    Ok(())
+}
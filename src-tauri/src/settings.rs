@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use crate::audit_log::AUDIT_LOGGER;
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::error::{Circle9Error, Result};
+use crate::listing_cache::DEFAULT_LISTING_CACHE_TTL;
+use crate::permission_agent::PermissionProfile;
+use crate::scheduler::AllowedHours;
+use crate::ssh_client::{ConnectionTuning, PoisonPolicy, SSHClient};
+use crate::transform::LineEndingOverrides;
+use crate::utils::ProgressThrottleConfig;
+
+/// How a Windows → Linux case-only name clash is resolved on a case-sensitive remote
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CaseStrategy {
+    #[default]
+    AutoRename,
+    Prompt,
+}
+
+/// Every app-wide tunable, persisted as `settings.json` in the app data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub max_concurrent_transfers: usize,
+    pub progress_throttle: ProgressThrottleConfig,
+    /// Tuning given to new connections; `set_connection_tuning` overrides it per connection
+    pub default_tuning: ConnectionTuning,
+    /// Bandwidth cap for new connections in bytes/sec, 0 for none
+    pub default_bandwidth_limit: u64,
+    pub keepalive_interval_secs: u64,
+    /// Timeout for remote commands that don't specify their own
+    pub command_timeout_secs: u64,
+    pub listing_cache_ttl_secs: u64,
+    pub case_strategy: CaseStrategy,
+    /// Audit entries older than this are dropped at startup; None keeps everything
+    pub audit_retention_days: Option<u32>,
+    /// Check the target directory is writable before the copy agent starts an upload
+    pub preflight_write_check: bool,
+    pub permission_profile: PermissionProfile,
+    /// Window transfers without their own `allowed_hours` may run in; None means any time
+    pub allowed_hours: Option<AllowedHours>,
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Remote and local mtimes this close are treated as equal after correcting for clock skew
+    pub mtime_tolerance_secs: u64,
+    /// Clock skew beyond this raises `clock_skew_warning`
+    pub clock_skew_warning_secs: u64,
+    pub keepalive_poison_policy: PoisonPolicy,
+    /// Files larger than this are refused when a transfer is created unless overridden; None for no limit
+    pub max_transfer_file_size: Option<u64>,
+    /// Resume transfers interrupted by the last shutdown as soon as the app starts
+    pub auto_resume_transfers: bool,
+    /// Extensions `auto_line_endings` treats as text or binary without looking at the content
+    pub line_ending_overrides: LineEndingOverrides,
+    /// Permission bits cleared from files and directories the app creates on a remote
+    pub umask: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_transfers: 3,
+            progress_throttle: ProgressThrottleConfig::default(),
+            default_tuning: ConnectionTuning::default(),
+            default_bandwidth_limit: 0,
+            keepalive_interval_secs: 60,
+            command_timeout_secs: 30,
+            listing_cache_ttl_secs: DEFAULT_LISTING_CACHE_TTL.as_secs(),
+            case_strategy: CaseStrategy::AutoRename,
+            audit_retention_days: None,
+            preflight_write_check: true,
+            permission_profile: PermissionProfile::default(),
+            allowed_hours: None,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            mtime_tolerance_secs: 2,
+            clock_skew_warning_secs: 120,
+            keepalive_poison_policy: PoisonPolicy::default(),
+            max_transfer_file_size: None,
+            auto_resume_transfers: false,
+            line_ending_overrides: LineEndingOverrides::default(),
+            umask: 0o022,
+        }
+    }
+}
+
+/// Fields to change in `update_settings`; anything left out keeps its current value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsPatch {
+    pub max_concurrent_transfers: Option<usize>,
+    pub progress_throttle: Option<ProgressThrottleConfig>,
+    pub default_tuning: Option<ConnectionTuning>,
+    pub default_bandwidth_limit: Option<u64>,
+    pub keepalive_interval_secs: Option<u64>,
+    pub command_timeout_secs: Option<u64>,
+    pub listing_cache_ttl_secs: Option<u64>,
+    pub case_strategy: Option<CaseStrategy>,
+    /// Some(None) clears the retention limit
+    pub audit_retention_days: Option<Option<u32>>,
+    pub preflight_write_check: Option<bool>,
+    pub permission_profile: Option<PermissionProfile>,
+    /// Some(None) removes the window
+    pub allowed_hours: Option<Option<AllowedHours>>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub mtime_tolerance_secs: Option<u64>,
+    pub clock_skew_warning_secs: Option<u64>,
+    pub keepalive_poison_policy: Option<PoisonPolicy>,
+    /// Some(None) removes the limit
+    pub max_transfer_file_size: Option<Option<u64>>,
+    pub auto_resume_transfers: Option<bool>,
+    pub line_ending_overrides: Option<LineEndingOverrides>,
+    pub umask: Option<u32>,
+}
+
+impl Settings {
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_secs(self.command_timeout_secs)
+    }
+
+    pub fn keepalive_interval(&self) -> Duration {
+        Duration::from_secs(self.keepalive_interval_secs)
+    }
+
+    /// Mode for a new remote file after the umask
+    pub fn file_mode(&self) -> i32 {
+        (0o666 & !self.umask) as i32
+    }
+
+    /// Mode for a new remote directory after the umask
+    pub fn dir_mode(&self) -> i32 {
+        (0o777 & !self.umask) as i32
+    }
+
+    /// Merge `patch` in. A permission profile goes through the same normalisation as
+    /// `set_permission_profile`, so extensions match however they were written.
+    fn apply_patch(&mut self, patch: SettingsPatch) -> Result<()> {
+        if let Some(v) = patch.max_concurrent_transfers { self.max_concurrent_transfers = v; }
+        if let Some(v) = patch.progress_throttle { self.progress_throttle = v; }
+        if let Some(v) = patch.default_tuning { self.default_tuning = v; }
+        if let Some(v) = patch.default_bandwidth_limit { self.default_bandwidth_limit = v; }
+        if let Some(v) = patch.keepalive_interval_secs { self.keepalive_interval_secs = v; }
+        if let Some(v) = patch.command_timeout_secs { self.command_timeout_secs = v; }
+        if let Some(v) = patch.listing_cache_ttl_secs { self.listing_cache_ttl_secs = v; }
+        if let Some(v) = patch.case_strategy { self.case_strategy = v; }
+        if let Some(v) = patch.audit_retention_days { self.audit_retention_days = v; }
+        if let Some(v) = patch.preflight_write_check { self.preflight_write_check = v; }
+        if let Some(v) = patch.permission_profile {
+            self.permission_profile = PermissionProfile::normalized(v.by_extension, v.default_mode)?;
+        }
+        if let Some(v) = patch.allowed_hours { self.allowed_hours = v; }
+        if let Some(v) = patch.circuit_breaker { self.circuit_breaker = v; }
+        if let Some(v) = patch.mtime_tolerance_secs { self.mtime_tolerance_secs = v; }
+        if let Some(v) = patch.clock_skew_warning_secs { self.clock_skew_warning_secs = v; }
+        if let Some(v) = patch.keepalive_poison_policy { self.keepalive_poison_policy = v; }
+        if let Some(v) = patch.max_transfer_file_size { self.max_transfer_file_size = v; }
+        if let Some(v) = patch.auto_resume_transfers { self.auto_resume_transfers = v; }
+        if let Some(v) = patch.line_ending_overrides { self.line_ending_overrides = v; }
+        if let Some(v) = patch.umask { self.umask = v; }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.max_concurrent_transfers == 0 || self.keepalive_interval_secs == 0 || self.command_timeout_secs == 0 {
+            return Err(Circle9Error::InvalidValue(
+                "Concurrency, keepalive interval and command timeout must be greater than zero".to_string(),
+            ));
+        }
+        if self.max_transfer_file_size == Some(0) {
+            return Err(Circle9Error::InvalidValue(
+                "Maximum transfer file size must be greater than zero; clear it to remove the limit".to_string(),
+            ));
+        }
+        if self.umask > 0o777 {
+            return Err(Circle9Error::InvalidValue(format!("Umask {:o} has bits outside 0777", self.umask)));
+        }
+        if let Some(window) = &self.allowed_hours {
+            window.validate()?;
+        }
+        self.circuit_breaker.validate()?;
+        self.default_tuning.validate()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+}
+
+/// Snapshot of the current settings
+pub fn current() -> Settings {
+    SETTINGS.read()
+        .map(|s| s.clone())
+        .unwrap_or_default()
+}
+
+fn settings_file() -> Result<PathBuf> {
+    Ok(crate::utils::app_data_dir()?.join("settings.json"))
+}
+
+/// Load `settings.json` at startup and apply it; a missing or unreadable file means defaults
+pub fn load(app_handle: &AppHandle) -> Result<Settings> {
+    let path = settings_file()?;
+    let settings = if path.exists() {
+        match serde_json::from_str::<Settings>(&std::fs::read_to_string(&path)?) {
+            Ok(settings) if settings.validate().is_ok() => settings,
+            Ok(_) | Err(_) => {
+                tracing::warn!("Ignoring invalid settings file {}, using defaults", path.display());
+                Settings::default()
+            }
+        }
+    } else {
+        Settings::default()
+    };
+
+    *SETTINGS.write().map_err(|_| Circle9Error::MutexPoisoned)? = settings.clone();
+    apply(app_handle, None, &settings);
+    Ok(settings)
+}
+
+/// Merge `patch` into the current settings, persist them and apply what can change live
+pub fn update(app_handle: &AppHandle, patch: SettingsPatch) -> Result<Settings> {
+    let (previous, settings) = {
+        let mut current = SETTINGS.write().map_err(|_| Circle9Error::MutexPoisoned)?;
+        let mut updated = current.clone();
+        updated.apply_patch(patch)?;
+        updated.validate()?;
+
+        let path = settings_file()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::utils::write_atomic(&path, serde_json::to_string_pretty(&updated)?.as_bytes())?;
+
+        let previous = std::mem::replace(&mut *current, updated.clone());
+        (previous, updated)
+    };
+
+    apply(app_handle, Some(&previous), &settings);
+    if let Err(e) = app_handle.emit_all("settings_changed", &settings) {
+        tracing::error!("Failed to emit settings_changed: {}", e);
+    }
+    Ok(settings)
+}
+
+/// Push settings into components that cache them. Everything else (transfer concurrency,
+/// progress throttling, timeouts, case strategy) reads `current()` when it needs a value;
+/// connection defaults and keepalive apply to connections opened afterwards.
+fn apply(app_handle: &AppHandle, previous: Option<&Settings>, settings: &Settings) {
+    app_handle.state::<SSHClient>()
+        .listing_cache
+        .set_ttl(Duration::from_secs(settings.listing_cache_ttl_secs));
+
+    let retention_changed = previous.map_or(true, |p| p.audit_retention_days != settings.audit_retention_days);
+    if let (true, Some(days)) = (retention_changed, settings.audit_retention_days) {
+        match AUDIT_LOGGER.prune_older_than(days) {
+            Ok(0) => {}
+            Ok(dropped) => tracing::info!("Dropped {} audit entries older than {} days", dropped, days),
+            Err(e) => tracing::error!("Failed to prune audit log: {}", e),
+        }
+    }
+}
+
+// Tauri commands for settings
+
+#[tauri::command]
+pub async fn get_settings() -> Result<Settings, String> {
+    Ok(current())
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    app_handle: AppHandle,
+    patch: SettingsPatch,
+) -> Result<Settings, String> {
+    update(&app_handle, patch)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn profile_patch(ext: &str, mode: u32) -> SettingsPatch {
+        SettingsPatch {
+            permission_profile: Some(PermissionProfile {
+                by_extension: HashMap::from([(ext.to_string(), mode)]),
+                default_mode: None,
+            }),
+            ..SettingsPatch::default()
+        }
+    }
+
+    #[test]
+    fn patched_permission_profile_is_normalised() {
+        let mut settings = Settings::default();
+        settings.apply_patch(profile_patch(".SH", 0o755)).unwrap();
+        assert_eq!(settings.permission_profile.by_extension.get("sh"), Some(&0o755));
+    }
+
+    #[test]
+    fn patched_permission_profile_rejects_bad_modes() {
+        let mut settings = Settings::default();
+        assert!(settings.apply_patch(profile_patch("sh", 0o17777)).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use crate::audit_log::AUDIT_LOGGER;
+use crate::copy_agent::CopyAgent;
+use crate::ssh_client::SSHClient;
+
+/// How long in-flight chunk writes get to finish before we stop waiting
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Upper bound on the grace period the frontend may ask for
+const MAX_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
+/// Set once the shutdown sequence has run, so an exit it triggers doesn't run it again
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Pause transfers, persist the queue, flush the audit log and close SSH sessions
+pub async fn graceful_shutdown(app_handle: &AppHandle, grace: Duration) {
+    if SHUT_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tracing::info!("Shutting down Circle9");
+
+    let copy_agent = app_handle.state::<CopyAgent>();
+    match copy_agent.pause_all_transfers() {
+        Ok(paused) => tracing::info!("Paused {} transfers for shutdown", paused),
+        Err(e) => tracing::error!("Failed to pause transfers: {}", e),
+    }
+
+    // Give running chunk loops a chance to notice the pause and flush their writers
+    let deadline = Instant::now() + grace;
+    while copy_agent.has_in_flight_transfers() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    if copy_agent.has_in_flight_transfers() {
+        tracing::warn!("Grace period elapsed with transfers still in flight");
+    }
+
+    if let Err(e) = copy_agent.persist_queue() {
+        tracing::error!("Failed to persist transfer queue: {}", e);
+    }
+
+    if let Err(e) = AUDIT_LOGGER.flush() {
+        tracing::error!("Failed to flush audit log: {}", e);
+    }
+
+    app_handle.state::<SSHClient>().disconnect_all();
+}
+
+// Tauri commands for shutdown
+
+/// Run the shutdown sequence and then exit with `exit_code`, for "Quit safely" and
+/// automated tests; closing the window isn't guaranteed to run cleanup
+#[tauri::command]
+pub async fn shutdown_app(
+    app_handle: AppHandle,
+    grace_secs: Option<u64>,
+    exit_code: Option<i32>,
+) -> Result<(), String> {
+    let grace = grace_secs
+        .map(Duration::from_secs)
+        .unwrap_or(SHUTDOWN_GRACE_PERIOD)
+        .min(MAX_GRACE_PERIOD);
+    graceful_shutdown(&app_handle, grace).await;
+
+    let exit_code = exit_code.unwrap_or(0);
+    tracing::info!("Exiting with code {}", exit_code);
+    app_handle.exit(exit_code);
+    Ok(())
+}
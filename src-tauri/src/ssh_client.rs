@@ -1,15 +1,62 @@
-use ssh2::{Session, Sftp};
-use std::net::TcpStream;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ssh2::{HashType, Session, Sftp};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio::time::interval;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tauri::{AppHandle, State};
+use chrono::{DateTime, Utc};
+use crate::background_jobs;
 use crate::error::{Circle9Error, Result};
 use crate::types::ConnectionId;
-use crate::utils::with_timeout;
+use crate::listing_cache::ListingCache;
+use crate::remote_env::RemoteEnvironment;
+use crate::rate_limit::RateLimiter;
+use crate::remote_users::PasswdEntry;
+use crate::settings;
+use crate::utils::{lock_or_error, with_timeout};
+
+/// Connections with no activity for this long are closed by the keepalive task
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What the keepalive task does when a panic elsewhere left the connection table's lock poisoned
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum PoisonPolicy {
+    /// Stop this connection's keepalive; the connection itself stays usable
+    #[default]
+    Terminate,
+    /// Keep going with the data behind the lock, which a panic may have left half-updated
+    Recover,
+}
+
+type ConnectionTable = HashMap<String, SSHConnection>;
+
+/// Lock the connection table for the keepalive task of `connection_id`.
+/// None means the lock is poisoned and the policy says to stop.
+fn lock_for_keepalive<'a>(connections: &'a Mutex<ConnectionTable>, connection_id: &str) -> Option<MutexGuard<'a, ConnectionTable>> {
+    lock_with_policy(connections, connection_id, settings::current().keepalive_poison_policy)
+}
+
+fn lock_with_policy<'a, T>(mutex: &'a Mutex<T>, connection_id: &str, policy: PoisonPolicy) -> Option<MutexGuard<'a, T>> {
+    match mutex.lock() {
+        Ok(guard) => Some(guard),
+        Err(poisoned) => match policy {
+            PoisonPolicy::Recover => {
+                tracing::warn!("Connection table lock is poisoned; keepalive for {} carrying on", connection_id);
+                Some(poisoned.into_inner())
+            }
+            PoisonPolicy::Terminate => {
+                tracing::error!("Connection table lock is poisoned; stopping keepalive for {}", connection_id);
+                None
+            }
+        },
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SSHConfig {
@@ -18,33 +65,392 @@ pub struct SSHConfig {
     pub username: String,
     pub key_path: Option<String>,
     pub password: Option<String>,
+    /// Decrypts `key_path` when the private key is password-protected
+    #[serde(default)]
+    pub key_passphrase: Option<String>,
+    /// Pinned host key, as `SHA256:<base64>` or MD5 hex (`MD5:aa:bb:...` or `aa:bb:...`);
+    /// the connection is refused if the server presents any other key
+    #[serde(default)]
+    pub expected_host_key_fingerprint: Option<String>,
+}
+
+/// A problem with one field of an `SSHConfig`, for form validation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl SSHConfig {
+    /// The same config logging in with `password`
+    pub fn with_password(&self, password: &str) -> SSHConfig {
+        SSHConfig { password: Some(password.to_string()), ..self.clone() }
+    }
+
+    /// Check the fields without touching the network. An empty list means the config looks usable.
+    pub fn validate(&self) -> Vec<ConfigFieldError> {
+        let mut errors = Vec::new();
+        let mut error = |field: &str, message: String| errors.push(ConfigFieldError {
+            field: field.to_string(),
+            message,
+        });
+
+        if self.host.trim().is_empty() {
+            error("host", "Host is required".to_string());
+        } else if self.host.chars().any(char::is_whitespace) {
+            error("host", "Host must not contain spaces".to_string());
+        }
+        if self.port == 0 {
+            error("port", "Port must be between 1 and 65535".to_string());
+        }
+        if self.username.trim().is_empty() {
+            error("username", "Username is required".to_string());
+        }
+
+        let key_path = self.key_path.as_deref().filter(|p| !p.trim().is_empty());
+        let password = self.password.as_deref().filter(|p| !p.is_empty());
+        if key_path.is_none() && password.is_none() {
+            error("password", "Provide a password or a private key".to_string());
+        }
+
+        if let Some(key_path) = key_path {
+            match std::fs::read_to_string(key_path) {
+                Err(e) => error("key_path", format!("Cannot read key file: {}", e)),
+                Ok(contents) if !contents.contains("PRIVATE KEY") => {
+                    error("key_path", "File is not a private key".to_string());
+                }
+                Ok(contents) => {
+                    let has_passphrase = self.key_passphrase.as_deref().map_or(false, |p| !p.is_empty());
+                    if Self::is_encrypted_key(&contents) && !has_passphrase {
+                        error("key_passphrase", "This key is password-protected; enter its passphrase".to_string());
+                    }
+                }
+            }
+        }
+
+        if let Some(fingerprint) = self.expected_host_key_fingerprint.as_deref().filter(|f| !f.trim().is_empty()) {
+            if let Err(e) = FingerprintPin::parse(fingerprint) {
+                error("expected_host_key_fingerprint", e.to_string());
+            }
+        }
+
+        errors
+    }
+
+    /// Whether a private key file needs a passphrase, for OpenSSH and legacy PEM formats
+    fn is_encrypted_key(contents: &str) -> bool {
+        if contents.contains("ENCRYPTED") {
+            return true;
+        }
+        ssh_key::PrivateKey::from_openssh(contents)
+            .map(|key| key.is_encrypted())
+            .unwrap_or(false)
+    }
+}
+
+/// The server's host key fingerprints in the formats `ssh-keygen -l` prints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostKeyFingerprints {
+    /// `SHA256:` followed by unpadded base64
+    pub sha256: String,
+    /// `MD5:` followed by colon-separated hex
+    pub md5: String,
+}
+
+impl HostKeyFingerprints {
+    pub fn of(session: &Session) -> Option<Self> {
+        let sha256 = session.host_key_hash(HashType::Sha256)?;
+        let md5 = session.host_key_hash(HashType::Md5)?;
+        Some(Self {
+            sha256: format!("SHA256:{}", STANDARD_NO_PAD.encode(sha256)),
+            md5: format!("MD5:{}", md5.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")),
+        })
+    }
+}
+
+/// An expected fingerprint, normalised for comparison
+enum FingerprintPin {
+    Sha256(String),
+    Md5(String),
+}
+
+impl FingerprintPin {
+    fn parse(fingerprint: &str) -> Result<Self> {
+        let fingerprint = fingerprint.trim();
+        if let Some(encoded) = fingerprint.strip_prefix("SHA256:") {
+            let encoded = encoded.trim_end_matches('=');
+            if encoded.len() != 43 {
+                return Err(Circle9Error::SSHError("SHA-256 fingerprint must be 43 base64 characters".to_string()));
+            }
+            return Ok(Self::Sha256(encoded.to_string()));
+        }
+
+        let hex: String = fingerprint.strip_prefix("MD5:").unwrap_or(fingerprint)
+            .chars()
+            .filter(|c| *c != ':')
+            .collect::<String>()
+            .to_lowercase();
+        if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Circle9Error::SSHError(
+                "Fingerprint must be SHA256:<base64> or a 16-byte MD5 hex string".to_string(),
+            ));
+        }
+        Ok(Self::Md5(hex))
+    }
+
+    fn matches(&self, actual: &HostKeyFingerprints) -> bool {
+        match self {
+            Self::Sha256(expected) => actual.sha256.strip_prefix("SHA256:") == Some(expected.as_str()),
+            Self::Md5(expected) => actual.md5.trim_start_matches("MD5:").replace(':', "") == *expected,
+        }
+    }
+}
+
+/// Refuse the server unless its host key matches the pinned fingerprint, if there is one
+fn verify_host_key(session: &Session, config: &SSHConfig) -> Result<()> {
+    let expected = match config.expected_host_key_fingerprint.as_deref().filter(|f| !f.trim().is_empty()) {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+    let pin = FingerprintPin::parse(expected)?;
+    let actual = HostKeyFingerprints::of(session)
+        .ok_or_else(|| Circle9Error::SSHError("Server did not present a host key".to_string()))?;
+    if !pin.matches(&actual) {
+        return Err(Circle9Error::SSHError(format!(
+            "Host key mismatch for {}: expected {}, got {} ({})",
+            config.host, expected.trim(), actual.sha256, actual.md5
+        )));
+    }
+    Ok(())
+}
+
+/// Login directory read from a shell, for connections without SFTP's `realpath`
+fn login_directory(session: &Session) -> Option<String> {
+    use std::io::Read;
+    let mut channel = session.channel_session().ok()?;
+    channel.exec("pwd").ok()?;
+    let mut output = String::new();
+    channel.read_to_string(&mut output).ok()?;
+    channel.wait_close().ok()?;
+    let dir = output.trim();
+    (channel.exit_status().ok()? == 0 && dir.starts_with('/')).then(|| dir.to_string())
+}
+
+/// Stage at which a connection attempt failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ConnectFailureKind {
+    Dns,
+    TcpRefused,
+    TcpUnreachable,
+    Timeout,
+    Handshake,
+    HostKeyMismatch,
+    AuthFailed,
+    SftpUnavailable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionTestResult {
+    pub success: bool,
+    pub failure: Option<ConnectFailureKind>,
+    pub message: String,
+}
+
+/// Payload of the `ssh-idle-timeout` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleTimeoutEvent {
+    pub connection_id: String,
+    pub reason: String,
+    pub last_activity: DateTime<Utc>,
+}
+
+/// Protocol used to move file contents.
+/// SCP can be faster for large single files and works where the SFTP subsystem is
+/// disabled, but it can't resume an interrupted transfer; SFTP can.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum TransferProtocol {
+    #[default]
+    Sftp,
+    Scp,
+}
+
+/// Largest payload libssh2 puts in a single SFTP read or write request
+pub const SFTP_REQUEST_SIZE: usize = 30000;
+
+/// Default number of SFTP requests kept in flight; about 480 KB, enough for ~40 Mbit/s at 100 ms RTT
+pub const DEFAULT_SFTP_PIPELINE_DEPTH: usize = 16;
+
+/// Beyond this the extra buffering stops paying off and servers may start refusing requests
+pub const MAX_SFTP_PIPELINE_DEPTH: usize = 64;
+
+fn default_sftp_pipeline_depth() -> usize {
+    DEFAULT_SFTP_PIPELINE_DEPTH
+}
+
+/// Per-connection performance knobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionTuning {
+    pub chunk_size: usize,
+    pub max_concurrent_transfers: usize,
+    /// Upper bound on concurrent stat/readdir calls, e.g. during recursive walks
+    pub max_concurrent_metadata_ops: usize,
+    #[serde(default)]
+    pub transfer_protocol: TransferProtocol,
+    /// Outstanding SFTP read/write requests per file (1 to `MAX_SFTP_PIPELINE_DEPTH`).
+    /// Raise it on high-latency links; 1 means strictly one request per round trip.
+    #[serde(default = "default_sftp_pipeline_depth")]
+    pub sftp_pipeline_depth: usize,
+}
+
+impl ConnectionTuning {
+    pub fn validate(&self) -> Result<()> {
+        if self.chunk_size == 0 || self.max_concurrent_transfers == 0 || self.max_concurrent_metadata_ops == 0 {
+            return Err(Circle9Error::SSHError("Tuning values must be greater than zero".to_string()));
+        }
+        if self.sftp_pipeline_depth == 0 || self.sftp_pipeline_depth > MAX_SFTP_PIPELINE_DEPTH {
+            return Err(Circle9Error::SSHError(format!(
+                "SFTP pipeline depth must be between 1 and {}", MAX_SFTP_PIPELINE_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    /// Buffer size handed to each SFTP read/write. libssh2 splits a large buffer into
+    /// `SFTP_REQUEST_SIZE` requests and keeps them all in flight, so this sets the pipeline depth.
+    pub fn sftp_buffer_size(&self) -> usize {
+        self.chunk_size.max(self.sftp_pipeline_depth * SFTP_REQUEST_SIZE)
+    }
+}
+
+impl Default for ConnectionTuning {
+    fn default() -> Self {
+        Self {
+            chunk_size: 8192,
+            max_concurrent_transfers: 3,
+            max_concurrent_metadata_ops: 8,
+            transfer_protocol: TransferProtocol::Sftp,
+            sftp_pipeline_depth: DEFAULT_SFTP_PIPELINE_DEPTH,
+        }
+    }
+}
+
+/// Answers every keyboard-interactive prompt with the configured password
+struct PasswordPrompter<'a>(&'a str);
+
+impl ssh2::KeyboardInteractivePrompt for PasswordPrompter<'_> {
+    fn prompt<'b>(&mut self, _username: &str, _instructions: &str, prompts: &[ssh2::Prompt<'b>]) -> Vec<String> {
+        prompts.iter().map(|_| self.0.to_string()).collect()
+    }
+}
+
+/// One open session; several may share a `target` when they carry different labels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub connection_id: String,
+    /// `user@host:port` without the label
+    pub target: String,
+    pub label: Option<String>,
 }
 
 pub struct SSHConnection {
     pub session: Arc<Mutex<Session>>,
-    pub sftp: Arc<Mutex<Sftp>>,
+    /// None when the server has no SFTP subsystem and transfers go over SCP
+    pub sftp: Arc<Mutex<Option<Sftp>>>,
     pub last_activity: Arc<Mutex<Instant>>,
     pub config: SSHConfig,
+    pub tuning: Arc<Mutex<ConnectionTuning>>,
+    /// Remote OS and tooling, filled in the first time it's detected
+    pub environment: Arc<Mutex<Option<RemoteEnvironment>>>,
+    /// `command -v` results for commands outside the environment probe
+    pub available_commands: Arc<Mutex<HashMap<String, bool>>>,
+    /// Login directory, resolved once at connect for `~` expansion
+    pub home_dir: Option<String>,
+    /// Parsed `/etc/passwd`, loaded on first use
+    pub passwd: Arc<Mutex<Option<Vec<PasswdEntry>>>>,
+    /// Server clock minus local clock in seconds, measured on first use
+    pub clock_skew: Arc<Mutex<Option<i64>>>,
+    /// Bandwidth cap shared by all transfers on this connection
+    pub bandwidth: Arc<RateLimiter>,
+    /// Distinguishes this session from others to the same `user@host:port`
+    pub label: Option<String>,
+    metadata_limiter: Arc<Mutex<Arc<Semaphore>>>,
+}
+
+/// Locked SFTP channel of a connection
+pub struct SftpGuard<'a>(MutexGuard<'a, Option<Sftp>>);
+
+impl std::ops::Deref for SftpGuard<'_> {
+    type Target = Sftp;
+
+    fn deref(&self) -> &Sftp {
+        self.0.as_ref().expect("SftpGuard is only built around an open channel")
+    }
+}
+
+/// How much `open_session` needs the SFTP subsystem
+#[derive(Clone, Copy, PartialEq)]
+enum SftpNeed {
+    Required,
+    /// Tried, but a server without it is still usable over SCP
+    Optional,
+    Skip,
+}
+
+impl SSHConnection {
+    pub fn has_sftp(&self) -> bool {
+        lock_or_error(&self.sftp).map_or(false, |sftp| sftp.is_some())
+    }
+
+    /// Lock the SFTP channel, failing on connections opened without one
+    pub fn sftp(&self) -> Result<SftpGuard<'_>> {
+        let guard = lock_or_error(&self.sftp)?;
+        if guard.is_none() {
+            return Err(Circle9Error::SSHError(
+                "SFTP is not available on this server; only SCP transfers can be used".to_string(),
+            ));
+        }
+        Ok(SftpGuard(guard))
+    }
+
+    pub fn tuning(&self) -> ConnectionTuning {
+        self.tuning.lock()
+            .map(|t| t.clone())
+            .unwrap_or_default()
+    }
+
+    /// Wait for a slot to run an SFTP metadata call (stat, readdir, ...)
+    pub async fn acquire_metadata_permit(&self) -> Result<OwnedSemaphorePermit> {
+        let limiter = self.metadata_limiter.lock()
+            .map_err(|_| Circle9Error::MutexPoisoned)?
+            .clone();
+        limiter.acquire_owned().await
+            .map_err(|_| Circle9Error::SSHError("Connection is closing".to_string()))
+    }
 }
 
 pub struct SSHClient {
-    connections: Arc<Mutex<HashMap<String, SSHConnection>>>,
-    keepalive_interval: Duration,
+    connections: Arc<Mutex<ConnectionTable>>,
     app_handle: Arc<AppHandle>,
+    pub listing_cache: Arc<ListingCache>,
 }
 
 impl SSHClient {
     pub fn new(app_handle: Arc<AppHandle>) -> Self {
         Self {
             connections: Arc::new(Mutex::new(HashMap::new())),
-            keepalive_interval: Duration::from_secs(60),
             app_handle,
+            listing_cache: Arc::new(ListingCache::new()),
         }
     }
 
-    pub async fn connect(&self, config: SSHConfig) -> Result<ConnectionId> {
-        let connection_id = ConnectionId::new(&config.username, &config.host, config.port);
-        tracing::info!("Attempting SSH connection to {}@{}:{}", config.username, config.host, config.port);
+    /// Connect, or return the existing id if a session with the same label is already open.
+    /// A distinct `label` opens a further independent session to the same host.
+    pub async fn connect(&self, config: SSHConfig, label: Option<String>) -> Result<ConnectionId> {
+        if label.as_deref().map_or(false, |l| l.is_empty() || l.contains('#')) {
+            return Err(Circle9Error::SSHError("Session label must be non-empty and contain no '#'".to_string()));
+        }
+        let connection_id = ConnectionId::with_label(&config.username, &config.host, config.port, label.as_deref());
+        tracing::info!("Attempting SSH connection to {}", connection_id.as_str());
         
         // Check if connection already exists
         {
@@ -55,18 +461,100 @@ impl SSHClient {
             }
         }
 
+        // A connection set up for SCP can do without the SFTP subsystem
+        let defaults = settings::current();
+        let sftp_need = match defaults.default_tuning.transfer_protocol {
+            TransferProtocol::Scp => SftpNeed::Optional,
+            TransferProtocol::Sftp => SftpNeed::Required,
+        };
+        let (session, sftp) = Self::open_session(&config, sftp_need).await
+            .map_err(|(_, e)| e)?;
+        let home_dir = match &sftp {
+            Some(sftp) => sftp.realpath(Path::new("."))
+                .map(|p| p.to_string_lossy().to_string())
+                .map_err(|e| tracing::warn!("Could not resolve remote home directory: {}", e))
+                .ok(),
+            None => login_directory(&session),
+        };
+
+        let bandwidth = RateLimiter::unlimited();
+        bandwidth.set_limit(Some(defaults.default_bandwidth_limit));
+
+        let connection = SSHConnection {
+            session: Arc::new(Mutex::new(session)),
+            sftp: Arc::new(Mutex::new(sftp)),
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            config: config.clone(),
+            tuning: Arc::new(Mutex::new(defaults.default_tuning.clone())),
+            environment: Arc::new(Mutex::new(None)),
+            available_commands: Arc::new(Mutex::new(HashMap::new())),
+            home_dir,
+            passwd: Arc::new(Mutex::new(None)),
+            clock_skew: Arc::new(Mutex::new(None)),
+            bandwidth: Arc::new(bandwidth),
+            label,
+            metadata_limiter: Arc::new(Mutex::new(Arc::new(Semaphore::new(
+                defaults.default_tuning.max_concurrent_metadata_ops,
+            )))),
+        };
+
+        // Store connection
+        {
+            let mut connections = self.connections.lock()
+                .map_err(|_| Circle9Error::MutexPoisoned)?;
+            connections.insert(connection_id.as_str().to_string(), connection);
+        }
+
+        // A fresh session proves the server is back, so stop holding its transfers
+        if let Err(e) = crate::circuit_breaker::close(&self.app_handle, connection_id.as_str()) {
+            tracing::warn!("Failed to close circuit breaker for {}: {}", connection_id.as_str(), e);
+        }
+
+        // Emit connected event
+        if let Err(e) = self.app_handle.emit_all("ssh-connected", connection_id.as_str()) {
+            tracing::error!("Failed to emit ssh-connected: {}", e);
+        } else {
+            tracing::info!("SSH connection established: {}", connection_id.as_str());
+        }
+
+        // Start keepalive for this connection
+        self.start_keepalive(connection_id.clone()).await;
+
+        Ok(connection_id)
+    }
+
+    /// Open a separate session for long-running channels that shouldn't hold the shared session lock
+    pub(crate) async fn open_dedicated_session(config: &SSHConfig) -> Result<Session> {
+        Self::open_session(config, SftpNeed::Skip).await
+            .map(|(session, _)| session)
+            .map_err(|(_, e)| e)
+    }
+
+    /// Dial, handshake, authenticate and start SFTP as far as `sftp_need` asks, reporting which
+    /// stage failed
+    async fn open_session(
+        config: &SSHConfig,
+        sftp_need: SftpNeed,
+    ) -> std::result::Result<(Session, Option<Sftp>), (ConnectFailureKind, Circle9Error)> {
+        let addr = (config.host.as_str(), config.port).to_socket_addrs()
+            .map_err(|e| (ConnectFailureKind::Dns, Circle9Error::SSHError(format!("Failed to resolve {}: {}", config.host, e))))?
+            .next()
+            .ok_or_else(|| (ConnectFailureKind::Dns, Circle9Error::SSHError(format!("No addresses found for {}", config.host))))?;
+
         // Create new connection with timeout
-        let tcp = with_timeout(
-            Duration::from_secs(30),
-            async {
-                TcpStream::connect(format!("{}:{}", config.host, config.port))
-                    .map_err(|e| Circle9Error::SSHError(format!("Failed to connect to SSH server: {}", e)))
-            }
-        ).await?;
-        
+        let tcp = TcpStream::connect_timeout(&addr, Duration::from_secs(30))
+            .map_err(|e| {
+                let kind = match e.kind() {
+                    std::io::ErrorKind::ConnectionRefused => ConnectFailureKind::TcpRefused,
+                    std::io::ErrorKind::TimedOut => ConnectFailureKind::Timeout,
+                    _ => ConnectFailureKind::TcpUnreachable,
+                };
+                (kind, Circle9Error::SSHError(format!("Failed to connect to SSH server: {}", e)))
+            })?;
+
         let mut session = Session::new()
-            .map_err(|e| Circle9Error::SSHError(format!("Failed to create SSH session: {}", e)))?;
-        
+            .map_err(|e| (ConnectFailureKind::Handshake, Circle9Error::SSHError(format!("Failed to create SSH session: {}", e))))?;
+
         session.set_tcp_stream(tcp);
         with_timeout(
             Duration::from_secs(30),
@@ -74,28 +562,23 @@ impl SSHClient {
                 session.handshake()
                     .map_err(|e| Circle9Error::SSHError(format!("SSH handshake failed: {}", e)))
             }
-        ).await?;
+        ).await.map_err(|e| (ConnectFailureKind::Handshake, e))?;
+
+        // Checked before authenticating so no credentials go to an impostor
+        verify_host_key(&session, config).map_err(|e| (ConnectFailureKind::HostKeyMismatch, e))?;
 
         // Authentication with timeout
         with_timeout(
             Duration::from_secs(30),
-            async {
-                if let Some(key_path) = &config.key_path {
-                    let key_path = Path::new(key_path);
-                    session.userauth_pubkey_file(&config.username, None, key_path, None)
-                        .map_err(|e| Circle9Error::SSHError(format!("SSH key authentication failed: {}", e)))?;
-                } else if let Some(password) = &config.password {
-                    session.userauth_password(&config.username, password)
-                        .map_err(|e| Circle9Error::SSHError(format!("SSH password authentication failed: {}", e)))?;
-                } else {
-                    return Err(Circle9Error::SSHError("No authentication method provided".to_string()));
-                }
-                Ok(())
-            }
-        ).await?;
+            async { Self::authenticate(&session, config) }
+        ).await.map_err(|e| (ConnectFailureKind::AuthFailed, e))?;
 
         if !session.authenticated() {
-            return Err(Circle9Error::SSHError("SSH authentication failed".to_string()));
+            return Err((ConnectFailureKind::AuthFailed, Circle9Error::SSHError("SSH authentication failed".to_string())));
+        }
+
+        if sftp_need == SftpNeed::Skip {
+            return Ok((session, None));
         }
 
         // Create SFTP subsystem with timeout
@@ -105,46 +588,163 @@ impl SSHClient {
                 session.sftp()
                     .map_err(|e| Circle9Error::SSHError(format!("Failed to create SFTP subsystem: {}", e)))
             }
-        ).await?;
+        ).await;
+        match (sftp, sftp_need) {
+            (Ok(sftp), _) => Ok((session, Some(sftp))),
+            (Err(e), SftpNeed::Optional) => {
+                tracing::warn!("No SFTP on {}, continuing with SCP only: {}", config.host, e);
+                Ok((session, None))
+            }
+            (Err(e), _) => Err((ConnectFailureKind::SftpUnavailable, e)),
+        }
+    }
 
-        let connection = SSHConnection {
-            session: Arc::new(Mutex::new(session)),
-            sftp: Arc::new(Mutex::new(sftp)),
-            last_activity: Arc::new(Mutex::new(Instant::now())),
-            config: config.clone(),
+    /// Try agent, public key, keyboard-interactive and password auth in turn, using whichever
+    /// credentials are present, and fail only once all of them have been exhausted
+    fn authenticate(session: &Session, config: &SSHConfig) -> Result<()> {
+        let username = config.username.as_str();
+        // Listing the offered methods may itself succeed via "none" auth
+        let offered = session.auth_methods(username)
+            .map(|methods| methods.split(',').map(str::to_string).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if session.authenticated() {
+            return Ok(());
+        }
+        let is_offered = |method: &str| offered.is_empty() || offered.iter().any(|m| m == method);
+
+        let mut failures = Vec::new();
+        let mut attempt = |method: &str, unavailable_reason: Option<&str>, auth: &dyn Fn() -> std::result::Result<(), ssh2::Error>| {
+            if let Some(reason) = unavailable_reason {
+                failures.push(format!("{}: {}", method, reason));
+                return false;
+            }
+            match auth() {
+                Ok(()) if session.authenticated() => true,
+                Ok(()) => {
+                    failures.push(format!("{}: rejected", method));
+                    false
+                }
+                Err(e) => {
+                    failures.push(format!("{}: {}", method, e.message()));
+                    false
+                }
+            }
         };
 
-        // Store connection
-        {
-            let mut connections = self.connections.lock()
-                .map_err(|_| Circle9Error::MutexPoisoned)?;
-            connections.insert(connection_id.as_str().to_string(), connection);
+        let unavailable = |method: &str, has_credential: bool, missing: &'static str| {
+            if !is_offered(method) {
+                Some("not offered by server")
+            } else if !has_credential {
+                Some(missing)
+            } else {
+                None
+            }
+        };
+
+        if attempt("agent", unavailable("publickey", true, ""), &|| session.userauth_agent(username)) {
+            return Ok(());
         }
 
-        // Emit connected event
-        if let Err(e) = self.app_handle.emit_all("ssh-connected", connection_id.as_str()) {
-            tracing::error!("Failed to emit ssh-connected: {}", e);
-        } else {
-            tracing::info!("SSH connection established: {}", connection_id.as_str());
+        let key_path = config.key_path.as_deref();
+        if attempt("publickey", unavailable("publickey", key_path.is_some(), "no key provided"), &|| {
+            session.userauth_pubkey_file(username, None, Path::new(key_path.unwrap_or_default()), config.key_passphrase.as_deref())
+        }) {
+            return Ok(());
         }
 
-        // Start keepalive for this connection
-        self.start_keepalive(connection_id.clone()).await;
+        let password = config.password.as_deref();
+        if attempt("keyboard-interactive", unavailable("keyboard-interactive", password.is_some(), "password not provided"), &|| {
+            let mut prompter = PasswordPrompter(password.unwrap_or_default());
+            session.userauth_keyboard_interactive(username, &mut prompter)
+        }) {
+            return Ok(());
+        }
 
-        Ok(connection_id)
+        if attempt("password", unavailable("password", password.is_some(), "password not provided"), &|| {
+            session.userauth_password(username, password.unwrap_or_default())
+        }) {
+            return Ok(());
+        }
+
+        Err(Circle9Error::SSHError(format!("SSH authentication failed ({})", failures.join("; "))))
+    }
+
+    /// Run the full connect sequence and stat the home directory, then hang up.
+    /// Nothing is registered and no keepalive is started.
+    pub async fn test_connection(config: SSHConfig) -> ConnectionTestResult {
+        tracing::info!("Testing SSH connection to {}@{}:{}", config.username, config.host, config.port);
+
+        let (session, sftp) = match Self::open_session(&config, SftpNeed::Required).await {
+            Ok((session, Some(sftp))) => (session, sftp),
+            Ok((_, None)) => {
+                return ConnectionTestResult {
+                    success: false,
+                    failure: Some(ConnectFailureKind::SftpUnavailable),
+                    message: "SFTP subsystem was not opened".to_string(),
+                };
+            }
+            Err((kind, e)) => {
+                return ConnectionTestResult {
+                    success: false,
+                    failure: Some(kind),
+                    message: e.to_string(),
+                };
+            }
+        };
+
+        let result = match sftp.stat(Path::new(".")) {
+            Ok(_) => ConnectionTestResult {
+                success: true,
+                failure: None,
+                message: format!("Connected to {}@{}:{}", config.username, config.host, config.port),
+            },
+            Err(e) => ConnectionTestResult {
+                success: false,
+                failure: Some(ConnectFailureKind::SftpUnavailable),
+                message: format!("SFTP could not stat the home directory: {}", e),
+            },
+        };
+
+        drop(sftp);
+        if let Err(e) = session.disconnect(None, "Connection test complete", None) {
+            tracing::debug!("Failed to close test session cleanly: {}", e);
+        }
+
+        result
     }
 
     pub fn get_connection(&self, connection_id: &str) -> Option<SSHConnection> {
+        self.lookup_connection(connection_id, true)
+    }
+
+    /// Like `get_connection`, but for background polling that shouldn't keep the connection
+    /// from going idle
+    pub fn peek_connection(&self, connection_id: &str) -> Option<SSHConnection> {
+        self.lookup_connection(connection_id, false)
+    }
+
+    fn lookup_connection(&self, connection_id: &str, touch: bool) -> Option<SSHConnection> {
         let mut connections = self.connections.lock()
             .map_err(|_| Circle9Error::MutexPoisoned)
             .ok()?;
         if let Some(conn) = connections.get_mut(connection_id) {
-            *conn.last_activity.lock().unwrap() = Instant::now();
+            if touch {
+                *conn.last_activity.lock().unwrap() = Instant::now();
+            }
             Some(SSHConnection {
                 session: conn.session.clone(),
                 sftp: conn.sftp.clone(),
                 last_activity: conn.last_activity.clone(),
                 config: conn.config.clone(),
+                tuning: conn.tuning.clone(),
+                environment: conn.environment.clone(),
+                available_commands: conn.available_commands.clone(),
+                home_dir: conn.home_dir.clone(),
+                passwd: conn.passwd.clone(),
+                clock_skew: conn.clock_skew.clone(),
+                bandwidth: conn.bandwidth.clone(),
+                label: conn.label.clone(),
+                metadata_limiter: conn.metadata_limiter.clone(),
             })
         } else {
             None
@@ -156,6 +756,8 @@ impl SSHClient {
             .map_err(|_| Circle9Error::MutexPoisoned)
             .unwrap_or_else(|_| return);
         connections.remove(connection_id);
+        drop(connections);
+        release_connection_state(&self.listing_cache, &self.app_handle, connection_id, "Connection closed");
 
         // Emit disconnect event
         if let Err(e) = self.app_handle.emit_all("ssh-disconnected", connection_id) {
@@ -163,45 +765,112 @@ impl SSHClient {
         }
     }
 
+    /// Replace the password a live connection gives when it opens further sessions, e.g. for
+    /// streaming exec, checksums or tar transfers, after it was changed on the server
+    pub fn set_password(&self, connection_id: &str, password: &str) -> Result<()> {
+        let mut connections = self.connections.lock()
+            .map_err(|_| Circle9Error::MutexPoisoned)?;
+        let connection = connections.get_mut(connection_id)
+            .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+        connection.config = connection.config.with_password(password);
+        Ok(())
+    }
+
+    /// Replace a connection's tuning; a new metadata limit applies to calls started afterwards
+    pub fn set_tuning(&self, connection_id: &str, tuning: ConnectionTuning) -> Result<()> {
+        tuning.validate()?;
+
+        let connection = self.get_connection(connection_id)
+            .ok_or_else(|| Circle9Error::SSHError("Connection not found".to_string()))?;
+
+        let limit_changed = connection.tuning().max_concurrent_metadata_ops != tuning.max_concurrent_metadata_ops;
+        if limit_changed {
+            *connection.metadata_limiter.lock().map_err(|_| Circle9Error::MutexPoisoned)? =
+                Arc::new(Semaphore::new(tuning.max_concurrent_metadata_ops));
+        }
+        *connection.tuning.lock().map_err(|_| Circle9Error::MutexPoisoned)? = tuning;
+
+        Ok(())
+    }
+
+    /// Disconnect every open session
+    pub fn disconnect_all(&self) {
+        for connection_id in self.list_connections() {
+            tracing::info!("Closing SSH connection {}", connection_id);
+            self.disconnect(&connection_id);
+        }
+    }
+
     async fn start_keepalive(&self, connection_id: ConnectionId) {
         let connections = Arc::clone(&self.connections);
-        let keepalive_interval = self.keepalive_interval;
+        let keepalive_interval = settings::current().keepalive_interval();
         let connection_id_str = connection_id.as_str().to_string();
+        let app_handle = Arc::clone(&self.app_handle);
+        let listing_cache = Arc::clone(&self.listing_cache);
         
         tokio::spawn(async move {
             let mut interval = interval(keepalive_interval);
             loop {
                 interval.tick().await;
                 
-                let should_disconnect = {
-                    let connections = connections.lock()
-                        .map_err(|_| Circle9Error::MutexPoisoned)
-                        .unwrap_or_else(|_| return);
-                    if let Some(conn) = connections.get(&connection_id_str) {
-                        // Check if connection is stale (no activity for 5 minutes)
-                        conn.last_activity.lock().unwrap().elapsed() > Duration::from_secs(300)
-                    } else {
-                        true // Connection was removed
-                    }
+                let mut table = match lock_for_keepalive(&connections, &connection_id_str) {
+                    Some(table) => table,
+                    None => break,
                 };
 
-                if should_disconnect {
-                    connections.lock()
-                        .map_err(|_| Circle9Error::MutexPoisoned)
-                        .unwrap_or_else(|_| return)
-                        .remove(&connection_id_str);
+                // An Instant can't be left half-written, so a poisoned activity lock is still readable.
+                // Transfers hold on to their connection rather than looking it up again, so data
+                // moving through the bandwidth limiter counts as activity too.
+                let idle = match table.get(&connection_id_str) {
+                    Some(conn) => conn.last_activity.lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .elapsed()
+                        .min(conn.bandwidth.idle_for()),
+                    None => break, // Connection was removed
+                };
+
+                // Check if connection is stale (no activity for 5 minutes)
+                if idle > IDLE_TIMEOUT {
+                    table.remove(&connection_id_str);
+                    drop(table);
+
+                    let event = IdleTimeoutEvent {
+                        connection_id: connection_id_str.clone(),
+                        reason: format!("No activity for {} seconds", idle.as_secs()),
+                        last_activity: Utc::now() - chrono::Duration::from_std(idle)
+                            .unwrap_or_else(|_| chrono::Duration::zero()),
+                    };
+                    tracing::info!("Closing idle SSH connection {}", connection_id_str);
+                    release_connection_state(&listing_cache, &app_handle, &connection_id_str, &event.reason);
+                    if let Err(e) = app_handle.emit_all("ssh-idle-timeout", &event) {
+                        tracing::error!("Failed to emit ssh-idle-timeout: {}", e);
+                    }
                     break;
                 }
 
-                // Send keepalive
-                if let Some(conn) = connections.lock()
-                    .map_err(|_| Circle9Error::MutexPoisoned)
-                    .unwrap_or_else(|_| return)
-                    .get_mut(&connection_id_str) {
-                    // SSH keepalive is handled automatically by the ssh2 library
-                    *conn.last_activity.lock().unwrap() = Instant::now();
+                // Send keepalive; this must not count as activity or nothing would ever go idle.
+                // The table is released first, and a session busy with a command or transfer
+                // is already showing the server traffic, so it's skipped rather than waited on.
+                let session = match table.get(&connection_id_str) {
+                    Some(conn) => Arc::clone(&conn.session),
+                    None => break,
+                };
+                drop(table);
+                match session.try_lock() {
+                    Ok(session) => {
+                        if let Err(e) = session.keepalive_send() {
+                            tracing::warn!("Keepalive failed for {}: {}", connection_id_str, e);
+                        }
+                    }
+                    Err(TryLockError::WouldBlock) => {
+                        tracing::debug!("Session {} is busy, skipping keepalive", connection_id_str);
+                    }
+                    Err(TryLockError::Poisoned(_)) => {
+                        tracing::warn!("Session lock for {} is poisoned, skipping keepalive", connection_id_str);
+                    }
                 }
             }
+            tracing::debug!("Keepalive task for {} stopped", connection_id_str);
         });
     }
 
@@ -218,7 +887,71 @@ impl SSHClient {
             .unwrap_or_else(|_| return Vec::new());
         connections.keys().cloned().collect()
     }
+
+    /// Open sessions, optionally only those to `user@host:port`
+    pub fn list_sessions(&self, target: Option<&str>) -> Vec<SessionInfo> {
+        let connections = match self.connections.lock() {
+            Ok(connections) => connections,
+            Err(_) => return Vec::new(),
+        };
+        let mut sessions: Vec<SessionInfo> = connections.iter()
+            .map(|(id, conn)| SessionInfo {
+                connection_id: id.clone(),
+                target: ConnectionId::new(&conn.config.username, &conn.config.host, conn.config.port)
+                    .as_str()
+                    .to_string(),
+                label: conn.label.clone(),
+            })
+            .filter(|session| target.map_or(true, |t| session.target == t))
+            .collect();
+        sessions.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+        sessions
+    }
+}
+
+/// Drop what's kept for a connection once it leaves the table, whether it was closed or timed out
+fn release_connection_state(listing_cache: &ListingCache, app_handle: &AppHandle, connection_id: &str, reason: &str) {
+    listing_cache.clear_connection(connection_id);
+    crate::remote_mounts::forget_connection(connection_id);
+    background_jobs::terminate_connection_jobs(app_handle, connection_id, reason);
 }
 
 // Remove Default implementation since SSHClient requires AppHandle
 // Remove global static - will use Tauri managed state instead
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poisoned_table() -> Arc<Mutex<ConnectionTable>> {
+        let table = Arc::new(Mutex::new(ConnectionTable::new()));
+        let holder = Arc::clone(&table);
+        let _ = std::thread::spawn(move || {
+            let _guard = holder.lock().unwrap();
+            panic!("poison the connection table");
+        }).join();
+        assert!(table.is_poisoned());
+        table
+    }
+
+    #[test]
+    fn keepalive_stops_on_a_poisoned_table_by_default() {
+        let table = poisoned_table();
+        assert_eq!(PoisonPolicy::default(), PoisonPolicy::Terminate);
+        assert!(lock_with_policy(&table, "conn", PoisonPolicy::Terminate).is_none());
+    }
+
+    #[test]
+    fn keepalive_recovers_a_poisoned_table_when_asked() {
+        let table = poisoned_table();
+        let guard = lock_with_policy(&table, "conn", PoisonPolicy::Recover);
+        assert!(guard.map_or(false, |table| table.is_empty()));
+    }
+
+    #[test]
+    fn healthy_table_locks_under_either_policy() {
+        let table = Mutex::new(ConnectionTable::new());
+        assert!(lock_with_policy(&table, "conn", PoisonPolicy::Terminate).is_some());
+        assert!(lock_with_policy(&table, "conn", PoisonPolicy::Recover).is_some());
+    }
+}
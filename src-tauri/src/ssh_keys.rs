@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use ssh2::{FileStat, OpenFlags, OpenType};
+use ssh_key::private::{Ed25519Keypair, KeypairData, RsaKeypair};
+use ssh_key::rand_core::OsRng;
+use ssh_key::{HashAlg, LineEnding, PrivateKey, PublicKey};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use crate::error::{Circle9Error, Result};
+use crate::ssh_client::{SSHClient, SSHConnection};
+
+/// Modulus size for generated RSA keys
+const RSA_KEY_BITS: usize = 3072;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    Ed25519,
+    Rsa,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedKeyPair {
+    pub private_key_path: String,
+    pub public_key_path: String,
+    /// The `authorized_keys` line
+    pub public_key: String,
+    pub fingerprint: String,
+    /// Whether the private key file is protected by a passphrase
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyInstallResult {
+    pub authorized_keys_path: String,
+    /// False when the key was already present and nothing was written
+    pub installed: bool,
+}
+
+/// Generate a key pair into the app's `keys` directory, never overwriting an existing key.
+/// With a `passphrase` the private key is encrypted (bcrypt-pbkdf + AES, as `ssh-keygen`
+/// does); without one it is only protected by the file's owner-only permissions.
+pub fn generate_keypair(key_type: KeyType, comment: &str, passphrase: Option<&str>) -> Result<GeneratedKeyPair> {
+    let keypair: KeypairData = match key_type {
+        KeyType::Ed25519 => Ed25519Keypair::random(&mut OsRng).into(),
+        KeyType::Rsa => RsaKeypair::random(&mut OsRng, RSA_KEY_BITS)
+            .map_err(|e| Circle9Error::SSHError(format!("Failed to generate key: {}", e)))?
+            .into(),
+    };
+    let private_key = PrivateKey::new(keypair, comment)
+        .map_err(|e| Circle9Error::SSHError(format!("Failed to generate key: {}", e)))?;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    let stored_key = match passphrase {
+        Some(passphrase) => private_key.encrypt(&mut OsRng, passphrase)
+            .map_err(|e| Circle9Error::SSHError(format!("Failed to encrypt private key: {}", e)))?,
+        None => private_key.clone(),
+    };
+
+    let private_pem = stored_key.to_openssh(LineEnding::LF)
+        .map_err(|e| Circle9Error::SSHError(format!("Failed to encode private key: {}", e)))?;
+    let public_line = private_key.public_key().to_openssh()
+        .map_err(|e| Circle9Error::SSHError(format!("Failed to encode public key: {}", e)))?;
+
+    let private_path = unused_key_path(key_type)?;
+    let public_path = private_path.with_extension("pub");
+    write_private(&private_path, private_pem.as_bytes())?;
+    std::fs::write(&public_path, format!("{}\n", public_line))?;
+
+    tracing::info!("Generated {:?} key pair at {}", key_type, private_path.display());
+    Ok(GeneratedKeyPair {
+        private_key_path: private_path.to_string_lossy().to_string(),
+        public_key_path: public_path.to_string_lossy().to_string(),
+        fingerprint: private_key.public_key().fingerprint(HashAlg::Sha256).to_string(),
+        public_key: public_line,
+        encrypted: passphrase.is_some(),
+    })
+}
+
+/// `<app data>/keys/circle9_<type>`, numbered if that name is taken
+fn unused_key_path(key_type: KeyType) -> Result<PathBuf> {
+    let dir = crate::utils::app_data_dir()?.join("keys");
+    std::fs::create_dir_all(&dir)?;
+
+    let base = match key_type {
+        KeyType::Ed25519 => "circle9_ed25519",
+        KeyType::Rsa => "circle9_rsa",
+    };
+    let mut candidate = dir.join(base);
+    let mut n = 1;
+    while candidate.exists() || candidate.with_extension("pub").exists() {
+        candidate = dir.join(format!("{}_{}", base, n));
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+/// Write the private key readable by the owner only
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)?;
+    Ok(())
+}
+
+/// Append `public_key` to the remote `~/.ssh/authorized_keys` unless it's already there,
+/// creating the directory and file with the permissions sshd insists on
+pub fn install_key(connection: &SSHConnection, public_key: &str) -> Result<KeyInstallResult> {
+    let key = PublicKey::from_openssh(public_key.trim())
+        .map_err(|e| Circle9Error::SSHError(format!("Invalid public key: {}", e)))?;
+
+    let home = connection.home_dir.clone()
+        .ok_or_else(|| Circle9Error::InvalidPath("Remote home directory is unknown".to_string()))?;
+    let ssh_dir = Path::new(&home).join(".ssh");
+    let authorized_keys = ssh_dir.join("authorized_keys");
+    let authorized_keys_path = authorized_keys.to_string_lossy().to_string();
+
+    let sftp = connection.sftp()?;
+    if sftp.stat(&ssh_dir).is_err() {
+        sftp.mkdir(&ssh_dir, 0o700)?;
+    }
+
+    let mut existing = String::new();
+    if let Ok(mut file) = sftp.open(&authorized_keys) {
+        file.read_to_string(&mut existing)?;
+    }
+    let mut line = key.to_openssh()
+        .map_err(|e| Circle9Error::SSHError(format!("Failed to encode public key: {}", e)))?;
+
+    // Match on the base64 key blob; the same key may carry different options or a different comment
+    let blob = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+    if existing.lines().any(|present| present.split_whitespace().any(|field| field == blob)) {
+        return Ok(KeyInstallResult { authorized_keys_path, installed: false });
+    }
+
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        line.insert(0, '\n');
+    }
+    line.push('\n');
+
+    let mut file = sftp.open_mode(
+        &authorized_keys,
+        OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE,
+        0o600,
+        OpenType::File,
+    )?;
+    file.write_all(line.as_bytes())?;
+    drop(file);
+
+    // An existing file or directory with looser permissions makes sshd ignore the key
+    for (path, mode) in [(&ssh_dir, 0o700), (&authorized_keys, 0o600)] {
+        sftp.setstat(path, FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        })?;
+    }
+
+    tracing::info!("Installed public key into {}", authorized_keys_path);
+    Ok(KeyInstallResult { authorized_keys_path, installed: true })
+}
+
+// Tauri commands for SSH key setup
+
+/// Generate a key pair; pass a `passphrase` to encrypt the private key, and the same value as
+/// `key_passphrase` when connecting with it
+#[tauri::command]
+pub async fn generate_ssh_keypair(
+    key_type: KeyType,
+    comment: Option<String>,
+    passphrase: Option<String>,
+) -> Result<GeneratedKeyPair, String> {
+    let comment = comment.unwrap_or_else(|| format!("{}@{}", whoami::username(), whoami::hostname()));
+    tauri::async_runtime::spawn_blocking(move || generate_keypair(key_type, &comment, passphrase.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Install the public key at `public_key_path` on the host behind `connection_id`,
+/// typically over a password login so the user can then switch to key auth
+#[tauri::command]
+pub async fn install_public_key(
+    ssh_client: State<'_, SSHClient>,
+    connection_id: String,
+    public_key_path: String,
+) -> Result<KeyInstallResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let public_key = std::fs::read_to_string(&public_key_path)
+        .map_err(|e| format!("Failed to read {}: {}", public_key_path, e))?;
+
+    install_key(&connection, &public_key)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Manager, State};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use crate::background_jobs::{self, BackgroundJobKind};
+use crate::copy_agent::CopyAgent;
+use crate::error::{Circle9Error, Result};
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::types::TransferProgress;
+use crate::utils::ProgressThrottle;
+
+/// Chunks that may sit between the SFTP reader and the writer; together with the one being
+/// read and the one being written this caps memory at `QUEUE_DEPTH + 2` buffers
+const QUEUE_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamDownloadResult {
+    pub job_id: String,
+    pub remote_path: String,
+    pub local_path: String,
+    pub bytes_written: u64,
+    pub cancelled: bool,
+}
+
+/// Reads the next chunk of a source into the buffer, returning 0 at its end
+type ChunkReader = Box<dyn FnMut(&mut [u8]) -> Result<usize> + Send>;
+
+/// Copy a remote file into `writer` (named `dest` in errors), returning the bytes written and
+/// whether it was cancelled. SFTP reads run on a blocking thread and hand chunks over a bounded
+/// channel, so a writer that can't keep up stalls the reads instead of piling data up in memory.
+pub async fn stream_to_writer<W, F>(
+    connection: SSHConnection,
+    remote_path: &str,
+    dest: &str,
+    writer: &mut W,
+    cancel: Arc<AtomicBool>,
+    progress: F,
+) -> Result<(u64, bool)>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(u64, u64),
+{
+    let path = remote_path.to_string();
+    let buffer_size = connection.tuning().sftp_buffer_size();
+    let open = move || -> Result<(ChunkReader, u64)> {
+        let mut file = connection.sftp()?.open(Path::new(&path))?;
+        let total = file.stat().ok().and_then(|stat| stat.size).unwrap_or(0);
+        let read: ChunkReader = Box::new(move |buffer: &mut [u8]| {
+            let _sftp = connection.sftp()?;
+            let n = file.read(buffer)?;
+            connection.bandwidth.acquire(n);
+            Ok(n)
+        });
+        Ok((read, total))
+    };
+    pipe(open, buffer_size, dest, writer, cancel, progress).await
+}
+
+/// The body of `stream_to_writer` for any source: `open` runs on the blocking reader thread
+/// and returns the source's chunk reader and total size
+async fn pipe<O, W, F>(
+    open: O,
+    buffer_size: usize,
+    dest: &str,
+    writer: &mut W,
+    cancel: Arc<AtomicBool>,
+    mut progress: F,
+) -> Result<(u64, bool)>
+where
+    O: FnOnce() -> Result<(ChunkReader, u64)> + Send + 'static,
+    W: AsyncWrite + Unpin,
+    F: FnMut(u64, u64),
+{
+    let (tx, mut rx) = mpsc::channel::<Result<Vec<u8>>>(QUEUE_DEPTH);
+    let (total_tx, total_rx) = tokio::sync::oneshot::channel();
+    let reader_cancel = Arc::clone(&cancel);
+
+    let reader = tauri::async_runtime::spawn_blocking(move || {
+        let (mut read, total) = match open() {
+            Ok(opened) => opened,
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e));
+                return;
+            }
+        };
+        let _ = total_tx.send(total);
+
+        while !reader_cancel.load(Ordering::SeqCst) {
+            let mut buffer = vec![0u8; buffer_size];
+            let chunk = match read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buffer.truncate(n);
+                    Ok(buffer)
+                }
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            // Blocks while the queue is full, which is what holds the reads back
+            if tx.blocking_send(chunk).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let total = total_rx.await.unwrap_or(0);
+    let mut written = 0u64;
+    while let Some(chunk) = rx.recv().await {
+        if cancel.load(Ordering::SeqCst) {
+            break;
+        }
+        let chunk = chunk?;
+        writer.write_all(&chunk).await
+            .map_err(|e| Circle9Error::from_write(e, dest, written))?;
+        written += chunk.len() as u64;
+        progress(written, total);
+    }
+    // Dropping the receiver unblocks a reader waiting on a full queue
+    drop(rx);
+    reader.await.map_err(|e| Circle9Error::TransferError(e.to_string()))?;
+
+    writer.flush().await
+        .map_err(|e| Circle9Error::from_write(e, dest, written))?;
+    Ok((written, cancel.load(Ordering::SeqCst)))
+}
+
+// Tauri commands for streamed downloads
+
+/// Download a remote file to `local_path` with bounded memory. It runs as a background job,
+/// so `cancel_background_job(job_id)` stops it; progress arrives as `transfer_progress` events.
+#[tauri::command]
+pub async fn stream_remote_file(
+    ssh_client: State<'_, SSHClient>,
+    app_handle: AppHandle,
+    connection_id: String,
+    remote_path: String,
+    local_path: String,
+    job_id: Option<String>,
+) -> Result<StreamDownloadResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let job_id = job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut file = tokio::fs::File::create(&local_path).await
+        .map_err(|e| format!("Failed to create {}: {}", local_path, e))?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let job_cancel = Arc::clone(&cancel);
+    background_jobs::register(&job_id, BackgroundJobKind::StreamDownload, &remote_path, Some(&connection_id), move || {
+        job_cancel.store(true, Ordering::SeqCst);
+    });
+
+    let filename = Path::new(&remote_path).file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let mut throttle = ProgressThrottle::new(app_handle.state::<CopyAgent>().progress_throttle());
+    let start_time = Instant::now();
+
+    let result = stream_to_writer(connection, &remote_path, &local_path, &mut file, cancel, |written, total| {
+        if throttle.should_emit(written, total) {
+            TransferProgress::new(&job_id, &filename, "download", written, total, start_time.elapsed())
+                .emit(&app_handle);
+        }
+    }).await;
+    background_jobs::unregister(&job_id);
+
+    let (bytes_written, cancelled) = result.map_err(|e| e.to_string())?;
+    if !cancelled {
+        file.sync_all().await.map_err(|e| e.to_string())?;
+    }
+
+    Ok(StreamDownloadResult {
+        job_id,
+        remote_path,
+        local_path,
+        bytes_written,
+        cancelled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    const CHUNK: usize = 64 * 1024;
+    const TOTAL: u64 = 16 * 1024 * 1024;
+
+    #[tokio::test]
+    async fn slow_sink_keeps_memory_bounded() {
+        // Bytes handed out by the source; whatever the sink hasn't taken yet is held in memory
+        let read = Arc::new(AtomicU64::new(0));
+        let source_read = Arc::clone(&read);
+        let open = move || -> Result<(ChunkReader, u64)> {
+            let mut remaining = TOTAL;
+            let reader: ChunkReader = Box::new(move |buffer: &mut [u8]| {
+                let n = buffer.len().min(remaining as usize);
+                remaining -= n as u64;
+                source_read.fetch_add(n as u64, Ordering::SeqCst);
+                Ok(n)
+            });
+            Ok((reader, TOTAL))
+        };
+
+        // The sink drains a pipe no bigger than one chunk, pausing between reads
+        let (mut writer, mut sink) = tokio::io::duplex(CHUNK);
+        let sink_read = Arc::clone(&read);
+        let drain = tokio::spawn(async move {
+            let mut buffer = vec![0u8; CHUNK];
+            let (mut drained, mut max_ahead) = (0u64, 0u64);
+            loop {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                let n = sink.read(&mut buffer).await.unwrap();
+                if n == 0 {
+                    return (drained, max_ahead);
+                }
+                drained += n as u64;
+                max_ahead = max_ahead.max(sink_read.load(Ordering::SeqCst) - drained);
+            }
+        });
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (written, cancelled) = pipe(open, CHUNK, "sink", &mut writer, cancel, |_, _| {}).await.unwrap();
+        drop(writer);
+        let (drained, max_ahead) = drain.await.unwrap();
+
+        assert!(!cancelled);
+        assert_eq!(written, TOTAL);
+        assert_eq!(drained, TOTAL);
+        // The queue, the chunk being read, the one being written and the pipe itself
+        let bound = ((QUEUE_DEPTH + 3) * CHUNK) as u64;
+        assert!(max_ahead <= bound, "reads got {} bytes ahead, bound is {}", max_ahead, bound);
+    }
+}
@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+use ssh2::FileStat;
+use tauri::{AppHandle, State};
+use crate::copy_agent::CopyAgent;
+use crate::dir_size::cached_dir_size;
+use crate::error::{Circle9Error, Result};
+use crate::rate_limit::{RateLimiter, ThrottledWriter};
+use crate::remote_dirs::{create_remote_dirs, plan_remote_dirs, DirCreationReport};
+use crate::remote_env::command_available;
+use crate::remote_exec::{exec_command, shell_quote};
+use crate::remote_walk::SkippedEntry;
+use crate::ssh_client::{SSHClient, SSHConnection};
+use crate::types::TransferProgress;
+use crate::utils::{ProgressThrottle, ProgressThrottleConfig};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TarTransferResult {
+    pub archive_path: String,
+    pub archive_bytes: u64,
+    /// Uncompressed size of the directory that was archived
+    pub source_bytes: u64,
+    pub extracted_to: Option<String>,
+    /// Mount points below the directory that were left out of the archive
+    pub skipped_mounts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TarUploadResult {
+    pub remote_dir: String,
+    pub source_bytes: u64,
+    /// False when the remote host had no `tar` and files were sent one by one over SFTP
+    pub used_tar: bool,
+    /// Directories the SFTP fallback couldn't create; their files were not uploaded
+    pub failed_dirs: Vec<SkippedEntry>,
+}
+
+/// Optional settings for `download_remote_dir_as_tar`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TarDownloadOptions {
+    /// Unpack the archive here once it has arrived
+    pub extract_to: Option<String>,
+    /// Lets the caller match progress events to this download
+    pub task_id: Option<String>,
+    /// Follow the directory into other mounted filesystems instead of leaving them out
+    pub cross_filesystems: bool,
+}
+
+/// A tar stream's dedicated session, with the connection's bandwidth limit and where its
+/// progress goes
+struct TarStream {
+    app_handle: AppHandle,
+    task_id: String,
+    session: ssh2::Session,
+    bandwidth: Arc<RateLimiter>,
+    throttle_config: ProgressThrottleConfig,
+}
+
+impl TarStream {
+    /// Open a session of its own, since the stream can take minutes and shouldn't hold the
+    /// shared one
+    async fn open(
+        connection: &SSHConnection,
+        app_handle: AppHandle,
+        task_id: String,
+        throttle_config: ProgressThrottleConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            app_handle,
+            task_id,
+            session: SSHClient::open_dedicated_session(&connection.config).await?,
+            bandwidth: Arc::clone(&connection.bandwidth),
+            throttle_config,
+        })
+    }
+
+    fn close(self, description: &str) {
+        let _ = self.session.disconnect(None, description, None);
+    }
+}
+
+/// Counts the uncompressed tar bytes passing through so upload progress can be reported
+struct ProgressWriter<'a, W: Write> {
+    inner: W,
+    written: u64,
+    total: u64,
+    task_id: &'a str,
+    filename: String,
+    throttle: ProgressThrottle,
+    start_time: Instant,
+    app_handle: &'a AppHandle,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        // Tar headers and padding push the stream past the file total, so cap it
+        let transferred = self.written.min(self.total);
+        if self.throttle.should_emit(transferred, self.total) {
+            TransferProgress::new(self.task_id, &self.filename, "upload", transferred, self.total, self.start_time.elapsed())
+                .emit(self.app_handle);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Stream `tar czf` output from an exec channel into `archive_path`.
+/// Progress compares compressed bytes received against the uncompressed directory size,
+/// so it usually finishes short of 100% before the final event.
+fn stream_remote_tar(
+    stream: &TarStream,
+    remote_dir: &str,
+    cross_filesystems: bool,
+    archive_path: &Path,
+    source_bytes: u64,
+) -> Result<u64> {
+    let TarStream { app_handle, task_id, session, bandwidth, throttle_config } = stream;
+    let mut channel = session.channel_session()?;
+    let one_file_system = if cross_filesystems { "" } else { "--one-file-system " };
+    channel.exec(&format!("tar czf - {}-C {} .", one_file_system, shell_quote(remote_dir)))?;
+
+    let mut archive = BufWriter::new(File::create(archive_path)?);
+    let filename = Path::new(remote_dir).file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let mut throttle = ProgressThrottle::new(throttle_config.clone());
+    let start_time = Instant::now();
+    let mut received = 0u64;
+    let mut buffer = [0u8; 32768];
+
+    loop {
+        let n = channel.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        bandwidth.acquire(n);
+        archive.write_all(&buffer[..n])?;
+        received += n as u64;
+
+        if throttle.should_emit(received.min(source_bytes), source_bytes) {
+            TransferProgress::new(task_id, &filename, "download", received.min(source_bytes), source_bytes, start_time.elapsed())
+                .emit(app_handle);
+        }
+    }
+    archive.flush()?;
+    TransferProgress::new(task_id, &filename, "download", source_bytes, source_bytes, start_time.elapsed())
+        .emit(app_handle);
+
+    let mut stderr = String::new();
+    channel.stderr().read_to_string(&mut stderr)?;
+    channel.wait_close()?;
+
+    let exit_status = channel.exit_status()?;
+    if exit_status != 0 {
+        return Err(Circle9Error::TransferError(format!(
+            "Remote tar exited with status {}: {}", exit_status, stderr.trim()
+        )));
+    }
+    Ok(received)
+}
+
+/// Build a gzipped tar of `local_dir` on the fly and pipe it into `tar xzpf` on the remote host
+fn stream_local_tar(stream: &TarStream, local_dir: &Path, remote_dir: &str, source_bytes: u64) -> Result<()> {
+    let TarStream { app_handle, task_id, session, bandwidth, throttle_config } = stream;
+    let mut channel = session.channel_session()?;
+    // -p keeps the archived permissions instead of applying the remote umask
+    channel.exec(&format!("tar xzpf - -C {}", shell_quote(remote_dir)))?;
+
+    let filename = local_dir.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let start_time = Instant::now();
+    {
+        // Progress counts tar bytes going into the encoder, which track source_bytes;
+        // the compressed stream coming out of it doesn't
+        let encoder = flate2::write::GzEncoder::new(
+            ThrottledWriter::new(&mut channel, bandwidth),
+            flate2::Compression::default(),
+        );
+        let writer = ProgressWriter {
+            inner: encoder,
+            written: 0,
+            total: source_bytes,
+            task_id,
+            filename: filename.clone(),
+            throttle: ProgressThrottle::new(throttle_config.clone()),
+            start_time,
+            app_handle,
+        };
+        let mut builder = tar::Builder::new(writer);
+        builder.follow_symlinks(false);
+        builder.append_dir_all(".", local_dir)?;
+        builder.into_inner()?.inner.finish()?.flush()?;
+    }
+    TransferProgress::new(task_id, &filename, "upload", source_bytes, source_bytes, start_time.elapsed())
+        .emit(app_handle);
+
+    channel.send_eof()?;
+    let mut stderr = String::new();
+    channel.stderr().read_to_string(&mut stderr)?;
+    channel.wait_close()?;
+
+    let exit_status = channel.exit_status()?;
+    if exit_status != 0 {
+        return Err(Circle9Error::TransferError(format!(
+            "Remote tar exited with status {}: {}", exit_status, stderr.trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Upload `local_dir` file by file over SFTP, for hosts without `tar`.
+/// The whole directory tree is created first so mkdir and write round-trips don't interleave.
+fn upload_dir_via_sftp(connection: &SSHConnection, local_dir: &Path, remote_dir: &Path) -> Result<DirCreationReport> {
+    let dirs = plan_remote_dirs(local_dir, remote_dir)?;
+    let report = create_remote_dirs(connection, &dirs)?;
+    upload_files_via_sftp(connection, local_dir, remote_dir, &report)?;
+    Ok(report)
+}
+
+fn upload_files_via_sftp(connection: &SSHConnection, local_dir: &Path, remote_dir: &Path, dirs: &DirCreationReport) -> Result<()> {
+    if dirs.is_failed(remote_dir) {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(local_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let remote_path = remote_dir.join(entry.file_name());
+
+        if metadata.is_dir() {
+            upload_files_via_sftp(connection, &entry.path(), &remote_path, dirs)?;
+        } else if metadata.is_file() {
+            let mut local_file = File::open(entry.path())?;
+            let sftp = connection.sftp()?;
+            let mut remote_file = sftp.create(&remote_path)?;
+            std::io::copy(&mut local_file, &mut ThrottledWriter::new(&mut remote_file, &connection.bandwidth))?;
+            sftp.setstat(&remote_path, local_stat(&metadata))?;
+        }
+    }
+    Ok(())
+}
+
+/// Timestamps and (on Unix) permissions to carry over to the remote copy
+fn local_stat(metadata: &std::fs::Metadata) -> FileStat {
+    let mtime = metadata.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    #[cfg(unix)]
+    let perm = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let perm = None;
+
+    FileStat { size: None, uid: None, gid: None, perm, atime: mtime, mtime }
+}
+
+/// Total size of the regular files below `dir`
+fn local_dir_size(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        total += if metadata.is_dir() {
+            local_dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+/// Unpack a gzipped tar archive into `dest`
+fn extract_archive(archive_path: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    let decoder = flate2::read::GzDecoder::new(File::open(archive_path)?);
+    tar::Archive::new(decoder).unpack(dest)?;
+    Ok(())
+}
+
+// Tauri commands for tar transfers
+
+/// Download a remote directory as a single gzipped tar stream, optionally extracting it locally
+#[tauri::command]
+pub async fn download_remote_dir_as_tar(
+    ssh_client: State<'_, SSHClient>,
+    copy_agent: State<'_, CopyAgent>,
+    app_handle: AppHandle,
+    connection_id: String,
+    remote_dir: String,
+    local_archive_path: String,
+    options: Option<TarDownloadOptions>,
+) -> Result<TarTransferResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+    let TarDownloadOptions { extract_to, task_id, cross_filesystems } = options.unwrap_or_default();
+
+    let source_size = cached_dir_size(&ssh_client.listing_cache.dir_sizes, &connection, &connection_id, &remote_dir, cross_filesystems, &AtomicBool::new(false), |_| {})
+        .await
+        .map_err(|e| e.to_string())?;
+    let source_bytes = source_size.total_bytes;
+
+    // The stream can take minutes, so keep it off the shared session and the async runtime
+    let task_id = task_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let stream = TarStream::open(&connection, app_handle, task_id, copy_agent.progress_throttle()).await
+        .map_err(|e| e.to_string())?;
+    let archive_path = local_archive_path.clone();
+    let remote = remote_dir.clone();
+
+    let archive_bytes = tauri::async_runtime::spawn_blocking(move || {
+        let result = stream_remote_tar(&stream, &remote, cross_filesystems, Path::new(&archive_path), source_bytes);
+        stream.close("Tar download finished");
+        if result.is_err() {
+            let _ = std::fs::remove_file(&archive_path);
+        }
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+
+    if let Some(dest) = &extract_to {
+        extract_archive(Path::new(&local_archive_path), Path::new(dest))
+            .map_err(|e| format!("Downloaded archive but failed to extract it: {}", e))?;
+    }
+
+    tracing::info!(
+        "Downloaded {} as tar ({} bytes compressed, {} bytes uncompressed)",
+        remote_dir, archive_bytes, source_bytes
+    );
+    Ok(TarTransferResult {
+        archive_path: local_archive_path,
+        archive_bytes,
+        source_bytes,
+        extracted_to: extract_to,
+        skipped_mounts: source_size.skipped_mounts,
+    })
+}
+
+/// Upload a local directory as a single gzipped tar stream, falling back to SFTP when the
+/// remote host has no `tar`
+#[tauri::command]
+pub async fn upload_local_dir_as_tar(
+    ssh_client: State<'_, SSHClient>,
+    copy_agent: State<'_, CopyAgent>,
+    app_handle: AppHandle,
+    connection_id: String,
+    local_dir: String,
+    remote_dir: String,
+    task_id: Option<String>,
+) -> Result<TarUploadResult, String> {
+    let connection = ssh_client.get_connection(&connection_id)
+        .ok_or("Connection not found")?;
+
+    let local_root = PathBuf::from(&local_dir);
+    if !local_root.is_dir() {
+        return Err(format!("{} is not a directory", local_dir));
+    }
+    let source_bytes = local_dir_size(&local_root).map_err(|e| e.to_string())?;
+
+    let mkdir = exec_command(&connection, &format!("mkdir -p {}", shell_quote(&remote_dir)))
+        .map_err(|e| e.to_string())?;
+    if !mkdir.success() {
+        return Err(format!("Failed to create {}: {}", remote_dir, mkdir.stderr.trim()));
+    }
+
+    let has_tar = command_available(&connection, "tar").unwrap_or(false);
+
+    let mut failed_dirs = Vec::new();
+    if has_tar {
+        let task_id = task_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let stream = TarStream::open(&connection, app_handle, task_id, copy_agent.progress_throttle()).await
+            .map_err(|e| e.to_string())?;
+        let remote = remote_dir.clone();
+
+        tauri::async_runtime::spawn_blocking(move || {
+            let result = stream_local_tar(&stream, &local_root, &remote, source_bytes);
+            stream.close("Tar upload finished");
+            result
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    } else {
+        tracing::warn!("Remote tar not found, uploading {} over SFTP", local_dir);
+        failed_dirs = upload_dir_via_sftp(&connection, &local_root, Path::new(&remote_dir))
+            .map_err(|e| e.to_string())?
+            .failed;
+    }
+
+    ssh_client.listing_cache.invalidate_parent(&connection_id, &remote_dir);
+    Ok(TarUploadResult {
+        remote_dir,
+        source_bytes,
+        used_tar: has_tar,
+        failed_dirs,
+    })
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use crate::error::{Circle9Error, Result};
+use crate::remote_exec::{check_safe_command, exec_command};
+use crate::ssh_client::SSHConnection;
+
+/// Output kept per stream; tasks are persisted, so a chatty hook is cut short
+const MAX_HOOK_OUTPUT: usize = 16 * 1024;
+
+/// Remote commands run around a transfer on its connection
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferHooks {
+    /// Runs before any data moves; if it fails the transfer doesn't start
+    pub pre_hook: Option<String>,
+    /// Runs once the transfer has succeeded, e.g. `systemctl reload nginx`
+    pub post_hook: Option<String>,
+}
+
+impl TransferHooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_hook.is_none() && self.post_hook.is_none()
+    }
+
+    /// Blank hooks are dropped; the rest must pass the destructive-command check
+    pub fn validated(self) -> Result<Self> {
+        let clean = |hook: Option<String>| -> Result<Option<String>> {
+            match hook.map(|h| h.trim().to_string()).filter(|h| !h.is_empty()) {
+                Some(hook) => check_safe_command(&hook).map(|_| Some(hook)),
+                None => Ok(None),
+            }
+        };
+        Ok(Self { pre_hook: clean(self.pre_hook)?, post_hook: clean(self.post_hook)? })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+/// One hook execution, kept on the task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub stage: HookStage,
+    pub command: String,
+    /// None when the command couldn't be run at all
+    pub exit_status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub ran_at: DateTime<Utc>,
+}
+
+impl HookRun {
+    pub fn success(&self) -> bool {
+        self.exit_status == Some(0)
+    }
+
+    /// Why the hook failed, for the task's error
+    pub fn failure(&self) -> Option<String> {
+        match self.exit_status {
+            Some(0) => None,
+            Some(code) => Some(format!("`{}` exited with {}: {}", self.command, code, self.stderr.trim())),
+            None => Some(format!("`{}` could not run: {}", self.command, self.stderr.trim())),
+        }
+    }
+
+    /// A hook that never reached the server
+    pub fn not_run(stage: HookStage, command: &str, reason: String) -> Self {
+        Self {
+            stage,
+            command: command.to_string(),
+            exit_status: None,
+            stdout: String::new(),
+            stderr: reason,
+            ran_at: Utc::now(),
+        }
+    }
+}
+
+fn truncated(mut output: String) -> String {
+    if output.len() > MAX_HOOK_OUTPUT {
+        let mut end = MAX_HOOK_OUTPUT;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n[output truncated]");
+    }
+    output
+}
+
+/// Run `command` over an exec channel. Failing to run it is recorded in the result rather
+/// than returned, so the caller decides what a failed hook means for the transfer.
+pub fn run_hook(connection: &SSHConnection, stage: HookStage, command: &str) -> HookRun {
+    let ran_at = Utc::now();
+    let run = match exec_command(connection, command) {
+        Ok(output) => HookRun {
+            stage,
+            command: command.to_string(),
+            exit_status: Some(output.exit_status),
+            stdout: truncated(output.stdout),
+            stderr: truncated(output.stderr),
+            ran_at,
+        },
+        Err(e) => HookRun::not_run(stage, command, e.to_string()),
+    };
+    match run.failure() {
+        Some(failure) => tracing::warn!("{:?} hook failed: {}", stage, failure),
+        None => tracing::info!("{:?} hook `{}` succeeded: {}", stage, command, run.stdout.trim()),
+    }
+    run
+}
+
+/// Hooks need a server to run on
+pub fn require_connection(hooks: &TransferHooks, connection_id: Option<&str>) -> Result<()> {
+    if !hooks.is_empty() && connection_id.is_none() {
+        return Err(Circle9Error::TransferError("Transfer hooks need a connection to run on".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use flate2::write::{GzDecoder, GzEncoder};
+use flate2::Compression;
+use crate::remote_file_type::looks_like_text;
+
+/// A rewrite applied to a transfer's bytes between reading and writing
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TransferTransform {
+    #[default]
+    None,
+    CrlfToLf,
+    LfToCrlf,
+    GzipCompress,
+    GzipDecompress,
+}
+
+/// Streaming state for one transfer's transform. Chunks go through `apply` in order and
+/// `finish` flushes whatever the transform was holding back.
+pub enum TransformStage {
+    Identity,
+    /// Whether the previous chunk ended in a `\r` not yet written
+    CrlfToLf { pending_cr: bool },
+    /// Whether the previous chunk ended in `\r`, so a leading `\n` is already paired
+    LfToCrlf { last_was_cr: bool },
+    Compress(GzEncoder<Vec<u8>>),
+    Decompress(GzDecoder<Vec<u8>>),
+}
+
+impl TransformStage {
+    pub fn new(transform: TransferTransform) -> Self {
+        match transform {
+            TransferTransform::None => Self::Identity,
+            TransferTransform::CrlfToLf => Self::CrlfToLf { pending_cr: false },
+            TransferTransform::LfToCrlf => Self::LfToCrlf { last_was_cr: false },
+            TransferTransform::GzipCompress => Self::Compress(GzEncoder::new(Vec::new(), Compression::default())),
+            TransferTransform::GzipDecompress => Self::Decompress(GzDecoder::new(Vec::new())),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        matches!(self, Self::Identity)
+    }
+
+    /// Transform `input`, appending the result to `output`
+    pub fn apply(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Self::Identity => output.extend_from_slice(input),
+            Self::CrlfToLf { pending_cr } => {
+                for &byte in input {
+                    if *pending_cr && byte != b'\n' {
+                        output.push(b'\r');
+                    }
+                    *pending_cr = byte == b'\r';
+                    if !*pending_cr {
+                        output.push(byte);
+                    }
+                }
+            }
+            Self::LfToCrlf { last_was_cr } => {
+                for &byte in input {
+                    if byte == b'\n' && !*last_was_cr {
+                        output.push(b'\r');
+                    }
+                    output.push(byte);
+                    *last_was_cr = byte == b'\r';
+                }
+            }
+            Self::Compress(encoder) => {
+                encoder.write_all(input)?;
+                output.append(encoder.get_mut());
+            }
+            Self::Decompress(decoder) => {
+                decoder.write_all(input)?;
+                output.append(decoder.get_mut());
+            }
+        }
+        Ok(())
+    }
+
+    /// Append anything still held back once the source is exhausted
+    pub fn finish(self, output: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Self::CrlfToLf { pending_cr: true } => output.push(b'\r'),
+            Self::Compress(encoder) => output.append(&mut encoder.finish()?),
+            Self::Decompress(decoder) => output.append(&mut decoder.finish()?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Extensions converted without sniffing the content
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "csv", "tsv", "log", "ini", "cfg", "conf", "toml", "yaml", "yml", "json", "xml",
+    "html", "htm", "css", "js", "ts", "sh", "bash", "zsh", "py", "rb", "pl", "php", "rs", "go",
+    "c", "h", "cpp", "hpp", "java", "sql", "properties", "env", "service",
+];
+
+/// Extensions never converted, whatever their first bytes look like
+const BINARY_EXTENSIONS: &[&str] = &[
+    "zip", "gz", "tgz", "bz2", "xz", "7z", "rar", "tar", "exe", "dll", "so", "bin", "iso", "img",
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "pdf", "mp3", "mp4", "mkv", "avi", "mov",
+    "db", "sqlite", "class", "jar", "pyc", "o", "a",
+];
+
+/// Bytes read from the start of a file to decide whether it is text
+pub const SNIFF_LENGTH: usize = 8192;
+
+/// User overrides for `auto_line_endings`, matched case-insensitively and without the dot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineEndingOverrides {
+    pub text_extensions: Vec<String>,
+    pub binary_extensions: Vec<String>,
+}
+
+fn listed(list: &[String], extension: &str) -> bool {
+    list.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/// Whether `path` is text: the user's overrides first, then the built-in extension lists,
+/// then the content of `head`
+pub fn is_text_file(path: &str, head: &[u8], overrides: &LineEndingOverrides) -> bool {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if listed(&overrides.binary_extensions, &extension) {
+        return false;
+    }
+    if listed(&overrides.text_extensions, &extension) {
+        return true;
+    }
+    if BINARY_EXTENSIONS.contains(&extension.as_str()) {
+        return false;
+    }
+    TEXT_EXTENSIONS.contains(&extension.as_str()) || looks_like_text(head)
+}
+
+/// The conversion to the destination's convention for a text file, LF going to Linux and
+/// CRLF going to Windows; None for binary files
+pub fn line_ending_transform(path: &str, head: &[u8], to_linux: bool, overrides: &LineEndingOverrides) -> TransferTransform {
+    match (is_text_file(path, head, overrides), to_linux) {
+        (false, _) => TransferTransform::None,
+        (true, true) => TransferTransform::CrlfToLf,
+        (true, false) => TransferTransform::LfToCrlf,
+    }
+}
+
+/// Up to `SNIFF_LENGTH` bytes from the start of `reader`
+pub fn read_head(reader: impl std::io::Read) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(SNIFF_LENGTH);
+    reader.take(SNIFF_LENGTH as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(transform: TransferTransform, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut stage = TransformStage::new(transform);
+        let mut output = Vec::new();
+        for chunk in chunks {
+            stage.apply(chunk, &mut output).unwrap();
+        }
+        stage.finish(&mut output).unwrap();
+        output
+    }
+
+    fn overrides(text: &[&str], binary: &[&str]) -> LineEndingOverrides {
+        LineEndingOverrides {
+            text_extensions: text.iter().map(|e| e.to_string()).collect(),
+            binary_extensions: binary.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        assert_eq!(run(TransferTransform::CrlfToLf, &[b"a\r", b"\nb"]), b"a\nb");
+        assert_eq!(run(TransferTransform::CrlfToLf, &[b"a\r", b"b\r\n"]), b"a\rb\n");
+    }
+
+    #[test]
+    fn trailing_cr_is_kept_at_finish() {
+        assert_eq!(run(TransferTransform::CrlfToLf, &[b"a\r\nb\r"]), b"a\nb\r");
+    }
+
+    #[test]
+    fn lf_to_crlf_leaves_existing_crlf() {
+        assert_eq!(run(TransferTransform::LfToCrlf, &[b"a\r\nb\nc"]), b"a\r\nb\r\nc");
+        assert_eq!(run(TransferTransform::LfToCrlf, &[b"a\r", b"\nb\n"]), b"a\r\nb\r\n");
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let original: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = run(TransferTransform::GzipCompress, &[&original[..40_000], &original[40_000..]]);
+        assert_ne!(compressed, original);
+        let (head, tail) = compressed.split_at(compressed.len() / 2);
+        assert_eq!(run(TransferTransform::GzipDecompress, &[head, tail]), original);
+    }
+
+    #[test]
+    fn built_in_extensions_beat_content() {
+        let none = LineEndingOverrides::default();
+        assert!(is_text_file("notes.TXT", b"\0\0", &none));
+        assert!(!is_text_file("photo.png", b"plain text", &none));
+        assert!(is_text_file("README", b"plain text\n", &none));
+        assert!(!is_text_file("blob", b"a\0b", &none));
+        assert!(!is_text_file("latin1", b"caf\xe9 au lait", &none));
+        // A character cut off by the sniff length still counts as UTF-8
+        assert!(is_text_file("cut", b"caf\xc3", &none));
+    }
+
+    #[test]
+    fn overrides_beat_built_in_lists() {
+        assert!(!is_text_file("data.json", b"{}", &overrides(&[], &["json"])));
+        assert!(is_text_file("image.png", b"\0", &overrides(&[".PNG"], &[])));
+        // Listed as both, binary wins
+        assert!(!is_text_file("x.cfg", b"a=1", &overrides(&["cfg"], &["cfg"])));
+    }
+
+    #[test]
+    fn transform_follows_direction() {
+        let none = LineEndingOverrides::default();
+        assert_eq!(line_ending_transform("a.sh", b"", true, &none), TransferTransform::CrlfToLf);
+        assert_eq!(line_ending_transform("a.sh", b"", false, &none), TransferTransform::LfToCrlf);
+        assert_eq!(line_ending_transform("a.zip", b"", true, &none), TransferTransform::None);
+        assert_eq!(
+            line_ending_transform("a.zip", b"", false, &overrides(&["zip"], &[])),
+            TransferTransform::LfToCrlf
+        );
+    }
+}
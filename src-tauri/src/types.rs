@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
 use uuid::Uuid;
+use crate::utils::calculate_progress;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionId(String);
@@ -8,6 +11,14 @@ impl ConnectionId {
     pub fn new(username: &str, host: &str, port: u16) -> Self {
         Self(format!("{}@{}:{}", username, host, port))
     }
+
+    /// Id for an additional session to the same host, e.g. `user@host:22#transfers`
+    pub fn with_label(username: &str, host: &str, port: u16, label: Option<&str>) -> Self {
+        match label {
+            Some(label) => Self(format!("{}@{}:{}#{}", username, host, port, label)),
+            None => Self::new(username, host, port),
+        }
+    }
     
     pub fn as_str(&self) -> &str {
         &self.0
@@ -38,3 +49,145 @@ impl From<String> for TaskId {
         Self(s)
     }
 }
+
+/// Payload of `transfer_skipped`, raised when an overwrite prompt is answered Skip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferSkippedEvent {
+    pub task_id: String,
+    pub path: String,
+}
+
+impl TransferSkippedEvent {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_skipped", self) {
+            tracing::error!("Failed to emit transfer skipped event: {}", e);
+        }
+    }
+}
+
+/// Payload of `transfer_renamed`, raised when an upload is written under a new name
+/// because its destination already existed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferRenamedEvent {
+    pub task_id: String,
+    pub requested_path: String,
+    pub path: String,
+}
+
+impl TransferRenamedEvent {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_renamed", self) {
+            tracing::error!("Failed to emit transfer renamed event: {}", e);
+        }
+    }
+}
+
+/// Payload of the `transfer_disk_full` event; the partial file is left in place for a retry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskFullEvent {
+    pub task_id: String,
+    pub path: String,
+    pub bytes_written: u64,
+}
+
+impl DiskFullEvent {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_disk_full", self) {
+            tracing::error!("Failed to emit disk full event: {}", e);
+        }
+    }
+}
+
+/// Payload of `transfer_fd_backoff`, raised when the server runs out of file handles
+/// and the queue slows down to let it recover
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FdBackoffEvent {
+    pub task_id: String,
+    pub connection_id: Option<String>,
+    /// Transfers allowed to run at once until the backoff ends
+    pub concurrency: usize,
+    pub backoff_secs: u64,
+    pub message: String,
+}
+
+impl FdBackoffEvent {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_fd_backoff", self) {
+            tracing::error!("Failed to emit fd backoff event: {}", e);
+        }
+    }
+}
+
+/// When a transfer should raise a `transfer_notification`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NotifyOn {
+    Complete,
+    Failed,
+    /// Once, the first time progress reaches this percentage
+    Percent(u8),
+}
+
+/// Payload of `transfer_notification`, ready to show as a native notification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferNotification {
+    pub task_id: String,
+    pub trigger: NotifyOn,
+    pub title: String,
+    pub body: String,
+}
+
+impl TransferNotification {
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_notification", self) {
+            tracing::error!("Failed to emit transfer notification: {}", e);
+        }
+    }
+}
+
+/// Payload of every `transfer_progress` event, whichever code path is moving the bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferProgress {
+    pub task_id: String,
+    pub filename: String,
+    pub direction: String, // "upload" or "download"
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub percentage: f64,
+    pub speed_bytes_per_sec: u64,
+    pub estimated_remaining_secs: u64,
+}
+
+impl TransferProgress {
+    pub fn new(
+        task_id: &str,
+        filename: &str,
+        direction: &str,
+        transferred: u64,
+        total: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let (percentage, speed) = calculate_progress(transferred, total, elapsed);
+        let estimated_remaining_secs = if speed > 0 {
+            total.saturating_sub(transferred) / speed
+        } else {
+            0
+        };
+
+        Self {
+            task_id: task_id.to_string(),
+            filename: filename.to_string(),
+            direction: direction.to_string(),
+            bytes_transferred: transferred,
+            total_bytes: total,
+            percentage,
+            speed_bytes_per_sec: speed,
+            estimated_remaining_secs,
+        }
+    }
+
+    pub fn emit(&self, app_handle: &AppHandle) {
+        if let Err(e) = app_handle.emit_all("transfer_progress", self) {
+            tracing::error!("Failed to emit transfer progress: {}", e);
+        }
+    }
+}
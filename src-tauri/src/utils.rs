@@ -1,5 +1,6 @@
+use serde::{Deserialize, Serialize};
 use std::sync::{Mutex, MutexGuard};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use crate::error::{Circle9Error, Result};
 
 /// Common mutex locking pattern with proper error handling
@@ -7,6 +8,27 @@ pub fn lock_or_error<T>(mutex: &Mutex<T>) -> Result<MutexGuard<T>> {
     mutex.lock().map_err(|_| Circle9Error::MutexPoisoned)
 }
 
+/// Replace `path` with `contents` so readers see either the old file or the new one, never a
+/// partial write: the data goes to a sibling temp file, is synced, then renamed over `path`
+pub fn write_atomic(path: &std::path::Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
 /// Calculate transfer progress
 pub fn calculate_progress(transferred: u64, total: u64, elapsed: Duration) -> (f64, u64) {
     let percentage = if total > 0 {
@@ -24,6 +46,62 @@ pub fn calculate_progress(transferred: u64, total: u64, elapsed: Duration) -> (f
     (percentage, speed)
 }
 
+/// Limits on how often progress events are emitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressThrottleConfig {
+    /// Minimum time between events
+    pub interval_ms: u64,
+    /// Also emit once this many bytes have moved since the last event (0 disables)
+    pub min_bytes: u64,
+}
+
+impl Default for ProgressThrottleConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 100,
+            min_bytes: 0,
+        }
+    }
+}
+
+/// Decides when a chunk loop should emit a progress event
+pub struct ProgressThrottle {
+    config: ProgressThrottleConfig,
+    last_emit: Option<Instant>,
+    last_emitted_bytes: u64,
+}
+
+impl ProgressThrottle {
+    pub fn new(config: ProgressThrottleConfig) -> Self {
+        Self {
+            config,
+            last_emit: None,
+            last_emitted_bytes: 0,
+        }
+    }
+
+    /// Whether to emit for this chunk; the chunk that reaches `total` always emits
+    pub fn should_emit(&mut self, transferred: u64, total: u64) -> bool {
+        let due = (total > 0 && transferred >= total)
+            || self.last_emit.map_or(true, |last| {
+                last.elapsed() >= Duration::from_millis(self.config.interval_ms)
+            })
+            || (self.config.min_bytes > 0
+                && transferred - self.last_emitted_bytes >= self.config.min_bytes);
+
+        if due {
+            self.last_emit = Some(Instant::now());
+            self.last_emitted_bytes = transferred;
+        }
+        due
+    }
+
+    /// Whether the final byte count still has to be emitted after the loop ends
+    pub fn needs_final(&self, transferred: u64) -> bool {
+        self.last_emit.is_none() || self.last_emitted_bytes != transferred
+    }
+}
+
 /// Validate file path to prevent path traversal attacks
 pub fn validate_path(path: &str) -> Result<std::path::PathBuf> {
     let path = std::path::PathBuf::from(path);
@@ -39,6 +117,36 @@ pub fn validate_path(path: &str) -> Result<std::path::PathBuf> {
     Ok(canonical)
 }
 
+/// Device a local file lives on, for not descending into other mounts
+#[cfg(unix)]
+pub fn device_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+/// std doesn't expose volume ids on this platform, so every directory counts as the same device
+#[cfg(not(unix))]
+pub fn device_id(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Get the application data directory
+pub fn app_data_dir() -> Result<std::path::PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        let app_data = std::env::var("APPDATA")
+            .map_err(|_| Circle9Error::InvalidPath("APPDATA environment variable not found".to_string()))?;
+        Ok(std::path::PathBuf::from(app_data).join("Circle9"))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let home = std::env::var("HOME")
+            .map_err(|_| Circle9Error::InvalidPath("HOME environment variable not found".to_string()))?;
+        Ok(std::path::PathBuf::from(home).join(".circle9"))
+    }
+}
+
 /// Add timeout wrapper for async operations
 pub async fn with_timeout<F, T>(duration: Duration, future: F) -> Result<T>
 where
@@ -46,11 +46,13 @@ class SSHStateManager {
     async connect(config: SSHConfig): Promise<string> {
         try {
             const connectionId = await invoke<string>('connect_ssh', {
-                host: config.host,
-                port: config.port,
-                username: config.username,
-                keyPath: config.keyPath,
-                password: config.password
+                config: {
+                    host: config.host,
+                    port: config.port,
+                    username: config.username,
+                    key_path: config.keyPath,
+                    password: config.password
+                }
             });
 
             const connection: SSHConnection = {